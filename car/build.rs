@@ -6,7 +6,8 @@ use std::path::{Path, PathBuf};
 use k256::ecdsa::SigningKey;
 use k256::pkcs8::EncodePublicKey;
 use ucsc_ectf_eeprom_layout::{
-    EepromReadField, EepromReadOnlyField, EepromReadWriteField, SECRET_SIZE,
+    EepromReadField, EepromReadOnlyField, EepromReadWriteField, DEVICE_TIME_SLOTS,
+    FEATURE_ACTIVATION_SIZE, NUM_FEATURES, SECRET_SIZE,
 };
 
 fn eeprom_field_from_path<P, F>(eeprom_file: &mut File, field: F, path: P)
@@ -117,6 +118,24 @@ fn main() {
             &buf.to_be_bytes(),
         );
 
+        // The device time starts at zero in every slot when the car is built.
+        for slot in 0..DEVICE_TIME_SLOTS as u32 {
+            eeprom_field_from_buf(
+                &mut eeprom_file,
+                EepromReadWriteField::DeviceTime(slot),
+                &0u64.to_be_bytes(),
+            );
+        }
+
+        // No feature has been accepted when the car is built.
+        for feature_number in 1..=NUM_FEATURES as u32 {
+            eeprom_field_from_buf(
+                &mut eeprom_file,
+                EepromReadWriteField::feature_activation(feature_number).unwrap(),
+                &[0; FEATURE_ACTIVATION_SIZE],
+            );
+        }
+
         println!("cargo:rerun-if-changed={secrets_dir}");
     }
 
@@ -22,12 +22,7 @@ pub(crate) fn get_feature_message(
 ) -> Option<[u8; MESSAGE_SIZE]> {
     let mut feature_msg_bytes = [0; MESSAGE_SIZE];
 
-    let feature_msg_type = match feature {
-        1 => EepromReadOnlyField::FeatureOneMessage,
-        2 => EepromReadOnlyField::FeatureTwoMessage,
-        3 => EepromReadOnlyField::FeatureThreeMessage,
-        _ => return None,
-    };
+    let feature_msg_type = EepromReadOnlyField::feature_message(feature)?;
 
     eeprom_controller
         .read_slice(feature_msg_type, &mut feature_msg_bytes)
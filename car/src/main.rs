@@ -11,18 +11,25 @@ use cortex_m::interrupt;
 use cortex_m_rt::entry;
 use tm4c123x_hal::{CorePeripherals, Peripherals};
 use ucsc_ectf_util_no_std::{
-    communication::RxChannel,
+    communication::{message_channel::MessageChannel, CommunicationError, RxChannel, TxChannel},
+    device_time::DeviceClock,
     eeprom::{EepromReadWriteField, SECRET_SIZE},
-    messages::Uart1Message,
-    Runtime, RuntimePeripherals,
+    messages::{
+        from_envelope_bytes, Capabilities, Hello, HelloMessage, Uart0Message, Uart1Message,
+    },
+    revocation, Runtime, RuntimePeripherals,
 };
 use zeroize::Zeroize;
 
 mod eeprom_messages;
 mod unlock;
 
-/// The maximum size of a message that can be received/sent.
-pub const MAX_MESSAGE_SIZE: usize = 1024;
+/// The size of the buffers messages are received into, which fit a message of
+/// [`MAX_MESSAGE_SIZE`](ucsc_ectf_util_no_std::MAX_MESSAGE_SIZE) bytes along with the metadata of the UART
+/// channels.
+pub const RECV_BUFFER_SIZE: usize = ucsc_ectf_util_no_std::RECV_BUFFER_SIZE;
+
+const MS_TO_WAIT_FOR_MSG: u64 = 5;
 
 // Jumps to the reset handler. This is used to allow the bootloader to execute our code.
 global_asm!(
@@ -36,6 +43,15 @@ jump_to_reset:
 "#
 );
 
+/// Sends a hello of the message type ``M`` in response to a hello received on ``channel``.
+fn respond_to_hello<M: HelloMessage, C: TxChannel>(channel: &mut C) {
+    if let Err(CommunicationError::InternalError) =
+        MessageChannel::<_, M, RECV_BUFFER_SIZE>::new(channel).send_hello()
+    {
+        panic!("Failed to send hello (internal error).");
+    }
+}
+
 #[entry]
 fn main() -> ! {
     // Enable interrupts because the bootloader disables them and leaves them disabled.
@@ -56,6 +72,9 @@ fn main() -> ! {
         &Default::default(),
     );
 
+    // Start counting the device time used to check the validity windows of features.
+    let mut device_clock = DeviceClock::new(&mut rt.eeprom_controller, &rt.hib_controller);
+
     // Transmit and receive using unlock keys.
     let mut key_fob_encryption_key = [0; SECRET_SIZE];
     let mut car_encryption_key = [0; SECRET_SIZE];
@@ -80,21 +99,63 @@ fn main() -> ! {
         .change_tx_key(&car_encryption_key.into());
     car_encryption_key.zeroize();
 
-    // Listen for unlock requests.
+    // Listen for unlock requests from key fobs and revocation lists from the host. UART1 is the
+    // blocking receiver, and UART0 is only read once the host has started sending and no key fob
+    // message is arriving, as the UART1 receive FIFO would overflow while UART0 is being read.
     loop {
-        let mut receive_buffer = [0; MAX_MESSAGE_SIZE];
+        device_clock.persist(&mut rt.eeprom_controller, &rt.hib_controller);
+
+        let mut receive_buffer = [0; RECV_BUFFER_SIZE];
 
         // Process message if one is received on UART1.
-        if let Ok(size_read) = rt.uart1_controller.recv_with_data_timeout(
+        if let Ok(received) = rt.uart1_controller.recv_view_with_data_timeout(
             &mut receive_buffer,
-            &mut rt.hib_controller.create_timer(Duration::from_secs(1000)),
+            &mut rt
+                .hib_controller
+                .create_timer(Duration::from_millis(MS_TO_WAIT_FOR_MSG)),
         ) {
-            let msg = match postcard::from_bytes::<Uart1Message>(&receive_buffer[..size_read]) {
-                Ok(msg) => msg,
-                Err(_) => continue,
-            };
+            if let Ok(envelope) = from_envelope_bytes::<Uart1Message>(received) {
+                // Respond to a key fob starting a session, unless it doesn't support what this car needs.
+                if let Uart1Message::Hello(hello) = envelope.message {
+                    if Hello::local().negotiate(&hello).is_some_and(|parameters| {
+                        parameters
+                            .capabilities
+                            .contains(Capabilities::REQUIRED_UART1)
+                    }) {
+                        respond_to_hello::<Uart1Message, _>(&mut rt.uart1_controller);
+                    }
+                } else {
+                    unlock::process_msg(
+                        &mut rt,
+                        &device_clock,
+                        &envelope.message,
+                        envelope.version,
+                    );
+                }
+            }
+
+            continue;
+        }
+
+        if !rt.uart1_controller.rx_fifo_is_empty() || rt.uart0_controller.rx_fifo_is_empty() {
+            continue;
+        }
 
-            unlock::process_msg(&mut rt, &msg);
+        // Process message if one is received on UART0.
+        if let Ok(received) = rt.uart0_controller.recv_view_with_data_timeout(
+            &mut receive_buffer,
+            &mut rt
+                .hib_controller
+                .create_timer(Duration::from_millis(MS_TO_WAIT_FOR_MSG)),
+        ) {
+            if let Ok(envelope) = from_envelope_bytes::<Uart0Message>(received) {
+                // Respond to a host tool starting a session.
+                if let Uart0Message::Hello(_) = envelope.message {
+                    respond_to_hello::<Uart0Message, _>(&mut rt.uart0_controller);
+                } else {
+                    revocation::process_msg(&mut rt, &envelope.message, envelope.version);
+                }
+            }
         }
     }
 }
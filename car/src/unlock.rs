@@ -1,41 +1,50 @@
-use crate::{eeprom_messages, MAX_MESSAGE_SIZE};
+use crate::{eeprom_messages, RECV_BUFFER_SIZE};
 use core::{mem, time::Duration};
 use ucsc_ectf_util_no_std::{
-    communication::{CommunicationError, RxChannel, TxChannel},
-    eeprom::{EepromReadWriteField, CAR_ID_SIZE, MESSAGE_SIZE},
-    features,
+    communication::{message_channel::MessageChannel, CommunicationError, Uart0Controller},
+    device_time::DeviceClock,
+    eeprom::{EepromController, EepromReadWriteField, CAR_ID_SIZE, MESSAGE_SIZE},
+    features::{self, FeatureVerification},
     messages::{
-        heapless::Vec, Nonce, Uart0Message, Uart1Message, UnlockChallenge, UnlockChallengeResponse,
-        UnlockMessage,
+        heapless::Vec, DeviceTime, Nonce, ProtocolVersion, Uart0Message, Uart1Message,
+        UnlockChallenge, UnlockChallengeResponse, UnlockMessage, NUM_FEATURES,
     },
-    timer::Timer,
-    Runtime,
+    Runtime, Uart0RxPin, Uart0TxPin,
 };
 
-/// Unlocks the car.
-fn unlock_car(rt: &mut Runtime, challenge_response: &UnlockChallengeResponse) {
-    let unlock_msg_bytes = eeprom_messages::get_unlock_message(&mut rt.eeprom_controller);
+/// Unlocks the car. Features that have been revoked or are outside of their validity window at the
+/// device time ``device_time`` are left out, with each window counted from when the car first
+/// accepted the feature. The features were verified by the paired key fob when they were enabled, so
+/// they are sent without their signatures.
+fn unlock_car(
+    eeprom_controller: &mut EepromController,
+    uart0_controller: &mut Uart0Controller<Uart0TxPin, Uart0RxPin>,
+    challenge_response: &UnlockChallengeResponse,
+    device_time: DeviceTime,
+) {
+    let unlock_msg_bytes = eeprom_messages::get_unlock_message(eeprom_controller);
     let mut feature_nums = Vec::new();
-    let mut feature_msgs_bytes: Vec<[u8; MESSAGE_SIZE], 3> = Vec::new();
+    let mut feature_msgs_bytes: Vec<[u8; MESSAGE_SIZE], NUM_FEATURES> = Vec::new();
 
     for feature in challenge_response.features.iter() {
         // Verify feature.
-        if !features::verify_packaged_feature_signed(&mut rt.eeprom_controller, feature) {
-            return;
+        match features::verify_packaged_feature(eeprom_controller, feature, Some(device_time)) {
+            FeatureVerification::Valid => (),
+            FeatureVerification::OutsideValidityWindow | FeatureVerification::Revoked => continue,
+            FeatureVerification::Invalid => return,
         }
 
         // Push feature number.
         feature_nums
-            .push(feature.packaged_feature.feature_number)
+            .push(feature.feature_number)
             .expect("Failed to push feature number.");
 
         // Push feature message.
-        let Some(feature_msg_bytes) = eeprom_messages::get_feature_message(
-                &mut rt.eeprom_controller,
-                feature.packaged_feature.feature_number,
-            ) else {
-                return;
-            };
+        let Some(feature_msg_bytes) =
+            eeprom_messages::get_feature_message(eeprom_controller, feature.feature_number)
+        else {
+            return;
+        };
 
         feature_msgs_bytes
             .push(feature_msg_bytes)
@@ -52,17 +61,27 @@ fn unlock_car(rt: &mut Runtime, challenge_response: &UnlockChallengeResponse) {
         car_id: challenge_response.car_id,
     });
 
-    let mut host_unlock_msg_buff = [0; MAX_MESSAGE_SIZE];
-
-    if let Err(CommunicationError::InternalError) = rt.uart0_controller.send(
-        postcard::to_slice(&host_unlock_msg, &mut host_unlock_msg_buff)
-            .expect("Failed to serialize host unlock message."),
-    ) {
-        panic!("Failed to send host unlock message (internal error).");
+    match MessageChannel::<_, Uart0Message, RECV_BUFFER_SIZE>::new(uart0_controller)
+        .send(&host_unlock_msg)
+    {
+        Err(CommunicationError::InternalError) => {
+            panic!("Failed to send host unlock message (internal error).")
+        }
+        Err(CommunicationError::EncodingError) => {
+            panic!("Failed to serialize host unlock message.")
+        }
+        _ => (),
     }
 }
 
-pub(crate) fn process_msg(rt: &mut Runtime, receive_msg: &Uart1Message) {
+/// Processes an unlock request, responding with the protocol version ``version`` it was sent with.
+/// Features are checked against the device time of ``device_clock``.
+pub(crate) fn process_msg(
+    rt: &mut Runtime,
+    device_clock: &DeviceClock,
+    receive_msg: &Uart1Message,
+    version: ProtocolVersion,
+) {
     let unlock_request = match receive_msg {
         Uart1Message::UnlockRequest(msg) => msg,
         _ => return,
@@ -86,45 +105,30 @@ pub(crate) fn process_msg(rt: &mut Runtime, receive_msg: &Uart1Message) {
 
     // Send challenge.
     let challenge_msg = Uart1Message::UnlockChallenge(UnlockChallenge { car_id, challenge });
-    let mut challenge_msg_buff = [0; MAX_MESSAGE_SIZE];
+    let mut uart1 =
+        MessageChannel::<_, Uart1Message, RECV_BUFFER_SIZE>::new(&mut rt.uart1_controller);
+    uart1.set_version(version);
 
-    match rt.uart1_controller.send(
-        postcard::to_slice(&challenge_msg, &mut challenge_msg_buff)
-            .expect("Failed to serialize unlock challenge."),
-    ) {
+    match uart1.send(&challenge_msg) {
         Ok(_) => (),
         Err(CommunicationError::InternalError) => {
             panic!("Failed to send unlock challenge (internal error).")
         }
+        Err(CommunicationError::EncodingError) => panic!("Failed to serialize unlock challenge."),
         Err(_) => return,
     }
 
     // Wait for challenge response.
-    let mut response_bytes = [0; MAX_MESSAGE_SIZE];
     let mut timeout_timer = rt.hib_controller.create_timer(Duration::from_secs(1));
 
-    let challenge_response = loop {
-        // Make sure timer hasn't expired on this iteration first.
-        if timeout_timer.poll() {
-            return;
-        }
-
-        let size_read = match rt
-            .uart1_controller
-            .recv_with_timeout(&mut response_bytes, &mut timeout_timer)
-        {
-            Ok(size_read) => size_read,
-            Err(CommunicationError::InternalError) => {
-                panic!("Failed to receive unlock challenge response (internal error).")
-            }
-            Err(_) => return,
-        };
-
-        if let Ok(Uart1Message::UnlockChallengeResponse(challenge_response)) =
-            postcard::from_bytes::<Uart1Message>(&response_bytes[..size_read])
-        {
-            break challenge_response;
+    let challenge_response = match uart1.recv_until_with_timeout(&mut timeout_timer, |msg| {
+        matches!(msg, Uart1Message::UnlockChallengeResponse(_))
+    }) {
+        Ok(Uart1Message::UnlockChallengeResponse(challenge_response)) => challenge_response,
+        Err(CommunicationError::InternalError) => {
+            panic!("Failed to receive unlock challenge response (internal error).")
         }
+        _ => return,
     };
 
     // Verify car ID.
@@ -138,5 +142,11 @@ pub(crate) fn process_msg(rt: &mut Runtime, receive_msg: &Uart1Message) {
     }
 
     // Unlock car.
-    unlock_car(rt, &challenge_response);
+    let device_time = device_clock.now(&rt.hib_controller);
+    unlock_car(
+        &mut rt.eeprom_controller,
+        &mut rt.uart0_controller,
+        &challenge_response,
+        device_time,
+    );
 }
@@ -24,7 +24,7 @@ pub fn empty_recv_error_test<T: RxTxChannel, U: Timer, V: Fn(Duration) -> U>(
 
     assert_eq!(
         res,
-        Err(CommunicationError::RecvError),
+        Err(CommunicationError::BufferTooSmall),
         "Failed empty recv error test"
     );
 }
@@ -48,7 +48,7 @@ pub fn too_small_recv_error_test<T: RxTxChannel, U: Timer, V: Fn(Duration) -> U>
 
     assert_eq!(
         res,
-        Err(CommunicationError::RecvError),
+        Err(CommunicationError::BufferTooSmall),
         "Failed too small recv error test"
     );
 }
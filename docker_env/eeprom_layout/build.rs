@@ -0,0 +1,32 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+fn main() {
+    // Get the number of features to build for. The car, fob, host tools and tests are all built
+    // against this crate, so they all use the number of features set in the ``NUM_FEATURES``
+    // environment variable, or in the file of the same name if it isn't set.
+    let num_features = env::var("NUM_FEATURES")
+        .or_else(|_| fs::read_to_string("NUM_FEATURES"))
+        .expect("Failed to read NUM_FEATURES.")
+        .trim()
+        .parse::<usize>()
+        .expect("NUM_FEATURES must contain a number.");
+
+    // Generate the number of features constant.
+    let out = PathBuf::from(env::var_os("OUT_DIR").unwrap());
+    fs::write(
+        out.join("num_features.rs"),
+        format!(
+            "/// The number of features, set at build time in the ``NUM_FEATURES`` environment \
+             variable or the ``NUM_FEATURES`` file of this crate.\npub const NUM_FEATURES: usize \
+             = {num_features};\n"
+        ),
+    )
+    .unwrap();
+
+    // Only re-run the build script when this file or the number of features is changed.
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=NUM_FEATURES");
+    println!("cargo:rerun-if-env-changed=NUM_FEATURES");
+}
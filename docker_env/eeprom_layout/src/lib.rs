@@ -3,10 +3,41 @@
 #![warn(missing_docs)]
 #![no_std]
 
+include!(concat!(env!("OUT_DIR"), "/num_features.rs"));
+
+/// The version of the EEPROM layout, which must be increased whenever a field is moved so that
+/// images built for an older layout aren't mixed with firmware built for this one.
+///
+/// - Version 1 stored every field in one header, with a signed packaged feature for each feature,
+///   followed by the messages from address 0x700.
+/// - Version 2 starts with the fields used by both the car and the key fob, followed by a region
+///   whose fields depend on the device, so that the fields of the car and the key fob share
+///   addresses. The key fob stores each feature as a packaged feature of
+///   [`PACKAGED_FEATURE_SIZE`] bytes without its signature, which is verified when the feature is
+///   enabled. The messages of features 1 to 3 and the unlock message stay from address 0x700, and
+///   the messages of features 4 and up are stored before them, over the key fob region. The car
+///   stores when it first accepted each feature, which validity windows are counted from.
+pub const EEPROM_LAYOUT_VERSION: u32 = 2;
+
+/// The maximum number of features. The messages of features 4 and up are stored before the message
+/// space at the end of the EEPROM, where they must not overlap the car region.
+pub const MAX_NUM_FEATURES: usize = 16;
+
+// Check that the number of features set at build time is supported.
+const _: () = assert!(
+    NUM_FEATURES >= 1 && NUM_FEATURES <= MAX_NUM_FEATURES,
+    "NUM_FEATURES must be from 1 to MAX_NUM_FEATURES."
+);
+
 /// The start address of the EEPROM.
 const EEPROM_START_ADDRESS: usize = 0x000;
 
-/// The start address of the EEPROM reserved message space.
+/// The size of the EEPROM.
+const EEPROM_SIZE: usize = 0x800;
+
+/// The start address of the EEPROM reserved message space, which holds the messages of features 1 to
+/// 3 and the unlock message. This address is fixed, as the messages are written to it when the car is
+/// deployed.
 const EEPROM_MESSAGES_START_ADDRESS: usize = 0x700;
 
 /// The size of encryption secrets. 256 bits = 32 bytes.
@@ -30,12 +61,100 @@ pub const BYTE_FIELD_SIZE: usize = 4;
 /// The size of the pairing PIN.
 pub const PAIRING_PIN_SIZE: usize = 4;
 
-/// The size of a signed packaged feature.
-pub const PACKAGED_FEATURE_SIGNED_SIZE: usize = 96;
+/// The size of a packaged feature stored on the key fob, which holds a tag byte followed by either a
+/// Postcard-encoded packaged feature or a feature digest.
+pub const PACKAGED_FEATURE_SIZE: usize = 36;
+
+/// The size of the device time. 64 bits = 8 bytes.
+pub const DEVICE_TIME_SIZE: usize = 8;
+
+/// The number of slots the device time is written to in turn, which spreads the wear of its writes
+/// over that many fields.
+pub const DEVICE_TIME_SLOTS: usize = 8;
+
+/// The size of a feature activation, which holds the 16 byte digest of a packaged feature followed by
+/// the device time when the car first accepted it.
+pub const FEATURE_ACTIVATION_SIZE: usize = 16 + DEVICE_TIME_SIZE;
+
+/// The size of a signed revocation list.
+pub const REVOCATION_LIST_SIGNED_SIZE: usize = 224;
+
+/// The size of the disable feature sequence number. 32 bits = 4 bytes.
+pub const DISABLE_FEATURE_SEQUENCE_SIZE: usize = 4;
+
+/// The bounds of the feature verifying key EEPROM field.
+const FEATURE_VERIFYING_KEY_BOUNDS: EepromFieldBounds = EepromFieldBounds {
+    address: EEPROM_START_ADDRESS,
+    size: PUBLIC_KEY_SIZE,
+};
+
+/// The bounds of the secret seed EEPROM field.
+const SECRET_SEED_BOUNDS: EepromFieldBounds = EepromFieldBounds {
+    address: FEATURE_VERIFYING_KEY_BOUNDS.address + FEATURE_VERIFYING_KEY_BOUNDS.size,
+    size: SECRET_SIZE,
+};
+
+/// The bounds of the key fob encryption key (unlock key 1) EEPROM field.
+const KEY_FOB_ENCRYPTION_KEY_BOUNDS: EepromFieldBounds = EepromFieldBounds {
+    address: SECRET_SEED_BOUNDS.address + SECRET_SEED_BOUNDS.size,
+    size: SECRET_SIZE,
+};
+
+/// The bounds of the car encryption key (unlock key 2) EEPROM field.
+const CAR_ENCRYPTION_KEY_BOUNDS: EepromFieldBounds = EepromFieldBounds {
+    address: KEY_FOB_ENCRYPTION_KEY_BOUNDS.address + KEY_FOB_ENCRYPTION_KEY_BOUNDS.size,
+    size: SECRET_SIZE,
+};
+
+/// The bounds of the car ID EEPROM field.
+const CAR_ID_BOUNDS: EepromFieldBounds = EepromFieldBounds {
+    address: CAR_ENCRYPTION_KEY_BOUNDS.address + CAR_ENCRYPTION_KEY_BOUNDS.size,
+    size: CAR_ID_SIZE,
+};
+
+/// The bounds of the revocation list EEPROM field.
+const REVOCATION_LIST_BOUNDS: EepromFieldBounds = EepromFieldBounds {
+    address: CAR_ID_BOUNDS.address + CAR_ID_BOUNDS.size,
+    size: REVOCATION_LIST_SIGNED_SIZE,
+};
+
+/// The start address of the fields used by only one device. The car and key fob regions both start
+/// here.
+const DEVICE_REGION_ADDRESS: usize = REVOCATION_LIST_BOUNDS.address + REVOCATION_LIST_BOUNDS.size;
+
+/// The address of the device time EEPROM fields, which are stored in order of slot. These are only
+/// used by the car.
+const DEVICE_TIMES_ADDRESS: usize = DEVICE_REGION_ADDRESS;
+
+/// Gets the bounds of the device time EEPROM field for slot ``slot``.
+const fn device_time_bounds(slot: u32) -> EepromFieldBounds {
+    EepromFieldBounds {
+        address: DEVICE_TIMES_ADDRESS + slot as usize * DEVICE_TIME_SIZE,
+        size: DEVICE_TIME_SIZE,
+    }
+}
+
+/// The address of the feature activation EEPROM fields, which are stored in order of feature number.
+/// These are only used by the car.
+const FEATURE_ACTIVATIONS_ADDRESS: usize =
+    DEVICE_TIMES_ADDRESS + DEVICE_TIME_SLOTS * DEVICE_TIME_SIZE;
+
+/// Gets the bounds of the feature activation EEPROM field for the feature in ``slot``.
+const fn feature_activation_bounds(slot: FeatureSlot) -> EepromFieldBounds {
+    EepromFieldBounds {
+        address: FEATURE_ACTIVATIONS_ADDRESS
+            + (slot.feature_number() as usize - 1) * FEATURE_ACTIVATION_SIZE,
+        size: FEATURE_ACTIVATION_SIZE,
+    }
+}
+
+/// The end address of the car region when the most features are supported.
+const CAR_REGION_END_ADDRESS: usize =
+    FEATURE_ACTIVATIONS_ADDRESS + MAX_NUM_FEATURES * FEATURE_ACTIVATION_SIZE;
 
 /// The bounds of the paired fob's pairing signing key EEPROM field.
 const PAIRED_FOB_PAIRING_SIGNING_KEY_BOUNDS: EepromFieldBounds = EepromFieldBounds {
-    address: EEPROM_START_ADDRESS,
+    address: DEVICE_REGION_ADDRESS,
     size: SECRET_SIZE,
 };
 
@@ -61,22 +180,10 @@ const PAIRING_MANUFACTURER_UNPAIRED_FOB_VERIFYING_KEY_BOUNDS: EepromFieldBounds
         size: PUBLIC_KEY_SIZE,
     };
 
-/// The bounds of the feature verifying key EEPROM field.
-const FEATURE_VERIFYING_KEY_BOUNDS: EepromFieldBounds = EepromFieldBounds {
-    address: PAIRING_MANUFACTURER_UNPAIRED_FOB_VERIFYING_KEY_BOUNDS.address
-        + PAIRING_MANUFACTURER_UNPAIRED_FOB_VERIFYING_KEY_BOUNDS.size,
-    size: PUBLIC_KEY_SIZE,
-};
-
-/// The bounds of the secret seed EEPROM field.
-const SECRET_SEED_BOUNDS: EepromFieldBounds = EepromFieldBounds {
-    address: FEATURE_VERIFYING_KEY_BOUNDS.address + FEATURE_VERIFYING_KEY_BOUNDS.size,
-    size: SECRET_SIZE,
-};
-
 /// The bounds of the unpaired fob's pairing signing key EEPROM field.
 const UNPAIRED_FOB_PAIRING_SIGNING_KEY_BOUNDS: EepromFieldBounds = EepromFieldBounds {
-    address: SECRET_SEED_BOUNDS.address + SECRET_SEED_BOUNDS.size,
+    address: PAIRING_MANUFACTURER_UNPAIRED_FOB_VERIFYING_KEY_BOUNDS.address
+        + PAIRING_MANUFACTURER_UNPAIRED_FOB_VERIFYING_KEY_BOUNDS.size,
     size: SECRET_SIZE,
 };
 
@@ -87,28 +194,10 @@ const UNPAIRED_FOB_PAIRING_PUBLIC_KEY_SIGNATURE_BOUNDS: EepromFieldBounds = Eepr
     size: SIGNATURE_SIZE,
 };
 
-/// The bounds of the key fob encryption key (unlock key 1) EEPROM field.
-const KEY_FOB_ENCRYPTION_KEY_BOUNDS: EepromFieldBounds = EepromFieldBounds {
-    address: UNPAIRED_FOB_PAIRING_PUBLIC_KEY_SIGNATURE_BOUNDS.address
-        + UNPAIRED_FOB_PAIRING_PUBLIC_KEY_SIGNATURE_BOUNDS.size,
-    size: SECRET_SIZE,
-};
-
-/// The bounds of the car encryption key (unlock key 2) EEPROM field.
-const CAR_ENCRYPTION_KEY_BOUNDS: EepromFieldBounds = EepromFieldBounds {
-    address: KEY_FOB_ENCRYPTION_KEY_BOUNDS.address + KEY_FOB_ENCRYPTION_KEY_BOUNDS.size,
-    size: SECRET_SIZE,
-};
-
-/// The bounds of the car ID EEPROM field.
-const CAR_ID_BOUNDS: EepromFieldBounds = EepromFieldBounds {
-    address: CAR_ENCRYPTION_KEY_BOUNDS.address + CAR_ENCRYPTION_KEY_BOUNDS.size,
-    size: CAR_ID_SIZE,
-};
-
 /// The bounds of the pairing byte EEPROM field.
 const PAIRING_BYTE_BOUNDS: EepromFieldBounds = EepromFieldBounds {
-    address: CAR_ID_BOUNDS.address + CAR_ID_BOUNDS.size,
+    address: UNPAIRED_FOB_PAIRING_PUBLIC_KEY_SIGNATURE_BOUNDS.address
+        + UNPAIRED_FOB_PAIRING_PUBLIC_KEY_SIGNATURE_BOUNDS.size,
     size: BYTE_FIELD_SIZE,
 };
 
@@ -124,48 +213,83 @@ const PAIRING_LONGER_COOLDOWN_BYTE_BOUNDS: EepromFieldBounds = EepromFieldBounds
     size: BYTE_FIELD_SIZE,
 };
 
-/// The bounds of the feature one signed packaged feature EEPROM field.
-const FEATURE_ONE_SIGNED_PACKAGED_BOUNDS: EepromFieldBounds = EepromFieldBounds {
+/// The bounds of the disable feature sequence number EEPROM field.
+const DISABLE_FEATURE_SEQUENCE_BOUNDS: EepromFieldBounds = EepromFieldBounds {
     address: PAIRING_LONGER_COOLDOWN_BYTE_BOUNDS.address + PAIRING_LONGER_COOLDOWN_BYTE_BOUNDS.size,
-    size: PACKAGED_FEATURE_SIGNED_SIZE,
+    size: DISABLE_FEATURE_SEQUENCE_SIZE,
 };
 
-/// The bounds of the feature two signed packaged feature EEPROM field.
-const FEATURE_TWO_SIGNED_PACKAGED_BOUNDS: EepromFieldBounds = EepromFieldBounds {
-    address: FEATURE_ONE_SIGNED_PACKAGED_BOUNDS.address + FEATURE_ONE_SIGNED_PACKAGED_BOUNDS.size,
-    size: PACKAGED_FEATURE_SIGNED_SIZE,
-};
-
-/// The bounds of the feature three signed packaged feature EEPROM field.
-const FEATURE_THREE_SIGNED_PACKAGED_BOUNDS: EepromFieldBounds = EepromFieldBounds {
-    address: FEATURE_TWO_SIGNED_PACKAGED_BOUNDS.address + FEATURE_TWO_SIGNED_PACKAGED_BOUNDS.size,
-    size: PACKAGED_FEATURE_SIGNED_SIZE,
-};
+/// The address of the packaged feature EEPROM fields, which are stored in order of feature number.
+const FEATURES_PACKAGED_ADDRESS: usize =
+    DISABLE_FEATURE_SEQUENCE_BOUNDS.address + DISABLE_FEATURE_SEQUENCE_BOUNDS.size;
 
-/// The bounds of the feature three message EEPROM field.
-const FEATURE_THREE_MESSAGE_BOUNDS: EepromFieldBounds = EepromFieldBounds {
-    address: EEPROM_MESSAGES_START_ADDRESS,
-    size: MESSAGE_SIZE,
-};
+/// Gets the bounds of the packaged feature EEPROM field for the feature in ``slot``.
+const fn feature_packaged_bounds(slot: FeatureSlot) -> EepromFieldBounds {
+    EepromFieldBounds {
+        address: FEATURES_PACKAGED_ADDRESS
+            + (slot.feature_number() as usize - 1) * PACKAGED_FEATURE_SIZE,
+        size: PACKAGED_FEATURE_SIZE,
+    }
+}
 
-/// The bounds of the feature two message EEPROM field.
-const FEATURE_TWO_MESSAGE_BOUNDS: EepromFieldBounds = EepromFieldBounds {
-    address: FEATURE_THREE_MESSAGE_BOUNDS.address + FEATURE_THREE_MESSAGE_BOUNDS.size,
-    size: MESSAGE_SIZE,
-};
+/// The end address of the key fob region when the most features are supported.
+const FOB_REGION_END_ADDRESS: usize =
+    FEATURES_PACKAGED_ADDRESS + MAX_NUM_FEATURES * PACKAGED_FEATURE_SIZE;
 
-/// The bounds of the feature one message EEPROM field.
-const FEATURE_ONE_MESSAGE_BOUNDS: EepromFieldBounds = EepromFieldBounds {
-    address: FEATURE_TWO_MESSAGE_BOUNDS.address + FEATURE_TWO_MESSAGE_BOUNDS.size,
-    size: MESSAGE_SIZE,
-};
+/// Gets the bounds of the message EEPROM field for the feature in ``slot``. The feature messages are
+/// stored in reverse order of feature number before the unlock message.
+const fn feature_message_bounds(slot: FeatureSlot) -> EepromFieldBounds {
+    EepromFieldBounds {
+        address: UNLOCK_MESSAGE_BOUNDS.address - slot.feature_number() as usize * MESSAGE_SIZE,
+        size: MESSAGE_SIZE,
+    }
+}
 
 /// The bounds of the unlock message EEPROM field.
 const UNLOCK_MESSAGE_BOUNDS: EepromFieldBounds = EepromFieldBounds {
-    address: FEATURE_ONE_MESSAGE_BOUNDS.address + FEATURE_ONE_MESSAGE_BOUNDS.size,
+    address: EEPROM_SIZE - MESSAGE_SIZE,
     size: MESSAGE_SIZE,
 };
 
+// Check that the messages of features 1 to 3 are where they are written when the car is deployed.
+const _: () = assert!(
+    UNLOCK_MESSAGE_BOUNDS.address - 3 * MESSAGE_SIZE == EEPROM_MESSAGES_START_ADDRESS,
+    "The message space must start at 0x700."
+);
+
+// Check that the car region doesn't overlap the messages of any feature, and that the key fob
+// region doesn't overlap the message space.
+const _: () = assert!(
+    CAR_REGION_END_ADDRESS <= UNLOCK_MESSAGE_BOUNDS.address - MAX_NUM_FEATURES * MESSAGE_SIZE,
+    "The car region overlaps the feature messages."
+);
+const _: () = assert!(
+    FOB_REGION_END_ADDRESS <= EEPROM_MESSAGES_START_ADDRESS,
+    "The key fob region overlaps the message space."
+);
+
+/// A feature number from 1 to [`NUM_FEATURES`], which feature fields are created with so that their
+/// bounds are always within the EEPROM. It can only be created with [`FeatureSlot::new`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FeatureSlot(u32);
+
+impl FeatureSlot {
+    /// Creates a [`FeatureSlot`] for feature ``feature_number``, or gives [`None`] if it isn't a
+    /// feature number from 1 to [`NUM_FEATURES`].
+    pub const fn new(feature_number: u32) -> Option<Self> {
+        if is_feature_number(feature_number) {
+            Some(Self(feature_number))
+        } else {
+            None
+        }
+    }
+
+    /// Gets the feature number.
+    pub const fn feature_number(self) -> u32 {
+        self.0
+    }
+}
+
 /// This enum specifies the fields of the EEPROM that can be read from, but not written to.
 #[derive(Copy, Clone)]
 pub enum EepromReadOnlyField {
@@ -181,12 +305,9 @@ pub enum EepromReadOnlyField {
     FeatureVerifyingKey,
     /// The key used as a starting point for the RNG seed hash.
     SecretSeed,
-    /// The message to be printed when feature three is enabled.
-    FeatureThreeMessage,
-    /// The message to be printed when feature two is enabled.
-    FeatureTwoMessage,
-    /// The message to be printed when feature one is enabled.
-    FeatureOneMessage,
+    /// The message to be printed when the contained feature number is enabled. This field is only
+    /// used by the car.
+    FeatureMessage(FeatureSlot),
     /// The message to be printed when the car is successfully unlocked.
     UnlockMessage,
 }
@@ -213,12 +334,57 @@ pub enum EepromReadWriteField {
     PairingPin,
     /// Whether or not the longer pairing cooldown is active.
     PairingLongerCooldownByte,
-    /// The signed packaged feature for feature one.
-    FeatureOneSignedPackaged,
-    /// The signed packaged feature for feature two.
-    FeatureTwoSignedPackaged,
-    /// The signed packaged feature for feature three.
-    FeatureThreeSignedPackaged,
+    /// The packaged feature installed for the contained feature number, or the digest of the last
+    /// one disabled, each following a tag byte. This field is only used by the key fob.
+    FeaturePackaged(FeatureSlot),
+    /// The number of seconds the car has been running for over its lifetime, as written to the
+    /// contained slot. The slot must be less than [`DEVICE_TIME_SLOTS`]. This field is only used by
+    /// the car.
+    DeviceTime(u32),
+    /// The digest of the last packaged feature the car accepted for the contained feature number,
+    /// followed by the device time when it first accepted it. This field is only used by the car.
+    FeatureActivation(FeatureSlot),
+    /// The signed list of revoked features.
+    RevocationList,
+    /// The sequence number of the last disable feature request accepted by a paired key fob. This
+    /// field is only used by the key fob.
+    DisableFeatureSequence,
+}
+
+impl EepromReadOnlyField {
+    /// Gets the message field for feature ``feature_number``, or [`None`] if it isn't a feature
+    /// number from 1 to [`NUM_FEATURES`].
+    pub const fn feature_message(feature_number: u32) -> Option<Self> {
+        match FeatureSlot::new(feature_number) {
+            Some(slot) => Some(Self::FeatureMessage(slot)),
+            None => None,
+        }
+    }
+}
+
+impl EepromReadWriteField {
+    /// Gets the packaged feature field for feature ``feature_number``, or [`None`] if it isn't a
+    /// feature number from 1 to [`NUM_FEATURES`].
+    pub const fn feature_packaged(feature_number: u32) -> Option<Self> {
+        match FeatureSlot::new(feature_number) {
+            Some(slot) => Some(Self::FeaturePackaged(slot)),
+            None => None,
+        }
+    }
+
+    /// Gets the feature activation field for feature ``feature_number``, or [`None`] if it isn't a
+    /// feature number from 1 to [`NUM_FEATURES`].
+    pub const fn feature_activation(feature_number: u32) -> Option<Self> {
+        match FeatureSlot::new(feature_number) {
+            Some(slot) => Some(Self::FeatureActivation(slot)),
+            None => None,
+        }
+    }
+}
+
+/// Checks whether ``feature_number`` is a feature number from 1 to [`NUM_FEATURES`].
+pub const fn is_feature_number(feature_number: u32) -> bool {
+    feature_number >= 1 && feature_number as usize <= NUM_FEATURES
 }
 
 /// A struct for EEPROM field bounds.
@@ -250,9 +416,7 @@ impl EepromReadField for EepromReadOnlyField {
             }
             Self::FeatureVerifyingKey => FEATURE_VERIFYING_KEY_BOUNDS,
             Self::SecretSeed => SECRET_SEED_BOUNDS,
-            Self::FeatureThreeMessage => FEATURE_THREE_MESSAGE_BOUNDS,
-            Self::FeatureTwoMessage => FEATURE_TWO_MESSAGE_BOUNDS,
-            Self::FeatureOneMessage => FEATURE_ONE_MESSAGE_BOUNDS,
+            Self::FeatureMessage(slot) => feature_message_bounds(*slot),
             Self::UnlockMessage => UNLOCK_MESSAGE_BOUNDS,
         }
     }
@@ -271,9 +435,17 @@ impl EepromReadField for EepromReadWriteField {
             Self::PairingByte => PAIRING_BYTE_BOUNDS,
             Self::PairingPin => PAIRING_PIN_BOUNDS,
             Self::PairingLongerCooldownByte => PAIRING_LONGER_COOLDOWN_BYTE_BOUNDS,
-            Self::FeatureOneSignedPackaged => FEATURE_ONE_SIGNED_PACKAGED_BOUNDS,
-            Self::FeatureTwoSignedPackaged => FEATURE_TWO_SIGNED_PACKAGED_BOUNDS,
-            Self::FeatureThreeSignedPackaged => FEATURE_THREE_SIGNED_PACKAGED_BOUNDS,
+            Self::FeaturePackaged(slot) => feature_packaged_bounds(*slot),
+            Self::DeviceTime(slot) => {
+                assert!(
+                    (*slot as usize) < DEVICE_TIME_SLOTS,
+                    "Invalid device time slot."
+                );
+                device_time_bounds(*slot)
+            }
+            Self::FeatureActivation(slot) => feature_activation_bounds(*slot),
+            Self::RevocationList => REVOCATION_LIST_BOUNDS,
+            Self::DisableFeatureSequence => DISABLE_FEATURE_SEQUENCE_BOUNDS,
         }
     }
 }
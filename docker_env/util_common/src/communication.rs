@@ -4,7 +4,8 @@
 //!
 //! The BogoStack consists of three layers.
 //! - The framing layer
-//!     - The framing layer is responsible for turning a stream of data into frames. The BogoStack uses BogoFraming.
+//!     - The framing layer is responsible for turning a stream of data into frames. The BogoStack uses BogoFraming
+//!       or COBS framing.
 //!       See the [`framing`](lower_layers::framing) module for the traits and structs provided to facilitate the
 //!       implementation of this layer.
 //! - The encryption and integrity layer
@@ -16,10 +17,59 @@
 //!       There are no implementations provided on this layer, but one can easily put together types consisting
 //!       of a secure channel primitive with a framing layer primitive to create a channel that conforms to the
 //!       BogoStack.
+//!
+//! An in-memory [`loopback`] channel pair is also provided so the BogoStack can be exercised without any
+//! hardware or sockets, and channels that inject faults into the data passing through them are provided in the
+//! [`fault_injection`] module. The [`reliable`] module provides acknowledgements and retransmits on top of
+//! any channel for flows that shouldn't fail on the first lost frame, and the [`fragmentation`] module lets
+//! messages larger than one receive buffer be split into fragments and reassembled. The [`message_channel`]
+//! module sends and receives typed messages encoded with [`postcard`] on top of any channel. The
+//! [`multiplexing`] module runs multiple independent streams, each optionally with its own key, over one
+//! channel, and the [`stats`] module counts the traffic and errors of a channel for diagnostics. The
+//! [`handshake`] module runs a Noise handshake over any channel to establish a session with a pair of keyed
+//! channels.
+//!
+//! ## Receive buffers
+//!
+//! Receiving into a buffer requires space for any metadata of the channel, such as the nonce and tag of the
+//! [`crypto`](lower_layers::crypto) channels. Each channel reports the most metadata it can receive along
+//! with a message in [`RxChannel::METADATA_SIZE`], so a buffer for messages of up to ``n`` bytes is sized
+//! as ``n + METADATA_SIZE`` bytes where the channel stack is put together. Channels wrapping another
+//! channel add their own metadata to the metadata of the wrapped channel.
+//!
+//! [`RxChannel::recv_view_with_timeout`] and [`RxChannel::recv_view_with_data_timeout`] return a view of the
+//! message inside of the buffer received into, so the message doesn't need to be sliced out of it, and a
+//! [`BufferedRxChannel`](buffered::BufferedRxChannel) owns its buffer so that it's only sized once.
+//!
+//! ## Async channels
+//!
+//! [`AsyncRxChannel`], [`AsyncTxChannel`], and
+//! [`AsyncFramedTxChannel`](lower_layers::framing::AsyncFramedTxChannel) are async counterparts of the
+//! channel traits, so one task can service multiple channels at once instead of blocking on each one.
+//! - They don't depend on any executor. While waiting for data, channels poll their timer and call
+//!   [`yield_now`] so other tasks can run, which works on the car and key fob as well as on the host.
+//! - The futures returned aren't required to be [`Send`], so they can be used on single-threaded executors.
+//! - The [`crypto`](lower_layers::crypto) channels implement the async traits when the channel they wrap
+//!   does, so the same wrappers are used for blocking and async channels.
+
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
 
 use crate::timer::Timer;
 
+pub mod buffered;
+pub mod fault_injection;
+pub mod fragmentation;
+pub mod handshake;
+pub mod loopback;
 pub mod lower_layers;
+pub mod message_channel;
+pub mod multiplexing;
+pub mod reliable;
+pub mod stats;
 
 /// Type definition for any [`CommunicationError`] [`Results`](core::result::Result).
 pub type Result<T> = core::result::Result<T, CommunicationError>;
@@ -27,6 +77,12 @@ pub type Result<T> = core::result::Result<T, CommunicationError>;
 /// A channel to receive data from. See the documentation for [`recv_with_timeout`](RxChannel::recv_with_timeout)
 /// and [`recv_with_data_timeout`](RxChannel::recv_with_data_timeout) for more info.
 pub trait RxChannel {
+    /// The largest number of bytes of metadata received along with each message, such as a nonce and an
+    /// authentication tag. A buffer of ``n + METADATA_SIZE`` bytes fits any message of up to ``n`` bytes.
+    /// Channels that don't receive any metadata, or that copy messages out of their own buffers, leave this
+    /// as zero.
+    const METADATA_SIZE: usize = 0;
+
     /// Receives data from the channel, putting the data received into ``dest``, returning the
     /// number of bytes written to it upon success. The buffer provided should have enough
     /// space to store the data that needs to be received along with its metadata size. The provided timeout
@@ -35,18 +91,32 @@ pub trait RxChannel {
     ///
     /// # ERRORS:
     ///
-    /// - [`CommunicationError::RecvError`] - There are a couple of cases when this can occur:
+    /// - [`CommunicationError::RecvError`]
     ///   - If this is a channel receiving communications from a
     ///     [`FramedTxChannel`](lower_layers::framing::FramedTxChannel), then this error could occur
-    ///     if the provided buffer is too small to fit a whole message sent in a frame or if a malformed
-    ///     message was sent.
+    ///     if a malformed message was sent that isn't covered by a more specific error.
+    /// - [`CommunicationError::Timeout`]
     ///   - The timeout is reached.
-    ///   - If this is a channel receiving communications from a channel in the crypto layer, such
-    ///     as from an [`XChachaPoly1305Channel`](lower_layers::crypto::XChacha20Poly1305TxChannel)
-    ///     then this error could occur if the provided buffer isn't big enough to store the additional
-    ///     metadata, which can include a nonce and/or an authentication tag. Additionally, if the message
-    ///     sent couldn't be authenticated, which can occur due to data corruption, then this error
-    ///     will be returned.
+    /// - [`CommunicationError::BufferTooSmall`]
+    ///   - The provided buffer is too small to fit a whole message sent in a frame. If this is a channel in
+    ///     the crypto layer, such as an [`XChachaPoly1305Channel`](lower_layers::crypto::XChacha20Poly1305RxChannel),
+    ///     this can also occur if the provided buffer isn't big enough to store the additional metadata, which
+    ///     can include a nonce and/or an authentication tag.
+    /// - [`CommunicationError::FrameTooShort`]
+    ///   - The message received is shorter than the minimum message length of the channel.
+    /// - [`CommunicationError::MalformedHexNibble`]
+    ///   - If this is a channel using [`BogoFraming`](lower_layers::framing::BogoFraming), this can occur
+    ///     if a frame was received with a character that isn't a hex digit or with an odd number of hex digits.
+    /// - [`CommunicationError::AuthenticationFailure`]
+    ///   - If this is a channel in the crypto layer, this can occur if the message received couldn't be
+    ///     authenticated, which can occur due to data corruption or tampering.
+    /// - [`CommunicationError::ReplayedMessage`]
+    ///   - If this is a channel in the crypto layer with replay protection enabled, this can occur if the
+    ///     message received was already received or is too old to be checked.
+    ///  - [`CommunicationError::CorruptFrame`]
+    ///    - If this is a channel using a framing protocol with a checksum, this can occur if a frame was
+    ///      received with an unknown header or a checksum that doesn't match. If this is a channel using
+    ///      [`CobsFraming`](lower_layers::framing::CobsFraming), this can occur if a frame was cut short.
     ///  - [`CommunicationError::InternalError`]
     ///    - This can occur if some internal error happens. This should only occur if something is wrong
     ///      with the implementation.
@@ -61,22 +131,74 @@ pub trait RxChannel {
     ///
     /// # ERRORS:
     ///
-    /// - [`CommunicationError::RecvError`] - There are a couple of cases when this can occur:
+    /// - [`CommunicationError::RecvError`]
     ///   - If this is a channel receiving communications from a
     ///     [`FramedTxChannel`](lower_layers::framing::FramedTxChannel), then this error could occur
-    ///     if the provided buffer is too small to fit a whole message sent in a frame or if a malformed
-    ///     message was sent.
+    ///     if a malformed message was sent that isn't covered by a more specific error.
+    /// - [`CommunicationError::Timeout`]
     ///   - The timeout is reached.
-    ///   - If this is a channel receiving communications from a channel in the crypto layer, such
-    ///     as from an [`XChachaPoly1305Channel`](lower_layers::crypto::XChacha20Poly1305TxChannel)
-    ///     then this error could occur if the provided buffer isn't big enough to store the additional
-    ///     metadata, which can include a nonce and/or an authentication tag. Additionally, if the message
-    ///     sent couldn't be authenticated, which can occur due to data corruption, then this error
-    ///     will be returned.
+    /// - [`CommunicationError::BufferTooSmall`]
+    ///   - The provided buffer is too small to fit a whole message sent in a frame. If this is a channel in
+    ///     the crypto layer, such as an [`XChachaPoly1305Channel`](lower_layers::crypto::XChacha20Poly1305RxChannel),
+    ///     this can also occur if the provided buffer isn't big enough to store the additional metadata, which
+    ///     can include a nonce and/or an authentication tag.
+    /// - [`CommunicationError::FrameTooShort`]
+    ///   - The message received is shorter than the minimum message length of the channel.
+    /// - [`CommunicationError::MalformedHexNibble`]
+    ///   - If this is a channel using [`BogoFraming`](lower_layers::framing::BogoFraming), this can occur
+    ///     if a frame was received with a character that isn't a hex digit or with an odd number of hex digits.
+    /// - [`CommunicationError::AuthenticationFailure`]
+    ///   - If this is a channel in the crypto layer, this can occur if the message received couldn't be
+    ///     authenticated, which can occur due to data corruption or tampering.
+    /// - [`CommunicationError::ReplayedMessage`]
+    ///   - If this is a channel in the crypto layer with replay protection enabled, this can occur if the
+    ///     message received was already received or is too old to be checked.
+    ///  - [`CommunicationError::CorruptFrame`]
+    ///    - If this is a channel using a framing protocol with a checksum, this can occur if a frame was
+    ///      received with an unknown header or a checksum that doesn't match. If this is a channel using
+    ///      [`CobsFraming`](lower_layers::framing::CobsFraming), this can occur if a frame was cut short.
     ///  - [`CommunicationError::InternalError`]
     ///    - This can occur if some internal error happens. This should only occur if something is wrong
     ///      with the implementation.
     fn recv_with_timeout<T: Timer>(&mut self, dest: &mut [u8], timer: &mut T) -> Result<usize>;
+
+    /// Receives data from the channel like [`recv_with_data_timeout`](RxChannel::recv_with_data_timeout),
+    /// using ``scratch`` as the buffer to receive into and returning a view of the message received inside
+    /// of it, so the message doesn't need to be sliced out of ``scratch``. ``scratch`` must be
+    /// [`METADATA_SIZE`](RxChannel::METADATA_SIZE) bytes larger than the largest message to be received.
+    /// See the documentation of [`recv_with_data_timeout`](RxChannel::recv_with_data_timeout) for the errors
+    /// that can be given.
+    fn recv_view_with_data_timeout<'a, T: Timer>(
+        &mut self,
+        scratch: &'a mut [u8],
+        timer: &mut T,
+    ) -> Result<&'a [u8]> {
+        let len = self.recv_with_data_timeout(scratch, timer)?;
+
+        Ok(&scratch[..len])
+    }
+
+    /// Receives data from the channel like [`recv_with_timeout`](RxChannel::recv_with_timeout), using
+    /// ``scratch`` as the buffer to receive into and returning a view of the message received inside of it
+    /// like [`recv_view_with_data_timeout`](RxChannel::recv_view_with_data_timeout). See the documentation
+    /// of [`recv_with_timeout`](RxChannel::recv_with_timeout) for the errors that can be given.
+    fn recv_view_with_timeout<'a, T: Timer>(
+        &mut self,
+        scratch: &'a mut [u8],
+        timer: &mut T,
+    ) -> Result<&'a [u8]> {
+        let len = self.recv_with_timeout(scratch, timer)?;
+
+        Ok(&scratch[..len])
+    }
+
+    /// Takes the number of bytes this channel discarded while looking for the start of a frame since this
+    /// was last called. Channels in the framing layer count the bytes discarded by their
+    /// [`FrameDecoder`](lower_layers::framing::FrameDecoder), which show that the receiver lost track of the
+    /// frames being sent even when no malformed frame was received. Other channels give 0.
+    fn take_discarded_bytes(&mut self) -> usize {
+        0
+    }
 }
 
 /// A channel to send data through. See the documentation for [`send`](TxChannel::send) for
@@ -95,13 +217,109 @@ pub trait TxChannel {
     fn send(&mut self, src: &mut [u8]) -> Result<()>;
 }
 
+/// An async channel to receive data from. This is the async counterpart of [`RxChannel`], and its functions
+/// behave the same way as the functions of [`RxChannel`] with the same names, except that they yield to the
+/// executor instead of blocking while waiting for data.
+#[allow(async_fn_in_trait)]
+pub trait AsyncRxChannel {
+    /// The largest number of bytes of metadata received along with each message. See
+    /// [`RxChannel::METADATA_SIZE`] for more details.
+    const METADATA_SIZE: usize = 0;
+
+    /// Receives data from the channel like
+    /// [`RxChannel::recv_with_data_timeout`](RxChannel::recv_with_data_timeout). See the documentation of
+    /// that function for more details, including the errors that can be given.
+    async fn recv_with_data_timeout<T: Timer>(
+        &mut self,
+        dest: &mut [u8],
+        timer: &mut T,
+    ) -> Result<usize>;
+
+    /// Receives data from the channel like [`RxChannel::recv_with_timeout`](RxChannel::recv_with_timeout).
+    /// See the documentation of that function for more details, including the errors that can be given.
+    async fn recv_with_timeout<T: Timer>(
+        &mut self,
+        dest: &mut [u8],
+        timer: &mut T,
+    ) -> Result<usize>;
+
+    /// Receives data from the channel like
+    /// [`RxChannel::recv_view_with_data_timeout`](RxChannel::recv_view_with_data_timeout), returning a view
+    /// of the message received inside of ``scratch``.
+    async fn recv_view_with_data_timeout<'a, T: Timer>(
+        &mut self,
+        scratch: &'a mut [u8],
+        timer: &mut T,
+    ) -> Result<&'a [u8]> {
+        let len = self.recv_with_data_timeout(scratch, timer).await?;
+
+        Ok(&scratch[..len])
+    }
+
+    /// Receives data from the channel like
+    /// [`RxChannel::recv_view_with_timeout`](RxChannel::recv_view_with_timeout), returning a view of the
+    /// message received inside of ``scratch``.
+    async fn recv_view_with_timeout<'a, T: Timer>(
+        &mut self,
+        scratch: &'a mut [u8],
+        timer: &mut T,
+    ) -> Result<&'a [u8]> {
+        let len = self.recv_with_timeout(scratch, timer).await?;
+
+        Ok(&scratch[..len])
+    }
+
+    /// Takes the number of bytes this channel discarded while looking for the start of a frame since this
+    /// was last called, like [`RxChannel::take_discarded_bytes`].
+    fn take_discarded_bytes(&mut self) -> usize {
+        0
+    }
+}
+
+/// An async channel to send data through. This is the async counterpart of [`TxChannel`].
+#[allow(async_fn_in_trait)]
+pub trait AsyncTxChannel {
+    /// Sends the data from ``src`` through the channel like [`TxChannel::send`](TxChannel::send). See the
+    /// documentation of that function for more details, including the errors that can be given.
+    async fn send(&mut self, src: &mut [u8]) -> Result<()>;
+}
+
+/// Yields to the executor once, letting other tasks run before this one continues. Async channels call
+/// this while waiting for data so they don't need any executor-specific way of being woken up.
+pub async fn yield_now() {
+    YieldNow { yielded: false }.await
+}
+
+/// The future returned by [`yield_now`], which is pending the first time it's polled and ready the
+/// second time.
+struct YieldNow {
+    yielded: bool,
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.yielded {
+            return Poll::Ready(());
+        }
+
+        self.yielded = true;
+
+        // Wake ourselves right away so the executor polls this task again after the others.
+        cx.waker().wake_by_ref();
+
+        Poll::Pending
+    }
+}
+
 /// The possible errors that can occur while sending or receiving data through an [`RxChannel`] or a
 /// [`TxChannel`].
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[non_exhaustive]
 pub enum CommunicationError {
-    /// An error that can occur during a receive operation. See [RxChannel::recv_with_timeout] and
-    /// [RxChannel::recv_with_data_timeout] for more details.
+    /// An error that can occur during a receive operation that isn't covered by a more specific error.
+    /// See [RxChannel::recv_with_timeout] and [RxChannel::recv_with_data_timeout] for more details.
     RecvError,
 
     /// An error that can occur during a send operation. See [TxChannel::send] for more details.
@@ -109,4 +327,53 @@ pub enum CommunicationError {
 
     /// An error that can occur if an internal error is encountered that should never happen.
     InternalError,
+
+    /// An error that can occur during a receive operation if a frame was received with a header or checksum
+    /// that doesn't match its contents, such as with [`CrcFraming`](lower_layers::framing::CrcFraming), or
+    /// a frame that was cut short, such as a [`CobsFraming`](lower_layers::framing::CobsFraming) frame with
+    /// a NULL character in the middle of a block.
+    CorruptFrame,
+
+    /// An error that can occur during a receive operation if the timer expired before a whole message
+    /// was received.
+    Timeout,
+
+    /// An error that can occur during a receive operation if a received message couldn't be
+    /// authenticated by a channel in the [`crypto`](lower_layers::crypto) layer.
+    AuthenticationFailure,
+
+    /// An error that can occur during a receive operation if the provided buffer is too small to fit
+    /// the message received along with any metadata.
+    BufferTooSmall,
+
+    /// An error that can occur during a receive operation if a frame sent with
+    /// [`BogoFraming`](lower_layers::framing::BogoFraming) contains a character that isn't a hex digit
+    /// or an odd number of hex digits.
+    MalformedHexNibble,
+
+    /// An error that can occur during a receive operation if the message received is shorter than the
+    /// minimum message length of the channel.
+    FrameTooShort,
+
+    /// An error that can occur during a receive operation if replay protection is enabled and the
+    /// message received was already received or is too old to be checked.
+    ReplayedMessage,
+
+    /// An error that can occur while sending or receiving through a
+    /// [`MessageChannel`](message_channel::MessageChannel) if a message couldn't be serialized or
+    /// deserialized.
+    EncodingError,
+
+    /// An error that can occur during a receive operation if padding is enabled and the message received
+    /// doesn't end with valid padding, such as if the sender doesn't pad its messages.
+    MalformedPadding,
+
+    /// An error that can occur during a [`handshake`] if a handshake message is malformed or the remote
+    /// party's static key is rejected.
+    HandshakeFailure,
+
+    /// An error that can occur during a receive operation if a message was sent with a protocol version
+    /// that isn't supported, or if a peer has no protocol version in common with this one. See the
+    /// [`messages`](crate::messages) module for more details.
+    UnsupportedVersion,
 }
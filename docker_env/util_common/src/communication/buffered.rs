@@ -0,0 +1,106 @@
+//! This module contains a channel that owns the buffer messages are received into, so callers get a view
+//! of each message received without passing a buffer to every receive.
+//!
+//! - Buffered channels
+//!     - A [`BufferedRxChannel`] wraps around an [`RxChannel`] or [`AsyncRxChannel`] and receives into its
+//!       own buffer of ``N`` bytes, returning a view of the message inside of it. The view borrows the
+//!       channel, so it must be dropped before the next receive.
+//!     - ``N`` must fit the largest message along with the
+//!       [`METADATA_SIZE`](RxChannel::METADATA_SIZE) of the wrapped channel, so it's written as the largest
+//!       message size plus that constant where the channel stack is put together, such as
+//!       ``BufferedRxChannel<C, { 256 + <C as RxChannel>::METADATA_SIZE }>`` for messages of up to 256 bytes.
+//!       Receiving on a channel with an ``N`` that can't fit any message fails to compile.
+//!     - The buffer keeps the last message received until it's overwritten by the next one. Messages that
+//!       shouldn't stay in memory can be erased with [`BufferedRxChannel::clear`].
+
+use zeroize::Zeroize;
+
+use crate::{
+    communication::{self, AsyncRxChannel, RxChannel},
+    timer::Timer,
+};
+
+/// The message given when the buffer of a [`BufferedRxChannel`] can't fit any message.
+const BUFFER_TOO_SMALL: &str = "The buffer can't fit the metadata of the wrapped channel.";
+
+/// A channel that receives messages into its own buffer of ``N`` bytes. See the [`module`](self)
+/// documentation for more details.
+pub struct BufferedRxChannel<T, const N: usize> {
+    channel: T,
+    buf: [u8; N],
+}
+
+impl<T, const N: usize> BufferedRxChannel<T, N> {
+    /// Creates a new [`BufferedRxChannel`] around ``channel``.
+    pub fn new(channel: T) -> Self {
+        Self {
+            channel,
+            buf: [0; N],
+        }
+    }
+
+    /// Erases the last message received from the buffer.
+    pub fn clear(&mut self) {
+        self.buf.zeroize();
+    }
+
+    /// Gets a mutable reference to the wrapped channel.
+    pub fn channel_mut(&mut self) -> &mut T {
+        &mut self.channel
+    }
+
+    /// Unwraps this channel, returning the wrapped channel.
+    pub fn into_inner(self) -> T {
+        self.channel
+    }
+}
+
+impl<T: RxChannel, const N: usize> BufferedRxChannel<T, N> {
+    /// Receives a message like [`RxChannel::recv_with_data_timeout`], returning a view of it inside of
+    /// this channel's buffer. See the documentation of that function for the errors that can be given.
+    pub fn recv_with_data_timeout<U: Timer>(
+        &mut self,
+        timer: &mut U,
+    ) -> communication::Result<&[u8]> {
+        const { assert!(N > T::METADATA_SIZE, "{}", BUFFER_TOO_SMALL) };
+
+        self.channel
+            .recv_view_with_data_timeout(&mut self.buf, timer)
+    }
+
+    /// Receives a message like [`RxChannel::recv_with_timeout`], returning a view of it inside of this
+    /// channel's buffer. See the documentation of that function for the errors that can be given.
+    pub fn recv_with_timeout<U: Timer>(&mut self, timer: &mut U) -> communication::Result<&[u8]> {
+        const { assert!(N > T::METADATA_SIZE, "{}", BUFFER_TOO_SMALL) };
+
+        self.channel.recv_view_with_timeout(&mut self.buf, timer)
+    }
+}
+
+impl<T: AsyncRxChannel, const N: usize> BufferedRxChannel<T, N> {
+    /// Receives a message like [`AsyncRxChannel::recv_with_data_timeout`], returning a view of it inside
+    /// of this channel's buffer.
+    pub async fn async_recv_with_data_timeout<U: Timer>(
+        &mut self,
+        timer: &mut U,
+    ) -> communication::Result<&[u8]> {
+        const { assert!(N > T::METADATA_SIZE, "{}", BUFFER_TOO_SMALL) };
+
+        self.channel
+            .recv_view_with_data_timeout(&mut self.buf, timer)
+            .await
+    }
+
+    /// Receives a message like [`AsyncRxChannel::recv_with_timeout`], returning a view of it inside of
+    /// this channel's buffer.
+    pub async fn async_recv_with_timeout<U: Timer>(
+        &mut self,
+        timer: &mut U,
+    ) -> communication::Result<&[u8]> {
+        const { assert!(N > T::METADATA_SIZE, "{}", BUFFER_TOO_SMALL) };
+
+        self.channel
+            .recv_view_with_timeout(&mut self.buf, timer)
+            .await
+    }
+}
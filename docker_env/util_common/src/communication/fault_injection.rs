@@ -0,0 +1,380 @@
+//! This module contains channels that wrap around other channels to inject faults into the data
+//! passing through them, which can be used to test how the BogoStack and the flows built on it behave
+//! under line noise and adversarial tampering.
+//!
+//! - Fault-injecting channels
+//!     - A [`FaultyTxChannel`] wraps around a [`FramedTxChannel`] and a [`FaultyRxChannel`] wraps around an
+//!       [`RxChannel`]. Both can hold messages of up to ``N`` bytes.
+//!     - Faults are chosen by a [`SeededRng`], so a run can be reproduced by reusing its seed.
+//!     - The rate of each fault is set in a [`FaultConfig`] as the number of times out of [`RATE_SCALE`] the
+//!       fault occurs. Byte faults are applied to each byte of a message and frame faults are applied to each
+//!       message.
+//!     - Byte faults drop, flip, duplicate, or inject bytes in a message. Frame faults drop, duplicate, delay,
+//!       or inject whole messages. A delayed message is sent or received after the message following it.
+//!     - Faults are applied to the messages passed to the wrapped channel, so wrapping a framing layer channel
+//!       tests the layers above it, such as the [`crypto`](crate::communication::lower_layers::crypto) layer.
+
+use crate::{
+    communication::{
+        self,
+        lower_layers::{
+            crypto::RandomSource,
+            framing::{Frame, FramedTxChannel},
+        },
+        CommunicationError, RxChannel,
+    },
+    timer::Timer,
+};
+
+/// The scale of the rates in a [`FaultConfig`]. A rate of [`RATE_SCALE`] means the fault always occurs.
+pub const RATE_SCALE: u32 = 10_000;
+
+/// The rates of the faults injected by a [`FaultyTxChannel`] or a [`FaultyRxChannel`]. Each rate is the
+/// number of times out of [`RATE_SCALE`] the fault occurs. The default configuration injects no faults.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct FaultConfig {
+    /// The rate at which each byte is dropped.
+    pub byte_drop_rate: u32,
+
+    /// The rate at which one random bit in each byte is flipped.
+    pub byte_flip_rate: u32,
+
+    /// The rate at which each byte is duplicated.
+    pub byte_duplicate_rate: u32,
+
+    /// The rate at which a random byte is injected before each byte.
+    pub byte_inject_rate: u32,
+
+    /// The rate at which each message is dropped.
+    pub frame_drop_rate: u32,
+
+    /// The rate at which each message is duplicated.
+    pub frame_duplicate_rate: u32,
+
+    /// The rate at which each message is delayed until after the message following it.
+    pub frame_delay_rate: u32,
+
+    /// The rate at which a message of random bytes is injected before each message.
+    pub frame_inject_rate: u32,
+}
+
+/// A small seeded pseudorandom number generator used to choose faults. This is not cryptographically
+/// secure and must only be used for testing.
+#[derive(Debug, Clone)]
+pub struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    /// Creates a new [`SeededRng`] from a seed. The same seed always produces the same numbers.
+    pub fn new(seed: u64) -> Self {
+        let state = seed ^ 0x9E37_79B9_7F4A_7C15;
+
+        // The state of xorshift can't be zero.
+        Self {
+            state: if state == 0 { 1 } else { state },
+        }
+    }
+
+    /// Gets the next pseudorandom number using xorshift64*.
+    pub fn next_u32(&mut self) -> u32 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+
+        (self.state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 32) as u32
+    }
+
+    /// Gets a pseudorandom number less than ``bound``, which must not be zero.
+    fn below(&mut self, bound: u32) -> u32 {
+        self.next_u32() % bound
+    }
+
+    /// Returns ``true`` ``rate`` times out of [`RATE_SCALE`].
+    fn chance(&mut self, rate: u32) -> bool {
+        rate > 0 && self.below(RATE_SCALE) < rate
+    }
+}
+
+impl RandomSource for SeededRng {
+    fn fill_rand_slice<T: AsMut<[u8]>>(&mut self, mut slice_ref: T) {
+        for byte in slice_ref.as_mut() {
+            *byte = self.next_u32() as u8;
+        }
+    }
+}
+
+/// A message held by a fault-injecting channel to be sent or received later.
+#[derive(Copy, Clone)]
+struct HeldMessage<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+}
+
+/// The state shared by both fault-injecting channels.
+struct FaultInjector {
+    config: FaultConfig,
+    rng: SeededRng,
+}
+
+impl FaultInjector {
+    /// Copies ``src`` into ``dest`` with byte faults applied, returning the number of bytes written.
+    /// Bytes that don't fit in ``dest`` are dropped.
+    fn mangle(&mut self, src: &[u8], dest: &mut [u8]) -> usize {
+        let mut ct = 0;
+        let mut push = |byte| {
+            if let Some(dest_byte) = dest.get_mut(ct) {
+                *dest_byte = byte;
+                ct += 1;
+            }
+        };
+
+        for &byte in src {
+            if self.rng.chance(self.config.byte_inject_rate) {
+                push(self.rng.next_u32() as u8);
+            }
+
+            if self.rng.chance(self.config.byte_drop_rate) {
+                continue;
+            }
+
+            let byte = if self.rng.chance(self.config.byte_flip_rate) {
+                byte ^ (1 << self.rng.below(8))
+            } else {
+                byte
+            };
+
+            push(byte);
+
+            if self.rng.chance(self.config.byte_duplicate_rate) {
+                push(byte);
+            }
+        }
+
+        ct
+    }
+
+    /// Fills ``dest`` with a message of random bytes, returning its length.
+    fn random_message(&mut self, dest: &mut [u8]) -> usize {
+        if dest.is_empty() {
+            return 0;
+        }
+
+        let len = self.rng.below(dest.len() as u32) as usize + 1;
+        self.rng.fill_rand_slice(&mut dest[..len]);
+
+        len
+    }
+}
+
+/// A [`FramedTxChannel`] that wraps around another [`FramedTxChannel`] and injects faults into the
+/// messages sent through it. Messages longer than ``N`` bytes can't be sent. See the [`module`](self)
+/// documentation for more details.
+pub struct FaultyTxChannel<T: FramedTxChannel, const N: usize> {
+    channel: T,
+    injector: FaultInjector,
+    delayed: Option<HeldMessage<N>>,
+}
+
+impl<T: FramedTxChannel, const N: usize> FaultyTxChannel<T, N> {
+    /// Creates a new [`FaultyTxChannel`] around ``channel`` that injects faults at the rates in ``config``,
+    /// choosing them with a [`SeededRng`] seeded with ``seed``.
+    pub fn new(channel: T, config: FaultConfig, seed: u64) -> Self {
+        Self {
+            channel,
+            injector: FaultInjector {
+                config,
+                rng: SeededRng::new(seed),
+            },
+            delayed: None,
+        }
+    }
+
+    /// Gets a mutable reference to the wrapped channel.
+    pub fn channel_mut(&mut self) -> &mut T {
+        &mut self.channel
+    }
+
+    /// Unwraps this channel, returning the wrapped channel. Any delayed message is discarded.
+    pub fn into_inner(self) -> T {
+        self.channel
+    }
+
+    fn send_raw(&mut self, src: &[u8]) -> communication::Result<()> {
+        self.channel.frame::<1>(|| Frame::new().append(src))
+    }
+}
+
+impl<T: FramedTxChannel, const N: usize> FramedTxChannel for FaultyTxChannel<T, N> {
+    fn frame<'a, const FRAME_CT: usize>(
+        &mut self,
+        frame: impl FnOnce() -> communication::Result<Frame<'a, FRAME_CT>>,
+    ) -> communication::Result<()> {
+        let frame = frame()?;
+
+        if frame.len() > N {
+            return Err(CommunicationError::SendError);
+        }
+
+        let mut src = [0; N];
+        let mut src_len = 0;
+
+        for frame_piece in frame {
+            src[src_len..src_len + frame_piece.len()].copy_from_slice(frame_piece);
+            src_len += frame_piece.len();
+        }
+
+        let config = self.injector.config;
+
+        if self.injector.rng.chance(config.frame_inject_rate) {
+            let mut injected = [0; N];
+            let injected_len = self.injector.random_message(&mut injected);
+            self.send_raw(&injected[..injected_len])?;
+        }
+
+        if self.injector.rng.chance(config.frame_drop_rate) {
+            return Ok(());
+        }
+
+        let mut message = HeldMessage {
+            buf: [0; N],
+            len: 0,
+        };
+        message.len = self.injector.mangle(&src[..src_len], &mut message.buf);
+
+        // Only one message is delayed at a time.
+        if self.delayed.is_none() && self.injector.rng.chance(config.frame_delay_rate) {
+            self.delayed = Some(message);
+            return Ok(());
+        }
+
+        self.send_raw(&message.buf[..message.len])?;
+
+        if self.injector.rng.chance(config.frame_duplicate_rate) {
+            self.send_raw(&message.buf[..message.len])?;
+        }
+
+        if let Some(delayed) = self.delayed.take() {
+            self.send_raw(&delayed.buf[..delayed.len])?;
+        }
+
+        Ok(())
+    }
+}
+
+/// An [`RxChannel`] that wraps around another [`RxChannel`] and injects faults into the messages
+/// received from it. Messages longer than ``N`` bytes can't be received. See the [`module`](self)
+/// documentation for more details.
+pub struct FaultyRxChannel<T: RxChannel, const N: usize> {
+    channel: T,
+    injector: FaultInjector,
+    delayed: Option<HeldMessage<N>>,
+    pending: Option<HeldMessage<N>>,
+}
+
+impl<T: RxChannel, const N: usize> FaultyRxChannel<T, N> {
+    /// Creates a new [`FaultyRxChannel`] around ``channel`` that injects faults at the rates in ``config``,
+    /// choosing them with a [`SeededRng`] seeded with ``seed``.
+    pub fn new(channel: T, config: FaultConfig, seed: u64) -> Self {
+        Self {
+            channel,
+            injector: FaultInjector {
+                config,
+                rng: SeededRng::new(seed),
+            },
+            delayed: None,
+            pending: None,
+        }
+    }
+
+    /// Gets a mutable reference to the wrapped channel.
+    pub fn channel_mut(&mut self) -> &mut T {
+        &mut self.channel
+    }
+
+    /// Unwraps this channel, returning the wrapped channel. Any delayed or duplicated messages are
+    /// discarded.
+    pub fn into_inner(self) -> T {
+        self.channel
+    }
+
+    fn recv_with<U: Timer>(
+        &mut self,
+        dest: &mut [u8],
+        mut recv_method: impl FnMut(&mut T, &mut [u8], &mut U) -> communication::Result<usize>,
+        timer: &mut U,
+    ) -> communication::Result<usize> {
+        // Messages that were duplicated or delayed are received before any new messages.
+        if let Some(message) = self.pending.take() {
+            return copy_message(&message, dest);
+        }
+
+        let config = self.injector.config;
+
+        if self.injector.rng.chance(config.frame_inject_rate) {
+            let injected_len = self.injector.random_message(dest);
+
+            return Ok(injected_len);
+        }
+
+        let mut src = [0; N];
+
+        loop {
+            let src_len = recv_method(&mut self.channel, &mut src, timer)?;
+
+            if self.injector.rng.chance(config.frame_drop_rate) {
+                continue;
+            }
+
+            let mut message = HeldMessage {
+                buf: [0; N],
+                len: 0,
+            };
+            message.len = self.injector.mangle(&src[..src_len], &mut message.buf);
+
+            // Only one message is delayed at a time.
+            if self.delayed.is_none() && self.injector.rng.chance(config.frame_delay_rate) {
+                self.delayed = Some(message);
+                continue;
+            }
+
+            if self.injector.rng.chance(config.frame_duplicate_rate) {
+                self.pending = Some(message);
+            } else {
+                self.pending = self.delayed.take();
+            }
+
+            return copy_message(&message, dest);
+        }
+    }
+}
+
+/// Copies a held message into ``dest``, returning its length.
+fn copy_message<const N: usize>(
+    message: &HeldMessage<N>,
+    dest: &mut [u8],
+) -> communication::Result<usize> {
+    dest.get_mut(..message.len)
+        .ok_or(CommunicationError::BufferTooSmall)?
+        .copy_from_slice(&message.buf[..message.len]);
+
+    Ok(message.len)
+}
+
+impl<T: RxChannel, const N: usize> RxChannel for FaultyRxChannel<T, N> {
+    fn recv_with_data_timeout<U: Timer>(
+        &mut self,
+        dest: &mut [u8],
+        timer: &mut U,
+    ) -> communication::Result<usize> {
+        self.recv_with(dest, |ch, d, t| ch.recv_with_data_timeout(d, t), timer)
+    }
+
+    fn recv_with_timeout<U: Timer>(
+        &mut self,
+        dest: &mut [u8],
+        timer: &mut U,
+    ) -> communication::Result<usize> {
+        self.recv_with(dest, |ch, d, t| ch.recv_with_timeout(d, t), timer)
+    }
+}
@@ -0,0 +1,298 @@
+//! This module contains a fragmentation layer that lets messages larger than one frame be sent through
+//! any [`TxChannel`] and reassembled from any [`RxChannel`].
+//!
+//! - Fragmentation
+//!     - A [`FragmentingTxChannel`] splits each message into numbered fragments of up to ``N`` bytes,
+//!       including a [`FRAGMENT_HEADER_SIZE`] byte header, and sends each one as its own message.
+//!     - The header contains the ID of the message, the index of the fragment, the number of fragments in
+//!       the message, and the size of every fragment but the last. This lets the receiver place each fragment
+//!       in the message without knowing how the sender was configured.
+//! - Reassembly
+//!     - A [`ReassemblingRxChannel`] receives fragments into a buffer of ``N`` bytes, which must also fit any
+//!       metadata needed by the wrapped channel, and reassembles them directly into the caller's buffer or
+//!       into a [`heapless::Vec`].
+//!     - Fragments must arrive in order. A fragment that doesn't continue the message being reassembled
+//!       discards it, and reassembly starts over at the next first fragment. On a channel that can lose
+//!       messages, this layer should be used on top of a
+//!       [`ReliableChannel`](crate::communication::reliable::ReliableChannel).
+
+use heapless::Vec;
+
+use crate::{
+    communication::{self, CommunicationError, RxChannel, TxChannel},
+    timer::Timer,
+};
+
+/// The size of the header at the start of every fragment.
+pub const FRAGMENT_HEADER_SIZE: usize = 7;
+
+/// The header at the start of every fragment.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct FragmentHeader {
+    message_id: u8,
+    index: u16,
+    count: u16,
+    fragment_size: u16,
+}
+
+impl FragmentHeader {
+    fn to_bytes(self) -> [u8; FRAGMENT_HEADER_SIZE] {
+        let [index_hi, index_lo] = self.index.to_be_bytes();
+        let [count_hi, count_lo] = self.count.to_be_bytes();
+        let [size_hi, size_lo] = self.fragment_size.to_be_bytes();
+
+        [
+            self.message_id,
+            index_hi,
+            index_lo,
+            count_hi,
+            count_lo,
+            size_hi,
+            size_lo,
+        ]
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        match *bytes {
+            [message_id, index_hi, index_lo, count_hi, count_lo, size_hi, size_lo, ..] => {
+                Some(Self {
+                    message_id,
+                    index: u16::from_be_bytes([index_hi, index_lo]),
+                    count: u16::from_be_bytes([count_hi, count_lo]),
+                    fragment_size: u16::from_be_bytes([size_hi, size_lo]),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A [`TxChannel`] that splits messages into fragments of up to ``N`` bytes, including the header, and
+/// sends them through another [`TxChannel`]. See the [`module`](self) documentation for more details.
+pub struct FragmentingTxChannel<T: TxChannel, const N: usize> {
+    channel: T,
+    next_message_id: u8,
+}
+
+impl<T: TxChannel, const N: usize> FragmentingTxChannel<T, N> {
+    /// Creates a new [`FragmentingTxChannel`] around ``channel``.
+    pub fn new(channel: T) -> Self {
+        Self {
+            channel,
+            next_message_id: 0,
+        }
+    }
+
+    /// Gets a mutable reference to the wrapped channel.
+    pub fn channel_mut(&mut self) -> &mut T {
+        &mut self.channel
+    }
+
+    /// Unwraps this channel, returning the wrapped channel.
+    pub fn into_inner(self) -> T {
+        self.channel
+    }
+}
+
+impl<T: TxChannel, const N: usize> TxChannel for FragmentingTxChannel<T, N> {
+    /// Sends the data from ``src`` through the channel as one or more fragments. Upon an error, a
+    /// [`CommunicationError`] is given.
+    ///
+    /// # ERRORS:
+    ///
+    /// - [`CommunicationError::SendError`]
+    ///   - ``N`` is too small to fit a fragment header and at least one byte of data.
+    ///   - The message needs more than [`u16::MAX`] fragments.
+    /// - Any error given by the wrapped channel while sending a fragment.
+    fn send(&mut self, src: &mut [u8]) -> communication::Result<()> {
+        let fragment_size = N
+            .checked_sub(FRAGMENT_HEADER_SIZE)
+            .filter(|&size| size > 0)
+            .ok_or(CommunicationError::SendError)?
+            .min(u16::MAX as usize);
+
+        // An empty message is still sent as one empty fragment.
+        let count: u16 = src
+            .len()
+            .div_ceil(fragment_size)
+            .max(1)
+            .try_into()
+            .map_err(|_| CommunicationError::SendError)?;
+
+        let message_id = self.next_message_id;
+        self.next_message_id = self.next_message_id.wrapping_add(1);
+
+        for index in 0..count {
+            let start = index as usize * fragment_size;
+            let piece = &src[start..(start + fragment_size).min(src.len())];
+            let header = FragmentHeader {
+                message_id,
+                index,
+                count,
+                fragment_size: fragment_size as u16,
+            };
+
+            // The wrapped channel can modify the message in place, so each fragment is copied.
+            let mut fragment = [0; N];
+            fragment[..FRAGMENT_HEADER_SIZE].copy_from_slice(&header.to_bytes());
+            fragment[FRAGMENT_HEADER_SIZE..FRAGMENT_HEADER_SIZE + piece.len()]
+                .copy_from_slice(piece);
+
+            self.channel
+                .send(&mut fragment[..FRAGMENT_HEADER_SIZE + piece.len()])?;
+        }
+
+        Ok(())
+    }
+}
+
+/// An [`RxChannel`] that receives fragments of up to ``N`` bytes, including the header and any metadata
+/// needed by the wrapped channel, and reassembles them into whole messages. See the [`module`](self)
+/// documentation for more details.
+pub struct ReassemblingRxChannel<T: RxChannel, const N: usize> {
+    channel: T,
+    fragment_buf: [u8; N],
+}
+
+impl<T: RxChannel, const N: usize> ReassemblingRxChannel<T, N> {
+    /// Creates a new [`ReassemblingRxChannel`] around ``channel``.
+    pub fn new(channel: T) -> Self {
+        Self {
+            channel,
+            fragment_buf: [0; N],
+        }
+    }
+
+    /// Gets a mutable reference to the wrapped channel.
+    pub fn channel_mut(&mut self) -> &mut T {
+        &mut self.channel
+    }
+
+    /// Unwraps this channel, returning the wrapped channel.
+    pub fn into_inner(self) -> T {
+        self.channel
+    }
+
+    /// Receives a whole message into ``dest`` like [`recv_with_timeout`](RxChannel::recv_with_timeout),
+    /// resizing ``dest`` to the length of the message.
+    pub fn recv_vec_with_timeout<U: Timer, const M: usize>(
+        &mut self,
+        dest: &mut Vec<u8, M>,
+        timer: &mut U,
+    ) -> communication::Result<usize> {
+        recv_into_vec(dest, |d| self.recv_with_timeout(d, timer))
+    }
+
+    /// Receives a whole message into ``dest`` like
+    /// [`recv_with_data_timeout`](RxChannel::recv_with_data_timeout), resizing ``dest`` to the length of
+    /// the message.
+    pub fn recv_vec_with_data_timeout<U: Timer, const M: usize>(
+        &mut self,
+        dest: &mut Vec<u8, M>,
+        timer: &mut U,
+    ) -> communication::Result<usize> {
+        recv_into_vec(dest, |d| self.recv_with_data_timeout(d, timer))
+    }
+
+    fn recv_with<U: Timer>(
+        &mut self,
+        dest: &mut [u8],
+        mut recv_method: impl FnMut(&mut T, &mut [u8], &mut U) -> communication::Result<usize>,
+        timer: &mut U,
+    ) -> communication::Result<usize> {
+        // The header of the first fragment of the message being reassembled and the number of bytes
+        // reassembled so far.
+        let mut current: Option<(FragmentHeader, usize)> = None;
+
+        loop {
+            let len = recv_method(&mut self.channel, &mut self.fragment_buf, timer)?;
+            let header = match FragmentHeader::from_bytes(&self.fragment_buf[..len]) {
+                Some(header) if header.fragment_size > 0 && header.index < header.count => header,
+                _ => {
+                    current = None;
+                    continue;
+                }
+            };
+            let piece = &self.fragment_buf[FRAGMENT_HEADER_SIZE..len];
+            let fragment_size = header.fragment_size as usize;
+            let is_last = header.index + 1 == header.count;
+
+            // Every fragment but the last must be full.
+            if piece.len() > fragment_size || (!is_last && piece.len() < fragment_size) {
+                current = None;
+                continue;
+            }
+
+            // A first fragment always starts reassembly over. Any other fragment must be the next
+            // fragment of the message being reassembled.
+            if header.index == 0 {
+                current = Some((header, 0));
+            }
+
+            let Some((first, ct)) = &mut current else {
+                continue;
+            };
+
+            if header.message_id != first.message_id
+                || header.count != first.count
+                || header.fragment_size != first.fragment_size
+                || header.index as usize != *ct / fragment_size
+            {
+                current = None;
+                continue;
+            }
+
+            dest.get_mut(*ct..*ct + piece.len())
+                .ok_or(CommunicationError::BufferTooSmall)?
+                .copy_from_slice(piece);
+            *ct += piece.len();
+
+            if is_last {
+                return Ok(*ct);
+            }
+        }
+    }
+}
+
+/// Receives a message into the whole capacity of ``dest`` with ``recv``, resizing ``dest`` to the
+/// length of the message.
+fn recv_into_vec<const M: usize>(
+    dest: &mut Vec<u8, M>,
+    recv: impl FnOnce(&mut [u8]) -> communication::Result<usize>,
+) -> communication::Result<usize> {
+    dest.clear();
+
+    // Filling to capacity can't fail.
+    let _ = dest.resize_default(M);
+    let result = recv(dest);
+
+    dest.truncate(*result.as_ref().unwrap_or(&0));
+
+    result
+}
+
+impl<T: RxChannel, const N: usize> RxChannel for ReassemblingRxChannel<T, N> {
+    /// Receives a whole message from the channel like
+    /// [`RxChannel::recv_with_data_timeout`](crate::communication::RxChannel::recv_with_data_timeout),
+    /// reassembling it from its fragments. ``dest`` only needs to fit the message itself. If ``dest`` is
+    /// too small, a [`CommunicationError::BufferTooSmall`] is given and the rest of the message is discarded.
+    fn recv_with_data_timeout<U: Timer>(
+        &mut self,
+        dest: &mut [u8],
+        timer: &mut U,
+    ) -> communication::Result<usize> {
+        self.recv_with(dest, |ch, d, t| ch.recv_with_data_timeout(d, t), timer)
+    }
+
+    /// Receives a whole message from the channel like
+    /// [`RxChannel::recv_with_timeout`](crate::communication::RxChannel::recv_with_timeout),
+    /// reassembling it from its fragments. ``dest`` only needs to fit the message itself. If ``dest`` is
+    /// too small, a [`CommunicationError::BufferTooSmall`] is given and the rest of the message is discarded.
+    fn recv_with_timeout<U: Timer>(
+        &mut self,
+        dest: &mut [u8],
+        timer: &mut U,
+    ) -> communication::Result<usize> {
+        self.recv_with(dest, |ch, d, t| ch.recv_with_timeout(d, t), timer)
+    }
+}
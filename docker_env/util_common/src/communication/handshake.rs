@@ -0,0 +1,488 @@
+//! This module contains a handshake that establishes a session between two parties over any channel
+//! implementing [`RxChannel`] and [`FramedTxChannel`], producing a pair of keyed
+//! [`crypto`](crate::communication::lower_layers::crypto) channels, so new protocols don't each need their
+//! own key exchange.
+//!
+//! - The handshake
+//!     - The handshake implements the Noise XX pattern as ``Noise_XX_secp256k1_ChaChaPoly_SHA256``. Both
+//!       parties have a static secp256k1 key pair and learn each other's static public key during the
+//!       handshake, so neither needs to know the other's key beforehand.
+//!     - The handshake takes three messages: the initiator sends its ephemeral key, the responder sends its
+//!       ephemeral key and encrypted static key, and the initiator sends its encrypted static key. Messages
+//!       carry no payloads. Public keys are sent as compressed SEC1 points, and the result of a
+//!       Diffie-Hellman operation is the x-coordinate of the shared point.
+//!     - Each party checks the other's static public key with a callback, such as by checking that it's
+//!       signed or that it's in a list of known keys. The initiator checks the responder's key before
+//!       revealing its own static key. A rejected key gives a [`CommunicationError::HandshakeFailure`].
+//!       The initiator finishes before the responder checks its key, so an initiator that was rejected only
+//!       finds out when its messages go unanswered.
+//!     - Both parties can give a prologue, which is authenticated by the handshake and must be the same on
+//!       both sides. This can bind the session to a protocol name and version.
+//! - Sessions
+//!     - A completed handshake gives a [`NoiseSession`], which contains an
+//!       [`XChacha20Poly1305RxChannel`] and an [`XChacha20Poly1305TxChannel`] wrapping the channels the
+//!       handshake ran over. Each direction has its own key, and both channels use sequence numbers with
+//!       replay protection, so buffers used to receive messages must have space for
+//!       [`SEQUENCED_METADATA_SIZE`](crate::communication::lower_layers::crypto::SEQUENCED_METADATA_SIZE)
+//!       bytes of metadata.
+//!     - The session also contains the remote static public key and the handshake hash, which is unique to
+//!       the session and can be signed to bind other data to it.
+//!     - A handshake that fails can't be resumed. A new handshake must be started on both sides.
+
+use crate::{
+    communication::{
+        self,
+        lower_layers::{
+            crypto::{RandomSource, XChacha20Poly1305RxChannel, XChacha20Poly1305TxChannel},
+            framing::FramedTxChannel,
+        },
+        CommunicationError, RxChannel,
+    },
+    timer::Timer,
+};
+use chacha20poly1305::{
+    aead::{AeadInPlace, KeyInit},
+    ChaCha20Poly1305, Key, Nonce, Tag,
+};
+use hkdf::Hkdf;
+use k256::{ecdh, elliptic_curve::sec1::ToEncodedPoint, NonZeroScalar, PublicKey, SecretKey};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroize;
+
+/// The name of the Noise protocol implemented by this module.
+pub const PROTOCOL_NAME: &[u8] = b"Noise_XX_secp256k1_ChaChaPoly_SHA256";
+
+/// The size of a public key sent during the handshake, which is a compressed SEC1 point.
+pub const PUBLIC_KEY_SIZE: usize = 33;
+
+/// The size of the authentication tag added to each encrypted part of a handshake message.
+pub const TAG_SIZE: usize = 16;
+
+/// The size of the handshake hash of a [`NoiseSession`].
+pub const HANDSHAKE_HASH_SIZE: usize = 32;
+
+/// The size of the first handshake message, which contains the initiator's ephemeral key.
+const MESSAGE_1_SIZE: usize = PUBLIC_KEY_SIZE;
+
+/// The size of the second handshake message, which contains the responder's ephemeral key, its encrypted
+/// static key, and the tag of its empty payload.
+const MESSAGE_2_SIZE: usize = PUBLIC_KEY_SIZE + PUBLIC_KEY_SIZE + TAG_SIZE + TAG_SIZE;
+
+/// The size of the third handshake message, which contains the initiator's encrypted static key and the tag
+/// of its empty payload.
+const MESSAGE_3_SIZE: usize = PUBLIC_KEY_SIZE + TAG_SIZE + TAG_SIZE;
+
+/// The size of the largest handshake message. Buffers used by the channels the handshake runs over must
+/// fit messages of this size along with any metadata needed by those channels.
+pub const MAX_HANDSHAKE_MESSAGE_SIZE: usize = MESSAGE_2_SIZE;
+
+/// The chaining key, handshake hash, and cipher key of a handshake, as described by the ``SymmetricState``
+/// and ``CipherState`` objects of the Noise specification.
+struct SymmetricState {
+    ck: [u8; 32],
+    h: [u8; 32],
+    k: Option<[u8; 32]>,
+    n: u64,
+}
+
+impl SymmetricState {
+    fn new(prologue: &[u8]) -> Self {
+        // The protocol name is longer than a hash, so it's hashed to form the initial handshake hash.
+        let h: [u8; 32] = Sha256::digest(PROTOCOL_NAME).into();
+        let mut state = Self {
+            ck: h,
+            h,
+            k: None,
+            n: 0,
+        };
+
+        state.mix_hash(prologue);
+
+        state
+    }
+
+    fn mix_hash(&mut self, data: &[u8]) {
+        self.h = Sha256::new()
+            .chain_update(self.h)
+            .chain_update(data)
+            .finalize()
+            .into();
+    }
+
+    /// Derives two keys from the chaining key and ``ikm``.
+    fn hkdf(&self, ikm: &[u8]) -> ([u8; 32], [u8; 32]) {
+        let mut okm = [0; 64];
+        let mut outputs = ([0; 32], [0; 32]);
+
+        // Expanding 64 bytes can't fail.
+        let _ = Hkdf::<Sha256>::new(Some(&self.ck), ikm).expand(&[], &mut okm);
+        outputs.0.copy_from_slice(&okm[..32]);
+        outputs.1.copy_from_slice(&okm[32..]);
+        okm.zeroize();
+
+        outputs
+    }
+
+    fn mix_key(&mut self, ikm: &[u8]) {
+        let (ck, k) = self.hkdf(ikm);
+
+        self.ck = ck;
+        self.k = Some(k);
+        self.n = 0;
+    }
+
+    fn nonce(&self) -> Nonce {
+        let mut nonce = Nonce::default();
+        nonce[4..].copy_from_slice(&self.n.to_le_bytes());
+
+        nonce
+    }
+
+    /// Encrypts the first ``len`` bytes of ``buf`` in place, appending the tag, and mixes the ciphertext
+    /// into the handshake hash. Returns the length of the ciphertext and tag.
+    fn encrypt_and_hash(&mut self, buf: &mut [u8], len: usize) -> communication::Result<usize> {
+        let cipher = match &self.k {
+            Some(k) => ChaCha20Poly1305::new(Key::from_slice(k)),
+            None => {
+                self.mix_hash(&buf[..len]);
+
+                return Ok(len);
+            }
+        };
+
+        let (plaintext, tag_buf) = buf.split_at_mut(len);
+        let tag = cipher
+            .encrypt_in_place_detached(&self.nonce(), &self.h, plaintext)
+            .map_err(|_| CommunicationError::InternalError)?;
+
+        tag_buf
+            .get_mut(..TAG_SIZE)
+            .ok_or(CommunicationError::InternalError)?
+            .copy_from_slice(&tag);
+        self.n += 1;
+        self.mix_hash(&buf[..len + TAG_SIZE]);
+
+        Ok(len + TAG_SIZE)
+    }
+
+    /// Decrypts ``buf`` in place, which ends with a tag if a key has been derived, and mixes the
+    /// ciphertext into the handshake hash. Returns the length of the plaintext.
+    fn decrypt_and_hash(&mut self, buf: &mut [u8]) -> communication::Result<usize> {
+        let cipher = match &self.k {
+            Some(k) => ChaCha20Poly1305::new(Key::from_slice(k)),
+            None => {
+                self.mix_hash(buf);
+
+                return Ok(buf.len());
+            }
+        };
+
+        let len = buf
+            .len()
+            .checked_sub(TAG_SIZE)
+            .ok_or(CommunicationError::HandshakeFailure)?;
+        let h = self.h;
+
+        // The ciphertext is mixed in before it's decrypted in place.
+        self.mix_hash(buf);
+
+        let (ciphertext, tag) = buf.split_at_mut(len);
+
+        cipher
+            .decrypt_in_place_detached(&self.nonce(), &h, ciphertext, Tag::from_slice(tag))
+            .map_err(|_| CommunicationError::AuthenticationFailure)?;
+        self.n += 1;
+
+        Ok(len)
+    }
+
+    /// Derives the keys used by the initiator to send and by the responder to send, in that order.
+    fn split(&self) -> ([u8; 32], [u8; 32]) {
+        self.hkdf(&[])
+    }
+}
+
+impl Drop for SymmetricState {
+    fn drop(&mut self) {
+        self.ck.zeroize();
+        self.h.zeroize();
+        self.k.zeroize();
+    }
+}
+
+/// A secret scalar used during the handshake, which is erased when dropped so it's erased on every way out
+/// of the handshake, including errors.
+struct SecretScalar(NonZeroScalar);
+
+impl Drop for SecretScalar {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+/// Performs a Diffie-Hellman operation, returning the x-coordinate of the shared point.
+fn dh(secret: &NonZeroScalar, public: &PublicKey) -> [u8; 32] {
+    let shared = ecdh::diffie_hellman(secret, public.as_affine());
+    let mut out = [0; 32];
+
+    out.copy_from_slice(shared.raw_secret_bytes());
+
+    out
+}
+
+/// Performs a Diffie-Hellman operation and mixes its result into the chaining key.
+fn mix_dh(state: &mut SymmetricState, secret: &NonZeroScalar, public: &PublicKey) {
+    let mut shared = dh(secret, public);
+
+    state.mix_key(&shared);
+    shared.zeroize();
+}
+
+fn encode_public_key(key: &PublicKey, dest: &mut [u8]) {
+    dest[..PUBLIC_KEY_SIZE].copy_from_slice(key.to_encoded_point(true).as_bytes());
+}
+
+fn decode_public_key(src: &[u8]) -> communication::Result<PublicKey> {
+    PublicKey::from_sec1_bytes(src).map_err(|_| CommunicationError::HandshakeFailure)
+}
+
+/// Generates an ephemeral secret key with ``random_source``.
+fn generate_ephemeral<U: RandomSource>(random_source: &mut U) -> SecretScalar {
+    let mut bytes = [0; 32];
+
+    loop {
+        random_source.fill_rand_slice(&mut bytes);
+
+        // Almost every 32-byte value is a valid scalar.
+        if let Ok(scalar) = NonZeroScalar::try_from(&bytes[..]) {
+            bytes.zeroize();
+
+            return SecretScalar(scalar);
+        }
+    }
+}
+
+/// Receives a handshake message of exactly ``N`` bytes.
+fn recv_message<R: RxChannel, V: Timer, const N: usize>(
+    rx: &mut R,
+    timer: &mut V,
+) -> communication::Result<[u8; N]> {
+    let mut buf = [0; N];
+    let len = rx.recv_with_timeout(&mut buf, timer)?;
+
+    if len != N {
+        return Err(CommunicationError::HandshakeFailure);
+    }
+
+    Ok(buf)
+}
+
+/// A session established by a [`NoiseHandshake`]. See the [`module`](self) documentation for more
+/// details.
+pub struct NoiseSession<R, T, U: RandomSource> {
+    /// The channel used to receive messages from the remote party.
+    pub rx_channel: XChacha20Poly1305RxChannel<R>,
+
+    /// The channel used to send messages to the remote party.
+    pub tx_channel: XChacha20Poly1305TxChannel<T, U>,
+
+    /// The static public key of the remote party, which was accepted by the verification callback.
+    pub remote_static_key: PublicKey,
+
+    /// The handshake hash, which is the same for both parties and unique to this session.
+    pub handshake_hash: [u8; HANDSHAKE_HASH_SIZE],
+}
+
+impl<R, T, U: RandomSource> NoiseSession<R, T, U> {
+    fn new(
+        rx: R,
+        tx: T,
+        random_source: U,
+        remote_static_key: PublicKey,
+        state: &SymmetricState,
+        initiator: bool,
+    ) -> Self {
+        let (mut initiator_key, mut responder_key) = state.split();
+        let (rx_key, tx_key) = if initiator {
+            (&responder_key, &initiator_key)
+        } else {
+            (&initiator_key, &responder_key)
+        };
+
+        let session = Self {
+            rx_channel: XChacha20Poly1305RxChannel::new_with_replay_protection(rx, rx_key.into()),
+            tx_channel: XChacha20Poly1305TxChannel::new_with_sequence_numbers(
+                tx,
+                random_source,
+                tx_key.into(),
+            ),
+            remote_static_key,
+            handshake_hash: state.h,
+        };
+
+        initiator_key.zeroize();
+        responder_key.zeroize();
+
+        session
+    }
+}
+
+/// A Noise XX handshake for one party, which establishes a [`NoiseSession`] when run with
+/// [`initiate`](Self::initiate) on one side and [`respond`](Self::respond) on the other. See the
+/// [`module`](self) documentation for more details.
+pub struct NoiseHandshake<'a, U: RandomSource> {
+    static_key: &'a SecretKey,
+    prologue: &'a [u8],
+    random_source: U,
+}
+
+impl<'a, U: RandomSource> NoiseHandshake<'a, U> {
+    /// Creates a new [`NoiseHandshake`] with the static key ``static_key``. ``random_source`` is used to
+    /// generate the ephemeral key and is then given to the [`XChacha20Poly1305TxChannel`] of the session.
+    pub fn new(static_key: &'a SecretKey, random_source: U) -> Self {
+        Self {
+            static_key,
+            prologue: b"",
+            random_source,
+        }
+    }
+
+    /// Sets the prologue authenticated by the handshake, which must match the prologue given by the
+    /// remote party.
+    pub fn with_prologue(mut self, prologue: &'a [u8]) -> Self {
+        self.prologue = prologue;
+
+        self
+    }
+
+    /// Runs the handshake as the initiator, sending handshake messages through ``tx`` and receiving them
+    /// through ``rx``. ``timer`` limits how long each handshake message is waited for, and
+    /// ``verify_remote`` is given the responder's static public key and returns whether to accept it.
+    /// Upon an error, a [`CommunicationError`] is given.
+    ///
+    /// # ERRORS:
+    ///
+    /// - [`CommunicationError::HandshakeFailure`]
+    ///   - A handshake message had the wrong length or contained an invalid public key.
+    ///   - ``verify_remote`` rejected the responder's static public key.
+    /// - [`CommunicationError::AuthenticationFailure`]
+    ///   - A handshake message couldn't be authenticated, which can occur due to data corruption,
+    ///     tampering, or a prologue that doesn't match.
+    /// - Any error given by ``rx`` or ``tx`` while receiving or sending.
+    pub fn initiate<R: RxChannel, T: FramedTxChannel, V: Timer>(
+        mut self,
+        mut rx: R,
+        mut tx: T,
+        timer: &mut V,
+        verify_remote: impl FnOnce(&PublicKey) -> bool,
+    ) -> communication::Result<NoiseSession<R, T, U>> {
+        let mut state = SymmetricState::new(self.prologue);
+        let s = SecretScalar(self.static_key.to_nonzero_scalar());
+        let e = generate_ephemeral(&mut self.random_source);
+
+        // -> e
+        let mut msg_1 = [0; MESSAGE_1_SIZE];
+        encode_public_key(&PublicKey::from_secret_scalar(&e.0), &mut msg_1);
+        state.mix_hash(&msg_1);
+        state.encrypt_and_hash(&mut [], 0)?;
+        tx.send(&mut msg_1)?;
+
+        // <- e, ee, s, es
+        let mut msg_2 = recv_message::<_, _, MESSAGE_2_SIZE>(&mut rx, timer)?;
+        let (re, rest) = msg_2.split_at_mut(PUBLIC_KEY_SIZE);
+        let (rs, payload) = rest.split_at_mut(PUBLIC_KEY_SIZE + TAG_SIZE);
+        let re_key = decode_public_key(re)?;
+        state.mix_hash(re);
+        mix_dh(&mut state, &e.0, &re_key);
+        state.decrypt_and_hash(rs)?;
+        let rs_key = decode_public_key(&rs[..PUBLIC_KEY_SIZE])?;
+        mix_dh(&mut state, &e.0, &rs_key);
+        state.decrypt_and_hash(payload)?;
+
+        if !verify_remote(&rs_key) {
+            return Err(CommunicationError::HandshakeFailure);
+        }
+
+        // -> s, se
+        let mut msg_3 = [0; MESSAGE_3_SIZE];
+        encode_public_key(&self.static_key.public_key(), &mut msg_3);
+        let len = state.encrypt_and_hash(&mut msg_3, PUBLIC_KEY_SIZE)?;
+        mix_dh(&mut state, &s.0, &re_key);
+        state.encrypt_and_hash(&mut msg_3[len..], 0)?;
+        tx.send(&mut msg_3)?;
+
+        Ok(NoiseSession::new(
+            rx,
+            tx,
+            self.random_source,
+            rs_key,
+            &state,
+            true,
+        ))
+    }
+
+    /// Runs the handshake as the responder, sending handshake messages through ``tx`` and receiving them
+    /// through ``rx``. ``timer`` limits how long each handshake message is waited for, and
+    /// ``verify_remote`` is given the initiator's static public key and returns whether to accept it.
+    /// Upon an error, a [`CommunicationError`] is given.
+    ///
+    /// # ERRORS:
+    ///
+    /// - [`CommunicationError::HandshakeFailure`]
+    ///   - A handshake message had the wrong length or contained an invalid public key.
+    ///   - ``verify_remote`` rejected the initiator's static public key.
+    /// - [`CommunicationError::AuthenticationFailure`]
+    ///   - A handshake message couldn't be authenticated, which can occur due to data corruption,
+    ///     tampering, or a prologue that doesn't match.
+    /// - Any error given by ``rx`` or ``tx`` while receiving or sending.
+    pub fn respond<R: RxChannel, T: FramedTxChannel, V: Timer>(
+        mut self,
+        mut rx: R,
+        mut tx: T,
+        timer: &mut V,
+        verify_remote: impl FnOnce(&PublicKey) -> bool,
+    ) -> communication::Result<NoiseSession<R, T, U>> {
+        let mut state = SymmetricState::new(self.prologue);
+        let s = SecretScalar(self.static_key.to_nonzero_scalar());
+
+        // -> e
+        let mut msg_1 = recv_message::<_, _, MESSAGE_1_SIZE>(&mut rx, timer)?;
+        let re_key = decode_public_key(&msg_1)?;
+        state.mix_hash(&msg_1);
+        state.decrypt_and_hash(&mut msg_1[PUBLIC_KEY_SIZE..])?;
+
+        // <- e, ee, s, es
+        let e = generate_ephemeral(&mut self.random_source);
+        let mut msg_2 = [0; MESSAGE_2_SIZE];
+        encode_public_key(&PublicKey::from_secret_scalar(&e.0), &mut msg_2);
+        state.mix_hash(&msg_2[..PUBLIC_KEY_SIZE]);
+        mix_dh(&mut state, &e.0, &re_key);
+        encode_public_key(&self.static_key.public_key(), &mut msg_2[PUBLIC_KEY_SIZE..]);
+        let len = PUBLIC_KEY_SIZE
+            + state.encrypt_and_hash(&mut msg_2[PUBLIC_KEY_SIZE..], PUBLIC_KEY_SIZE)?;
+        mix_dh(&mut state, &s.0, &re_key);
+        state.encrypt_and_hash(&mut msg_2[len..], 0)?;
+        tx.send(&mut msg_2)?;
+
+        // -> s, se
+        let mut msg_3 = recv_message::<_, _, MESSAGE_3_SIZE>(&mut rx, timer)?;
+        let (rs, payload) = msg_3.split_at_mut(PUBLIC_KEY_SIZE + TAG_SIZE);
+        state.decrypt_and_hash(rs)?;
+        let rs_key = decode_public_key(&rs[..PUBLIC_KEY_SIZE])?;
+        mix_dh(&mut state, &e.0, &rs_key);
+        state.decrypt_and_hash(payload)?;
+
+        if !verify_remote(&rs_key) {
+            return Err(CommunicationError::HandshakeFailure);
+        }
+
+        Ok(NoiseSession::new(
+            rx,
+            tx,
+            self.random_source,
+            rs_key,
+            &state,
+            false,
+        ))
+    }
+}
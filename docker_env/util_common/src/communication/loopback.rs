@@ -0,0 +1,248 @@
+//! This module contains an in-memory loopback channel pair, which is a bounded byte pipe with a
+//! [`FramedTxChannel`] on one end and an [`RxChannel`] on the other.
+//!
+//! - Loopback channels
+//!     - A [`LoopbackPipe`] holds up to ``N`` bytes of framed data that have been sent but not yet received.
+//!       It doesn't need an allocator, so it can be used on the car and key fob as well as on the host.
+//!     - A [`LoopbackTxChannel`] and a [`LoopbackRxChannel`] are obtained by [`splitting`](LoopbackPipe::split)
+//!       a pipe. Data sent through the [`LoopbackTxChannel`] is framed with the [`FramingProtocol`] chosen by
+//!       the type parameter and can be received from the [`LoopbackRxChannel`].
+//!     - The [`LoopbackRxChannel`] honours the [`Timer`] given to it, so receives on an empty pipe time out the
+//!       same way they would on a real channel. It counts the bytes discarded outside of frames like any other
+//!       framing layer channel, which are given by [`RxChannel::take_discarded_bytes`].
+//!     - Both ends also implement the async channel traits, [`AsyncFramedTxChannel`] and [`AsyncRxChannel`].
+//!       Async receives yield to the executor while the pipe is empty, so a sending task and a receiving task
+//!       sharing a pipe can be run concurrently on a single thread.
+//!     - Bytes in the pipe can be corrupted with [`LoopbackPipe::corrupt_byte`] and stray bytes can be written
+//!       into it with [`LoopbackPipe::inject_bytes`] to test how receivers handle data corrupted on the wire,
+//!       which the [`fault_injection`](crate::communication::fault_injection) channels can't do as they inject
+//!       faults before framing.
+//!     - This lets the whole BogoStack, such as the [`crypto`](crate::communication::lower_layers::crypto)
+//!       channels wrapped around a loopback pair, be exercised without any hardware or sockets.
+
+use core::{cell::RefCell, marker::PhantomData};
+
+use heapless::Deque;
+
+use crate::{
+    communication::{
+        self,
+        lower_layers::framing::{
+            AsyncFramedTxChannel, BogoFraming, Frame, FramedTxChannel, FramingProtocol,
+        },
+        AsyncRxChannel, CommunicationError, RxChannel,
+    },
+    timer::Timer,
+};
+
+/// The minimum size a loopback message can be.
+pub const MIN_LOOPBACK_MESSAGE: usize = 0;
+
+/// A bounded in-memory byte pipe that can hold up to ``N`` bytes of framed data. See the
+/// [`module`](self) documentation for more details.
+pub struct LoopbackPipe<const N: usize> {
+    buf: RefCell<Deque<u8, N>>,
+}
+
+impl<const N: usize> LoopbackPipe<N> {
+    /// Creates a new empty [`LoopbackPipe`].
+    pub const fn new() -> Self {
+        Self {
+            buf: RefCell::new(Deque::new()),
+        }
+    }
+
+    /// Splits the pipe into a [`LoopbackTxChannel`] and a [`LoopbackRxChannel`] using the
+    /// framing protocol ``F``.
+    pub fn split<F: FramingProtocol>(
+        &self,
+    ) -> (LoopbackTxChannel<'_, N, F>, LoopbackRxChannel<'_, N, F>) {
+        (
+            LoopbackTxChannel {
+                pipe: self,
+                _framing: PhantomData,
+            },
+            LoopbackRxChannel {
+                pipe: self,
+                discarded: 0,
+                _framing: PhantomData,
+            },
+        )
+    }
+
+    /// Gets the number of bytes in the pipe that haven't been received yet.
+    pub fn len(&self) -> usize {
+        self.buf.borrow().len()
+    }
+
+    /// Returns ``true`` if there are no bytes in the pipe.
+    pub fn is_empty(&self) -> bool {
+        self.buf.borrow().is_empty()
+    }
+
+    /// Discards all bytes in the pipe that haven't been received yet.
+    pub fn clear(&self) {
+        self.buf.borrow_mut().clear();
+    }
+
+    /// Flips the bits set in ``mask`` in the byte at ``index`` of the bytes in the pipe that haven't
+    /// been received yet, returning ``false`` if there's no byte at ``index``. This can be used to test
+    /// how receivers handle data corrupted on the wire.
+    pub fn corrupt_byte(&self, index: usize, mask: u8) -> bool {
+        match self.buf.borrow_mut().iter_mut().nth(index) {
+            Some(byte) => {
+                *byte ^= mask;
+
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Writes ``src`` into the pipe as is, without framing it, which can be used to test how receivers
+    /// handle stray bytes on the wire between frames.
+    pub fn inject_bytes(&self, src: &[u8]) -> communication::Result<()> {
+        self.write(src)
+    }
+
+    fn write(&self, src: &[u8]) -> communication::Result<()> {
+        let mut buf = self.buf.borrow_mut();
+
+        for &byte in src {
+            buf.push_back(byte)
+                .map_err(|_| CommunicationError::SendError)?;
+        }
+
+        Ok(())
+    }
+
+    fn read(&self) -> communication::Result<u8> {
+        self.buf
+            .borrow_mut()
+            .pop_front()
+            .ok_or(CommunicationError::RecvError)
+    }
+}
+
+impl<const N: usize> Default for LoopbackPipe<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The [`FramedTxChannel`] end of a [`LoopbackPipe`]. The framing protocol used is chosen by the
+/// [`FramingProtocol`] type parameter, which defaults to [`BogoFraming`].
+///
+/// A frame that doesn't fit in the pipe results in a [`CommunicationError::SendError`]. The part of the
+/// frame that did fit is left in the pipe and is discarded by the receiver like any other partial frame.
+pub struct LoopbackTxChannel<'a, const N: usize, F: FramingProtocol = BogoFraming> {
+    pipe: &'a LoopbackPipe<N>,
+    _framing: PhantomData<F>,
+}
+
+/// The [`RxChannel`] end of a [`LoopbackPipe`]. The framing protocol used is chosen by the
+/// [`FramingProtocol`] type parameter, which defaults to [`BogoFraming`].
+pub struct LoopbackRxChannel<'a, const N: usize, F: FramingProtocol = BogoFraming> {
+    pipe: &'a LoopbackPipe<N>,
+    discarded: usize,
+    _framing: PhantomData<F>,
+}
+
+impl<'a, const N: usize, F: FramingProtocol> FramedTxChannel for LoopbackTxChannel<'a, N, F> {
+    fn frame<'b, const FRAME_CT: usize>(
+        &mut self,
+        frame: impl FnOnce() -> communication::Result<Frame<'b, FRAME_CT>>,
+    ) -> communication::Result<()> {
+        F::frame(
+            self,
+            frame()?,
+            |ch, s| ch.pipe.write(s),
+            MIN_LOOPBACK_MESSAGE,
+        )
+    }
+}
+
+impl<'a, const N: usize, F: FramingProtocol> RxChannel for LoopbackRxChannel<'a, N, F> {
+    fn recv_with_data_timeout<T: Timer>(
+        &mut self,
+        dest: &mut [u8],
+        timer: &mut T,
+    ) -> communication::Result<usize> {
+        F::recv_frame_with_data_timeout(
+            &mut self.pipe,
+            dest,
+            timer,
+            |pipe| pipe.read(),
+            MIN_LOOPBACK_MESSAGE,
+            &mut self.discarded,
+        )
+    }
+
+    fn recv_with_timeout<T: Timer>(
+        &mut self,
+        dest: &mut [u8],
+        timer: &mut T,
+    ) -> communication::Result<usize> {
+        F::recv_frame_with_timeout(
+            &mut self.pipe,
+            dest,
+            timer,
+            |pipe| pipe.read(),
+            MIN_LOOPBACK_MESSAGE,
+            &mut self.discarded,
+        )
+    }
+
+    fn take_discarded_bytes(&mut self) -> usize {
+        core::mem::take(&mut self.discarded)
+    }
+}
+
+impl<'a, const N: usize, F: FramingProtocol> AsyncFramedTxChannel for LoopbackTxChannel<'a, N, F> {
+    /// Writes the frame to the pipe like [`FramedTxChannel::frame`]. The pipe never blocks, so this
+    /// completes without yielding.
+    async fn frame<'b, const FRAME_CT: usize>(
+        &mut self,
+        frame: impl FnOnce() -> communication::Result<Frame<'b, FRAME_CT>>,
+    ) -> communication::Result<()> {
+        FramedTxChannel::frame(self, frame)
+    }
+}
+
+impl<'a, const N: usize, F: FramingProtocol> AsyncRxChannel for LoopbackRxChannel<'a, N, F> {
+    async fn recv_with_data_timeout<T: Timer>(
+        &mut self,
+        dest: &mut [u8],
+        timer: &mut T,
+    ) -> communication::Result<usize> {
+        F::async_recv_frame_with_data_timeout(
+            &mut self.pipe,
+            dest,
+            timer,
+            |pipe| pipe.read(),
+            MIN_LOOPBACK_MESSAGE,
+            &mut self.discarded,
+        )
+        .await
+    }
+
+    async fn recv_with_timeout<T: Timer>(
+        &mut self,
+        dest: &mut [u8],
+        timer: &mut T,
+    ) -> communication::Result<usize> {
+        F::async_recv_frame_with_timeout(
+            &mut self.pipe,
+            dest,
+            timer,
+            |pipe| pipe.read(),
+            MIN_LOOPBACK_MESSAGE,
+            &mut self.discarded,
+        )
+        .await
+    }
+
+    fn take_discarded_bytes(&mut self) -> usize {
+        core::mem::take(&mut self.discarded)
+    }
+}
@@ -7,22 +7,48 @@
 //! generation and when they do, they'll require some implementation of [`RandomSource`].
 //!
 //! # Current secure channel implementations:
-//! ## [`XChacha20Poly1305RxChannel`] and [`XChacha20Poly1305TxChannel`]
-//! These channels provide message integrity and confidentiality by using XChacha20Poly1305.
-//! Each message sent will contain a 24-byte nonce, a 16-byte authentication tag, and the
-//! ciphertext given a 32-byte symmetric key to encrypt and decrypt communications. The
-//! authentication tag provided will be checked against the message body to prevent message tampering.
-//! This means that any buffers used to received messages from an [`XChacha20Poly1305RxChannel`] must
-//! have enough space to store the additional metadata, totaling 40 bytes. This is stored in the
-//! constant ``XChacha20Poly1305RxChannel::METADATA_SIZE``. This channel requires random number
-//! generation. Because of this, it requires a [`RandomSource`].
+//! ## [`AeadRxChannel`] and [`AeadTxChannel`]
+//! These channels provide message integrity and confidentiality by using any AEAD cipher implementing
+//! [`ChannelCipher`]. Each message sent will contain a nonce, an authentication tag, and the ciphertext
+//! given a symmetric key to encrypt and decrypt communications. The authentication tag provided will be
+//! checked against the message body to prevent message tampering. This means that any buffers used to
+//! received messages from an [`AeadRxChannel`] must have enough space to store the additional metadata,
+//! the size of which is stored in [`ChannelCipher::METADATA_SIZE`]. The
+//! [`RxChannel::METADATA_SIZE`](crate::communication::RxChannel::METADATA_SIZE) of an [`AeadRxChannel`]
+//! covers the metadata of every configuration, including sequence numbers and ratchet epochs, so buffers
+//! sized with it fit any message. These channels generate nonces with [`ChannelCipher::fill_nonce`].
+//! Because of this, they require a [`RandomSource`].
+//!
+//! The following ciphers are available, each with type aliases for its channels:
+//! - XChaCha20-Poly1305, with [`XChacha20Poly1305RxChannel`] and [`XChacha20Poly1305TxChannel`]. Each
+//!   message carries a 24-byte nonce and a 16-byte tag, totaling 40 bytes, which is also stored in the
+//!   constant [`METADATA_SIZE`].
+//! - AES-256-GCM-SIV, with [`Aes256GcmSivRxChannel`] and [`Aes256GcmSivTxChannel`]. Each message carries
+//!   a 12-byte nonce and a 16-byte tag, totaling 28 bytes. Its nonces are short enough to repeat when chosen
+//!   at random, but AES-GCM-SIV is nonce-misuse resistant, so a repeated nonce only reveals whether the
+//!   same message was sent twice.
+//!
+//! Both ciphers take a 32-byte [`Key`]. Both ends of a channel must use the same cipher.
+//!
+//! These channels can optionally send and check sequence numbers to protect against replayed messages.
+//! They can also authenticate a per-channel context and per-message associated data with each message,
+//! which binds a message to its direction and channel. Finally, they can ratchet their keys with HKDF,
+//! either automatically every N messages, sending the epoch of the key with each message so the receiver
+//! can catch up after lost messages, or manually with [`RatchetingChannel::ratchet`], for example after
+//! each completed exchange. They can also pad messages according to a [`PaddingPolicy`] to hide
+//! their exact length. See [`AeadRxChannel`] for more details.
 //!
 //! See the documentation for [`communication`](crate::communication) for a description of the BogoStack
 //! and more info on the other layers of the BogoStack.
 
-mod chachapoly1305;
+mod aead_channel;
 
-pub use chachapoly1305::*;
+pub use aead_channel::*;
+
+pub use aes_gcm_siv::Aes256GcmSiv;
+use chacha20poly1305::aead::{AeadInPlace, KeyInit, Nonce};
+pub use chacha20poly1305::XChaCha20Poly1305;
+use typenum::Unsigned;
 
 /// Implemented for any channel that has encryption/decryption keys that can be changed after channel
 /// creation.
@@ -34,8 +60,39 @@ pub trait KeyedChannel {
     fn change_key(&mut self, new_key: &Self::KeyType);
 }
 
+/// Implemented for any [`KeyedChannel`] that can derive its next key from its current key. Both ends of a
+/// channel must ratchet at the same point in the message stream to keep communicating.
+pub trait RatchetingChannel: KeyedChannel {
+    /// Derives the next key from the current key, switching to it and erasing the current key. A key
+    /// compromised after ratcheting can't be used to decrypt messages sent before ratcheting.
+    fn ratchet(&mut self);
+}
+
 /// Trait used for secure channels when they need random number generation.
 pub trait RandomSource {
     /// Fills the provided slice with random bytes.
     fn fill_rand_slice<T: AsMut<[u8]>>(&mut self, slice_ref: T);
 }
+
+/// Implemented for any AEAD cipher that can be used by an [`AeadRxChannel`] and an [`AeadTxChannel`].
+/// The nonce of each message is chosen by [`fill_nonce`](ChannelCipher::fill_nonce), which picks random
+/// nonces unless overridden, so this should only be implemented for ciphers with nonces long enough to
+/// never repeat when chosen at random or that are nonce-misuse resistant.
+pub trait ChannelCipher: AeadInPlace + KeyInit {
+    /// The total metadata size required when receiving on an [`AeadRxChannel`] using this cipher.
+    const METADATA_SIZE: usize =
+        <Self::TagSize as Unsigned>::USIZE + <Self::NonceSize as Unsigned>::USIZE;
+
+    /// The total metadata size required when receiving on an [`AeadRxChannel`] using this cipher with
+    /// replay protection enabled.
+    const SEQUENCED_METADATA_SIZE: usize = Self::METADATA_SIZE + SEQUENCE_NUMBER_SIZE;
+
+    /// Fills ``nonce`` with the nonce used to send the next message.
+    fn fill_nonce<R: RandomSource>(random_source: &mut R, nonce: &mut Nonce<Self>) {
+        random_source.fill_rand_slice(nonce);
+    }
+}
+
+impl ChannelCipher for XChaCha20Poly1305 {}
+
+impl ChannelCipher for Aes256GcmSiv {}
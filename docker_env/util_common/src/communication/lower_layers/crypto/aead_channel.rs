@@ -0,0 +1,1131 @@
+use core::num::{NonZeroU32, NonZeroUsize};
+
+use super::{ChannelCipher, KeyedChannel, RandomSource, RatchetingChannel};
+use crate::communication::{
+    self,
+    lower_layers::framing::{AsyncFramedTxChannel, Frame, FramedTxChannel},
+    AsyncRxChannel, AsyncTxChannel, CommunicationError, RxChannel, Timer, TxChannel,
+};
+use aes_gcm_siv::Aes256GcmSiv;
+use chacha20poly1305::{
+    aead::{Key as CipherKey, Nonce, Tag},
+    XChaCha20Poly1305,
+};
+use hkdf::Hkdf;
+use sha2::Sha256;
+use typenum::Unsigned;
+use zeroize::Zeroize;
+
+pub use chacha20poly1305::Key;
+
+/// The total metadata size required when receiving on a [`XChacha20Poly1305RxChannel`]. See
+/// [`ChannelCipher::METADATA_SIZE`] for the metadata size of other ciphers.
+pub const METADATA_SIZE: usize = <XChaCha20Poly1305 as ChannelCipher>::METADATA_SIZE;
+
+/// The size of the sequence number sent with each message when sequence numbers are enabled.
+pub const SEQUENCE_NUMBER_SIZE: usize = core::mem::size_of::<u64>();
+
+/// The size of the ratchet epoch sent with each message when automatic key ratcheting is enabled.
+pub const RATCHET_EPOCH_SIZE: usize = core::mem::size_of::<u32>();
+
+/// The largest number of epochs an [`AeadRxChannel`] ratchets forward to catch up to a message from a
+/// later epoch. Messages further ahead are rejected, which bounds the work a forged message can cause.
+pub const MAX_RATCHET_EPOCH_SKIP: u32 = 16;
+
+/// The total metadata size required when receiving on a [`XChacha20Poly1305RxChannel`] with
+/// replay protection enabled. See [`ChannelCipher::SEQUENCED_METADATA_SIZE`] for the metadata size of
+/// other ciphers.
+pub const SEQUENCED_METADATA_SIZE: usize =
+    <XChaCha20Poly1305 as ChannelCipher>::SEQUENCED_METADATA_SIZE;
+
+/// An [`AeadRxChannel`] using XChaCha20-Poly1305.
+pub type XChacha20Poly1305RxChannel<T> = AeadRxChannel<T, XChaCha20Poly1305>;
+
+/// An [`AeadTxChannel`] using XChaCha20-Poly1305.
+pub type XChacha20Poly1305TxChannel<T, U> = AeadTxChannel<T, U, XChaCha20Poly1305>;
+
+/// An [`AeadRxChannel`] using AES-256-GCM-SIV.
+pub type Aes256GcmSivRxChannel<T> = AeadRxChannel<T, Aes256GcmSiv>;
+
+/// An [`AeadTxChannel`] using AES-256-GCM-SIV.
+pub type Aes256GcmSivTxChannel<T, U> = AeadTxChannel<T, U, Aes256GcmSiv>;
+
+/// The number of sequence numbers below the highest one received that are still accepted by a
+/// [`AeadRxChannel`] with replay protection enabled.
+pub const REPLAY_WINDOW_SIZE: u64 = u64::BITS as u64;
+
+/// The largest amount of context and per-message associated data, combined, that can be authenticated
+/// with a message.
+pub const MAX_ASSOCIATED_DATA_SIZE: usize = 64;
+
+/// The largest size a message can be padded to by an [`AeadTxChannel`] with padding enabled. Messages
+/// are copied into a buffer of this size to be padded, so longer messages can't be sent with padding
+/// enabled.
+pub const MAX_PADDED_MESSAGE_SIZE: usize = 1024;
+
+/// The byte that starts the padding of a message. It's followed by zero bytes up to the padded length.
+const PADDING_MARKER: u8 = 0x80;
+
+/// How an [`AeadTxChannel`] pads messages before encrypting them, which hides their exact length from
+/// anyone observing the channel. Padding is encrypted and authenticated along with the message.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum PaddingPolicy {
+    /// Messages aren't padded.
+    #[default]
+    None,
+
+    /// Messages are padded to the next multiple of the given bucket size. A bucket size at least as large
+    /// as the largest message sent makes every message the same size.
+    Bucket(NonZeroUsize),
+
+    /// Messages are padded to the next power of two.
+    PowerOfTwo,
+}
+
+impl PaddingPolicy {
+    /// Gets the length a message of ``len`` bytes is padded to, which always has space for at least one
+    /// byte of padding and is capped at ``max_len`` where possible, or [`None`] if messages aren't padded.
+    fn padded_len(self, len: usize, max_len: usize) -> Option<usize> {
+        let min_len = len + 1;
+        let padded_len = match self {
+            Self::None => return None,
+            Self::Bucket(size) => min_len.checked_next_multiple_of(size.get()),
+            Self::PowerOfTwo => min_len.checked_next_power_of_two(),
+        };
+
+        Some(padded_len.unwrap_or(usize::MAX).min(max_len).max(min_len))
+    }
+}
+
+/// Copies ``message`` into the start of ``padded``, which must be zeroed and longer than ``message``, and
+/// marks the start of the padding after it.
+fn pad(message: &[u8], padded: &mut [u8]) {
+    padded[..message.len()].copy_from_slice(message);
+    padded[message.len()] = PADDING_MARKER;
+}
+
+/// Removes the padding from the end of ``message``, returning the length of the message without it.
+///
+/// # ERRORS:
+///
+/// - [`CommunicationError::MalformedPadding`] - ``message`` doesn't end with a padding marker followed by
+///   zero bytes.
+fn unpad(message: &[u8]) -> communication::Result<usize> {
+    match message.iter().rposition(|&b| b != 0) {
+        Some(len) if message[len] == PADDING_MARKER => Ok(len),
+        _ => Err(CommunicationError::MalformedPadding),
+    }
+}
+
+/// The size of the length prefix of the context in the associated data of a message.
+const CONTEXT_LEN_SIZE: usize = core::mem::size_of::<u16>();
+
+/// The size of a buffer that can hold all of the associated data of a message.
+const ASSOCIATED_DATA_BUFFER_SIZE: usize =
+    SEQUENCE_NUMBER_SIZE + RATCHET_EPOCH_SIZE + CONTEXT_LEN_SIZE + MAX_ASSOCIATED_DATA_SIZE;
+
+/// Writes the associated data authenticated with a message into ``buf``, returning the part of ``buf``
+/// that was used. The associated data is the sequence number and ratchet epoch, if any, followed by the
+/// length of the context, the context, and the per-message associated data. The context is length-prefixed
+/// so it can't be confused with the per-message associated data. If there's no context or per-message
+/// associated data, only the sequence number and ratchet epoch are used.
+///
+/// # ERRORS:
+///
+/// - [`CommunicationError::InternalError`] - The context and per-message associated data together are
+///   longer than [`MAX_ASSOCIATED_DATA_SIZE`].
+fn write_associated_data<'a>(
+    buf: &'a mut [u8; ASSOCIATED_DATA_BUFFER_SIZE],
+    seq: &[u8],
+    epoch: &[u8],
+    context: &[u8],
+    message_ad: &[u8],
+) -> communication::Result<&'a [u8]> {
+    if context.len() + message_ad.len() > MAX_ASSOCIATED_DATA_SIZE {
+        return Err(CommunicationError::InternalError);
+    }
+
+    let mut len = 0;
+    let mut push = |piece: &[u8]| {
+        buf[len..len + piece.len()].copy_from_slice(piece);
+        len += piece.len();
+    };
+
+    push(seq);
+    push(epoch);
+
+    if !context.is_empty() || !message_ad.is_empty() {
+        push(&(context.len() as u16).to_be_bytes());
+        push(context);
+        push(message_ad);
+    }
+
+    Ok(&buf[..len])
+}
+
+/// The HKDF info string used to derive the next key when ratcheting.
+const RATCHET_INFO: &[u8] = b"ucsc-ectf-bogostack-ratchet";
+
+/// Keeps track of the current key of a channel, its epoch, and when it should next be ratcheted.
+struct KeyRatchet<C: ChannelCipher> {
+    key: CipherKey<C>,
+    /// Whether the epoch is sent with each message.
+    sends_epochs: bool,
+    /// The number of messages after which the key is ratcheted, if it's ratcheted by count.
+    interval: Option<NonZeroU32>,
+    messages: u32,
+    epoch: u32,
+}
+
+impl<C: ChannelCipher> KeyRatchet<C> {
+    fn new(key: &CipherKey<C>) -> Self {
+        Self {
+            key: key.clone(),
+            sends_epochs: false,
+            interval: None,
+            messages: 0,
+            epoch: 0,
+        }
+    }
+
+    /// Replaces the current key, erasing it and restarting the message count and epoch.
+    fn set_key(&mut self, key: &CipherKey<C>) {
+        self.key.zeroize();
+        self.key = key.clone();
+        self.messages = 0;
+        self.epoch = 0;
+    }
+
+    /// Derives the key following ``key`` with HKDF-SHA256.
+    fn next_key(key: &CipherKey<C>) -> CipherKey<C> {
+        let mut next_key = CipherKey::<C>::default();
+
+        // Every key size used by a channel cipher is a valid HKDF-SHA256 output length, so this never
+        // fails.
+        let _ = Hkdf::<Sha256>::new(None, key).expand(RATCHET_INFO, &mut next_key);
+
+        next_key
+    }
+
+    /// Switches to the next key, erasing the current key and moving to the next epoch.
+    fn advance(&mut self) {
+        let mut next_key = Self::next_key(&self.key);
+        self.key.zeroize();
+        self.key = next_key.clone();
+        next_key.zeroize();
+        self.messages = 0;
+
+        // Each epoch takes at least one message, so the epoch can't realistically overflow.
+        self.epoch = self.epoch.saturating_add(1);
+    }
+
+    /// Derives the key of the later epoch ``epoch`` without switching to it, or gives [`None`] if
+    /// ``epoch`` isn't after the current epoch or is more than [`MAX_RATCHET_EPOCH_SKIP`] epochs ahead.
+    fn key_for_epoch(&self, epoch: u32) -> Option<CipherKey<C>> {
+        let skip = epoch.checked_sub(self.epoch)?;
+
+        if skip == 0 || skip > MAX_RATCHET_EPOCH_SKIP {
+            return None;
+        }
+
+        let mut key = self.key.clone();
+
+        for _ in 0..skip {
+            let mut next_key = Self::next_key(&key);
+            key.zeroize();
+            key = next_key.clone();
+            next_key.zeroize();
+        }
+
+        Some(key)
+    }
+
+    /// Switches to ``key``, which was derived for the later epoch ``epoch``, erasing the current key.
+    fn catch_up(&mut self, key: &CipherKey<C>, epoch: u32) {
+        self.set_key(key);
+        self.epoch = epoch;
+    }
+
+    /// Gets the bytes of the current epoch to send with a message, or [`None`] if the channel doesn't
+    /// ratchet automatically.
+    fn epoch_bytes(&self) -> Option<[u8; RATCHET_EPOCH_SIZE]> {
+        self.sends_epochs.then(|| self.epoch.to_be_bytes())
+    }
+
+    /// Gets the size of the epoch sent with each message.
+    fn epoch_size(&self) -> usize {
+        if self.sends_epochs {
+            RATCHET_EPOCH_SIZE
+        } else {
+            0
+        }
+    }
+
+    /// Counts a message sent, returning ``true`` if the channel should ratchet.
+    fn record_message(&mut self) -> bool {
+        let Some(interval) = self.interval else {
+            return false;
+        };
+
+        self.messages += 1;
+
+        self.messages >= interval.get()
+    }
+}
+
+impl<C: ChannelCipher> Drop for KeyRatchet<C> {
+    fn drop(&mut self) {
+        self.key.zeroize();
+    }
+}
+
+/// A sliding anti-replay window over the sequence numbers received.
+#[derive(Default)]
+struct ReplayWindow {
+    /// The highest sequence number received, if any.
+    highest: Option<u64>,
+    /// Bit ``n`` is set if the sequence number ``highest - n`` has been received.
+    received: u64,
+}
+
+impl ReplayWindow {
+    /// Records ``seq`` as received, returning an error if it was already received or is too old
+    /// to be tracked by the window.
+    fn check_and_update(&mut self, seq: u64) -> communication::Result<()> {
+        let Some(highest) = self.highest else {
+            self.highest = Some(seq);
+            self.received = 1;
+
+            return Ok(());
+        };
+
+        if seq > highest {
+            // Slide the window forward so the new sequence number is at bit 0.
+            self.received = self
+                .received
+                .checked_shl((seq - highest) as u32)
+                .unwrap_or(0)
+                | 1;
+            self.highest = Some(seq);
+
+            return Ok(());
+        }
+
+        let age = highest - seq;
+
+        if age >= REPLAY_WINDOW_SIZE || self.received & (1 << age) != 0 {
+            return Err(CommunicationError::ReplayedMessage);
+        }
+
+        self.received |= 1 << age;
+
+        Ok(())
+    }
+}
+
+/// The metadata sent after the ciphertext of a message by an [`AeadTxChannel`].
+struct SealedMetadata<C: ChannelCipher> {
+    seq_bytes: Option<[u8; SEQUENCE_NUMBER_SIZE]>,
+    epoch_bytes: Option<[u8; RATCHET_EPOCH_SIZE]>,
+    nonce: Nonce<C>,
+    tag: Tag<C>,
+}
+
+impl<C: ChannelCipher> SealedMetadata<C> {
+    /// Creates the frame for a message from its ciphertext and this metadata.
+    fn frame<'a>(&'a self, ciphertext: &'a [u8]) -> communication::Result<Frame<'a, 5>> {
+        // Write message in following order: Ciphertext + Sequence number + Epoch + Nonce + Tag
+        Frame::new()
+            .append(ciphertext)?
+            .append(self.seq_bytes.as_ref().map_or(&[][..], |b| &b[..]))?
+            .append(self.epoch_bytes.as_ref().map_or(&[][..], |b| &b[..]))?
+            .append(&self.nonce)?
+            .append(&self.tag)
+    }
+}
+
+/// This [`RxChannel`] wraps around another [`RxChannel`] to decrypt communications encrypted
+/// by an [`AeadTxChannel`] using the [`ChannelCipher`] ``C``, providing message authenticity and
+/// confidentiality. It is also an [`AsyncRxChannel`] when it wraps around an [`AsyncRxChannel`].
+/// When reading from an [`AeadRxChannel`], care must be taken to ensure that there is sufficient space
+/// to store the tag and nonce of the cipher as well, totaling
+/// [`C::METADATA_SIZE`](ChannelCipher::METADATA_SIZE) bytes.
+/// If a received message doesn't contain a nonce or authentication tag, a
+/// [`CommunicationError::FrameTooShort`] is given, and if it has an invalid authentication tag,
+/// a [`CommunicationError::AuthenticationFailure`] is given. Any error given by the underlying
+/// channel will be propagated up. Data sent and received through this channel must be at least
+/// 1 byte long.
+///
+/// # ERRORS:
+///
+/// - [`CommunicationError::FrameTooShort`] - The message didn't contain a nonce of the right size,
+///   an authentication tag, and at least 1 byte of ciphertext.
+/// - [`CommunicationError::AuthenticationFailure`] - The message didn't match the authentication tag
+///   provided.
+/// - [`CommunicationError::BufferTooSmall`] - The message couldn't be read into the buffer because it
+///   was too small.
+/// - [`CommunicationError::ReplayedMessage`] - Replay protection is enabled and the message's sequence
+///   number was already received or is too old to be checked.
+/// - [`CommunicationError::AuthenticationFailure`] - Automatic key ratcheting is enabled and the message is
+///   from an epoch whose key was already erased or more than [`MAX_RATCHET_EPOCH_SKIP`] epochs ahead.
+/// - [`CommunicationError::InternalError`] - The context and per-message associated data together are
+///   longer than [`MAX_ASSOCIATED_DATA_SIZE`].
+/// - [`CommunicationError::MalformedPadding`] - Padding is enabled and the message didn't end with valid
+///   padding.
+/// - Any error that occurred while receiving the message from the wrapped channel.
+///
+/// ## Associated data
+/// A context can be set with [`with_context`](Self::with_context), which is authenticated with every
+/// message received. Additional associated data can be authenticated with a single message by receiving it
+/// with [`recv_with_ad_and_timeout`](Self::recv_with_ad_and_timeout) or
+/// [`recv_with_ad_and_data_timeout`](Self::recv_with_ad_and_data_timeout). Both must match the context and
+/// associated data the message was sent with or the message fails authentication. Giving each direction
+/// and each channel its own context means a message reflected back to its sender or moved to another
+/// channel is rejected.
+///
+/// ## Replay protection
+/// A channel created with [`new_with_replay_protection`](Self::new_with_replay_protection) only accepts
+/// messages from an [`AeadTxChannel`] created with
+/// [`new_with_sequence_numbers`](AeadTxChannel::new_with_sequence_numbers). Each message
+/// carries a sequence number that is authenticated along with the message. A message is rejected if its
+/// sequence number was already received or is [`REPLAY_WINDOW_SIZE`] or more below the highest one received,
+/// which stops captured messages from being replayed on the same key. Buffers used to receive messages
+/// must then have space for [`C::SEQUENCED_METADATA_SIZE`](ChannelCipher::SEQUENCED_METADATA_SIZE) bytes of
+/// metadata. Changing the key resets the window.
+///
+/// ## Key ratcheting
+/// A channel configured with [`with_ratchet_epochs`](Self::with_ratchet_epochs) receives messages from an
+/// [`AeadTxChannel`] that ratchets its key automatically. Each message carries the epoch of the key it was
+/// sent with, the number of times the sending channel has ratcheted, which is authenticated along with the
+/// message. The channel doesn't count messages itself: an authenticated message from a later epoch makes it
+/// derive that epoch's key with HKDF-SHA256 and erase its current key, as long as the epoch is at most
+/// [`MAX_RATCHET_EPOCH_SKIP`] epochs ahead. The channel therefore only ever follows the sender, so lost
+/// messages can't make it fall behind and replayed messages can't make it ratchet ahead. The key is only
+/// switched once the message is authenticated, and messages from earlier epochs are rejected because their
+/// keys are erased. Buffers used to receive messages must then have space for [`RATCHET_EPOCH_SIZE`] more
+/// bytes of metadata.
+///
+/// A channel can also be ratcheted manually with [`ratchet`](RatchetingChannel::ratchet), for example after
+/// each completed exchange. The [`AeadTxChannel`] sending the messages must then ratchet at the same
+/// point.
+///
+/// ## Padding
+/// A channel configured with [`with_padding`](Self::with_padding) removes the padding added to each
+/// message by an [`AeadTxChannel`] configured with [`with_padding`](AeadTxChannel::with_padding). Because
+/// the padding is encrypted and authenticated with the message, it can't be changed by an attacker.
+/// Buffers used to receive messages must have space for the padded message, and a message without valid
+/// padding gives a [`CommunicationError::MalformedPadding`].
+///
+/// See the [`module`](super) documentation for more information on the ciphers available.
+pub struct AeadRxChannel<T, C: ChannelCipher> {
+    channel: T,
+    decryptor: C,
+    replay_window: Option<ReplayWindow>,
+    context: &'static [u8],
+    ratchet: KeyRatchet<C>,
+    padded: bool,
+}
+
+impl<T, C: ChannelCipher> AeadRxChannel<T, C> {
+    /// Creates a new [`AeadRxChannel`] given an inner [`RxChannel`] or [`AsyncRxChannel`]
+    /// and a decryption key.
+    pub fn new(channel: T, rx_key: &CipherKey<C>) -> Self {
+        Self {
+            channel,
+            decryptor: C::new(rx_key),
+            replay_window: None,
+            context: b"",
+            ratchet: KeyRatchet::new(rx_key),
+            padded: false,
+        }
+    }
+
+    /// Creates a new [`AeadRxChannel`] with replay protection given an inner [`RxChannel`]
+    /// or [`AsyncRxChannel`] and a decryption key. See the struct-level documentation for more info.
+    pub fn new_with_replay_protection(channel: T, rx_key: &CipherKey<C>) -> Self {
+        Self {
+            channel,
+            decryptor: C::new(rx_key),
+            replay_window: Some(ReplayWindow::default()),
+            context: b"",
+            ratchet: KeyRatchet::new(rx_key),
+            padded: false,
+        }
+    }
+
+    /// Sets the context that is authenticated with every message received on this channel. This must
+    /// match the context of the [`AeadTxChannel`] sending the messages. See the struct-level
+    /// documentation for more info.
+    pub fn with_context(mut self, context: &'static [u8]) -> Self {
+        self.context = context;
+
+        self
+    }
+
+    /// Makes this channel follow the ratchet epoch sent with every message by an [`AeadTxChannel`]
+    /// configured with [`with_ratchet_interval`](AeadTxChannel::with_ratchet_interval). See the
+    /// struct-level documentation for more info.
+    pub fn with_ratchet_epochs(mut self) -> Self {
+        self.ratchet.sends_epochs = true;
+
+        self
+    }
+
+    /// Makes this channel remove the padding from every message received, which must be sent by an
+    /// [`AeadTxChannel`] with padding enabled. See the struct-level documentation for more info.
+    pub fn with_padding(mut self) -> Self {
+        self.padded = true;
+
+        self
+    }
+
+    /// Gets a reference to the wrapped channel.
+    pub fn channel(&self) -> &T {
+        &self.channel
+    }
+
+    /// Gets a mutable reference to the wrapped channel.
+    pub fn channel_mut(&mut self) -> &mut T {
+        &mut self.channel
+    }
+
+    fn metadata_size(&self) -> usize {
+        let metadata_size = match self.replay_window {
+            Some(_) => C::SEQUENCED_METADATA_SIZE,
+            None => C::METADATA_SIZE,
+        };
+
+        metadata_size + self.ratchet.epoch_size()
+    }
+
+    fn reset_replay_window(&mut self) {
+        if let Some(replay_window) = &mut self.replay_window {
+            *replay_window = ReplayWindow::default();
+        }
+    }
+
+    /// Checks that ``dest`` has space for the metadata and at least one byte of ciphertext.
+    fn check_buffer_size(&self, dest: &[u8]) -> communication::Result<()> {
+        if dest.len() <= self.metadata_size() {
+            return Err(CommunicationError::BufferTooSmall);
+        }
+
+        Ok(())
+    }
+
+    /// Authenticates and decrypts the message in ``dest`` that was read from the inner channel,
+    /// returning the length of the decrypted message at the start of ``dest``.
+    fn open(&mut self, dest: &mut [u8], message_ad: &[u8]) -> communication::Result<usize> {
+        let metadata_size = self.metadata_size();
+
+        // Check we have at least one byte of ciphertext.
+        if dest.len() <= metadata_size {
+            return Err(CommunicationError::FrameTooShort);
+        }
+
+        // Split message from metadata.
+        let (msg_body, metadata) = dest.split_at_mut(dest.len() - metadata_size);
+
+        // Take the sequence number and epoch, if there are any.
+        let (&mut ref header, metadata) = metadata.split_at_mut(metadata_size - C::METADATA_SIZE);
+        let (seq_bytes, epoch_bytes) = header.split_at(header.len() - self.ratchet.epoch_size());
+
+        // Take nonce and tag
+        let (&mut ref nonce, &mut ref tag) = metadata.split_at_mut(C::NonceSize::USIZE);
+
+        let mut ad_buf = [0; ASSOCIATED_DATA_BUFFER_SIZE];
+        let associated_data = write_associated_data(
+            &mut ad_buf,
+            seq_bytes,
+            epoch_bytes,
+            self.context,
+            message_ad,
+        )?;
+
+        let epoch = match epoch_bytes.try_into() {
+            Ok(epoch) => u32::from_be_bytes(epoch),
+            Err(_) => self.ratchet.epoch,
+        };
+
+        if epoch == self.ratchet.epoch {
+            // Decrypt in place using the ciphertext, nonce, tag, and associated data
+            self.decryptor
+                .decrypt_in_place_detached(nonce.into(), associated_data, msg_body, tag.into())
+                .map_err(|_| CommunicationError::AuthenticationFailure)?;
+        } else {
+            // The message is from a later epoch, so messages were lost. Only switch to its key once the
+            // message is authenticated so forged messages can't move the epoch.
+            let mut key = self
+                .ratchet
+                .key_for_epoch(epoch)
+                .ok_or(CommunicationError::AuthenticationFailure)?;
+            let result = C::new(&key).decrypt_in_place_detached(
+                nonce.into(),
+                associated_data,
+                msg_body,
+                tag.into(),
+            );
+
+            if result.is_ok() {
+                self.decryptor = C::new(&key);
+                self.ratchet.catch_up(&key, epoch);
+                self.reset_replay_window();
+            }
+
+            key.zeroize();
+            result.map_err(|_| CommunicationError::AuthenticationFailure)?;
+        }
+
+        // The sequence number is only checked after the message is authenticated so forged messages
+        // can't move the window.
+        if let Some(replay_window) = &mut self.replay_window {
+            let seq = seq_bytes
+                .try_into()
+                .map_err(|_| CommunicationError::InternalError)?;
+
+            replay_window.check_and_update(u64::from_be_bytes(seq))?;
+        }
+
+        // Our decrypted buffer is at the beginning of our slice and we return the length of it.
+        if self.padded {
+            unpad(msg_body)
+        } else {
+            Ok(msg_body.len())
+        }
+    }
+}
+
+impl<T: RxChannel, C: ChannelCipher> AeadRxChannel<T, C> {
+    /// Receives data from the channel like [`recv_with_timeout`](RxChannel::recv_with_timeout),
+    /// additionally authenticating ``associated_data``, which must match the associated data the
+    /// message was sent with. See the struct-level documentation for more info.
+    pub fn recv_with_ad_and_timeout<U: Timer>(
+        &mut self,
+        dest: &mut [u8],
+        associated_data: &[u8],
+        timer: &mut U,
+    ) -> communication::Result<usize> {
+        self.recv_with(
+            dest,
+            associated_data,
+            |ch, d, t| ch.channel.recv_with_timeout(d, t),
+            timer,
+        )
+    }
+
+    /// Receives data from the channel like [`recv_with_data_timeout`](RxChannel::recv_with_data_timeout),
+    /// additionally authenticating ``associated_data``, which must match the associated data the
+    /// message was sent with. See the struct-level documentation for more info.
+    pub fn recv_with_ad_and_data_timeout<U: Timer>(
+        &mut self,
+        dest: &mut [u8],
+        associated_data: &[u8],
+        timer: &mut U,
+    ) -> communication::Result<usize> {
+        self.recv_with(
+            dest,
+            associated_data,
+            |ch, d, t| ch.channel.recv_with_data_timeout(d, t),
+            timer,
+        )
+    }
+
+    fn recv_with<U: Timer>(
+        &mut self,
+        dest: &mut [u8],
+        message_ad: &[u8],
+        read_fn: impl FnOnce(&mut Self, &mut [u8], &mut U) -> communication::Result<usize>,
+        timer: &mut U,
+    ) -> communication::Result<usize> {
+        self.check_buffer_size(dest)?;
+
+        // Read message from inner channel.
+        let bytes_read = read_fn(self, dest, timer)?;
+
+        self.open(&mut dest[..bytes_read], message_ad)
+    }
+}
+
+impl<T: AsyncRxChannel, C: ChannelCipher> AeadRxChannel<T, C> {
+    /// Receives data from the channel like
+    /// [`AsyncRxChannel::recv_with_timeout`](AsyncRxChannel::recv_with_timeout), additionally
+    /// authenticating ``associated_data`` like [`recv_with_ad_and_timeout`](Self::recv_with_ad_and_timeout).
+    pub async fn async_recv_with_ad_and_timeout<U: Timer>(
+        &mut self,
+        dest: &mut [u8],
+        associated_data: &[u8],
+        timer: &mut U,
+    ) -> communication::Result<usize> {
+        self.check_buffer_size(dest)?;
+
+        // Read message from inner channel.
+        let bytes_read = self.channel.recv_with_timeout(dest, timer).await?;
+
+        self.open(&mut dest[..bytes_read], associated_data)
+    }
+
+    /// Receives data from the channel like
+    /// [`AsyncRxChannel::recv_with_data_timeout`](AsyncRxChannel::recv_with_data_timeout), additionally
+    /// authenticating ``associated_data`` like
+    /// [`recv_with_ad_and_data_timeout`](Self::recv_with_ad_and_data_timeout).
+    pub async fn async_recv_with_ad_and_data_timeout<U: Timer>(
+        &mut self,
+        dest: &mut [u8],
+        associated_data: &[u8],
+        timer: &mut U,
+    ) -> communication::Result<usize> {
+        self.check_buffer_size(dest)?;
+
+        // Read message from inner channel.
+        let bytes_read = self.channel.recv_with_data_timeout(dest, timer).await?;
+
+        self.open(&mut dest[..bytes_read], associated_data)
+    }
+}
+
+impl<T, C: ChannelCipher> KeyedChannel for AeadRxChannel<T, C> {
+    type KeyType = CipherKey<C>;
+
+    fn change_key(&mut self, new_key: &Self::KeyType) {
+        self.decryptor = C::new(new_key);
+        self.ratchet.set_key(new_key);
+        self.reset_replay_window();
+    }
+}
+
+impl<T, C: ChannelCipher> RatchetingChannel for AeadRxChannel<T, C> {
+    fn ratchet(&mut self) {
+        self.ratchet.advance();
+        self.decryptor = C::new(&self.ratchet.key);
+        self.reset_replay_window();
+    }
+}
+
+impl<T: RxChannel, C: ChannelCipher> RxChannel for AeadRxChannel<T, C> {
+    /// The metadata of the wrapped channel, along with the nonce, tag, sequence number, and ratchet epoch,
+    /// which are the most metadata sent with a message by any configuration of the channel.
+    const METADATA_SIZE: usize = T::METADATA_SIZE + C::SEQUENCED_METADATA_SIZE + RATCHET_EPOCH_SIZE;
+
+    /// Receives data from the channel, putting the data received into ``dest``, returning the
+    /// number of bytes written to it upon success. The buffer provided should have enough
+    /// space to store the data that needs to be received along with its metadata size. The provided timeout
+    /// is reset on each byte received. If the timeout has passed and not enough bytes have been received, this
+    /// function returns an error. Upon an error, a [`CommunicationError`] is given.
+    ///
+    /// # ERRORS:
+    ///
+    /// - [`CommunicationError::BufferTooSmall`] - The provided buffer is too small to fit a whole message
+    ///   sent in a frame. In this channel, there must be enough space to accomodate for
+    ///   [`C::METADATA_SIZE`](ChannelCipher::METADATA_SIZE) bytes + 1 additional byte of message data. A
+    ///   blank message can neither be sent nor received.
+    /// - [`CommunicationError::FrameTooShort`] - The message received is too short to contain the
+    ///   [`C::METADATA_SIZE`](ChannelCipher::METADATA_SIZE) bytes of metadata and 1 byte of message data.
+    /// - [`CommunicationError::AuthenticationFailure`] - The message received couldn't be authenticated.
+    /// - [`CommunicationError::Timeout`] - The timeout is reached.
+    /// - Any other error given by the wrapped channel, such as [`CommunicationError::RecvError`] if a
+    ///   malformed message was sent.
+    ///  - [`CommunicationError::InternalError`]
+    ///    - This can occur if some internal error happens. This should only occur if something is wrong
+    ///      with the implementation.
+    fn recv_with_data_timeout<U: Timer>(
+        &mut self,
+        dest: &mut [u8],
+        timer: &mut U,
+    ) -> communication::Result<usize> {
+        self.recv_with_ad_and_data_timeout(dest, b"", timer)
+    }
+
+    /// Receives data from the channel, putting the data received into ``dest``, returning the
+    /// number of bytes written to it upon success. The buffer provided should have enough
+    /// space to store the data that needs to be received along with its metadata size. The provided time to
+    /// block is for the entire receive operation. If the timeout has passed and not enough bytes have been received,
+    /// this function returns an error. Upon an error, a [`CommunicationError`] is given.
+    ///
+    /// # ERRORS:
+    ///
+    /// - [`CommunicationError::BufferTooSmall`] - The provided buffer is too small to fit a whole message
+    ///   sent in a frame. In this channel, there must be enough space to accomodate for
+    ///   [`C::METADATA_SIZE`](ChannelCipher::METADATA_SIZE) bytes + 1 additional byte of message data. A
+    ///   blank message can neither be sent nor received.
+    /// - [`CommunicationError::FrameTooShort`] - The message received is too short to contain the
+    ///   [`C::METADATA_SIZE`](ChannelCipher::METADATA_SIZE) bytes of metadata and 1 byte of message data.
+    /// - [`CommunicationError::AuthenticationFailure`] - The message received couldn't be authenticated.
+    /// - [`CommunicationError::Timeout`] - The timeout is reached.
+    /// - Any other error given by the wrapped channel, such as [`CommunicationError::RecvError`] if a
+    ///   malformed message was sent.
+    ///  - [`CommunicationError::InternalError`]
+    ///    - This can occur if some internal error happens. This should only occur if something is wrong
+    ///      with the implementation.
+    fn recv_with_timeout<U: Timer>(
+        &mut self,
+        dest: &mut [u8],
+        timer: &mut U,
+    ) -> communication::Result<usize> {
+        self.recv_with_ad_and_timeout(dest, b"", timer)
+    }
+}
+
+impl<T: AsyncRxChannel, C: ChannelCipher> AsyncRxChannel for AeadRxChannel<T, C> {
+    /// The metadata of the wrapped channel, along with the nonce, tag, sequence number, and ratchet epoch,
+    /// which are the most metadata sent with a message by any configuration of the channel.
+    const METADATA_SIZE: usize = T::METADATA_SIZE + C::SEQUENCED_METADATA_SIZE + RATCHET_EPOCH_SIZE;
+
+    /// Receives data from the channel like
+    /// [`RxChannel::recv_with_data_timeout`](RxChannel::recv_with_data_timeout) on a blocking
+    /// [`AeadRxChannel`], yielding to the executor while waiting for data. See the
+    /// documentation of that function for the errors that can be given.
+    async fn recv_with_data_timeout<U: Timer>(
+        &mut self,
+        dest: &mut [u8],
+        timer: &mut U,
+    ) -> communication::Result<usize> {
+        self.async_recv_with_ad_and_data_timeout(dest, b"", timer)
+            .await
+    }
+
+    /// Receives data from the channel like [`RxChannel::recv_with_timeout`](RxChannel::recv_with_timeout)
+    /// on a blocking [`AeadRxChannel`], yielding to the executor while waiting for data. See
+    /// the documentation of that function for the errors that can be given.
+    async fn recv_with_timeout<U: Timer>(
+        &mut self,
+        dest: &mut [u8],
+        timer: &mut U,
+    ) -> communication::Result<usize> {
+        self.async_recv_with_ad_and_timeout(dest, b"", timer).await
+    }
+}
+
+/// This [`TxChannel`] wraps around a [`FramedTxChannel`] to encrypt communications with the [`ChannelCipher`]
+/// ``C`` for an [`AeadRxChannel`], providing message authenticity and confidentiality. This channel requires a
+/// [`RandomSource`] to generate nonces with [`ChannelCipher::fill_nonce`].
+/// It is also an [`AsyncTxChannel`] when it wraps around an [`AsyncFramedTxChannel`].
+///
+/// A channel created with [`new_with_sequence_numbers`](Self::new_with_sequence_numbers) sends a
+/// monotonically increasing sequence number with each message, authenticated along with the message,
+/// for an [`AeadRxChannel`] with replay protection to check. Changing the key resets the
+/// sequence number.
+///
+/// A context can be set with [`with_context`](Self::with_context), which is authenticated with every
+/// message sent, and additional associated data can be authenticated with a single message by sending it
+/// with [`send_with_ad`](Self::send_with_ad). See [`AeadRxChannel`] for more details.
+///
+/// A channel configured with [`with_ratchet_interval`](Self::with_ratchet_interval) ratchets its key after
+/// every N messages sent and sends the epoch of its key with each message, so the receiver can catch up
+/// when messages are lost. See [`AeadRxChannel`] for more details on key ratcheting.
+///
+/// A channel configured with [`with_padding`](Self::with_padding) pads each message before encrypting it
+/// according to a [`PaddingPolicy`], so the length of a message on the wire only reveals roughly how long
+/// it is. The padding is a single ``0x80`` byte followed by zero bytes. Messages are never padded past
+/// [`MAX_PADDED_MESSAGE_SIZE`] bytes, or past a lower limit set with
+/// [`with_max_padded_len`](Self::with_max_padded_len), and messages that don't fit in that many bytes once
+/// padded can't be sent. The limit should be the largest message the [`AeadRxChannel`] on the other end
+/// can receive, so a padded message never overflows its buffer.
+///
+/// See the module-level documentation for more information on the ciphers available.
+pub struct AeadTxChannel<T, U: RandomSource, C: ChannelCipher> {
+    channel: T,
+    random_source: U,
+    encryptor: C,
+    next_sequence_number: Option<u64>,
+    context: &'static [u8],
+    ratchet: KeyRatchet<C>,
+    padding: PaddingPolicy,
+    max_padded_len: usize,
+}
+
+impl<T, U: RandomSource, C: ChannelCipher> AeadTxChannel<T, U, C> {
+    /// Creates a new [`AeadTxChannel`] given an inner [`FramedTxChannel`] or
+    /// [`AsyncFramedTxChannel`] and an encryption key.
+    pub fn new(channel: T, random_source: U, tx_key: &CipherKey<C>) -> Self {
+        Self {
+            channel,
+            random_source,
+            encryptor: C::new(tx_key),
+            next_sequence_number: None,
+            context: b"",
+            ratchet: KeyRatchet::new(tx_key),
+            padding: PaddingPolicy::None,
+            max_padded_len: MAX_PADDED_MESSAGE_SIZE,
+        }
+    }
+
+    /// Creates a new [`AeadTxChannel`] that sends sequence numbers given an inner
+    /// [`FramedTxChannel`] or [`AsyncFramedTxChannel`] and an encryption key. See the struct-level
+    /// documentation for more info.
+    pub fn new_with_sequence_numbers(channel: T, random_source: U, tx_key: &CipherKey<C>) -> Self {
+        Self {
+            channel,
+            random_source,
+            encryptor: C::new(tx_key),
+            next_sequence_number: Some(0),
+            context: b"",
+            ratchet: KeyRatchet::new(tx_key),
+            padding: PaddingPolicy::None,
+            max_padded_len: MAX_PADDED_MESSAGE_SIZE,
+        }
+    }
+
+    /// Sets the context that is authenticated with every message sent on this channel. See the
+    /// struct-level documentation for more info.
+    pub fn with_context(mut self, context: &'static [u8]) -> Self {
+        self.context = context;
+
+        self
+    }
+
+    /// Makes this channel ratchet its key after every ``messages`` messages sent. See the struct-level
+    /// documentation for more info.
+    pub fn with_ratchet_interval(mut self, messages: NonZeroU32) -> Self {
+        self.ratchet.sends_epochs = true;
+        self.ratchet.interval = Some(messages);
+
+        self
+    }
+
+    /// Makes this channel pad every message sent according to ``policy``. The [`AeadRxChannel`] receiving
+    /// the messages must have padding enabled. See the struct-level documentation for more info.
+    pub fn with_padding(mut self, policy: PaddingPolicy) -> Self {
+        self.padding = policy;
+
+        self
+    }
+
+    /// Caps the length messages are padded to at ``len`` bytes, or [`MAX_PADDED_MESSAGE_SIZE`] bytes if
+    /// ``len`` is larger. This should be the largest message the [`AeadRxChannel`] on the other end can
+    /// receive, which is the size of its receive buffer minus the metadata size. See the struct-level
+    /// documentation for more info.
+    pub fn with_max_padded_len(mut self, len: usize) -> Self {
+        self.max_padded_len = len.min(MAX_PADDED_MESSAGE_SIZE);
+
+        self
+    }
+
+    /// Gets a reference to the wrapped channel.
+    pub fn channel(&self) -> &T {
+        &self.channel
+    }
+
+    /// Gets a mutable reference to the wrapped channel.
+    pub fn channel_mut(&mut self) -> &mut T {
+        &mut self.channel
+    }
+
+    /// Gets the length ``buff`` is padded to according to the padding policy, or [`None`] if messages
+    /// aren't padded.
+    ///
+    /// # ERRORS:
+    ///
+    /// - [`CommunicationError::SendError`] - ``buff`` is empty or doesn't fit in the maximum padded length
+    ///   once padded.
+    fn padded_len(&self, buff: &[u8]) -> communication::Result<Option<usize>> {
+        if buff.is_empty() {
+            return Err(CommunicationError::SendError);
+        }
+
+        match self.padding.padded_len(buff.len(), self.max_padded_len) {
+            Some(padded_len) if padded_len > self.max_padded_len => {
+                Err(CommunicationError::SendError)
+            }
+            padded_len => Ok(padded_len),
+        }
+    }
+
+    /// Encrypts ``buff`` in place with the next nonce and sequence number, authenticating the context and
+    /// ``associated_data``, and returns the metadata to send after the ciphertext.
+    fn seal(
+        &mut self,
+        buff: &mut [u8],
+        associated_data: &[u8],
+    ) -> communication::Result<SealedMetadata<C>> {
+        let mut nonce = Nonce::<C>::default();
+
+        // Fill nonce with the nonce for the next message.
+        C::fill_nonce(&mut self.random_source, &mut nonce);
+
+        // Take the next sequence number, if sequence numbers are enabled.
+        let seq_bytes = match &mut self.next_sequence_number {
+            Some(seq) => {
+                let seq_bytes = seq.to_be_bytes();
+                *seq = seq.checked_add(1).ok_or(CommunicationError::SendError)?;
+
+                Some(seq_bytes)
+            }
+            None => None,
+        };
+
+        let epoch_bytes = self.ratchet.epoch_bytes();
+
+        let mut ad_buf = [0; ASSOCIATED_DATA_BUFFER_SIZE];
+        let associated_data = write_associated_data(
+            &mut ad_buf,
+            seq_bytes.as_ref().map_or(&[][..], |b| &b[..]),
+            epoch_bytes.as_ref().map_or(&[][..], |b| &b[..]),
+            self.context,
+            associated_data,
+        )?;
+
+        // Encrypt buff completely in place with the associated data, returning the auth tag.
+        let tag = self
+            .encryptor
+            .encrypt_in_place_detached(&nonce, associated_data, buff)
+            .map_err(|_| CommunicationError::SendError)?;
+
+        Ok(SealedMetadata {
+            seq_bytes,
+            epoch_bytes,
+            nonce,
+            tag,
+        })
+    }
+
+    /// Counts a message sent, ratcheting the key if the ratchet interval is reached.
+    fn record_sent(&mut self) {
+        if self.ratchet.record_message() {
+            self.ratchet();
+        }
+    }
+}
+
+impl<T: FramedTxChannel, U: RandomSource, C: ChannelCipher> AeadTxChannel<T, U, C> {
+    /// Sends the data from ``buff`` through the channel like [`send`](TxChannel::send), additionally
+    /// authenticating ``associated_data``. The associated data isn't sent, so the receiver must receive
+    /// the message with the same associated data. See the struct-level documentation for more info.
+    ///
+    /// # ERRORS:
+    ///
+    /// - [`CommunicationError::SendError`] - See [`send`](TxChannel::send).
+    /// - [`CommunicationError::InternalError`] - The context and ``associated_data`` together are longer
+    ///   than [`MAX_ASSOCIATED_DATA_SIZE`].
+    pub fn send_with_ad(
+        &mut self,
+        buff: &mut [u8],
+        associated_data: &[u8],
+    ) -> communication::Result<()> {
+        match self.padded_len(buff)? {
+            Some(padded_len) => self.send_padded(buff, padded_len, associated_data),
+            None => self.seal_and_send(buff, associated_data),
+        }
+    }
+
+    /// Pads ``buff`` to ``padded_len`` bytes in a buffer of its own and sends it. This isn't inlined so
+    /// the padding buffer is only put on the stack when padding is enabled.
+    #[inline(never)]
+    fn send_padded(
+        &mut self,
+        buff: &[u8],
+        padded_len: usize,
+        associated_data: &[u8],
+    ) -> communication::Result<()> {
+        let mut padded_buf = [0; MAX_PADDED_MESSAGE_SIZE];
+        let padded = &mut padded_buf[..padded_len];
+        pad(buff, padded);
+
+        self.seal_and_send(padded, associated_data)
+    }
+
+    fn seal_and_send(
+        &mut self,
+        buff: &mut [u8],
+        associated_data: &[u8],
+    ) -> communication::Result<()> {
+        let metadata = self.seal(buff, associated_data)?;
+
+        self.channel.frame::<5>(|| metadata.frame(buff))?;
+        self.record_sent();
+
+        Ok(())
+    }
+}
+
+impl<T: AsyncFramedTxChannel, U: RandomSource, C: ChannelCipher> AeadTxChannel<T, U, C> {
+    /// Sends the data from ``buff`` through the channel like [`AsyncTxChannel::send`](AsyncTxChannel::send),
+    /// additionally authenticating ``associated_data`` like [`send_with_ad`](Self::send_with_ad).
+    pub async fn async_send_with_ad(
+        &mut self,
+        buff: &mut [u8],
+        associated_data: &[u8],
+    ) -> communication::Result<()> {
+        match self.padded_len(buff)? {
+            Some(padded_len) => {
+                // The padding buffer is only zeroed when padding is enabled.
+                let mut padded_buf = [0; MAX_PADDED_MESSAGE_SIZE];
+                let padded = &mut padded_buf[..padded_len];
+                pad(buff, padded);
+
+                self.async_seal_and_send(padded, associated_data).await
+            }
+            None => self.async_seal_and_send(buff, associated_data).await,
+        }
+    }
+
+    async fn async_seal_and_send(
+        &mut self,
+        buff: &mut [u8],
+        associated_data: &[u8],
+    ) -> communication::Result<()> {
+        let metadata = self.seal(buff, associated_data)?;
+
+        self.channel.frame::<5>(|| metadata.frame(buff)).await?;
+        self.record_sent();
+
+        Ok(())
+    }
+}
+
+impl<T, U: RandomSource, C: ChannelCipher> KeyedChannel for AeadTxChannel<T, U, C> {
+    type KeyType = CipherKey<C>;
+
+    fn change_key(&mut self, new_key: &Self::KeyType) {
+        self.encryptor = C::new(new_key);
+        self.ratchet.set_key(new_key);
+
+        if let Some(seq) = &mut self.next_sequence_number {
+            *seq = 0;
+        }
+    }
+}
+
+impl<T, U: RandomSource, C: ChannelCipher> RatchetingChannel for AeadTxChannel<T, U, C> {
+    fn ratchet(&mut self) {
+        self.ratchet.advance();
+        self.encryptor = C::new(&self.ratchet.key);
+
+        if let Some(seq) = &mut self.next_sequence_number {
+            *seq = 0;
+        }
+    }
+}
+
+impl<T: FramedTxChannel, U: RandomSource, C: ChannelCipher> TxChannel for AeadTxChannel<T, U, C> {
+    /// Sends the data from ``src`` through the channel. Upon an error, a [`CommunicationError`]
+    /// is given.
+    ///
+    /// # ERRORS:
+    ///
+    /// - [`CommunicationError::SendError`]
+    ///   - This could occur if any implementation-based error occurs while sending data.
+    ///     This could be because:
+    ///     - The message was too short. With this channel, at least one byte of data must be sent.
+    ///     - An error occurred during message encryption.
+    ///     - Sequence numbers are enabled and every sequence number has been used.
+    ///     - Padding is enabled and the message doesn't fit in the maximum padded length once padded,
+    ///       which is [`MAX_PADDED_MESSAGE_SIZE`] bytes unless lowered with
+    ///       [`with_max_padded_len`](AeadTxChannel::with_max_padded_len).
+    /// - [`CommunicationError::InternalError`]
+    ///   - This can occur if some internal error happens. This should only occur if something is wrong
+    ///     with the implementation.
+    fn send(&mut self, buff: &mut [u8]) -> communication::Result<()> {
+        self.send_with_ad(buff, b"")
+    }
+}
+
+impl<T: AsyncFramedTxChannel, U: RandomSource, C: ChannelCipher> AsyncTxChannel
+    for AeadTxChannel<T, U, C>
+{
+    /// Sends the data from ``src`` through the channel like [`TxChannel::send`](TxChannel::send) on a
+    /// blocking [`AeadTxChannel`]. See the documentation of that function for the errors that
+    /// can be given.
+    async fn send(&mut self, buff: &mut [u8]) -> communication::Result<()> {
+        self.async_send_with_ad(buff, b"").await
+    }
+}
@@ -10,15 +10,323 @@
 //!     - To prevent conflating \1 characters with the underlying data, the underlying data is hex encoded
 //!       and decoded. NULL characters are completely ignored and won't affect the message.
 //!     - Helper functions to implement channels using this type of framing are in the [`bogoframing`] module.
+//! - COBS framing
+//!     - COBS framing uses Consistent Overhead Byte Stuffing to remove all NULL characters from the underlying
+//!       data. Each message begins and ends with one NULL character.
+//!     - This adds at most one byte of overhead for every 254 bytes of data instead of doubling the size of
+//!       the data like BogoFraming does.
+//!     - Helper functions to implement channels using this type of framing are in the [`cobsframing`] module.
+//! - CRC framing
+//!     - CRC framing is a length-prefixed framing protocol. Each message is preceded by two sync bytes and a
+//!       checksummed header containing a version byte, the kind of the message, and the length of the message,
+//!       and is followed by a CRC-32 checksum.
+//!     - Corrupt frames are rejected before they reach the encryption and integrity layer, and corrupt headers
+//!       are rejected as soon as they are read.
+//!     - Helper functions to implement channels using this type of framing are in the [`crcframing`] module.
+//!
+//! Channels can be made generic over the framing protocol they use with the [`FramingProtocol`] trait,
+//! which is implemented by [`BogoFraming`], [`CobsFraming`], and [`CrcFraming`].
+//!
+//! Each framing protocol also has a push-based [`FrameDecoder`] that bytes can be fed into one at a time,
+//! such as from a UART receive interrupt handler. The blocking receive helpers for each protocol are
+//! implemented on top of its decoder, so both share one parser. Async receive helpers that yield to the
+//! executor instead of blocking while waiting for bytes are also implemented on top of each decoder, and
+//! [`AsyncFramedTxChannel`] is the async counterpart of [`FramedTxChannel`].
+//!
+//! Decoders count the bytes they discard while looking for the start of a frame, which the receive helpers
+//! report so that channels can tell when they lost track of the frames being sent, even when no malformed
+//! frame was received. See [`RxChannel::take_discarded_bytes`](crate::communication::RxChannel::take_discarded_bytes).
 //!
 //! See the documentation for [`communication`](crate::communication) for a description of full communication
 //! stack.
 
 pub mod bogoframing;
+pub mod cobsframing;
+pub mod crcframing;
 
 use chacha20poly1305::aead::heapless;
 
-use crate::communication::{CommunicationError, TxChannel};
+use crate::communication::{self, yield_now, AsyncTxChannel, CommunicationError, Timer, TxChannel};
+
+pub use bogoframing::BogoFraming;
+pub use cobsframing::CobsFraming;
+pub use crcframing::CrcFraming;
+
+/// Whether a timeout resets after receiving a byte or applies to receiving an entire frame.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub(crate) enum TimeoutType {
+    ByteLevel,
+    FrameLevel,
+}
+
+/// Reads a byte using ``read_fn``, returning an error upon timeout. The timer is reset after
+/// a byte is read if ``timeout_type`` is [`TimeoutType::ByteLevel`].
+pub(crate) fn read_byte<T, U: Timer>(
+    read_fn_arg: &mut T,
+    mut read_fn: impl FnMut(&mut T) -> communication::Result<u8>,
+    timer: &mut U,
+    timeout_type: TimeoutType,
+) -> communication::Result<u8> {
+    loop {
+        if timer.poll() {
+            return Err(CommunicationError::Timeout);
+        }
+
+        if let Ok(read) = read_fn(read_fn_arg) {
+            // Reset the timer if the timeout is per byte.
+            if timeout_type == TimeoutType::ByteLevel {
+                timer.reset();
+            }
+
+            return Ok(read);
+        }
+    }
+}
+
+/// Reads a byte like [`read_byte`], yielding to the executor instead of blocking while no byte
+/// is available.
+pub(crate) async fn async_read_byte<T, U: Timer>(
+    read_fn_arg: &mut T,
+    mut read_fn: impl FnMut(&mut T) -> communication::Result<u8>,
+    timer: &mut U,
+    timeout_type: TimeoutType,
+) -> communication::Result<u8> {
+    loop {
+        if timer.poll() {
+            return Err(CommunicationError::Timeout);
+        }
+
+        if let Ok(read) = read_fn(read_fn_arg) {
+            // Reset the timer if the timeout is per byte.
+            if timeout_type == TimeoutType::ByteLevel {
+                timer.reset();
+            }
+
+            return Ok(read);
+        }
+
+        yield_now().await;
+    }
+}
+
+/// Receives a frame by reading bytes with ``read_fn`` and pushing them into ``decoder`` until a
+/// complete frame is decoded into ``dest``. [`TimeoutType`] determines whether the timeout resets
+/// after receiving a byte or whether the timemout applies to receiving the entire frame. This is
+/// used to implement the blocking receive functions of every framing protocol.
+pub(crate) fn recv_frame<T, U: Timer, D: FrameDecoder>(
+    decoder: &mut D,
+    read_arg: &mut T,
+    dest: &mut [u8],
+    timer: &mut U,
+    mut read_fn: impl FnMut(&mut T) -> communication::Result<u8>,
+    min_message_len: usize,
+    timeout_type: TimeoutType,
+) -> communication::Result<usize> {
+    if dest.len() < min_message_len {
+        return Err(CommunicationError::BufferTooSmall);
+    }
+
+    loop {
+        let read = read_byte(read_arg, &mut read_fn, timer, timeout_type)?;
+
+        if let Some(ct) = decoder.push(read, dest)? {
+            return Ok(ct);
+        }
+    }
+}
+
+/// Receives a frame like [`recv_frame`], yielding to the executor instead of blocking while no byte
+/// is available. ``read_fn`` must not block. This is used to implement the async receive functions of
+/// every framing protocol.
+pub(crate) async fn async_recv_frame<T, U: Timer, D: FrameDecoder>(
+    decoder: &mut D,
+    read_arg: &mut T,
+    dest: &mut [u8],
+    timer: &mut U,
+    mut read_fn: impl FnMut(&mut T) -> communication::Result<u8>,
+    min_message_len: usize,
+    timeout_type: TimeoutType,
+) -> communication::Result<usize> {
+    if dest.len() < min_message_len {
+        return Err(CommunicationError::BufferTooSmall);
+    }
+
+    loop {
+        let read = async_read_byte(read_arg, &mut read_fn, timer, timeout_type).await?;
+
+        if let Some(ct) = decoder.push(read, dest)? {
+            return Ok(ct);
+        }
+    }
+}
+
+/// A push-based decoder for a framing protocol. Bytes are pushed into the decoder one at a time, such
+/// as from a UART receive interrupt handler, and decoded messages are written into a caller-provided
+/// buffer. A decoder never blocks or allocates, so it can be used from interrupt context. The same buffer
+/// must be provided for every byte of a frame.
+pub trait FrameDecoder {
+    /// Creates a new decoder that rejects messages shorter than ``min_message_len`` bytes.
+    fn new(min_message_len: usize) -> Self;
+
+    /// Pushes a received byte into the decoder, writing any decoded data into ``dest``. Returns
+    /// ``Ok(Some(len))`` once a complete message has been decoded into the first ``len`` bytes of
+    /// ``dest`` or ``Ok(None)`` if the frame isn't complete yet. After a complete message or an error,
+    /// the decoder starts looking for the start of the next frame.
+    ///
+    /// # ERRORS:
+    ///
+    /// - [`CommunicationError::RecvError`] - Occurs when a malformed frame is received that isn't
+    ///   covered by a more specific error.
+    /// - [`CommunicationError::BufferTooSmall`] - Occurs when the message in the frame doesn't fit in
+    ///   ``dest``.
+    /// - [`CommunicationError::FrameTooShort`] - Occurs when the message is shorter than the minimum
+    ///   message length.
+    /// - [`CommunicationError::MalformedHexNibble`] - Occurs when a BogoFraming frame contains a character
+    ///   that isn't a hex digit or an odd number of hex digits.
+    /// - [`CommunicationError::CorruptFrame`] - Occurs when a frame with an unknown header or a checksum
+    ///   that doesn't match is received, or when a COBS frame is cut short.
+    fn push(&mut self, byte: u8, dest: &mut [u8]) -> communication::Result<Option<usize>>;
+
+    /// Discards any partially decoded frame and starts looking for the start of the next frame.
+    fn reset(&mut self);
+
+    /// Gets the number of bytes discarded while looking for the start of a frame since the decoder was
+    /// created. These bytes weren't part of any frame, such as noise on the line or the rest of a frame
+    /// whose start was lost, so discarding them means the receiver was out of sync with the sender.
+    fn discarded(&self) -> usize;
+}
+
+/// A trait implemented by each framing protocol to provide the helper functions needed to implement
+/// [`RxChannels`](crate::communication::RxChannel) and [`FramedTxChannels`](FramedTxChannel) using
+/// that protocol. This allows channels to be generic over the framing protocol they use.
+pub trait FramingProtocol {
+    /// The push-based [`FrameDecoder`] for this framing protocol.
+    type Decoder: FrameDecoder;
+
+    /// Receives a frame, blocking until the timer has elapsed from the beginning of this
+    /// function call. This function mirrors
+    /// [`RxChannel::recv_with_timeout`](crate::communication::RxChannel::recv_with_timeout()).
+    /// See the documentation of that function for more details. The number of bytes discarded outside
+    /// of any frame, as given by [`FrameDecoder::discarded`], is added to ``discarded``.
+    fn recv_frame_with_timeout<T, U: Timer>(
+        read_arg: &mut T,
+        dest: &mut [u8],
+        timer: &mut U,
+        read_fn: impl FnMut(&mut T) -> communication::Result<u8>,
+        min_message_len: usize,
+        discarded: &mut usize,
+    ) -> communication::Result<usize> {
+        let mut decoder = Self::Decoder::new(min_message_len);
+        let result = recv_frame(
+            &mut decoder,
+            read_arg,
+            dest,
+            timer,
+            read_fn,
+            min_message_len,
+            TimeoutType::FrameLevel,
+        );
+        *discarded += decoder.discarded();
+
+        result
+    }
+
+    /// Receives a frame with the timeout provided by the specified timer.
+    /// This timeout resets each time a byte is read. This function mirrors
+    /// [`RxChannel::recv_with_data_timeout`](crate::communication::RxChannel::recv_with_data_timeout()).
+    /// See the documentation of that function for more details. The number of bytes discarded outside
+    /// of any frame is added to ``discarded`` like [`recv_frame_with_timeout`](Self::recv_frame_with_timeout).
+    fn recv_frame_with_data_timeout<T, U: Timer>(
+        read_arg: &mut T,
+        dest: &mut [u8],
+        timer: &mut U,
+        read_fn: impl FnMut(&mut T) -> communication::Result<u8>,
+        min_message_len: usize,
+        discarded: &mut usize,
+    ) -> communication::Result<usize> {
+        let mut decoder = Self::Decoder::new(min_message_len);
+        let result = recv_frame(
+            &mut decoder,
+            read_arg,
+            dest,
+            timer,
+            read_fn,
+            min_message_len,
+            TimeoutType::ByteLevel,
+        );
+        *discarded += decoder.discarded();
+
+        result
+    }
+
+    /// Receives a frame like [`recv_frame_with_timeout`](Self::recv_frame_with_timeout), yielding to the
+    /// executor instead of blocking while no byte is available. ``read_fn`` must give an error instead of
+    /// blocking when no byte is available. This function mirrors
+    /// [`AsyncRxChannel::recv_with_timeout`](crate::communication::AsyncRxChannel::recv_with_timeout()).
+    #[allow(async_fn_in_trait)]
+    async fn async_recv_frame_with_timeout<T, U: Timer>(
+        read_arg: &mut T,
+        dest: &mut [u8],
+        timer: &mut U,
+        read_fn: impl FnMut(&mut T) -> communication::Result<u8>,
+        min_message_len: usize,
+        discarded: &mut usize,
+    ) -> communication::Result<usize> {
+        let mut decoder = Self::Decoder::new(min_message_len);
+        let result = async_recv_frame(
+            &mut decoder,
+            read_arg,
+            dest,
+            timer,
+            read_fn,
+            min_message_len,
+            TimeoutType::FrameLevel,
+        )
+        .await;
+        *discarded += decoder.discarded();
+
+        result
+    }
+
+    /// Receives a frame like [`recv_frame_with_data_timeout`](Self::recv_frame_with_data_timeout),
+    /// yielding to the executor instead of blocking while no byte is available. ``read_fn`` must give an
+    /// error instead of blocking when no byte is available. This function mirrors
+    /// [`AsyncRxChannel::recv_with_data_timeout`](crate::communication::AsyncRxChannel::recv_with_data_timeout()).
+    #[allow(async_fn_in_trait)]
+    async fn async_recv_frame_with_data_timeout<T, U: Timer>(
+        read_arg: &mut T,
+        dest: &mut [u8],
+        timer: &mut U,
+        read_fn: impl FnMut(&mut T) -> communication::Result<u8>,
+        min_message_len: usize,
+        discarded: &mut usize,
+    ) -> communication::Result<usize> {
+        let mut decoder = Self::Decoder::new(min_message_len);
+        let result = async_recv_frame(
+            &mut decoder,
+            read_arg,
+            dest,
+            timer,
+            read_fn,
+            min_message_len,
+            TimeoutType::ByteLevel,
+        )
+        .await;
+        *discarded += decoder.discarded();
+
+        result
+    }
+
+    /// Sends a frame with the given [`Frame`]. This function mirrors
+    /// [`FramedTxChannel::frame`](FramedTxChannel::frame()). See the documentation of
+    /// that function for more details.
+    fn frame<const FRAME_CT: usize, T>(
+        write_arg: &mut T,
+        frame: Frame<FRAME_CT>,
+        write_fn: impl FnMut(&mut T, &[u8]) -> communication::Result<()>,
+        min_message_len: usize,
+    ) -> communication::Result<()>;
+}
 
 /// A trait to be implemented by all transmission channels in framing protocol implementations.
 /// This contains one function to specify the slices that go into the frame to be transmitted.
@@ -30,8 +338,8 @@ pub trait FramedTxChannel: TxChannel {
     /// # ERRORS:
     ///
     /// - [`CommunicationError::SendError`] - Occurs when there's no more space
-    /// in the frame for the number of slices provided or some error occurs when
-    /// sending the frame through the [`TxChannel`].
+    ///   in the frame for the number of slices provided or some error occurs when
+    ///   sending the frame through the [`TxChannel`].
     fn frame<'a, const FRAME_CT: usize>(
         &mut self,
         frame: impl FnOnce() -> Result<Frame<'a, FRAME_CT>, CommunicationError>,
@@ -44,6 +352,24 @@ impl<T: FramedTxChannel> TxChannel for T {
     }
 }
 
+/// The async counterpart of [`FramedTxChannel`], to be implemented by async transmission channels in
+/// framing protocol implementations. Every [`AsyncFramedTxChannel`] is also an [`AsyncTxChannel`].
+#[allow(async_fn_in_trait)]
+pub trait AsyncFramedTxChannel {
+    /// Transmits a frame through the [`AsyncTxChannel`] like [`FramedTxChannel::frame`]. See the
+    /// documentation of that function for more details, including the errors that can be given.
+    async fn frame<'a, const FRAME_CT: usize>(
+        &mut self,
+        frame: impl FnOnce() -> Result<Frame<'a, FRAME_CT>, CommunicationError>,
+    ) -> Result<(), CommunicationError>;
+}
+
+impl<T: AsyncFramedTxChannel> AsyncTxChannel for T {
+    async fn send(&mut self, src: &mut [u8]) -> Result<(), CommunicationError> {
+        self.frame::<1>(|| Frame::new().append(src)).await
+    }
+}
+
 /// A struct that keeps track of slices of u8's to write as one frame
 /// in a [`FramedTxChannel`]. This can be used to write discontiguous
 /// pieces of memory into one frame. The const generic ``FRAME_SLICES``
@@ -78,7 +404,7 @@ impl<'a, const FRAME_CT: usize> Frame<'a, FRAME_CT> {
     /// # ERRORS:
     ///
     /// - [`CommunicationError::InternalError`] - Occurs when there's no more space
-    /// in the frame for another slice.
+    ///   in the frame for another slice.
     pub fn append(mut self, buff: &'a [u8]) -> Result<Self, CommunicationError> {
         match self.frame_components.push(buff) {
             Ok(_) => {
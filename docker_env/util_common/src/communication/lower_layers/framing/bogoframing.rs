@@ -7,125 +7,143 @@
 //!       and decoded. NULL characters are completely ignored and won't affect the message.
 //!     - Helper functions to implement channels using this type of framing are in the [`bogoframing`](self) module.
 
-use super::Frame;
+use super::{recv_frame, Frame, FrameDecoder, FramingProtocol, TimeoutType};
 use crate::communication::{self, CommunicationError, Timer};
 
-#[derive(Copy, Clone, PartialEq, Eq)]
-enum TimeoutType {
-    ByteLevel,
-    FrameLevel,
-}
+/// The [`FramingProtocol`] for BogoFraming. See the [`module`](self) documentation for more details.
+pub struct BogoFraming;
 
-/// Receives a bogoframe. [`TimeoutType`] determines whether the timeout resets after
-/// receiving a byte or whether the timemout applies to receiving the entire frame.
-fn recv_bogoframe<T, U: Timer>(
-    read_arg: &mut T,
-    dest: &mut [u8],
-    timer: &mut U,
-    mut read_fn: impl FnMut(&mut T) -> communication::Result<u8>,
-    min_message_len: usize,
-    timeout_type: TimeoutType,
-) -> communication::Result<usize> {
-    /// Reads a hex nibble, returning ``Ok(None)`` if \1 character is encountered,
-    /// or the hex digit as a number if successful, or an error upon receiving
-    /// an invalid character or timeout. This function ignores any NULL characters
-    /// by reading again.
-    fn read_hex_nibble<T, U: Timer>(
-        read_fn_arg: &mut T,
-        mut read_fn: impl FnMut(&mut T) -> communication::Result<u8>,
-        timer: &mut U,
-        timeout_type: TimeoutType,
-    ) -> communication::Result<Option<u8>> {
-        let nibble = loop {
-            if timer.poll() {
-                break Err(CommunicationError::RecvError);
-            }
+impl FramingProtocol for BogoFraming {
+    type Decoder = BogoFrameDecoder;
 
-            if let Ok(read) = read_fn(read_fn_arg) {
-                match read {
-                    b'\0' => continue,
-                    1 => break Ok(None),
-                    b'0'..=b'9' => break Ok(Some(read - b'0')),
-                    b'a'..=b'f' => break Ok(Some(read - b'a' + 10)),
-                    _ => break Err(CommunicationError::RecvError),
-                };
-            }
-        };
+    fn frame<const FRAME_CT: usize, T>(
+        write_arg: &mut T,
+        frame: Frame<FRAME_CT>,
+        write_fn: impl FnMut(&mut T, &[u8]) -> communication::Result<()>,
+        min_message_len: usize,
+    ) -> communication::Result<()> {
+        frame_bogoframe(write_arg, frame, write_fn, min_message_len)
+    }
+}
 
-        // Reset the timer if the timeout is per byte only if a valid byte was encountered.
-        if nibble.is_ok() && timeout_type == TimeoutType::ByteLevel {
-            timer.reset();
-        }
+/// A character read while decoding a BogoFrame.
+enum BogoChar {
+    Null,
+    Delimiter,
+    Nibble(u8),
+    Invalid,
+}
 
-        nibble
+impl From<u8> for BogoChar {
+    fn from(read: u8) -> Self {
+        match read {
+            b'\0' => BogoChar::Null,
+            1 => BogoChar::Delimiter,
+            b'0'..=b'9' => BogoChar::Nibble(read - b'0'),
+            b'a'..=b'f' => BogoChar::Nibble(read - b'a' + 10),
+            _ => BogoChar::Invalid,
+        }
     }
+}
 
-    if dest.len() < min_message_len {
-        return Err(CommunicationError::RecvError);
-    }
+#[derive(Copy, Clone)]
+enum BogoDecoderState {
+    /// Discarding data until a \1 character is found.
+    Hunting,
+    /// A \1 character was found and the first hex digit of the message hasn't been read yet.
+    Start,
+    /// Waiting for the first hex digit of a byte or the \1 character ending the frame.
+    FirstNibble,
+    /// Waiting for the second hex digit of a byte.
+    SecondNibble(u8),
+}
 
-    // First, read and discard data until a \1 character is found because any data that's not \1
-    // is garbage, keeping the timeout in mind.
-    loop {
-        if timer.poll() {
-            return Err(CommunicationError::RecvError);
-        }
+/// The push-based [`FrameDecoder`] for BogoFraming. See the [`module`](self) documentation for more details.
+pub struct BogoFrameDecoder {
+    state: BogoDecoderState,
+    ct: usize,
+    min_message_len: usize,
+    discarded: usize,
+}
 
-        if let Ok(n) = read_fn(read_arg) {
-            // Reset the timer if the timeout is per byte.
-            if timeout_type == TimeoutType::ByteLevel {
-                timer.reset();
-            }
+impl BogoFrameDecoder {
+    fn fail(&mut self, error: CommunicationError) -> communication::Result<Option<usize>> {
+        self.reset();
 
-            if n == 1 {
-                break;
-            }
-        }
+        Err(error)
     }
+}
 
-    // Once a \1 is found, keep reading until we find a non-\1 and non-NULL character to indicate the start of
-    // a message, keeping the timeout in mind. We can do this because a frame must contain at least
-    // 1 character.
-    let mut first_nibble = loop {
-        // If we find an non-hex, non-\1, or non-NULL character, we return an error.
-        // If it's a \1 character, we continue.
-        // If it's a hex character, we return the number it represents.
-        if let Some(n) = read_hex_nibble(read_arg, &mut read_fn, timer, timeout_type)? {
-            break Some(n);
+impl FrameDecoder for BogoFrameDecoder {
+    fn new(min_message_len: usize) -> Self {
+        Self {
+            state: BogoDecoderState::Hunting,
+            ct: 0,
+            min_message_len,
+            discarded: 0,
         }
-    };
-
-    // Start reading bytes into dest until a \1 is found, the buffer is full before a \1 is reached,
-    // a non-hex character is read, or the timeout occurs.
-    for (idx, byte) in dest.iter_mut().enumerate() {
-        let second_nibble = read_hex_nibble(read_arg, &mut read_fn, timer, timeout_type)?;
-
-        let read = if let (Some(first), Some(second)) = (first_nibble, second_nibble) {
-            (first << 4) | second
-        } else {
-            // We've received a \1 character in the second nibble, which means we have an odd
-            // number of hex digits.
-            return Err(CommunicationError::RecvError);
-        };
-
-        *byte = read;
+    }
 
-        first_nibble = read_hex_nibble(read_arg, &mut read_fn, timer, timeout_type)?;
+    fn push(&mut self, byte: u8, dest: &mut [u8]) -> communication::Result<Option<usize>> {
+        match (self.state, BogoChar::from(byte)) {
+            // Any data that's not \1 before the start of a frame is garbage.
+            (BogoDecoderState::Hunting, BogoChar::Delimiter) => {
+                self.state = BogoDecoderState::Start;
+            }
+            // NULL characters between frames are ignored too, so they aren't counted as garbage.
+            (BogoDecoderState::Hunting, BogoChar::Null) => (),
+            (BogoDecoderState::Hunting, _) => self.discarded += 1,
+
+            // NULL characters are ignored everywhere in a frame.
+            (_, BogoChar::Null) => (),
+
+            // Keep reading until we find a non-\1 character to indicate the start of a message.
+            // We can do this because a frame must contain at least 1 character.
+            (BogoDecoderState::Start, BogoChar::Delimiter) => (),
+
+            // We've received a \1 character in the right place.
+            (BogoDecoderState::FirstNibble, BogoChar::Delimiter) => {
+                let ct = self.ct;
+                self.reset();
+
+                if ct < self.min_message_len {
+                    return Err(CommunicationError::FrameTooShort);
+                } else {
+                    return Ok(Some(ct));
+                }
+            }
+            (BogoDecoderState::Start | BogoDecoderState::FirstNibble, BogoChar::Nibble(n)) => {
+                self.state = BogoDecoderState::SecondNibble(n);
+            }
+            (BogoDecoderState::SecondNibble(first), BogoChar::Nibble(second)) => {
+                // The buffer is full and we haven't read \1.
+                let Some(dest_byte) = dest.get_mut(self.ct) else {
+                    return self.fail(CommunicationError::BufferTooSmall);
+                };
 
-        // We've received a \1 character in the right place.
-        if first_nibble.is_none() {
-            let ct = idx + 1;
+                *dest_byte = (first << 4) | second;
+                self.ct += 1;
+                self.state = BogoDecoderState::FirstNibble;
+            }
 
-            if ct < min_message_len {
-                return Err(CommunicationError::RecvError);
-            } else {
-                return Ok(ct);
+            // We've received a \1 character in the second nibble, which means we have an odd
+            // number of hex digits, or we've received a non-hex character.
+            (_, BogoChar::Delimiter | BogoChar::Invalid) => {
+                return self.fail(CommunicationError::MalformedHexNibble)
             }
         }
+
+        Ok(None)
+    }
+
+    fn reset(&mut self) {
+        self.state = BogoDecoderState::Hunting;
+        self.ct = 0;
     }
 
-    // If we've reached this point, it means we've reached the end of the buffer and haven't read \1
-    Err(CommunicationError::RecvError)
+    fn discarded(&self) -> usize {
+        self.discarded
+    }
 }
 
 /// Receives a BogoFrame, blocking until the timer has elapsed from the beginning of this
@@ -139,7 +157,8 @@ pub fn recv_frame_with_timeout<T, U: Timer>(
     read_fn: impl FnMut(&mut T) -> communication::Result<u8>,
     min_message_len: usize,
 ) -> communication::Result<usize> {
-    recv_bogoframe(
+    recv_frame(
+        &mut BogoFrameDecoder::new(min_message_len),
         read_arg,
         dest,
         timer,
@@ -160,7 +179,8 @@ pub fn recv_frame_with_data_timeout<T, U: Timer>(
     read_fn: impl FnMut(&mut T) -> communication::Result<u8>,
     min_message_len: usize,
 ) -> communication::Result<usize> {
-    recv_bogoframe(
+    recv_frame(
+        &mut BogoFrameDecoder::new(min_message_len),
         read_arg,
         dest,
         timer,
@@ -0,0 +1,254 @@
+//! The functions in this module are to help implement COBS based [`FramedTxChannels`](super::FramedTxChannel) and
+//! [`RxChannels`](crate::communication::RxChannel).
+//!
+//! - COBS framing
+//!     - COBS framing uses Consistent Overhead Byte Stuffing to remove all NULL characters from the underlying
+//!       data. Each message begins and ends with one NULL character.
+//!     - The encoded data is split into blocks. Each block starts with a code byte that is one more than the
+//!       number of non-NULL bytes following it. A block with a code byte below 0xFF is followed by a NULL byte
+//!       in the underlying data unless it is the last block in the frame.
+//!     - A frame cut short by a NULL character in the middle of a block is rejected with a
+//!       [`CommunicationError::CorruptFrame`], and the NULL character is treated as the start of the next frame.
+//!     - Helper functions to implement channels using this type of framing are in the [`cobsframing`](self) module.
+
+use super::{recv_frame, Frame, FrameDecoder, FramingProtocol, TimeoutType};
+use crate::communication::{self, CommunicationError, Timer};
+
+/// The largest code byte in a COBS block, which indicates a block of 254 non-NULL bytes that
+/// isn't followed by a NULL byte in the underlying data.
+const MAX_BLOCK_CODE: u8 = 0xFF;
+
+/// The [`FramingProtocol`] for COBS framing. See the [`module`](self) documentation for more details.
+pub struct CobsFraming;
+
+impl FramingProtocol for CobsFraming {
+    type Decoder = CobsFrameDecoder;
+
+    fn frame<const FRAME_CT: usize, T>(
+        write_arg: &mut T,
+        frame: Frame<FRAME_CT>,
+        write_fn: impl FnMut(&mut T, &[u8]) -> communication::Result<()>,
+        min_message_len: usize,
+    ) -> communication::Result<()> {
+        frame_cobsframe(write_arg, frame, write_fn, min_message_len)
+    }
+}
+
+#[derive(Copy, Clone)]
+enum CobsDecoderState {
+    /// Discarding data until a NULL character is found.
+    Hunting,
+    /// A NULL character was found and the code byte of the first block hasn't been read yet.
+    Start,
+    /// Reading the non-NULL bytes in a block.
+    Block { code: u8, remaining: u8 },
+    /// Waiting for the code byte of the next block or the NULL character ending the frame.
+    BlockEnd { code: u8 },
+}
+
+impl CobsDecoderState {
+    /// Gets the state for the start of a block with the given code byte.
+    fn block(code: u8) -> Self {
+        match code - 1 {
+            0 => CobsDecoderState::BlockEnd { code },
+            remaining => CobsDecoderState::Block { code, remaining },
+        }
+    }
+}
+
+/// The push-based [`FrameDecoder`] for COBS framing. See the [`module`](self) documentation for more details.
+pub struct CobsFrameDecoder {
+    state: CobsDecoderState,
+    ct: usize,
+    min_message_len: usize,
+    discarded: usize,
+}
+
+impl CobsFrameDecoder {
+    fn fail(&mut self, error: CommunicationError) -> communication::Result<Option<usize>> {
+        self.reset();
+
+        Err(error)
+    }
+
+    /// Writes a decoded byte into ``dest``, returning ``false`` if ``dest`` is full.
+    fn write(&mut self, byte: u8, dest: &mut [u8]) -> bool {
+        match dest.get_mut(self.ct) {
+            Some(dest_byte) => {
+                *dest_byte = byte;
+                self.ct += 1;
+
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl FrameDecoder for CobsFrameDecoder {
+    fn new(min_message_len: usize) -> Self {
+        Self {
+            state: CobsDecoderState::Hunting,
+            ct: 0,
+            min_message_len,
+            discarded: 0,
+        }
+    }
+
+    fn push(&mut self, byte: u8, dest: &mut [u8]) -> communication::Result<Option<usize>> {
+        match (self.state, byte) {
+            // Any data that's not NULL before the start of a frame is garbage.
+            (CobsDecoderState::Hunting, 0) => self.state = CobsDecoderState::Start,
+            (CobsDecoderState::Hunting, _) => self.discarded += 1,
+
+            // Keep reading until we find a non-NULL character, which is the code byte of the first
+            // block in the frame.
+            (CobsDecoderState::Start, 0) => (),
+            (CobsDecoderState::Start, code) => self.state = CobsDecoderState::block(code),
+
+            // A NULL character in the middle of a block means the frame was cut short. The NULL
+            // character may start the next frame, so it isn't discarded.
+            (CobsDecoderState::Block { .. }, 0) => {
+                self.reset();
+                self.state = CobsDecoderState::Start;
+
+                return Err(CommunicationError::CorruptFrame);
+            }
+            (CobsDecoderState::Block { code, remaining }, read) => {
+                if !self.write(read, dest) {
+                    return self.fail(CommunicationError::BufferTooSmall);
+                }
+
+                self.state = match remaining - 1 {
+                    0 => CobsDecoderState::BlockEnd { code },
+                    remaining => CobsDecoderState::Block { code, remaining },
+                };
+            }
+
+            // We've received a NULL character at the end of a block, which ends the frame.
+            (CobsDecoderState::BlockEnd { .. }, 0) => {
+                let ct = self.ct;
+                self.reset();
+
+                if ct < self.min_message_len {
+                    return Err(CommunicationError::FrameTooShort);
+                } else {
+                    return Ok(Some(ct));
+                }
+            }
+            (CobsDecoderState::BlockEnd { code }, next_code) => {
+                // Every block that isn't full and isn't the last block is followed by a NULL byte.
+                if code != MAX_BLOCK_CODE && !self.write(0, dest) {
+                    return self.fail(CommunicationError::BufferTooSmall);
+                }
+
+                self.state = CobsDecoderState::block(next_code);
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn reset(&mut self) {
+        self.state = CobsDecoderState::Hunting;
+        self.ct = 0;
+    }
+
+    fn discarded(&self) -> usize {
+        self.discarded
+    }
+}
+
+/// Receives a COBS frame, blocking until the timer has elapsed from the beginning of this
+/// function call. This function mirrors
+/// [`RxChannel::recv_with_timeout`](crate::communication::RxChannel::recv_with_timeout()).
+/// See the documentation of that function for more details.
+pub fn recv_frame_with_timeout<T, U: Timer>(
+    read_arg: &mut T,
+    dest: &mut [u8],
+    timer: &mut U,
+    read_fn: impl FnMut(&mut T) -> communication::Result<u8>,
+    min_message_len: usize,
+) -> communication::Result<usize> {
+    recv_frame(
+        &mut CobsFrameDecoder::new(min_message_len),
+        read_arg,
+        dest,
+        timer,
+        read_fn,
+        min_message_len,
+        TimeoutType::FrameLevel,
+    )
+}
+
+/// Receives a COBS frame with the timeout provided by the specified timer.
+/// This timeout resets each time a byte is read. This function mirrors
+/// [`RxChannel::recv_with_data_timeout`](crate::communication::RxChannel::recv_with_data_timeout()).
+/// See the documentation of that function for more details.
+pub fn recv_frame_with_data_timeout<T, U: Timer>(
+    read_arg: &mut T,
+    dest: &mut [u8],
+    timer: &mut U,
+    read_fn: impl FnMut(&mut T) -> communication::Result<u8>,
+    min_message_len: usize,
+) -> communication::Result<usize> {
+    recv_frame(
+        &mut CobsFrameDecoder::new(min_message_len),
+        read_arg,
+        dest,
+        timer,
+        read_fn,
+        min_message_len,
+        TimeoutType::ByteLevel,
+    )
+}
+
+/// Sends a COBS frame with the given [`Frame`]. This function mirrors
+/// [`FramedTxChannel::frame`](super::FramedTxChannel::frame()). See the documentation of
+/// that function for more details.
+pub fn frame_cobsframe<const FRAME_CT: usize, T>(
+    write_arg: &mut T,
+    frame: Frame<FRAME_CT>,
+    mut write_fn: impl FnMut(&mut T, &[u8]) -> communication::Result<()>,
+    min_message_len: usize,
+) -> communication::Result<()> {
+    const BLOCK_LEN: usize = MAX_BLOCK_CODE as usize;
+
+    if frame.len() < min_message_len {
+        return Err(CommunicationError::SendError);
+    }
+
+    // The first byte of the block is reserved for the code byte.
+    let mut block = [0; BLOCK_LEN];
+    let mut block_len = 1;
+
+    write_fn(write_arg, &[0])?;
+
+    for frame_piece in frame {
+        for &byte in frame_piece {
+            if byte == 0 {
+                // A NULL byte ends the current block and is encoded by the code byte.
+                block[0] = block_len as u8;
+                write_fn(write_arg, &block[..block_len])?;
+                block_len = 1;
+            } else {
+                block[block_len] = byte;
+                block_len += 1;
+
+                // A full block isn't followed by a NULL byte.
+                if block_len == BLOCK_LEN {
+                    block[0] = MAX_BLOCK_CODE;
+                    write_fn(write_arg, &block)?;
+                    block_len = 1;
+                }
+            }
+        }
+    }
+
+    block[0] = block_len as u8;
+    write_fn(write_arg, &block[..block_len])?;
+
+    write_fn(write_arg, &[0])?;
+
+    Ok(())
+}
@@ -0,0 +1,419 @@
+//! The functions in this module are to help implement CRC framing based [`FramedTxChannels`](super::FramedTxChannel)
+//! and [`RxChannels`](crate::communication::RxChannel).
+//!
+//! - CRC framing
+//!     - CRC framing is a length-prefixed framing protocol. Each message begins with the two sync bytes in
+//!       [`SYNC_BYTES`], followed by a [`CrcFrameHeader`], the message itself, and a big-endian CRC-32
+//!       checksum of the header and the message.
+//!     - The header contains a version byte, a [`FrameKind`] byte, the big-endian length of the message, and a
+//!       CRC-8 checksum of the header. This lets the receiver reject a frame with an unknown version or kind or a
+//!       length that won't fit before reading the message, tell message kinds apart before decrypting them, and
+//!       tell a truncated frame from a complete one.
+//!     - The header checksum is checked as soon as the header is read, so a header with a corrupted length is
+//!       rejected immediately instead of swallowing the frames that follow it.
+//!     - Frames with an unknown version or kind or a checksum that doesn't match are rejected with a
+//!       [`CommunicationError::CorruptFrame`].
+//!     - Helper functions to implement channels using this type of framing are in the [`crcframing`](self) module.
+
+use super::{recv_frame, Frame, FrameDecoder, FramingProtocol, TimeoutType};
+use crate::communication::{self, CommunicationError, Timer};
+use crc::{Crc, CRC_32_ISO_HDLC, CRC_8_SMBUS};
+
+/// The bytes that mark the start of every CRC frame.
+pub const SYNC_BYTES: [u8; 2] = [0xB0, 0x60];
+
+/// The version of CRC framing implemented by this module.
+pub const CRC_FRAMING_VERSION: u8 = 1;
+
+/// The size of a [`CrcFrameHeader`] on the wire, including its checksum.
+pub const HEADER_SIZE: usize = 5;
+
+/// The size of the CRC-32 checksum at the end of every frame.
+pub const CHECKSUM_SIZE: usize = 4;
+
+/// The largest message that can be sent in one CRC frame.
+pub const MAX_MESSAGE_LEN: usize = u16::MAX as usize;
+
+const CRC: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+
+const HEADER_CRC: Crc<u8> = Crc::<u8>::new(&CRC_8_SMBUS);
+
+/// The kind of message carried by a CRC frame, which lets a receiver tell messages apart before
+/// decrypting them.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub enum FrameKind {
+    /// A message from the layer above, such as an encrypted message.
+    Data = 0,
+    /// A message used to set up a session, such as a handshake message.
+    Handshake = 1,
+    /// A message used to control the channel itself, such as an acknowledgement.
+    Control = 2,
+}
+
+impl TryFrom<u8> for FrameKind {
+    type Error = CommunicationError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(FrameKind::Data),
+            1 => Ok(FrameKind::Handshake),
+            2 => Ok(FrameKind::Control),
+            _ => Err(CommunicationError::CorruptFrame),
+        }
+    }
+}
+
+/// The header sent at the start of every CRC frame, right after the [`SYNC_BYTES`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CrcFrameHeader {
+    /// The version of CRC framing used to send this frame.
+    pub version: u8,
+
+    /// The kind of message in this frame.
+    pub kind: FrameKind,
+
+    /// The length of the message in this frame.
+    pub len: u16,
+}
+
+impl CrcFrameHeader {
+    /// Converts the header into the bytes sent on the wire, ending with a checksum of the header.
+    pub fn to_bytes(self) -> [u8; HEADER_SIZE] {
+        let [len_hi, len_lo] = self.len.to_be_bytes();
+        let mut bytes = [self.version, self.kind as u8, len_hi, len_lo, 0];
+        bytes[HEADER_SIZE - 1] = HEADER_CRC.checksum(&bytes[..HEADER_SIZE - 1]);
+
+        bytes
+    }
+
+    /// Parses a header from the bytes sent on the wire.
+    ///
+    /// # ERRORS:
+    ///
+    /// - [`CommunicationError::CorruptFrame`] - Occurs when the header checksum doesn't match or the
+    ///   header has an unknown version or kind.
+    pub fn from_bytes(bytes: [u8; HEADER_SIZE]) -> communication::Result<Self> {
+        let (fields, checksum) = bytes.split_at(HEADER_SIZE - 1);
+
+        if HEADER_CRC.checksum(fields) != checksum[0] || fields[0] != CRC_FRAMING_VERSION {
+            return Err(CommunicationError::CorruptFrame);
+        }
+
+        Ok(Self {
+            version: fields[0],
+            kind: fields[1].try_into()?,
+            len: u16::from_be_bytes([fields[2], fields[3]]),
+        })
+    }
+}
+
+/// The [`FramingProtocol`] for CRC framing. See the [`module`](self) documentation for more details.
+pub struct CrcFraming;
+
+impl FramingProtocol for CrcFraming {
+    type Decoder = CrcFrameDecoder;
+
+    fn frame<const FRAME_CT: usize, T>(
+        write_arg: &mut T,
+        frame: Frame<FRAME_CT>,
+        write_fn: impl FnMut(&mut T, &[u8]) -> communication::Result<()>,
+        min_message_len: usize,
+    ) -> communication::Result<()> {
+        frame_crcframe(write_arg, frame, write_fn, min_message_len)
+    }
+}
+
+#[derive(Copy, Clone)]
+enum CrcDecoderState {
+    /// Discarding data until the sync bytes are found. Contains whether the last byte read was the
+    /// first sync byte.
+    Hunting { first_sync_read: bool },
+    /// Reading the header. Contains the number of header bytes read.
+    Header { read: usize },
+    /// Reading the message. Contains the length of the message from the header.
+    Message { len: usize },
+    /// Reading the checksum. Contains the number of checksum bytes read.
+    Checksum { read: usize },
+}
+
+/// The push-based [`FrameDecoder`] for CRC framing. See the [`module`](self) documentation for more details.
+pub struct CrcFrameDecoder {
+    state: CrcDecoderState,
+    header_bytes: [u8; HEADER_SIZE],
+    checksum_bytes: [u8; CHECKSUM_SIZE],
+    ct: usize,
+    min_message_len: usize,
+    kind: FrameKind,
+    decoded_kind: Option<FrameKind>,
+    discarded: usize,
+}
+
+impl CrcFrameDecoder {
+    /// Gets the [`FrameKind`] of the last message decoded, or [`None`] if no message has been
+    /// decoded yet.
+    pub fn kind(&self) -> Option<FrameKind> {
+        self.decoded_kind
+    }
+
+    fn fail(&mut self, error: CommunicationError) -> communication::Result<Option<usize>> {
+        self.reset();
+
+        Err(error)
+    }
+
+    /// Gets the state after the header is read, checking the header before the message is read.
+    fn check_header(&mut self, dest: &[u8]) -> communication::Result<CrcDecoderState> {
+        let header = CrcFrameHeader::from_bytes(self.header_bytes)?;
+        self.kind = header.kind;
+
+        match header.len as usize {
+            len if len < self.min_message_len => Err(CommunicationError::FrameTooShort),
+            len if len > dest.len() => Err(CommunicationError::BufferTooSmall),
+            0 => Ok(CrcDecoderState::Checksum { read: 0 }),
+            len => Ok(CrcDecoderState::Message { len }),
+        }
+    }
+}
+
+impl FrameDecoder for CrcFrameDecoder {
+    fn new(min_message_len: usize) -> Self {
+        Self {
+            state: CrcDecoderState::Hunting {
+                first_sync_read: false,
+            },
+            header_bytes: [0; HEADER_SIZE],
+            checksum_bytes: [0; CHECKSUM_SIZE],
+            ct: 0,
+            min_message_len,
+            kind: FrameKind::Data,
+            decoded_kind: None,
+            discarded: 0,
+        }
+    }
+
+    fn push(&mut self, byte: u8, dest: &mut [u8]) -> communication::Result<Option<usize>> {
+        match self.state {
+            // Any data before the sync bytes is garbage.
+            CrcDecoderState::Hunting { first_sync_read } => {
+                self.state = if first_sync_read && byte == SYNC_BYTES[1] {
+                    // The first sync byte was counted as garbage when it was read.
+                    self.discarded -= 1;
+
+                    CrcDecoderState::Header { read: 0 }
+                } else {
+                    // A first sync byte is only garbage if it isn't followed by the second one,
+                    // which is corrected above.
+                    self.discarded += 1;
+
+                    CrcDecoderState::Hunting {
+                        first_sync_read: byte == SYNC_BYTES[0],
+                    }
+                };
+            }
+            CrcDecoderState::Header { read } => {
+                self.header_bytes[read] = byte;
+
+                self.state = if read + 1 == HEADER_SIZE {
+                    // Check the header before reading the message.
+                    match self.check_header(dest) {
+                        Ok(state) => state,
+                        Err(error) => return self.fail(error),
+                    }
+                } else {
+                    CrcDecoderState::Header { read: read + 1 }
+                };
+            }
+            CrcDecoderState::Message { len } => {
+                let Some(dest_byte) = dest.get_mut(self.ct) else {
+                    return self.fail(CommunicationError::BufferTooSmall);
+                };
+
+                *dest_byte = byte;
+                self.ct += 1;
+
+                if self.ct == len {
+                    self.state = CrcDecoderState::Checksum { read: 0 };
+                }
+            }
+            CrcDecoderState::Checksum { read } if read + 1 < CHECKSUM_SIZE => {
+                self.checksum_bytes[read] = byte;
+                self.state = CrcDecoderState::Checksum { read: read + 1 };
+            }
+            CrcDecoderState::Checksum { read } => {
+                self.checksum_bytes[read] = byte;
+
+                let ct = self.ct;
+                let Some(message) = dest.get(..ct) else {
+                    return self.fail(CommunicationError::BufferTooSmall);
+                };
+
+                let mut digest = CRC.digest();
+                digest.update(&self.header_bytes);
+                digest.update(message);
+
+                if digest.finalize() != u32::from_be_bytes(self.checksum_bytes) {
+                    return self.fail(CommunicationError::CorruptFrame);
+                }
+
+                self.reset();
+                self.decoded_kind = Some(self.kind);
+
+                return Ok(Some(ct));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn reset(&mut self) {
+        self.state = CrcDecoderState::Hunting {
+            first_sync_read: false,
+        };
+        self.ct = 0;
+    }
+
+    fn discarded(&self) -> usize {
+        self.discarded
+    }
+}
+
+/// Receives a CRC frame, blocking until the timer has elapsed from the beginning of this
+/// function call. This function mirrors
+/// [`RxChannel::recv_with_timeout`](crate::communication::RxChannel::recv_with_timeout()).
+/// See the documentation of that function for more details.
+pub fn recv_frame_with_timeout<T, U: Timer>(
+    read_arg: &mut T,
+    dest: &mut [u8],
+    timer: &mut U,
+    read_fn: impl FnMut(&mut T) -> communication::Result<u8>,
+    min_message_len: usize,
+) -> communication::Result<usize> {
+    recv_frame(
+        &mut CrcFrameDecoder::new(min_message_len),
+        read_arg,
+        dest,
+        timer,
+        read_fn,
+        min_message_len,
+        TimeoutType::FrameLevel,
+    )
+}
+
+/// Receives a CRC frame with the timeout provided by the specified timer.
+/// This timeout resets each time a byte is read. This function mirrors
+/// [`RxChannel::recv_with_data_timeout`](crate::communication::RxChannel::recv_with_data_timeout()).
+/// See the documentation of that function for more details.
+pub fn recv_frame_with_data_timeout<T, U: Timer>(
+    read_arg: &mut T,
+    dest: &mut [u8],
+    timer: &mut U,
+    read_fn: impl FnMut(&mut T) -> communication::Result<u8>,
+    min_message_len: usize,
+) -> communication::Result<usize> {
+    recv_frame(
+        &mut CrcFrameDecoder::new(min_message_len),
+        read_arg,
+        dest,
+        timer,
+        read_fn,
+        min_message_len,
+        TimeoutType::ByteLevel,
+    )
+}
+
+/// Receives a CRC frame like [`recv_frame_with_timeout`], also giving the [`FrameKind`] of the
+/// message received.
+pub fn recv_typed_frame_with_timeout<T, U: Timer>(
+    read_arg: &mut T,
+    dest: &mut [u8],
+    timer: &mut U,
+    read_fn: impl FnMut(&mut T) -> communication::Result<u8>,
+    min_message_len: usize,
+) -> communication::Result<(FrameKind, usize)> {
+    let mut decoder = CrcFrameDecoder::new(min_message_len);
+    let len = recv_frame(
+        &mut decoder,
+        read_arg,
+        dest,
+        timer,
+        read_fn,
+        min_message_len,
+        TimeoutType::FrameLevel,
+    )?;
+
+    Ok((decoder.kind().unwrap_or(FrameKind::Data), len))
+}
+
+/// Receives a CRC frame like [`recv_frame_with_data_timeout`], also giving the [`FrameKind`] of the
+/// message received.
+pub fn recv_typed_frame_with_data_timeout<T, U: Timer>(
+    read_arg: &mut T,
+    dest: &mut [u8],
+    timer: &mut U,
+    read_fn: impl FnMut(&mut T) -> communication::Result<u8>,
+    min_message_len: usize,
+) -> communication::Result<(FrameKind, usize)> {
+    let mut decoder = CrcFrameDecoder::new(min_message_len);
+    let len = recv_frame(
+        &mut decoder,
+        read_arg,
+        dest,
+        timer,
+        read_fn,
+        min_message_len,
+        TimeoutType::ByteLevel,
+    )?;
+
+    Ok((decoder.kind().unwrap_or(FrameKind::Data), len))
+}
+
+/// Sends a CRC frame with the given [`Frame`]. This function mirrors
+/// [`FramedTxChannel::frame`](super::FramedTxChannel::frame()). See the documentation of
+/// that function for more details. Frames longer than [`MAX_MESSAGE_LEN`] can't be sent. The frame is
+/// sent as a [`FrameKind::Data`] frame.
+pub fn frame_crcframe<const FRAME_CT: usize, T>(
+    write_arg: &mut T,
+    frame: Frame<FRAME_CT>,
+    write_fn: impl FnMut(&mut T, &[u8]) -> communication::Result<()>,
+    min_message_len: usize,
+) -> communication::Result<()> {
+    frame_typed_crcframe(write_arg, FrameKind::Data, frame, write_fn, min_message_len)
+}
+
+/// Sends a CRC frame like [`frame_crcframe`] with the given [`FrameKind`].
+pub fn frame_typed_crcframe<const FRAME_CT: usize, T>(
+    write_arg: &mut T,
+    kind: FrameKind,
+    frame: Frame<FRAME_CT>,
+    mut write_fn: impl FnMut(&mut T, &[u8]) -> communication::Result<()>,
+    min_message_len: usize,
+) -> communication::Result<()> {
+    if frame.len() < min_message_len {
+        return Err(CommunicationError::SendError);
+    }
+
+    let header = CrcFrameHeader {
+        version: CRC_FRAMING_VERSION,
+        kind,
+        len: frame
+            .len()
+            .try_into()
+            .map_err(|_| CommunicationError::SendError)?,
+    };
+    let header_bytes = header.to_bytes();
+    let mut digest = CRC.digest();
+
+    write_fn(write_arg, &SYNC_BYTES)?;
+    write_fn(write_arg, &header_bytes)?;
+    digest.update(&header_bytes);
+
+    for frame_piece in frame {
+        write_fn(write_arg, frame_piece)?;
+        digest.update(frame_piece);
+    }
+
+    write_fn(write_arg, &digest.finalize().to_be_bytes())?;
+
+    Ok(())
+}
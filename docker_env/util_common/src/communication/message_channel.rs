@@ -0,0 +1,300 @@
+//! This module contains a typed channel that sends and receives [`serde`] messages encoded with
+//! [`postcard`] through any channel implementing [`RxChannel`] and/or [`TxChannel`].
+//!
+//! - Message channels
+//!     - A [`MessageChannel`] serializes each message sent into a buffer of ``N`` bytes and deserializes
+//!       each message received from another buffer of ``N`` bytes, so flows don't need to manage their own
+//!       buffers. The send buffer is zeroized after each send, and the receive buffer is zeroized before each
+//!       receive and when the channel is dropped.
+//!     - The type of the messages is chosen by a [`Message`] type parameter, such as
+//!       [`Uart0Message`](crate::messages::Uart0Message) or [`Uart1Message`](crate::messages::Uart1Message).
+//!       Messages received borrow from the receive buffer, so a message must be dropped before the next
+//!       receive.
+//!     - [`recv_until_with_timeout`](MessageChannel::recv_until_with_timeout) and
+//!       [`recv_until_with_data_timeout`](MessageChannel::recv_until_with_data_timeout) receive messages until
+//!       one matches, such as a specific variant of an enum, or until the timer expires. Messages that don't
+//!       match or can't be deserialized are skipped.
+//!     - Messages that can't be serialized or deserialized give a [`CommunicationError::EncodingError`].
+//! - Versioning
+//!     - Every message is sent in an [`Envelope`](crate::messages::Envelope) with the channel's protocol
+//!       version, which starts as [`PROTOCOL_VERSION`](crate::messages::PROTOCOL_VERSION), using the layout
+//!       of that version. Messages received with an unsupported version give a
+//!       [`CommunicationError::UnsupportedVersion`]. The version of the last message received is given by
+//!       [`received_version`](MessageChannel::received_version), so a reply can be sent with the version
+//!       of the message it replies to.
+//!     - For messages implementing [`HelloMessage`], such as [`Uart0Message`](crate::messages::Uart0Message)
+//!       and [`Uart1Message`](crate::messages::Uart1Message), [`exchange_hello_with_timeout`](MessageChannel::exchange_hello_with_timeout)
+//!       starts a session by exchanging a [`Hello`] with the peer and switching the channel to the negotiated
+//!       version. See the [`messages`](crate::messages) module for more details.
+
+use core::marker::PhantomData;
+
+use zeroize::Zeroize;
+
+use crate::{
+    communication::{self, CommunicationError, RxChannel, TxChannel},
+    messages::{
+        from_envelope_bytes, to_envelope_slice, Hello, HelloMessage, ProtocolVersion,
+        SessionParameters, VersionedMessage, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION,
+    },
+    timer::Timer,
+};
+
+/// A type of message that can be sent and received through a [`MessageChannel`]. Because messages can
+/// borrow from the buffer they're deserialized from, this is implemented for every lifetime of a message
+/// type, with [`Borrowed`](Message::Borrowed) giving the message type for a given lifetime.
+pub trait Message {
+    /// The message type borrowing from a buffer with the lifetime ``'de``.
+    type Borrowed<'de>: VersionedMessage<'de>;
+}
+
+/// A channel that sends and receives messages of type ``M`` through another channel, using buffers of
+/// ``N`` bytes. ``N`` must also fit any metadata needed by the wrapped channel. See the [`module`](self)
+/// documentation for more details.
+pub struct MessageChannel<'a, C, M: Message, const N: usize> {
+    channel: &'a mut C,
+    tx_buf: [u8; N],
+    rx_buf: [u8; N],
+    version: ProtocolVersion,
+    received_version: Option<ProtocolVersion>,
+    _message: PhantomData<M>,
+}
+
+impl<'a, C, M: Message, const N: usize> MessageChannel<'a, C, M, N> {
+    /// Creates a new [`MessageChannel`] around ``channel``.
+    pub fn new(channel: &'a mut C) -> Self {
+        Self {
+            channel,
+            tx_buf: [0; N],
+            rx_buf: [0; N],
+            version: PROTOCOL_VERSION,
+            received_version: None,
+            _message: PhantomData,
+        }
+    }
+
+    /// Gets the protocol version used to send messages.
+    pub fn version(&self) -> ProtocolVersion {
+        self.version
+    }
+
+    /// Sets the protocol version used to send messages, such as a version negotiated by a hello exchange
+    /// done without this channel.
+    pub fn set_version(&mut self, version: ProtocolVersion) {
+        self.version = version;
+    }
+
+    /// Gets the protocol version of the last message received, or [`None`] if no message has been
+    /// received.
+    pub fn received_version(&self) -> Option<ProtocolVersion> {
+        self.received_version
+    }
+
+    /// Gets a mutable reference to the wrapped channel.
+    pub fn channel_mut(&mut self) -> &mut C {
+        self.channel
+    }
+}
+
+impl<'a, C: TxChannel, M: Message, const N: usize> MessageChannel<'a, C, M, N> {
+    /// Serializes ``message`` and sends it through the channel. Upon an error, a [`CommunicationError`]
+    /// is given.
+    ///
+    /// # ERRORS:
+    ///
+    /// - [`CommunicationError::EncodingError`]
+    ///   - The message couldn't be serialized, such as if it doesn't fit in ``N`` bytes.
+    /// - Any error given by the wrapped channel while sending.
+    pub fn send(&mut self, message: &M::Borrowed<'_>) -> communication::Result<()> {
+        self.send_with_version(message, self.version)
+    }
+
+    fn send_with_version(
+        &mut self,
+        message: &M::Borrowed<'_>,
+        version: ProtocolVersion,
+    ) -> communication::Result<()> {
+        let result = match to_envelope_slice(message, version, &mut self.tx_buf) {
+            Ok(message_bytes) => self.channel.send(message_bytes),
+            Err(_) => Err(CommunicationError::EncodingError),
+        };
+
+        self.tx_buf.zeroize();
+
+        result
+    }
+}
+
+impl<'a, C: RxChannel, M: Message, const N: usize> MessageChannel<'a, C, M, N> {
+    /// Receives a message like [`RxChannel::recv_with_data_timeout`] and deserializes it. Upon an error, a
+    /// [`CommunicationError`] is given.
+    ///
+    /// # ERRORS:
+    ///
+    /// - [`CommunicationError::EncodingError`]
+    ///   - The message received couldn't be deserialized.
+    /// - [`CommunicationError::UnsupportedVersion`]
+    ///   - The message received was sent with an unsupported protocol version.
+    /// - Any error given by the wrapped channel while receiving.
+    pub fn recv_with_data_timeout<U: Timer>(
+        &mut self,
+        timer: &mut U,
+    ) -> communication::Result<M::Borrowed<'_>> {
+        let len = self.recv_bytes(|ch, d| ch.recv_with_data_timeout(d, timer))?;
+
+        self.deserialize(len)
+    }
+
+    /// Receives a message like [`RxChannel::recv_with_timeout`] and deserializes it. Upon an error, a
+    /// [`CommunicationError`] is given.
+    ///
+    /// # ERRORS:
+    ///
+    /// - [`CommunicationError::EncodingError`]
+    ///   - The message received couldn't be deserialized.
+    /// - [`CommunicationError::UnsupportedVersion`]
+    ///   - The message received was sent with an unsupported protocol version.
+    /// - Any error given by the wrapped channel while receiving.
+    pub fn recv_with_timeout<U: Timer>(
+        &mut self,
+        timer: &mut U,
+    ) -> communication::Result<M::Borrowed<'_>> {
+        let len = self.recv_bytes(|ch, d| ch.recv_with_timeout(d, timer))?;
+
+        self.deserialize(len)
+    }
+
+    /// Receives messages like [`RxChannel::recv_with_data_timeout`] until one matches ``matches``, skipping
+    /// messages that don't match or can't be deserialized. Upon an error, a [`CommunicationError`] is
+    /// given.
+    ///
+    /// # ERRORS:
+    ///
+    /// - [`CommunicationError::Timeout`]
+    ///   - ``timer`` expired before a matching message was received.
+    /// - Any error given by the wrapped channel while receiving.
+    pub fn recv_until_with_data_timeout<U: Timer>(
+        &mut self,
+        timer: &mut U,
+        matches: impl FnMut(&M::Borrowed<'_>) -> bool,
+    ) -> communication::Result<M::Borrowed<'_>> {
+        self.recv_until_with(|ch, d, t| ch.recv_with_data_timeout(d, t), timer, matches)
+    }
+
+    /// Receives messages like [`RxChannel::recv_with_timeout`] until one matches ``matches``, skipping
+    /// messages that don't match or can't be deserialized. Upon an error, a [`CommunicationError`] is
+    /// given.
+    ///
+    /// # ERRORS:
+    ///
+    /// - [`CommunicationError::Timeout`]
+    ///   - ``timer`` expired before a matching message was received.
+    /// - Any error given by the wrapped channel while receiving.
+    pub fn recv_until_with_timeout<U: Timer>(
+        &mut self,
+        timer: &mut U,
+        matches: impl FnMut(&M::Borrowed<'_>) -> bool,
+    ) -> communication::Result<M::Borrowed<'_>> {
+        self.recv_until_with(|ch, d, t| ch.recv_with_timeout(d, t), timer, matches)
+    }
+
+    fn recv_until_with<U: Timer>(
+        &mut self,
+        mut recv_method: impl FnMut(&mut C, &mut [u8], &mut U) -> communication::Result<usize>,
+        timer: &mut U,
+        mut matches: impl FnMut(&M::Borrowed<'_>) -> bool,
+    ) -> communication::Result<M::Borrowed<'_>> {
+        let len = loop {
+            // Make sure timer hasn't expired on this iteration first.
+            if timer.poll() {
+                return Err(CommunicationError::Timeout);
+            }
+
+            let len = self.recv_bytes(|ch, d| recv_method(ch, d, timer))?;
+
+            if let Ok(envelope) = from_envelope_bytes::<M::Borrowed<'_>>(&self.rx_buf[..len]) {
+                if matches(&envelope.message) {
+                    break len;
+                }
+            }
+        };
+
+        // The message is deserialized again so it can borrow from the receive buffer after the loop.
+        self.deserialize(len)
+    }
+
+    /// Zeroizes the receive buffer and receives a message into it with ``recv_method``, returning its
+    /// length.
+    fn recv_bytes(
+        &mut self,
+        recv_method: impl FnOnce(&mut C, &mut [u8]) -> communication::Result<usize>,
+    ) -> communication::Result<usize> {
+        self.rx_buf.zeroize();
+
+        recv_method(self.channel, &mut self.rx_buf)
+    }
+
+    /// Deserializes the message of ``len`` bytes in the receive buffer, recording its version.
+    fn deserialize(&mut self, len: usize) -> communication::Result<M::Borrowed<'_>> {
+        let envelope = from_envelope_bytes(&self.rx_buf[..len])?;
+        self.received_version = Some(envelope.version);
+
+        Ok(envelope.message)
+    }
+}
+
+impl<'a, C: TxChannel, M: HelloMessage, const N: usize> MessageChannel<'a, C, M, N> {
+    /// Sends a [`Hello`] with the local versions and capabilities, which is sent with the
+    /// [`MIN_PROTOCOL_VERSION`] so older peers can decode it. This is used to respond to a [`Hello`]
+    /// received from a peer starting a session. Upon an error, a [`CommunicationError`] is given.
+    ///
+    /// # ERRORS:
+    ///
+    /// - [`CommunicationError::EncodingError`]
+    ///   - The message couldn't be serialized, such as if it doesn't fit in ``N`` bytes.
+    /// - Any error given by the wrapped channel while sending.
+    pub fn send_hello(&mut self) -> communication::Result<()> {
+        self.send_with_version(&M::hello(Hello::local()), MIN_PROTOCOL_VERSION)
+    }
+}
+
+impl<'a, C: TxChannel + RxChannel, M: HelloMessage, const N: usize> MessageChannel<'a, C, M, N> {
+    /// Starts a session by sending a [`Hello`] and receiving the peer's [`Hello`] like
+    /// [`RxChannel::recv_with_timeout`], skipping any other messages. The channel then sends messages with
+    /// the negotiated version, and the negotiated [`SessionParameters`] are returned. Upon an error, a
+    /// [`CommunicationError`] is given.
+    ///
+    /// # ERRORS:
+    ///
+    /// - [`CommunicationError::UnsupportedVersion`]
+    ///   - The peer has no protocol version in common with this one.
+    /// - [`CommunicationError::Timeout`]
+    ///   - ``timer`` expired before the peer's [`Hello`] was received.
+    /// - Any error given by the wrapped channel while sending or receiving.
+    pub fn exchange_hello_with_timeout<U: Timer>(
+        &mut self,
+        timer: &mut U,
+    ) -> communication::Result<SessionParameters> {
+        self.send_hello()?;
+
+        let remote_hello = self
+            .recv_until_with_timeout(timer, |msg| M::as_hello(msg).is_some())
+            .map(|msg| M::as_hello(&msg).copied())?
+            .ok_or(CommunicationError::InternalError)?;
+
+        let parameters = Hello::local()
+            .negotiate(&remote_hello)
+            .ok_or(CommunicationError::UnsupportedVersion)?;
+
+        self.version = parameters.version;
+
+        Ok(parameters)
+    }
+}
+
+impl<'a, C, M: Message, const N: usize> Drop for MessageChannel<'a, C, M, N> {
+    fn drop(&mut self) {
+        self.tx_buf.zeroize();
+        self.rx_buf.zeroize();
+    }
+}
@@ -0,0 +1,231 @@
+//! This module contains a multiplexing layer that runs multiple independent logical streams over one
+//! channel implementing [`RxChannel`] and/or [`TxChannel`], such as a UART controller.
+//!
+//! - Multiplexers
+//!     - A [`Multiplexer`] owns the wrapped channel and hands out [`MuxStream`] handles for up to ``STREAMS``
+//!       streams, numbered from 0. Any number of handles can be held at once, including several for the same
+//!       stream.
+//!     - Every message sent starts with a [`STREAM_HEADER_SIZE`] byte header containing the ID of its stream.
+//!       ``N`` is the size of the buffers used to send and receive messages, which must fit the largest message
+//!       plus the header and any metadata needed by the wrapped channel.
+//! - Streams
+//!     - A [`MuxStream`] is an [`RxChannel`] when the wrapped channel is an [`RxChannel`] and a
+//!       [`FramedTxChannel`] when the wrapped channel is a [`TxChannel`].
+//!     - Each stream has its own buffer, which holds one message received for it while another stream is
+//!       receiving. Further messages for that stream are dropped until it's received from, as are messages for
+//!       streams that don't exist. A stream that can't keep up should be used with a
+//!       [`ReliableChannel`](crate::communication::reliable::ReliableChannel) on top of it.
+//!     - Held messages are zeroized once they're received and when the [`Multiplexer`] is dropped, as are the
+//!       send and receive buffers after each use.
+//!     - Because a [`MuxStream`] is a [`FramedTxChannel`], a stream can have its own key by wrapping its
+//!       handles in the [`crypto`](crate::communication::lower_layers::crypto) channels, such as an
+//!       [`XChacha20Poly1305TxChannel`](crate::communication::lower_layers::crypto::XChacha20Poly1305TxChannel)
+//!       around one handle and an
+//!       [`XChacha20Poly1305RxChannel`](crate::communication::lower_layers::crypto::XChacha20Poly1305RxChannel)
+//!       around another.
+
+use core::cell::RefCell;
+
+use zeroize::Zeroize;
+
+use crate::{
+    communication::{
+        self,
+        lower_layers::framing::{Frame, FramedTxChannel},
+        CommunicationError, RxChannel, TxChannel,
+    },
+    timer::Timer,
+};
+
+/// The size of the header at the start of every message sent by a [`MuxStream`].
+pub const STREAM_HEADER_SIZE: usize = 1;
+
+/// A message held for a stream until it's received from.
+struct HeldMessage<const N: usize> {
+    buf: [u8; N],
+    len: Option<usize>,
+}
+
+impl<const N: usize> HeldMessage<N> {
+    /// Holds ``message`` unless a message is already held, in which case it's dropped.
+    fn hold(&mut self, message: &[u8]) {
+        if self.len.is_none() {
+            self.buf[..message.len()].copy_from_slice(message);
+            self.len = Some(message.len());
+        }
+    }
+
+    /// Copies the held message into ``dest`` and zeroizes it, returning its length. The message stays
+    /// held if ``dest`` is too small.
+    fn take(&mut self, len: usize, dest: &mut [u8]) -> communication::Result<usize> {
+        dest.get_mut(..len)
+            .ok_or(CommunicationError::BufferTooSmall)?
+            .copy_from_slice(&self.buf[..len]);
+        self.buf[..len].zeroize();
+        self.len = None;
+
+        Ok(len)
+    }
+}
+
+impl<const N: usize> Drop for HeldMessage<N> {
+    fn drop(&mut self) {
+        self.buf.zeroize();
+    }
+}
+
+/// The state of a [`Multiplexer`], shared by all of its streams.
+struct MuxState<T, const STREAMS: usize, const N: usize> {
+    channel: T,
+    rx_buf: [u8; N],
+    held: [HeldMessage<N>; STREAMS],
+}
+
+/// A multiplexer running up to ``STREAMS`` logical streams over one channel, using buffers of ``N`` bytes.
+/// See the [`module`](self) documentation for more details.
+pub struct Multiplexer<T, const STREAMS: usize, const N: usize> {
+    state: RefCell<MuxState<T, STREAMS, N>>,
+}
+
+impl<T, const STREAMS: usize, const N: usize> Multiplexer<T, STREAMS, N> {
+    /// Creates a new [`Multiplexer`] around ``channel``.
+    pub fn new(channel: T) -> Self {
+        Self {
+            state: RefCell::new(MuxState {
+                channel,
+                rx_buf: [0; N],
+                held: core::array::from_fn(|_| HeldMessage {
+                    buf: [0; N],
+                    len: None,
+                }),
+            }),
+        }
+    }
+
+    /// Gets a handle to the stream with the ID ``id``, or [`None`] if ``id`` isn't less than ``STREAMS``.
+    pub fn stream(&self, id: u8) -> Option<MuxStream<'_, T, STREAMS, N>> {
+        ((id as usize) < STREAMS).then_some(MuxStream { mux: self, id })
+    }
+
+    /// Gets a mutable reference to the wrapped channel.
+    pub fn channel_mut(&mut self) -> &mut T {
+        &mut self.state.get_mut().channel
+    }
+
+    /// Unwraps this multiplexer, returning the wrapped channel. Any held messages are discarded.
+    pub fn into_inner(self) -> T {
+        self.state.into_inner().channel
+    }
+}
+
+/// A handle to one stream of a [`Multiplexer`]. See the [`module`](self) documentation for more details.
+pub struct MuxStream<'a, T, const STREAMS: usize, const N: usize> {
+    mux: &'a Multiplexer<T, STREAMS, N>,
+    id: u8,
+}
+
+impl<'a, T, const STREAMS: usize, const N: usize> MuxStream<'a, T, STREAMS, N> {
+    /// Gets the ID of this stream.
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+}
+
+impl<'a, T: RxChannel, const STREAMS: usize, const N: usize> MuxStream<'a, T, STREAMS, N> {
+    fn recv_with<U: Timer>(
+        &mut self,
+        dest: &mut [u8],
+        mut recv_method: impl FnMut(&mut T, &mut [u8], &mut U) -> communication::Result<usize>,
+        timer: &mut U,
+    ) -> communication::Result<usize> {
+        let mut state = self.mux.state.borrow_mut();
+        let state = &mut *state;
+        let id = self.id as usize;
+
+        loop {
+            // A message received while another stream was receiving is received first.
+            if let Some(len) = state.held[id].len {
+                return state.held[id].take(len, dest);
+            }
+
+            let len = recv_method(&mut state.channel, &mut state.rx_buf, timer)?;
+
+            if let Some((&stream_id, message)) = state.rx_buf[..len].split_first() {
+                if let Some(held) = state.held.get_mut(stream_id as usize) {
+                    held.hold(message);
+                }
+            }
+
+            state.rx_buf[..len].zeroize();
+        }
+    }
+}
+
+impl<'a, T: TxChannel, const STREAMS: usize, const N: usize> FramedTxChannel
+    for MuxStream<'a, T, STREAMS, N>
+{
+    /// Sends the frame given by ``frame`` through the wrapped channel on this stream. Upon an error, a
+    /// [`CommunicationError`] is given.
+    ///
+    /// # ERRORS:
+    ///
+    /// - [`CommunicationError::SendError`]
+    ///   - The message doesn't fit in the send buffer along with the header.
+    /// - Any error given by the wrapped channel while sending.
+    fn frame<'b, const FRAME_CT: usize>(
+        &mut self,
+        frame: impl FnOnce() -> communication::Result<Frame<'b, FRAME_CT>>,
+    ) -> communication::Result<()> {
+        let frame = frame()?;
+        let len = STREAM_HEADER_SIZE + frame.len();
+
+        if len > N {
+            return Err(CommunicationError::SendError);
+        }
+
+        let mut tx_buf = [0; N];
+        tx_buf[0] = self.id;
+
+        let mut ct = STREAM_HEADER_SIZE;
+
+        for frame_piece in frame {
+            tx_buf[ct..ct + frame_piece.len()].copy_from_slice(frame_piece);
+            ct += frame_piece.len();
+        }
+
+        let res = self.mux.state.borrow_mut().channel.send(&mut tx_buf[..len]);
+        tx_buf.zeroize();
+
+        res
+    }
+}
+
+impl<'a, T: RxChannel, const STREAMS: usize, const N: usize> RxChannel
+    for MuxStream<'a, T, STREAMS, N>
+{
+    /// Receives a message for this stream like
+    /// [`RxChannel::recv_with_data_timeout`](crate::communication::RxChannel::recv_with_data_timeout),
+    /// holding messages received for other streams. ``dest`` only needs to fit the message itself. If
+    /// ``dest`` is too small, a [`CommunicationError::BufferTooSmall`] is given and the message is kept for
+    /// the next receive.
+    fn recv_with_data_timeout<U: Timer>(
+        &mut self,
+        dest: &mut [u8],
+        timer: &mut U,
+    ) -> communication::Result<usize> {
+        self.recv_with(dest, |ch, d, t| ch.recv_with_data_timeout(d, t), timer)
+    }
+
+    /// Receives a message for this stream like
+    /// [`RxChannel::recv_with_timeout`](crate::communication::RxChannel::recv_with_timeout), holding
+    /// messages received for other streams. ``dest`` only needs to fit the message itself. If ``dest`` is
+    /// too small, a [`CommunicationError::BufferTooSmall`] is given and the message is kept for the next
+    /// receive.
+    fn recv_with_timeout<U: Timer>(
+        &mut self,
+        dest: &mut [u8],
+        timer: &mut U,
+    ) -> communication::Result<usize> {
+        self.recv_with(dest, |ch, d, t| ch.recv_with_timeout(d, t), timer)
+    }
+}
@@ -0,0 +1,269 @@
+//! This module contains a reliable delivery layer that can wrap around any channel implementing both
+//! [`RxChannel`] and [`TxChannel`], such as a UART controller or a TCP socket.
+//!
+//! - Reliable channels
+//!     - A [`ReliableChannel`] implements stop-and-wait ARQ. Each message is sent with a sequence number and
+//!       is retransmitted until the other end acknowledges it or the maximum number of retransmits is reached.
+//!     - The time to wait for an acknowledgement is given by a [`Timer`], which is reset before each
+//!       retransmit.
+//!     - Received messages are acknowledged, and a retransmitted copy of a message that was already
+//!       received is acknowledged again but isn't delivered twice. Only the message following the last one
+//!       received is delivered, so an older copy that arrives late, such as after being delayed on the way,
+//!       is ignored.
+//!     - Both ends of a channel must use a [`ReliableChannel`]. A message received while waiting for an
+//!       acknowledgement is held until the next receive. Frames that are lost, corrupt, or fail authentication
+//!       are treated the same way and recovered from by retransmitting.
+//!     - Every message sent starts with a [`HEADER_SIZE`] byte header containing the type of the message and its
+//!       sequence number. Sequence numbers start at 0 when a channel is created, so both ends should be created
+//!       at the start of a session.
+
+use crate::{
+    communication::{self, CommunicationError, RxChannel, TxChannel},
+    timer::Timer,
+};
+
+/// The size of the header at the start of every message sent by a [`ReliableChannel`].
+pub const HEADER_SIZE: usize = 2;
+
+/// The message type of a message carrying data.
+const DATA_MESSAGE: u8 = 0;
+
+/// The message type of an acknowledgement.
+const ACK_MESSAGE: u8 = 1;
+
+/// A message received from the wrapped channel.
+enum Received {
+    /// An acknowledgement of the message with the given sequence number.
+    Ack(u8),
+    /// A new message with the given number of bytes of data, which starts after the header in the
+    /// receive buffer.
+    Data(usize),
+    /// A message that was ignored, such as a duplicate.
+    Ignored,
+}
+
+/// Returns ``true`` if ``error`` means a frame was lost or corrupted, which is recovered from by
+/// retransmitting.
+fn is_lost_frame(error: CommunicationError) -> bool {
+    matches!(
+        error,
+        CommunicationError::RecvError
+            | CommunicationError::CorruptFrame
+            | CommunicationError::AuthenticationFailure
+            | CommunicationError::MalformedHexNibble
+            | CommunicationError::FrameTooShort
+            | CommunicationError::ReplayedMessage
+    )
+}
+
+/// The state of a [`ReliableChannel`], kept apart from its timer so both can be borrowed at once.
+struct ArqState<'a, T: RxChannel + TxChannel, const N: usize> {
+    channel: &'a mut T,
+    next_tx_seq: u8,
+    last_rx_seq: Option<u8>,
+    rx_buf: [u8; N],
+    held_len: Option<usize>,
+    held_buf: [u8; N],
+}
+
+impl<'a, T: RxChannel + TxChannel, const N: usize> ArqState<'a, T, N> {
+    fn send_ack(&mut self, seq: u8) -> communication::Result<()> {
+        self.channel.send(&mut [ACK_MESSAGE, seq])
+    }
+
+    /// Receives a message from the wrapped channel with ``recv_method``, acknowledging data messages. New
+    /// data is only acknowledged if ``accept_data`` is ``true``, so data that can't be kept is retransmitted.
+    fn recv_message<U: Timer>(
+        &mut self,
+        timer: &mut U,
+        recv_method: impl FnOnce(&mut T, &mut [u8], &mut U) -> communication::Result<usize>,
+        accept_data: bool,
+    ) -> communication::Result<Received> {
+        let len = recv_method(self.channel, &mut self.rx_buf, timer)?;
+
+        match self.rx_buf[..len] {
+            [ACK_MESSAGE, seq] => Ok(Received::Ack(seq)),
+            // The other end didn't get our acknowledgement, so acknowledge it again.
+            [DATA_MESSAGE, seq, ..] if self.last_rx_seq == Some(seq) => {
+                self.send_ack(seq)?;
+
+                Ok(Received::Ignored)
+            }
+            [DATA_MESSAGE, seq, ..] if accept_data && self.is_next_rx_seq(seq) => {
+                self.send_ack(seq)?;
+                self.last_rx_seq = Some(seq);
+
+                Ok(Received::Data(len - HEADER_SIZE))
+            }
+            _ => Ok(Received::Ignored),
+        }
+    }
+
+    /// Returns ``true`` if ``seq`` is the sequence number of the message following the last one received.
+    /// With stop-and-wait, any other sequence number belongs to an old copy of a message that arrived late.
+    fn is_next_rx_seq(&self, seq: u8) -> bool {
+        self.last_rx_seq
+            .is_none_or(|last_seq| seq == last_seq.wrapping_add(1))
+    }
+
+    /// Holds the data of the message in the receive buffer until the next receive.
+    fn hold(&mut self, len: usize) {
+        self.held_buf[..len].copy_from_slice(&self.rx_buf[HEADER_SIZE..HEADER_SIZE + len]);
+        self.held_len = Some(len);
+    }
+
+    /// Copies the held message into ``dest``, returning its length. The message stays held if ``dest``
+    /// is too small.
+    fn take_held(&mut self, len: usize, dest: &mut [u8]) -> communication::Result<usize> {
+        dest.get_mut(..len)
+            .ok_or(CommunicationError::BufferTooSmall)?
+            .copy_from_slice(&self.held_buf[..len]);
+        self.held_len = None;
+
+        Ok(len)
+    }
+}
+
+/// A channel providing reliable delivery over a channel that can lose messages. ``N`` is the size of the
+/// buffers used to send and receive messages, which must fit the largest message plus [`HEADER_SIZE`] and
+/// any metadata needed by the wrapped channel, such as
+/// [`METADATA_SIZE`](crate::communication::lower_layers::crypto::METADATA_SIZE). See the [`module`](self)
+/// documentation for more details.
+pub struct ReliableChannel<'a, T: RxChannel + TxChannel, U: Timer, const N: usize> {
+    state: ArqState<'a, T, N>,
+    retransmit_timer: U,
+    max_retransmits: u8,
+}
+
+impl<'a, T: RxChannel + TxChannel, U: Timer, const N: usize> ReliableChannel<'a, T, U, N> {
+    /// Creates a new [`ReliableChannel`] around ``channel``. After sending a message, the channel waits
+    /// for an acknowledgement until ``retransmit_timer`` expires and retransmits the message up to
+    /// ``max_retransmits`` times.
+    pub fn new(channel: &'a mut T, retransmit_timer: U, max_retransmits: u8) -> Self {
+        Self {
+            state: ArqState {
+                channel,
+                next_tx_seq: 0,
+                last_rx_seq: None,
+                rx_buf: [0; N],
+                held_len: None,
+                held_buf: [0; N],
+            },
+            retransmit_timer,
+            max_retransmits,
+        }
+    }
+
+    fn recv_with<V: Timer>(
+        &mut self,
+        dest: &mut [u8],
+        mut recv_method: impl FnMut(&mut T, &mut [u8], &mut V) -> communication::Result<usize>,
+        timer: &mut V,
+    ) -> communication::Result<usize> {
+        // A message received while waiting for an acknowledgement is received first.
+        if let Some(len) = self.state.held_len {
+            return self.state.take_held(len, dest);
+        }
+
+        loop {
+            match self.state.recv_message(timer, &mut recv_method, true) {
+                Ok(Received::Data(len)) => {
+                    self.state.hold(len);
+
+                    return self.state.take_held(len, dest);
+                }
+                Ok(_) => (),
+                Err(e) if is_lost_frame(e) => (),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl<'a, T: RxChannel + TxChannel, U: Timer, const N: usize> TxChannel
+    for ReliableChannel<'a, T, U, N>
+{
+    /// Sends the data from ``src`` through the channel, blocking until the other end acknowledges it.
+    /// Upon an error, a [`CommunicationError`] is given.
+    ///
+    /// # ERRORS:
+    ///
+    /// - [`CommunicationError::SendError`]
+    ///   - The message doesn't fit in the send buffer along with the header.
+    ///   - No acknowledgement was received after the maximum number of retransmits.
+    /// - Any other error given by the wrapped channel while sending or while waiting for an
+    ///   acknowledgement that doesn't come from a lost or corrupted frame.
+    fn send(&mut self, src: &mut [u8]) -> communication::Result<()> {
+        let len = HEADER_SIZE + src.len();
+
+        if len > N {
+            return Err(CommunicationError::SendError);
+        }
+
+        let seq = self.state.next_tx_seq;
+
+        for _ in 0..=self.max_retransmits {
+            // The wrapped channel can modify the message in place, so it's copied before each send.
+            let mut tx_buf = [0; N];
+            tx_buf[0] = DATA_MESSAGE;
+            tx_buf[1] = seq;
+            tx_buf[HEADER_SIZE..len].copy_from_slice(src);
+
+            self.state.channel.send(&mut tx_buf[..len])?;
+            self.retransmit_timer.reset();
+
+            loop {
+                let accept_data = self.state.held_len.is_none();
+
+                match self.state.recv_message(
+                    &mut self.retransmit_timer,
+                    |ch, d, t| ch.recv_with_timeout(d, t),
+                    accept_data,
+                ) {
+                    Ok(Received::Ack(ack_seq)) if ack_seq == seq => {
+                        self.state.next_tx_seq = seq.wrapping_add(1);
+
+                        return Ok(());
+                    }
+                    Ok(Received::Data(data_len)) => self.state.hold(data_len),
+                    Ok(_) => (),
+                    Err(CommunicationError::Timeout) => break,
+                    Err(e) if is_lost_frame(e) => (),
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+
+        Err(CommunicationError::SendError)
+    }
+}
+
+impl<'a, T: RxChannel + TxChannel, U: Timer, const N: usize> RxChannel
+    for ReliableChannel<'a, T, U, N>
+{
+    /// Receives data from the channel like
+    /// [`RxChannel::recv_with_data_timeout`](crate::communication::RxChannel::recv_with_data_timeout),
+    /// acknowledging the message received. Frames that are lost or corrupted are skipped. ``dest`` only
+    /// needs to fit the message itself. If ``dest`` is too small, a [`CommunicationError::BufferTooSmall`]
+    /// is given and the message is kept for the next receive.
+    fn recv_with_data_timeout<V: Timer>(
+        &mut self,
+        dest: &mut [u8],
+        timer: &mut V,
+    ) -> communication::Result<usize> {
+        self.recv_with(dest, |ch, d, t| ch.recv_with_data_timeout(d, t), timer)
+    }
+
+    /// Receives data from the channel like
+    /// [`RxChannel::recv_with_timeout`](crate::communication::RxChannel::recv_with_timeout),
+    /// acknowledging the message received. Frames that are lost or corrupted are skipped. ``dest`` only
+    /// needs to fit the message itself. If ``dest`` is too small, a [`CommunicationError::BufferTooSmall`]
+    /// is given and the message is kept for the next receive.
+    fn recv_with_timeout<V: Timer>(
+        &mut self,
+        dest: &mut [u8],
+        timer: &mut V,
+    ) -> communication::Result<usize> {
+        self.recv_with(dest, |ch, d, t| ch.recv_with_timeout(d, t), timer)
+    }
+}
@@ -0,0 +1,339 @@
+//! This module contains traffic and error counters for channels, which can be used to diagnose why a flow
+//! failed, such as whether frames were lost, malformed, failed authentication, or timed out.
+//!
+//! - Channel statistics
+//!     - A [`ChannelStats`] counts the messages and bytes sent and received through a channel and the errors
+//!       given while doing so. Counters saturate instead of wrapping around.
+//!     - Errors are counted by the [`CommunicationError`] given. Because the layers of the BogoStack pass
+//!       errors through unchanged, counting the results of a channel at the top of the stack counts the errors
+//!       of both the framing layer and the [`crypto`](crate::communication::lower_layers::crypto) layer.
+//!     - A resync is counted when a message is received after one or more framing errors or after bytes were
+//!       discarded outside of any frame, which means the receiver lost track of the frames being sent and
+//!       found the start of a frame again. Discarded bytes are only seen by a counter on the framing layer,
+//!       as framing protocols such as COBS framing and BogoFraming skip them without giving an error.
+//! - Statistics channels
+//!     - A [`StatsChannel`] wraps around an [`RxChannel`] and/or a [`FramedTxChannel`] and counts everything
+//!       passing through it, so counters can be kept on any layer. Wrapping a framing layer channel and
+//!       wrapping the crypto channel around it counts traffic at the framing layer, including messages that
+//!       later fail authentication, the bytes of the encrypted messages, and the bytes discarded outside of
+//!       frames.
+//!     - A [`LayerStats`] holds the counters of both layers of a stack, such as a UART controller, which keeps a
+//!       counter below its crypto channels and another one above them.
+//!     - Counting is opt-in. A [`StatsChannel`] can be created with counting disabled and enabled later, and
+//!       channels such as the UART controllers keep no counters until they are enabled.
+
+use crate::{
+    communication::{
+        self,
+        lower_layers::framing::{Frame, FramedTxChannel},
+        CommunicationError, RxChannel,
+    },
+    timer::Timer,
+};
+
+/// Traffic and error counters for a channel. See the [`module`](self) documentation for more details.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct ChannelStats {
+    /// The number of messages sent.
+    pub frames_sent: u32,
+
+    /// The number of messages received.
+    pub frames_received: u32,
+
+    /// The number of bytes in the messages sent.
+    pub bytes_sent: u32,
+
+    /// The number of bytes in the messages received.
+    pub bytes_received: u32,
+
+    /// The number of malformed frames received, which are counted for a
+    /// [`CommunicationError::RecvError`], [`CommunicationError::CorruptFrame`],
+    /// [`CommunicationError::MalformedHexNibble`], or [`CommunicationError::FrameTooShort`].
+    pub framing_errors: u32,
+
+    /// The number of messages received that failed authentication.
+    pub auth_failures: u32,
+
+    /// The number of receives that timed out.
+    pub timeouts: u32,
+
+    /// The number of times a message was received after one or more framing errors or after bytes were
+    /// discarded outside of any frame.
+    pub resyncs: u32,
+
+    /// The number of bytes discarded while looking for the start of a frame, which are only counted on
+    /// the framing layer. See [`RxChannel::take_discarded_bytes`].
+    pub bytes_discarded: u32,
+
+    /// The number of receives that gave any other error, such as a
+    /// [`CommunicationError::ReplayedMessage`] or a [`CommunicationError::BufferTooSmall`].
+    pub other_recv_errors: u32,
+
+    /// The number of sends that gave an error.
+    pub send_errors: u32,
+}
+
+impl ChannelStats {
+    /// Adds the counters of ``other`` to these counters, such as to combine the counters of the
+    /// channels sending and receiving on the same layer.
+    pub fn combined(self, other: ChannelStats) -> ChannelStats {
+        ChannelStats {
+            frames_sent: self.frames_sent.saturating_add(other.frames_sent),
+            frames_received: self.frames_received.saturating_add(other.frames_received),
+            bytes_sent: self.bytes_sent.saturating_add(other.bytes_sent),
+            bytes_received: self.bytes_received.saturating_add(other.bytes_received),
+            framing_errors: self.framing_errors.saturating_add(other.framing_errors),
+            auth_failures: self.auth_failures.saturating_add(other.auth_failures),
+            timeouts: self.timeouts.saturating_add(other.timeouts),
+            resyncs: self.resyncs.saturating_add(other.resyncs),
+            bytes_discarded: self.bytes_discarded.saturating_add(other.bytes_discarded),
+            other_recv_errors: self
+                .other_recv_errors
+                .saturating_add(other.other_recv_errors),
+            send_errors: self.send_errors.saturating_add(other.send_errors),
+        }
+    }
+
+    /// Counts the result of receiving a message, where ``result`` is the number of bytes received or the
+    /// error given. ``out_of_sync`` tracks whether the last message received was malformed, which must be
+    /// kept for the next call to count resyncs.
+    fn record_recv(&mut self, result: &communication::Result<usize>, out_of_sync: &mut bool) {
+        let counter = match result {
+            Ok(len) => {
+                self.bytes_received = self.bytes_received.saturating_add(*len as u32);
+
+                if core::mem::take(out_of_sync) {
+                    self.resyncs = self.resyncs.saturating_add(1);
+                }
+
+                &mut self.frames_received
+            }
+            Err(
+                CommunicationError::RecvError
+                | CommunicationError::CorruptFrame
+                | CommunicationError::MalformedHexNibble
+                | CommunicationError::FrameTooShort,
+            ) => {
+                *out_of_sync = true;
+
+                &mut self.framing_errors
+            }
+            Err(CommunicationError::AuthenticationFailure) => &mut self.auth_failures,
+            Err(CommunicationError::Timeout) => &mut self.timeouts,
+            Err(_) => &mut self.other_recv_errors,
+        };
+
+        *counter = counter.saturating_add(1);
+    }
+
+    /// Counts ``len`` bytes discarded outside of any frame, which puts the receiver out of sync until the
+    /// next message is received.
+    fn record_discarded(&mut self, len: usize, out_of_sync: &mut bool) {
+        if len > 0 {
+            self.bytes_discarded = self.bytes_discarded.saturating_add(len as u32);
+            *out_of_sync = true;
+        }
+    }
+
+    /// Counts the result of sending a message of ``len`` bytes.
+    fn record_send(&mut self, len: usize, result: &communication::Result<()>) {
+        match result {
+            Ok(()) => {
+                self.frames_sent = self.frames_sent.saturating_add(1);
+                self.bytes_sent = self.bytes_sent.saturating_add(len as u32);
+            }
+            Err(_) => self.send_errors = self.send_errors.saturating_add(1),
+        }
+    }
+}
+
+/// The counters of each layer of a stack of channels. See the [`module`](self) documentation for more
+/// details.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct LayerStats {
+    /// The counters of the framing layer, which count encrypted messages, framing errors, and resyncs.
+    pub framing: ChannelStats,
+
+    /// The counters of the crypto layer, which count decrypted messages and every error given by the stack,
+    /// including the framing errors passed up from the framing layer.
+    pub crypto: ChannelStats,
+}
+
+/// A [`ChannelStats`] that can be enabled and disabled, kept by channels that count their traffic.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct OptionalStats {
+    stats: Option<ChannelStats>,
+    out_of_sync: bool,
+}
+
+impl OptionalStats {
+    /// Creates a new [`OptionalStats`] with counting enabled if ``enabled`` is ``true``.
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            stats: enabled.then(ChannelStats::default),
+            out_of_sync: false,
+        }
+    }
+
+    /// Gets the counters, or [`None`] if counting is disabled.
+    pub fn get(&self) -> Option<ChannelStats> {
+        self.stats
+    }
+
+    /// Enables counting, starting from zero if counting was disabled.
+    pub fn enable(&mut self) {
+        self.stats.get_or_insert_with(ChannelStats::default);
+    }
+
+    /// Disables counting, discarding the counters.
+    pub fn disable(&mut self) {
+        self.stats = None;
+    }
+
+    /// Resets the counters to zero if counting is enabled.
+    pub fn reset(&mut self) {
+        if let Some(stats) = &mut self.stats {
+            *stats = ChannelStats::default();
+        }
+    }
+
+    /// Counts the result of receiving a message if counting is enabled, returning the result.
+    pub fn record_recv(
+        &mut self,
+        result: communication::Result<usize>,
+    ) -> communication::Result<usize> {
+        if let Some(stats) = &mut self.stats {
+            stats.record_recv(&result, &mut self.out_of_sync);
+        }
+
+        result
+    }
+
+    /// Counts ``len`` bytes discarded outside of any frame if counting is enabled. This must be called
+    /// before the result of the receive the bytes were discarded in is counted.
+    pub fn record_discarded(&mut self, len: usize) {
+        if let Some(stats) = &mut self.stats {
+            stats.record_discarded(len, &mut self.out_of_sync);
+        }
+    }
+
+    /// Counts the result of sending a message of ``len`` bytes if counting is enabled, returning the
+    /// result.
+    pub fn record_send(
+        &mut self,
+        len: usize,
+        result: communication::Result<()>,
+    ) -> communication::Result<()> {
+        if let Some(stats) = &mut self.stats {
+            stats.record_send(len, &result);
+        }
+
+        result
+    }
+}
+
+/// A channel that counts the traffic and errors passing through another channel. See the
+/// [`module`](self) documentation for more details.
+pub struct StatsChannel<T> {
+    channel: T,
+    stats: OptionalStats,
+}
+
+impl<T> StatsChannel<T> {
+    /// Creates a new [`StatsChannel`] around ``channel`` with counting enabled.
+    pub fn new(channel: T) -> Self {
+        Self {
+            channel,
+            stats: OptionalStats::new(true),
+        }
+    }
+
+    /// Creates a new [`StatsChannel`] around ``channel`` with counting disabled until
+    /// [`enable_stats`](Self::enable_stats) is called.
+    pub fn new_disabled(channel: T) -> Self {
+        Self {
+            channel,
+            stats: OptionalStats::new(false),
+        }
+    }
+
+    /// Gets the counters of this channel, or [`None`] if counting is disabled.
+    pub fn stats(&self) -> Option<ChannelStats> {
+        self.stats.get()
+    }
+
+    /// Enables counting, starting from zero if counting was disabled.
+    pub fn enable_stats(&mut self) {
+        self.stats.enable();
+    }
+
+    /// Disables counting, discarding the counters.
+    pub fn disable_stats(&mut self) {
+        self.stats.disable();
+    }
+
+    /// Resets the counters of this channel to zero.
+    pub fn reset_stats(&mut self) {
+        self.stats.reset();
+    }
+
+    /// Gets a reference to the wrapped channel.
+    pub fn channel(&self) -> &T {
+        &self.channel
+    }
+
+    /// Gets a mutable reference to the wrapped channel.
+    pub fn channel_mut(&mut self) -> &mut T {
+        &mut self.channel
+    }
+
+    /// Unwraps this channel, returning the wrapped channel.
+    pub fn into_inner(self) -> T {
+        self.channel
+    }
+}
+
+impl<T: FramedTxChannel> FramedTxChannel for StatsChannel<T> {
+    fn frame<'a, const FRAME_CT: usize>(
+        &mut self,
+        frame: impl FnOnce() -> communication::Result<Frame<'a, FRAME_CT>>,
+    ) -> communication::Result<()> {
+        let mut len = 0;
+        let result = self.channel.frame(|| {
+            let frame = frame()?;
+            len = frame.len();
+
+            Ok(frame)
+        });
+
+        self.stats.record_send(len, result)
+    }
+}
+
+impl<T: RxChannel> RxChannel for StatsChannel<T> {
+    const METADATA_SIZE: usize = T::METADATA_SIZE;
+
+    fn recv_with_data_timeout<U: Timer>(
+        &mut self,
+        dest: &mut [u8],
+        timer: &mut U,
+    ) -> communication::Result<usize> {
+        let result = self.channel.recv_with_data_timeout(dest, timer);
+        self.stats
+            .record_discarded(self.channel.take_discarded_bytes());
+
+        self.stats.record_recv(result)
+    }
+
+    fn recv_with_timeout<U: Timer>(
+        &mut self,
+        dest: &mut [u8],
+        timer: &mut U,
+    ) -> communication::Result<usize> {
+        let result = self.channel.recv_with_timeout(dest, timer);
+        self.stats
+            .record_discarded(self.channel.take_discarded_bytes());
+
+        self.stats.record_recv(result)
+    }
+}
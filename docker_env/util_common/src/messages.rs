@@ -1,14 +1,99 @@
 //! This module is responsible for providing [`serde`] serializable/deserializable structs
 //! for messages sent between the car, key fob, and host tools.
-
+//!
+//! - Versioning
+//!     - Every [`Uart0Message`] and [`Uart1Message`] is sent in an [`Envelope`] containing the
+//!       [`ProtocolVersion`] it was encoded with, using [`to_envelope_slice`] or a
+//!       [`MessageChannel`](crate::communication::message_channel::MessageChannel). A message with a version
+//!       outside of [`MIN_PROTOCOL_VERSION`] to [`PROTOCOL_VERSION`] is rejected by [`from_envelope_bytes`]
+//!       instead of being decoded as the wrong message.
+//!     - The version must be increased whenever a message changes in a way that older peers can't decode,
+//!       such as when a variant is added before another or a field is changed.
+//!     - Messages are encoded and decoded with the layout of their version by [`VersionedMessage`], so
+//!       older peers can still be talked to. Version 1 had no validity windows, so a version 1
+//!       [`PackagedFeatureUnsigned`] is decoded as a feature that is valid forever, and features with a
+//!       validity window can't be sent to version 1 peers. Version 1 features were signed over their
+//!       version 1 encoding, as given by [`PackagedFeatureUnsigned::to_v1_slice`]. Versions 1 and 2 sent
+//!       the signature of each feature in an [`UnlockChallengeResponse`], which is dropped when one is
+//!       decoded, and features can't be sent to peers older than [`UNSIGNED_FEATURES_VERSION`].
+//! - Hello exchange
+//!     - At the start of a session, the initiating peer sends a [`Hello`] containing the versions and
+//!       [`Capabilities`] it supports, and the other peer responds with its own. Both peers then use the
+//!       [`SessionParameters`] given by [`Hello::negotiate`], which are the newest version and the
+//!       capabilities both peers support, so newer peers downgrade to talk to older ones. Peers without a
+//!       version in common reject each other, as do car and key fob peers on UART1 without the
+//!       [`Capabilities::REQUIRED_UART1`].
+//!     - Messages sent after the exchange use the negotiated version, and responses use the version of
+//!       the message they respond to.
+//!     - A [`Hello`] is always sent with the sender's [`MIN_PROTOCOL_VERSION`] so that older peers can
+//!       decode it. The position of the ``Hello`` variants of [`Uart0Message`] and [`Uart1Message`] and
+//!       the layout of [`Hello`] must never change, so new variants are added after them.
+//! - Feature validity
+//!     - A [`PackagedFeatureUnsigned`] can have a validity window given by its ``not_before`` and
+//!       ``not_after`` fields, which are covered by its signature. Both are in seconds of [`DeviceTime`]
+//!       counted from when the car first accepts the feature, which is the first unlock with a key fob
+//!       it is enabled on, so features such as trials can be limited to part of the time the car runs
+//!       after they are enabled without knowing the car's device time. The window is checked with
+//!       [`PackagedFeatureUnsigned::is_valid_at`]. A feature without either field is valid forever.
+//!     - The car stores the digest of the last feature it accepted for each feature number along with
+//!       when it first accepted it, and only counts from a new time when the digest changes. Features
+//!       packaged for the same feature number that are used in turn each start their window over, so
+//!       a feature should be revoked once a newer one for the same feature number is packaged.
+//! - Feature storage
+//!     - A paired key fob verifies the signature of a [`PackagedFeatureSigned`] when it is enabled, then
+//!       stores only the [`PackagedFeatureUnsigned`], so that every feature fits in its EEPROM.
+//!     - Since [`UNSIGNED_FEATURES_VERSION`], the key fob sends its features in an
+//!       [`UnlockChallengeResponse`] without their signatures. The car trusts them because the response
+//!       is encrypted and authenticated with the keys only its paired key fobs have, and still checks
+//!       the car ID, feature number, revocation, and validity window of each feature.
+//!     - Cars older than [`UNSIGNED_FEATURES_VERSION`] verify the signature of each feature, so key fobs
+//!       send them no features.
+//! - Feature revocation
+//!     - A [`RevocationListSigned`] lists the [`FeatureDigests`](FeatureDigest) of packaged features for a
+//!       car that are no longer valid, such as after a refund or a leak, and is signed with the same key
+//!       as packaged features. A revoked feature is rejected even though its signature is valid.
+//!     - Each list replaces the last one installed, so it must contain every feature still revoked. Its
+//!       ``sequence`` must be greater than that of the list installed so that older lists can't be
+//!       installed again to undo a revocation.
+//!     - A list holds at most [`MAX_REVOKED_FEATURES`] digests, which must fit in the revocation list
+//!       field of the EEPROM. Since each list replaces the last one, this also limits how many packages
+//!       can be revoked for a car at once over its lifetime, not just per list. A package only needs to
+//!       stay on the list while it could still be accepted, so a package whose validity window has
+//!       ended can be dropped from the next list to make room.
+//! - Feature removal
+//!     - A [`DisableFeatureSigned`] removes a feature installed on a paired key fob, and is signed with
+//!       the same key as packaged features. It names the [`FeatureDigest`] of the packaged feature to
+//!       remove, so it only clears the feature if that exact package is still installed.
+//!     - The key fob keeps the digest of the package it removed in place of the package, and refuses to
+//!       install that package again, so a removed feature can't simply be enabled again. Only the last
+//!       package removed for each feature is kept, so packages that must never be installed again should
+//!       also be revoked.
+//!     - Its ``sequence`` must be greater than that of the last request the key fob accepted, so a
+//!       request can't be replayed to remove the same package again after it is enabled again.
+//! - Signed messages
+//!     - Packaged features, revocation lists, and disable feature requests are all signed with the same
+//!       key, so each is signed as a [`TaggedMessage`] starting with the [`SignatureTag`] of its type.
+//!       A signature for one type of message is then never valid for another type whose encoding
+//!       happens to match. Version 1 packaged features were signed without a tag.
+
+use core::mem;
 use k256::{
     ecdsa::{signature::Verifier, Signature, VerifyingKey},
     elliptic_curve::sec1::FromEncodedPoint,
     EncodedPoint, PublicKey,
 };
+use postcard::Error as PostcardError;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
+use ucsc_ectf_eeprom_layout::MESSAGE_SIZE;
+
+use crate::communication::{message_channel::Message, CommunicationError};
+
+mod v1;
+mod v2;
+
 pub use chacha20poly1305::Key;
 pub use heapless;
 
@@ -18,11 +103,42 @@ pub type CarId = u32;
 /// The feature number type
 pub type FeatureNumber = u32;
 
+/// The trusted time of a device, which is the number of seconds it has been running for over its
+/// lifetime. It never goes backwards, including across resets.
+pub type DeviceTime = u64;
+
 /// The type for a nonce/challenge.
 pub type Nonce = [u8; 16];
 
-/// The number of features.
-pub const NUM_FEATURES: usize = 3;
+pub use ucsc_ectf_eeprom_layout::NUM_FEATURES;
+
+/// The digest identifying a packaged feature in a revocation list.
+pub type FeatureDigest = [u8; 16];
+
+/// The maximum number of features in a revocation list. As each list replaces the last one, this is
+/// also the most packages that can be revoked for a car at once. See the [`module`](self)
+/// documentation for more details.
+pub const MAX_REVOKED_FEATURES: usize = 8;
+
+/// The protocol version type.
+pub type ProtocolVersion = u16;
+
+/// The newest protocol version supported, which is used to send messages unless an older version was
+/// negotiated.
+pub const PROTOCOL_VERSION: ProtocolVersion = 3;
+
+/// The oldest protocol version supported.
+pub const MIN_PROTOCOL_VERSION: ProtocolVersion = 1;
+
+/// The protocol version that added validity windows to packaged features.
+pub const VALIDITY_WINDOW_VERSION: ProtocolVersion = 2;
+
+/// The protocol version that stopped sending the signatures of packaged features in an
+/// [`UnlockChallengeResponse`].
+pub const UNSIGNED_FEATURES_VERSION: ProtocolVersion = 3;
+
+/// The maximum size of the Postcard-encoded [`ProtocolVersion`] an [`Envelope`] starts with.
+pub const ENVELOPE_VERSION_MAX_SIZE: usize = 3;
 
 /// This enum represents all possible messages that can be sent across UART0 between
 /// host tools and a car or a paired key fob.
@@ -40,8 +156,8 @@ pub enum Uart0Message<'a> {
     EnableFeatureResponse(HostToolAck),
 
     /// A message sent from a car to the unlock host tool containing
-    /// the unlock message, and up to 3 feature messages, which are sent
-    /// upon a successful unlock.
+    /// the unlock message, and up to [`NUM_FEATURES`](ucsc_ectf_eeprom_layout::NUM_FEATURES)
+    /// feature messages, which are sent upon a successful unlock.
     ///
     /// See [`UnlockMessage`] for more details.
     #[serde(borrow)]
@@ -58,6 +174,34 @@ pub enum Uart0Message<'a> {
     ///
     /// See [`HostToolAck`] for more details.
     PairingPinResponse(HostToolAck),
+
+    /// A message sent at the start of a session to negotiate the protocol version and capabilities
+    /// used. This variant must keep its position.
+    ///
+    /// See [`Hello`] for more details.
+    Hello(Hello),
+
+    /// A message sent from the install revocation list host tool to a car or a paired key fob
+    /// containing a signed list of revoked features to replace the one it has installed.
+    ///
+    /// See [`RevocationListMessage`] for more details.
+    #[serde(borrow)]
+    RevocationListRequest(RevocationListMessage<'a>),
+
+    /// The response sent from a car or a paired key fob to the install revocation list host tool in
+    /// response to a [`Uart0Message::RevocationListRequest`].
+    RevocationListResponse(HostToolAck),
+
+    /// A message sent from the disable feature host tool to a paired key fob containing a signed
+    /// request to remove an installed feature.
+    ///
+    /// See [`DisableFeatureMessage`] for more details.
+    #[serde(borrow)]
+    DisableFeatureRequest(DisableFeatureMessage<'a>),
+
+    /// The response sent from a paired key fob to the disable feature host tool in response to a
+    /// [`Uart0Message::DisableFeatureRequest`].
+    DisableFeatureResponse(HostToolAck),
 }
 
 /// This enum represents all possible messages that can be sent across UART1 between
@@ -81,7 +225,7 @@ pub enum Uart1Message<'a> {
     /// along with additional data to unlock the car.
     ///
     /// See [`UnlockChallengeResponse`] for more details.
-    UnlockChallengeResponse(UnlockChallengeResponse<'a>),
+    UnlockChallengeResponse(UnlockChallengeResponse),
 
     /// A message sent either from a paired key fob to an unpaired key fob and the other
     /// way around to establish a shared secret to symmetrically encrypt the pairing
@@ -108,6 +252,306 @@ pub enum Uart1Message<'a> {
     ///
     /// See [`PairingChallengeResponse`] for more details.
     PairingChallengeResponse(PairingChallengeResponse),
+
+    /// A message sent at the start of a session to negotiate the protocol version and capabilities
+    /// used. This variant must keep its position.
+    ///
+    /// See [`Hello`] for more details.
+    Hello(Hello),
+}
+
+impl<'de> VersionedMessage<'de> for Uart0Message<'de> {
+    fn to_versioned_slice<'b>(
+        &self,
+        version: ProtocolVersion,
+        buf: &'b mut [u8],
+    ) -> Result<&'b mut [u8], PostcardError> {
+        if version < VALIDITY_WINDOW_VERSION {
+            v1::uart0_to_slice(self, buf)
+        } else {
+            postcard::to_slice(self, buf)
+        }
+    }
+
+    fn from_versioned_bytes(
+        version: ProtocolVersion,
+        bytes: &'de [u8],
+    ) -> Result<Self, PostcardError> {
+        if version < VALIDITY_WINDOW_VERSION {
+            postcard::from_bytes::<v1::Uart0Message>(bytes).map(Into::into)
+        } else {
+            postcard::from_bytes(bytes)
+        }
+    }
+}
+
+impl<'de> VersionedMessage<'de> for Uart1Message<'de> {
+    fn to_versioned_slice<'b>(
+        &self,
+        version: ProtocolVersion,
+        buf: &'b mut [u8],
+    ) -> Result<&'b mut [u8], PostcardError> {
+        if version < UNSIGNED_FEATURES_VERSION {
+            v2::uart1_to_slice(self, buf)
+        } else {
+            postcard::to_slice(self, buf)
+        }
+    }
+
+    fn from_versioned_bytes(
+        version: ProtocolVersion,
+        bytes: &'de [u8],
+    ) -> Result<Self, PostcardError> {
+        if version < VALIDITY_WINDOW_VERSION {
+            postcard::from_bytes::<v1::Uart1Message>(bytes).map(Into::into)
+        } else if version < UNSIGNED_FEATURES_VERSION {
+            postcard::from_bytes::<v2::Uart1Message>(bytes).map(Into::into)
+        } else {
+            postcard::from_bytes(bytes)
+        }
+    }
+}
+
+impl Message for Uart0Message<'_> {
+    type Borrowed<'de> = Uart0Message<'de>;
+}
+
+impl Message for Uart1Message<'_> {
+    type Borrowed<'de> = Uart1Message<'de>;
+}
+
+impl HelloMessage for Uart0Message<'_> {
+    fn hello<'de>(hello: Hello) -> Uart0Message<'de> {
+        Uart0Message::Hello(hello)
+    }
+
+    fn as_hello<'b>(message: &'b Uart0Message<'_>) -> Option<&'b Hello> {
+        match message {
+            Uart0Message::Hello(hello) => Some(hello),
+            _ => None,
+        }
+    }
+}
+
+impl HelloMessage for Uart1Message<'_> {
+    fn hello<'de>(hello: Hello) -> Uart1Message<'de> {
+        Uart1Message::Hello(hello)
+    }
+
+    fn as_hello<'b>(message: &'b Uart1Message<'_>) -> Option<&'b Hello> {
+        match message {
+            Uart1Message::Hello(hello) => Some(hello),
+            _ => None,
+        }
+    }
+}
+
+/// A message whose layout depends on the protocol version it is encoded with. See the
+/// [`module`](self) documentation for more details.
+pub trait VersionedMessage<'de>: Serialize + Deserialize<'de> {
+    /// Serializes this message into ``buf`` with the layout of ``version``, returning the part of
+    /// ``buf`` used. Messages that can't be encoded with ``version`` give an error.
+    fn to_versioned_slice<'b>(
+        &self,
+        version: ProtocolVersion,
+        buf: &'b mut [u8],
+    ) -> Result<&'b mut [u8], PostcardError>;
+
+    /// Deserializes a message encoded with the layout of ``version`` from ``bytes``.
+    fn from_versioned_bytes(
+        version: ProtocolVersion,
+        bytes: &'de [u8],
+    ) -> Result<Self, PostcardError>;
+}
+
+/// Implemented for any [`Message`] that has a variant containing a [`Hello`].
+pub trait HelloMessage: Message {
+    /// Creates the message containing ``hello``.
+    fn hello<'de>(hello: Hello) -> Self::Borrowed<'de>;
+
+    /// Gets the [`Hello`] in ``message``, or [`None`] if it isn't a hello.
+    fn as_hello<'b>(message: &'b Self::Borrowed<'_>) -> Option<&'b Hello>;
+}
+
+/// A message along with the protocol version it was encoded with. See the [`module`](self) documentation
+/// for more details.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Envelope<M> {
+    /// The protocol version of ``message``.
+    pub version: ProtocolVersion,
+
+    /// The message.
+    pub message: M,
+}
+
+impl<M> Envelope<M> {
+    /// Creates a new [`Envelope`] with the current [`PROTOCOL_VERSION`].
+    pub fn new(message: M) -> Self {
+        Self::with_version(message, PROTOCOL_VERSION)
+    }
+
+    /// Creates a new [`Envelope`] with the protocol version ``version``, such as a version negotiated
+    /// with [`Hello::negotiate`].
+    pub fn with_version(message: M, version: ProtocolVersion) -> Self {
+        Self { version, message }
+    }
+}
+
+/// An error given when an [`Envelope`] can't be decoded.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EnvelopeError {
+    /// The message was encoded with an unsupported protocol version, which is contained.
+    UnsupportedVersion(ProtocolVersion),
+
+    /// The envelope or the message in it is malformed.
+    Malformed,
+}
+
+impl From<EnvelopeError> for CommunicationError {
+    fn from(err: EnvelopeError) -> Self {
+        match err {
+            EnvelopeError::UnsupportedVersion(_) => CommunicationError::UnsupportedVersion,
+            EnvelopeError::Malformed => CommunicationError::EncodingError,
+        }
+    }
+}
+
+/// Serializes ``message`` into ``buf`` in an [`Envelope`] with ``version``, returning the part of ``buf``
+/// used. ``version`` should be the version negotiated for the session, or the version of the message
+/// being responded to.
+pub fn to_envelope_slice<'de, 'b, M: VersionedMessage<'de>>(
+    message: &M,
+    version: ProtocolVersion,
+    buf: &'b mut [u8],
+) -> Result<&'b mut [u8], PostcardError> {
+    // An envelope is encoded as its version followed by its message.
+    let version_len = postcard::to_slice(&version, buf)?.len();
+    let message_len = message
+        .to_versioned_slice(version, &mut buf[version_len..])?
+        .len();
+
+    Ok(&mut buf[..version_len + message_len])
+}
+
+/// Serializes a [`Hello`] with the local versions and capabilities into ``buf`` in an [`Envelope`] with
+/// the [`MIN_PROTOCOL_VERSION`], returning the part of ``buf`` used.
+pub fn hello_to_envelope_slice<M: HelloMessage>(
+    buf: &mut [u8],
+) -> Result<&mut [u8], PostcardError> {
+    postcard::to_slice(
+        &Envelope::with_version(M::hello(Hello::local()), MIN_PROTOCOL_VERSION),
+        buf,
+    )
+}
+
+/// Deserializes an [`Envelope`] from ``bytes``, decoding the message with the layout of its version.
+/// Upon an error, an [`EnvelopeError`] is given.
+///
+/// # ERRORS:
+///
+/// - [`EnvelopeError::UnsupportedVersion`]
+///   - The version of the envelope isn't between [`MIN_PROTOCOL_VERSION`] and [`PROTOCOL_VERSION`].
+/// - [`EnvelopeError::Malformed`]
+///   - The envelope or the message in it couldn't be deserialized.
+pub fn from_envelope_bytes<'de, M: VersionedMessage<'de>>(
+    bytes: &'de [u8],
+) -> Result<Envelope<M>, EnvelopeError> {
+    let (version, message_bytes) = postcard::take_from_bytes::<ProtocolVersion>(bytes)
+        .map_err(|_| EnvelopeError::Malformed)?;
+
+    // The message is only decoded with a supported version, as its layout can differ otherwise.
+    if !(MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&version) {
+        return Err(EnvelopeError::UnsupportedVersion(version));
+    }
+
+    let message =
+        M::from_versioned_bytes(version, message_bytes).map_err(|_| EnvelopeError::Malformed)?;
+
+    Ok(Envelope { version, message })
+}
+
+/// A set of optional features supported by a peer, advertised in a [`Hello`].
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Capabilities(pub u32);
+
+impl Capabilities {
+    /// No capabilities.
+    pub const NONE: Self = Self(0);
+
+    /// The peer pads the messages it sends over UART1 to hide their length, and removes the padding from
+    /// the messages it receives.
+    pub const PADDED_UART1: Self = Self(1 << 0);
+
+    /// The peer can establish sessions with a Noise handshake, as done by the
+    /// [`handshake`](crate::communication::handshake) module. This is reserved for a future version, as
+    /// the car and key fob don't establish sessions this way yet, so it isn't in [`LOCAL`](Self::LOCAL).
+    pub const NOISE_HANDSHAKE: Self = Self(1 << 1);
+
+    /// The capabilities supported by this build. Only capabilities the car and key fob act on are
+    /// advertised.
+    pub const LOCAL: Self = Self::PADDED_UART1;
+
+    /// The capabilities a peer must support for the car and key fob to start a session with it on UART1.
+    /// The car and key fob pad every message sent on UART1, so peers that don't remove the padding are
+    /// rejected.
+    pub const REQUIRED_UART1: Self = Self::PADDED_UART1;
+
+    /// Checks whether every capability in ``other`` is in this set.
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Gets the capabilities in both this set and ``other``.
+    pub fn intersection(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+}
+
+/// The message sent by both peers at the start of a session, containing the protocol versions and
+/// capabilities supported by the sender. See the [`module`](self) documentation for more details.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Hello {
+    /// The oldest protocol version supported by the sender.
+    pub min_version: ProtocolVersion,
+
+    /// The newest protocol version supported by the sender.
+    pub max_version: ProtocolVersion,
+
+    /// The capabilities supported by the sender.
+    pub capabilities: Capabilities,
+}
+
+impl Hello {
+    /// Gets the [`Hello`] describing this build.
+    pub const fn local() -> Self {
+        Self {
+            min_version: MIN_PROTOCOL_VERSION,
+            max_version: PROTOCOL_VERSION,
+            capabilities: Capabilities::LOCAL,
+        }
+    }
+
+    /// Negotiates the parameters of a session between the sender of this [`Hello`] and the sender of
+    /// ``remote``, which are the newest version and the capabilities both support. Returns [`None`] if
+    /// there is no version both support.
+    pub fn negotiate(&self, remote: &Hello) -> Option<SessionParameters> {
+        let version = self.max_version.min(remote.max_version);
+
+        (version >= self.min_version.max(remote.min_version)).then_some(SessionParameters {
+            version,
+            capabilities: self.capabilities.intersection(remote.capabilities),
+        })
+    }
+}
+
+/// The parameters of a session negotiated with [`Hello::negotiate`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SessionParameters {
+    /// The protocol version used to send messages.
+    pub version: ProtocolVersion,
+
+    /// The capabilities supported by both peers.
+    pub capabilities: Capabilities,
 }
 
 /// The message to send to a car to signal the start of an unlock seequence.
@@ -127,7 +571,64 @@ pub struct UnlockChallenge {
     pub challenge: Nonce,
 }
 
-/// A packaged feature, containing the Car ID and Feature Number.
+/// The type of a message signed with the feature signing key, which is signed along with the
+/// message. See the [`module`](self) documentation for more details.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SignatureTag {
+    /// A [`PackagedFeatureUnsigned`].
+    PackagedFeature,
+
+    /// A [`RevocationListUnsigned`].
+    RevocationList,
+
+    /// A [`DisableFeatureUnsigned`].
+    DisableFeature,
+}
+
+impl SignatureTag {
+    /// The size of a Postcard-encoded [`SignatureTag`].
+    pub const SIZE: usize = 1;
+}
+
+/// A message signed with the feature signing key, which is signed in this form. See the
+/// [`module`](self) documentation for more details.
+#[derive(Serialize, Debug)]
+pub struct TaggedMessage<'a, T> {
+    /// The type of ``message``.
+    pub tag: SignatureTag,
+
+    /// The message.
+    pub message: &'a T,
+}
+
+/// Implemented for messages signed with the feature signing key.
+pub trait SignedMessage: Serialize + Sized {
+    /// The tag of this type of message.
+    const TAG: SignatureTag;
+
+    /// Gets this message in the form it is signed in.
+    fn tagged(&self) -> TaggedMessage<'_, Self> {
+        TaggedMessage {
+            tag: Self::TAG,
+            message: self,
+        }
+    }
+}
+
+impl SignedMessage for PackagedFeatureUnsigned {
+    const TAG: SignatureTag = SignatureTag::PackagedFeature;
+}
+
+impl SignedMessage for RevocationListUnsigned {
+    const TAG: SignatureTag = SignatureTag::RevocationList;
+}
+
+impl SignedMessage for DisableFeatureUnsigned {
+    const TAG: SignatureTag = SignatureTag::DisableFeature;
+}
+
+/// A packaged feature, containing the Car ID, Feature Number, and the window of time it is valid
+/// for. See the [`module`](self) documentation for more details.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct PackagedFeatureUnsigned {
     /// The ID of the car this feature is meant for.
@@ -135,16 +636,61 @@ pub struct PackagedFeatureUnsigned {
 
     /// The number for the feature to enable on the linked car
     pub feature_number: FeatureNumber,
+
+    /// The seconds of [`DeviceTime`] after the car first accepts this feature before which it isn't
+    /// valid, if any.
+    pub not_before: Option<DeviceTime>,
+
+    /// The seconds of [`DeviceTime`] after the car first accepts this feature after which it isn't
+    /// valid, if any.
+    pub not_after: Option<DeviceTime>,
+}
+
+impl PackagedFeatureUnsigned {
+    /// The maximum size of a Postcard-encoded [`PackagedFeatureUnsigned`].
+    pub const MAX_SIZE: usize = 2 * 5 + 2 * (1 + 10);
+
+    /// Checks whether this feature has a validity window, which features packaged for protocol
+    /// version 1 can't have.
+    pub fn has_validity_window(&self) -> bool {
+        self.not_before.is_some() || self.not_after.is_some()
+    }
+
+    /// Serializes this feature into ``buf`` with its protocol version 1 encoding, which version 1
+    /// features were signed over, returning the part of ``buf`` used. Gives [`None`] if this feature
+    /// has a validity window or doesn't fit in ``buf``.
+    pub fn to_v1_slice<'b>(&self, buf: &'b mut [u8]) -> Option<&'b mut [u8]> {
+        postcard::to_slice(&v1::PackagedFeatureUnsigned::try_from(self).ok()?, buf).ok()
+    }
+
+    /// Checks whether this feature is valid ``elapsed`` seconds of [`DeviceTime`] after the car first
+    /// accepted it. Both ends of the validity window are inclusive.
+    pub fn is_valid_at(&self, elapsed: DeviceTime) -> bool {
+        (self.not_before.unwrap_or(DeviceTime::MIN)..=self.not_after.unwrap_or(DeviceTime::MAX))
+            .contains(&elapsed)
+    }
+
+    /// Gets the [`FeatureDigest`] identifying this feature in a revocation list, which is the first
+    /// bytes of the SHA-256 hash of its Postcard encoding.
+    pub fn digest(&self) -> FeatureDigest {
+        let mut buf = [0; Self::MAX_SIZE];
+        let bytes = postcard::to_slice(self, &mut buf).expect("Packaged feature is too large.");
+        let hash = Sha256::digest(bytes);
+        let mut digest = FeatureDigest::default();
+        digest.copy_from_slice(&hash[..mem::size_of::<FeatureDigest>()]);
+
+        digest
+    }
 }
 
 /// A signed packaged feature associated with the car it's tied to.
 /// The signature guarantees that it's not tampered with.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct PackagedFeatureSigned<'a> {
-    /// The helper struct containing the Car ID and Feature Number.
+    /// The helper struct containing the Car ID, Feature Number, and validity window.
     pub packaged_feature: PackagedFeatureUnsigned,
 
-    /// A signature for the car ID and feature number encoded in DER format.
+    /// A signature for the car ID, feature number, and validity window encoded in DER format.
     pub signature: &'a [u8],
 }
 
@@ -152,16 +698,23 @@ pub struct PackagedFeatureSigned<'a> {
 /// from the challenge to prevent replay attacks. See the fields of this struct
 /// for more information.
 #[derive(Serialize, Deserialize)]
-pub struct UnlockChallengeResponse<'a> {
+pub struct UnlockChallengeResponse {
     /// The ID of the car to be unlocked.
     pub car_id: CarId,
 
     /// The [`Nonce`] in the [`UnlockChallenge`] sent before this response.
     pub challenge_response: Nonce,
 
-    /// A list of features that are enabled for this car.
-    #[serde(borrow)]
-    pub features: heapless::Vec<PackagedFeatureSigned<'a>, NUM_FEATURES>,
+    /// A list of features that are enabled for this car. Their signatures were verified by the key
+    /// fob when they were enabled, so they aren't sent. See the [`module`](self) documentation for
+    /// more details.
+    pub features: heapless::Vec<PackagedFeatureUnsigned, NUM_FEATURES>,
+}
+
+impl UnlockChallengeResponse {
+    /// The maximum size of a Postcard-encoded [`UnlockChallengeResponse`].
+    pub const MAX_SIZE: usize =
+        5 + mem::size_of::<Nonce>() + 1 + NUM_FEATURES * PackagedFeatureUnsigned::MAX_SIZE;
 }
 
 /// The message containing the unlock secret, the feature secrets of any enabled features on the
@@ -181,11 +734,104 @@ pub struct UnlockMessage<'a> {
     pub car_id: CarId,
 }
 
+impl UnlockMessage<'_> {
+    /// The maximum size of a Postcard-encoded [`UnlockMessage`] whose messages are each at most
+    /// [`MESSAGE_SIZE`] bytes.
+    pub const MAX_SIZE: usize =
+        (2 + MESSAGE_SIZE) + (1 + NUM_FEATURES * 5) + (1 + NUM_FEATURES * (2 + MESSAGE_SIZE)) + 5;
+}
+
 /// A message containing a signed packaged feature to enable a feature
 /// on a car. It is sent to a paired key fob associated with a car.
 #[derive(Serialize, Deserialize)]
 pub struct EnableFeatureMessage<'a>(#[serde(borrow)] pub PackagedFeatureSigned<'a>);
 
+/// A list of revoked features for a car. See the [`module`](self) documentation for more details.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RevocationListUnsigned {
+    /// The ID of the car this list is meant for.
+    pub car_id: CarId,
+
+    /// The sequence number of this list, which must be greater than that of the list it replaces.
+    pub sequence: u32,
+
+    /// The digests of the revoked features.
+    pub revoked: heapless::Vec<FeatureDigest, MAX_REVOKED_FEATURES>,
+}
+
+impl RevocationListUnsigned {
+    /// The maximum size of a Postcard-encoded [`RevocationListUnsigned`].
+    pub const MAX_SIZE: usize = 2 * 5 + 1 + MAX_REVOKED_FEATURES * mem::size_of::<FeatureDigest>();
+
+    /// Checks whether ``packaged_feature`` is revoked by this list.
+    pub fn is_revoked(&self, packaged_feature: &PackagedFeatureUnsigned) -> bool {
+        packaged_feature.car_id == self.car_id && self.revoked.contains(&packaged_feature.digest())
+    }
+}
+
+/// A signed list of revoked features associated with the car it's tied to.
+/// The signature guarantees that it's not tampered with.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RevocationListSigned<'a> {
+    /// The helper struct containing the Car ID, sequence number, and revoked features.
+    pub revocation_list: RevocationListUnsigned,
+
+    /// A signature for the car ID, sequence number, and revoked features encoded in DER format.
+    pub signature: &'a [u8],
+}
+
+/// A message containing a signed revocation list to install on a car
+/// or a paired key fob.
+#[derive(Serialize, Deserialize)]
+pub struct RevocationListMessage<'a>(#[serde(borrow)] pub RevocationListSigned<'a>);
+
+/// A request to remove an installed feature from a paired key fob. See the [`module`](self)
+/// documentation for more details.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DisableFeatureUnsigned {
+    /// The ID of the car the feature is installed for.
+    pub car_id: CarId,
+
+    /// The number of the feature to remove.
+    pub feature_number: FeatureNumber,
+
+    /// The digest of the packaged feature to remove.
+    pub feature_digest: FeatureDigest,
+
+    /// The sequence number of this request, which must be greater than that of the last request
+    /// accepted by the key fob.
+    pub sequence: u32,
+}
+
+impl DisableFeatureUnsigned {
+    /// The maximum size of a Postcard-encoded [`DisableFeatureUnsigned`].
+    pub const MAX_SIZE: usize = 3 * 5 + mem::size_of::<FeatureDigest>();
+
+    /// Checks whether ``packaged_feature`` is the feature this request removes.
+    pub fn matches(&self, packaged_feature: &PackagedFeatureUnsigned) -> bool {
+        packaged_feature.car_id == self.car_id
+            && packaged_feature.feature_number == self.feature_number
+            && packaged_feature.digest() == self.feature_digest
+    }
+}
+
+/// A signed request to remove an installed feature associated with the car it's tied to.
+/// The signature guarantees that it's not tampered with.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DisableFeatureSigned<'a> {
+    /// The helper struct containing the Car ID, feature number, feature digest, and sequence
+    /// number.
+    pub disable_feature: DisableFeatureUnsigned,
+
+    /// A signature for the car ID, feature number, feature digest, and sequence number encoded in
+    /// DER format.
+    pub signature: &'a [u8],
+}
+
+/// A message containing a signed request to remove a feature from a paired key fob.
+#[derive(Serialize, Deserialize)]
+pub struct DisableFeatureMessage<'a>(#[serde(borrow)] pub DisableFeatureSigned<'a>);
+
 /// A struct containing the pairing pin needed to initiate a pairing
 /// sequence.
 #[derive(Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
@@ -0,0 +1,183 @@
+//! The messages of protocol version 1, which are still decoded from and encoded for older peers. Only
+//! the messages whose layout has changed since are defined here, along with the enums containing them.
+//!
+//! Version 1 packaged features had no validity window, so a version 1 [`PackagedFeatureUnsigned`] is
+//! decoded as a feature without one, and a feature with a validity window can't be encoded for version 1.
+//! UART1 messages are encoded for version 1 with their version 2 layout, as described in the
+//! [`v2`](super::v2) module.
+
+use postcard::Error as PostcardError;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    CarId, DiffieHellmanMessage, FeatureNumber, Hello, HostToolAck, Nonce, PairingChallenge,
+    PairingChallengeResponse, PairingPin, PairingRequest, UnlockChallenge, UnlockMessage,
+    UnlockRequest, NUM_FEATURES,
+};
+
+/// The version 1 layout of [`super::PackagedFeatureUnsigned`], which had no validity window.
+#[derive(Serialize, Deserialize)]
+pub(super) struct PackagedFeatureUnsigned {
+    car_id: CarId,
+    feature_number: FeatureNumber,
+}
+
+impl From<PackagedFeatureUnsigned> for super::PackagedFeatureUnsigned {
+    fn from(packaged_feature: PackagedFeatureUnsigned) -> Self {
+        Self {
+            car_id: packaged_feature.car_id,
+            feature_number: packaged_feature.feature_number,
+            not_before: None,
+            not_after: None,
+        }
+    }
+}
+
+impl TryFrom<&super::PackagedFeatureUnsigned> for PackagedFeatureUnsigned {
+    type Error = PostcardError;
+
+    fn try_from(packaged_feature: &super::PackagedFeatureUnsigned) -> Result<Self, Self::Error> {
+        if packaged_feature.has_validity_window() {
+            return Err(PostcardError::SerdeSerCustom);
+        }
+
+        Ok(Self {
+            car_id: packaged_feature.car_id,
+            feature_number: packaged_feature.feature_number,
+        })
+    }
+}
+
+/// The version 1 layout of [`super::PackagedFeatureSigned`].
+#[derive(Serialize, Deserialize)]
+pub(super) struct PackagedFeatureSigned<'a> {
+    packaged_feature: PackagedFeatureUnsigned,
+    signature: &'a [u8],
+}
+
+impl<'a> From<PackagedFeatureSigned<'a>> for super::PackagedFeatureSigned<'a> {
+    fn from(packaged_feature_signed: PackagedFeatureSigned<'a>) -> Self {
+        Self {
+            packaged_feature: packaged_feature_signed.packaged_feature.into(),
+            signature: packaged_feature_signed.signature,
+        }
+    }
+}
+
+impl<'a> TryFrom<&super::PackagedFeatureSigned<'a>> for PackagedFeatureSigned<'a> {
+    type Error = PostcardError;
+
+    fn try_from(
+        packaged_feature_signed: &super::PackagedFeatureSigned<'a>,
+    ) -> Result<Self, Self::Error> {
+        Ok(Self {
+            packaged_feature: (&packaged_feature_signed.packaged_feature).try_into()?,
+            signature: packaged_feature_signed.signature,
+        })
+    }
+}
+
+/// The version 1 layout of [`super::UnlockChallengeResponse`], which sent the signature of each
+/// feature.
+#[derive(Serialize, Deserialize)]
+pub(super) struct UnlockChallengeResponse<'a> {
+    car_id: CarId,
+    challenge_response: Nonce,
+    #[serde(borrow)]
+    features: heapless::Vec<PackagedFeatureSigned<'a>, NUM_FEATURES>,
+}
+
+impl From<UnlockChallengeResponse<'_>> for super::UnlockChallengeResponse {
+    fn from(response: UnlockChallengeResponse<'_>) -> Self {
+        Self {
+            car_id: response.car_id,
+            challenge_response: response.challenge_response,
+            features: response
+                .features
+                .into_iter()
+                .map(|feature| feature.packaged_feature.into())
+                .collect(),
+        }
+    }
+}
+
+/// The version 1 layout of [`super::Uart0Message`], which ended at the ``Hello`` variant.
+#[derive(Serialize, Deserialize)]
+pub(super) enum Uart0Message<'a> {
+    EnableFeatureRequest(#[serde(borrow)] PackagedFeatureSigned<'a>),
+    EnableFeatureResponse(HostToolAck),
+    #[serde(borrow)]
+    HostUnlock(UnlockMessage<'a>),
+    PairingPin(PairingPin),
+    PairingPinResponse(HostToolAck),
+    Hello(Hello),
+}
+
+impl<'a> From<Uart0Message<'a>> for super::Uart0Message<'a> {
+    fn from(message: Uart0Message<'a>) -> Self {
+        match message {
+            Uart0Message::EnableFeatureRequest(packaged_feature_signed) => {
+                Self::EnableFeatureRequest(super::EnableFeatureMessage(
+                    packaged_feature_signed.into(),
+                ))
+            }
+            Uart0Message::EnableFeatureResponse(ack) => Self::EnableFeatureResponse(ack),
+            Uart0Message::HostUnlock(msg) => Self::HostUnlock(msg),
+            Uart0Message::PairingPin(pin) => Self::PairingPin(pin),
+            Uart0Message::PairingPinResponse(ack) => Self::PairingPinResponse(ack),
+            Uart0Message::Hello(hello) => Self::Hello(hello),
+        }
+    }
+}
+
+/// The version 1 layout of [`super::Uart1Message`].
+#[derive(Serialize, Deserialize)]
+pub(super) enum Uart1Message<'a> {
+    UnlockRequest(UnlockRequest),
+    UnlockChallenge(UnlockChallenge),
+    #[serde(borrow)]
+    UnlockChallengeResponse(UnlockChallengeResponse<'a>),
+    #[serde(borrow)]
+    DiffieHellman(DiffieHellmanMessage<'a>),
+    PairingRequest(PairingRequest),
+    PairingChallenge(PairingChallenge),
+    PairingChallengeResponse(PairingChallengeResponse),
+    Hello(Hello),
+}
+
+impl<'a> From<Uart1Message<'a>> for super::Uart1Message<'a> {
+    fn from(message: Uart1Message<'a>) -> Self {
+        match message {
+            Uart1Message::UnlockRequest(msg) => Self::UnlockRequest(msg),
+            Uart1Message::UnlockChallenge(msg) => Self::UnlockChallenge(msg),
+            Uart1Message::UnlockChallengeResponse(msg) => Self::UnlockChallengeResponse(msg.into()),
+            Uart1Message::DiffieHellman(msg) => Self::DiffieHellman(msg),
+            Uart1Message::PairingRequest(msg) => Self::PairingRequest(msg),
+            Uart1Message::PairingChallenge(msg) => Self::PairingChallenge(msg),
+            Uart1Message::PairingChallengeResponse(msg) => Self::PairingChallengeResponse(msg),
+            Uart1Message::Hello(hello) => Self::Hello(hello),
+        }
+    }
+}
+
+/// Serializes ``message`` into ``buf`` with its version 1 layout, returning the part of ``buf`` used.
+pub(super) fn uart0_to_slice<'b>(
+    message: &super::Uart0Message<'_>,
+    buf: &'b mut [u8],
+) -> Result<&'b mut [u8], PostcardError> {
+    use super::Uart0Message as Current;
+
+    match message {
+        Current::EnableFeatureRequest(msg) => postcard::to_slice(
+            &Uart0Message::EnableFeatureRequest((&msg.0).try_into()?),
+            buf,
+        ),
+        // The remaining version 1 variants haven't changed.
+        Current::EnableFeatureResponse(_)
+        | Current::HostUnlock(_)
+        | Current::PairingPin(_)
+        | Current::PairingPinResponse(_)
+        | Current::Hello(_) => postcard::to_slice(message, buf),
+        _ => Err(PostcardError::SerdeSerCustom),
+    }
+}
@@ -0,0 +1,85 @@
+//! The messages of protocol version 2, which are still decoded from and encoded for older peers. Only
+//! the messages whose layout has changed since are defined here, along with the enums containing them.
+//!
+//! Version 2 sent the signature of each feature in an [`UnlockChallengeResponse`], which is dropped
+//! when one is decoded. Features can't be encoded for version 2, and an [`UnlockChallengeResponse`]
+//! without any features is encoded the same way in versions 1 to 3, so UART1 messages are encoded for
+//! both versions 1 and 2 with [`uart1_to_slice`].
+
+use postcard::Error as PostcardError;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    CarId, DiffieHellmanMessage, Hello, Nonce, PackagedFeatureSigned, PairingChallenge,
+    PairingChallengeResponse, PairingRequest, UnlockChallenge, UnlockRequest, NUM_FEATURES,
+};
+
+/// The version 2 layout of [`super::UnlockChallengeResponse`], which sent the signature of each
+/// feature.
+#[derive(Serialize, Deserialize)]
+pub(super) struct UnlockChallengeResponse<'a> {
+    car_id: CarId,
+    challenge_response: Nonce,
+    #[serde(borrow)]
+    features: heapless::Vec<PackagedFeatureSigned<'a>, NUM_FEATURES>,
+}
+
+impl From<UnlockChallengeResponse<'_>> for super::UnlockChallengeResponse {
+    fn from(response: UnlockChallengeResponse<'_>) -> Self {
+        Self {
+            car_id: response.car_id,
+            challenge_response: response.challenge_response,
+            features: response
+                .features
+                .into_iter()
+                .map(|feature| feature.packaged_feature)
+                .collect(),
+        }
+    }
+}
+
+/// The version 2 layout of [`super::Uart1Message`].
+#[derive(Serialize, Deserialize)]
+pub(super) enum Uart1Message<'a> {
+    UnlockRequest(UnlockRequest),
+    UnlockChallenge(UnlockChallenge),
+    #[serde(borrow)]
+    UnlockChallengeResponse(UnlockChallengeResponse<'a>),
+    #[serde(borrow)]
+    DiffieHellman(DiffieHellmanMessage<'a>),
+    PairingRequest(PairingRequest),
+    PairingChallenge(PairingChallenge),
+    PairingChallengeResponse(PairingChallengeResponse),
+    Hello(Hello),
+}
+
+impl<'a> From<Uart1Message<'a>> for super::Uart1Message<'a> {
+    fn from(message: Uart1Message<'a>) -> Self {
+        match message {
+            Uart1Message::UnlockRequest(msg) => Self::UnlockRequest(msg),
+            Uart1Message::UnlockChallenge(msg) => Self::UnlockChallenge(msg),
+            Uart1Message::UnlockChallengeResponse(msg) => Self::UnlockChallengeResponse(msg.into()),
+            Uart1Message::DiffieHellman(msg) => Self::DiffieHellman(msg),
+            Uart1Message::PairingRequest(msg) => Self::PairingRequest(msg),
+            Uart1Message::PairingChallenge(msg) => Self::PairingChallenge(msg),
+            Uart1Message::PairingChallengeResponse(msg) => Self::PairingChallengeResponse(msg),
+            Uart1Message::Hello(hello) => Self::Hello(hello),
+        }
+    }
+}
+
+/// Serializes ``message`` into ``buf`` with its version 2 layout, which is also its version 1 layout,
+/// returning the part of ``buf`` used. An [`UnlockChallengeResponse`] with features gives an error, as
+/// their signatures aren't available.
+pub(super) fn uart1_to_slice<'b>(
+    message: &super::Uart1Message<'_>,
+    buf: &'b mut [u8],
+) -> Result<&'b mut [u8], PostcardError> {
+    match message {
+        super::Uart1Message::UnlockChallengeResponse(msg) if !msg.features.is_empty() => {
+            Err(PostcardError::SerdeSerCustom)
+        }
+        // No other message has changed.
+        _ => postcard::to_slice(message, buf),
+    }
+}
@@ -1,6 +1,19 @@
 //! Provides a common trait to implement a timer with the [`Timer`] trait.
+//!
+//! - Deadlines
+//!     - A [`Deadline`] wraps around a timer and ignores [`Timer::reset`], so it can be passed to functions
+//!       that reset their timer, such as for each attempt, without extending it. It's only restarted with
+//!       [`Deadline::restart`].
+//!     - An [`Earliest`] combines two timers and is up when either one is up. Combining a per-operation timer
+//!       with a [`Deadline`] with [`Timer::with_deadline`] gives a timer that is reset for each operation but
+//!       never runs past the deadline.
+//! - Mock timers
+//!     - A [`MockClock`] is a virtual clock that only moves when told to, either explicitly with
+//!       [`MockClock::advance`] or by a fixed step on every poll of its timers. Every [`MockTimer`] created
+//!       from a [`MockClock`] shares its time, so timeouts can be tested deterministically without waiting
+//!       for real time to pass.
 
-use core::time::Duration;
+use core::{cell::Cell, time::Duration};
 
 /// This trait represents a timer that can be poll and reset. It is used to provide a platform-indepentdent
 /// way to poll for whether time is up and reset the timer.
@@ -13,4 +26,209 @@ pub trait Timer {
 
     /// Gets the total duration of the timer.
     fn duration(&self) -> Duration;
+
+    /// Gets the time left until the timer is up, which is zero if time is up.
+    fn remaining(&self) -> Duration;
+
+    /// Gets the time passed since the timer was started or last reset, which is at most the total duration
+    /// of the timer.
+    fn elapsed(&self) -> Duration {
+        self.duration().saturating_sub(self.remaining())
+    }
+
+    /// Turns this timer into a [`Deadline`], which isn't reset by [`Timer::reset`].
+    fn into_deadline(self) -> Deadline<Self>
+    where
+        Self: Sized,
+    {
+        Deadline::new(self)
+    }
+
+    /// Combines this timer with ``deadline``, giving a timer that is up when either this timer or
+    /// ``deadline`` is up. Resetting the combined timer only resets this timer.
+    fn with_deadline<T: Timer>(self, deadline: &mut Deadline<T>) -> Earliest<Self, &mut Deadline<T>>
+    where
+        Self: Sized,
+    {
+        Earliest::new(self, deadline)
+    }
+}
+
+impl<T: Timer + ?Sized> Timer for &mut T {
+    fn poll(&mut self) -> bool {
+        (**self).poll()
+    }
+
+    fn reset(&mut self) {
+        (**self).reset()
+    }
+
+    fn duration(&self) -> Duration {
+        (**self).duration()
+    }
+
+    fn remaining(&self) -> Duration {
+        (**self).remaining()
+    }
+}
+
+/// A timer that isn't reset by [`Timer::reset`]. See the [`module`](self) documentation for more details.
+pub struct Deadline<T> {
+    timer: T,
+}
+
+impl<T: Timer> Deadline<T> {
+    /// Creates a new [`Deadline`] that is up when ``timer`` is up.
+    pub fn new(timer: T) -> Self {
+        Self { timer }
+    }
+
+    /// Restarts the deadline by resetting the wrapped timer.
+    pub fn restart(&mut self) {
+        self.timer.reset();
+    }
+
+    /// Unwraps this deadline, returning the wrapped timer.
+    pub fn into_inner(self) -> T {
+        self.timer
+    }
+}
+
+impl<T: Timer> Timer for Deadline<T> {
+    fn poll(&mut self) -> bool {
+        self.timer.poll()
+    }
+
+    /// Does nothing, as a deadline is only restarted with [`Deadline::restart`].
+    fn reset(&mut self) {}
+
+    fn duration(&self) -> Duration {
+        self.timer.duration()
+    }
+
+    fn remaining(&self) -> Duration {
+        self.timer.remaining()
+    }
+}
+
+/// A timer that is up when either of two timers is up. See the [`module`](self) documentation for more
+/// details.
+pub struct Earliest<T, U> {
+    first: T,
+    second: U,
+}
+
+impl<T: Timer, U: Timer> Earliest<T, U> {
+    /// Creates a new [`Earliest`] that is up when either ``first`` or ``second`` is up.
+    pub fn new(first: T, second: U) -> Self {
+        Self { first, second }
+    }
+
+    /// Unwraps this timer, returning the two wrapped timers.
+    pub fn into_inner(self) -> (T, U) {
+        (self.first, self.second)
+    }
+}
+
+impl<T: Timer, U: Timer> Timer for Earliest<T, U> {
+    fn poll(&mut self) -> bool {
+        // Both timers are polled so that neither misses a poll.
+        self.first.poll() | self.second.poll()
+    }
+
+    fn reset(&mut self) {
+        self.first.reset();
+        self.second.reset();
+    }
+
+    /// Gets the shorter of the total durations of the two timers.
+    fn duration(&self) -> Duration {
+        self.first.duration().min(self.second.duration())
+    }
+
+    fn remaining(&self) -> Duration {
+        self.first.remaining().min(self.second.remaining())
+    }
+}
+
+/// A virtual clock shared by [`MockTimers`](MockTimer). See the [`module`](self) documentation for more
+/// details.
+#[derive(Debug, Default)]
+pub struct MockClock {
+    now: Cell<Duration>,
+    step: Cell<Duration>,
+}
+
+impl MockClock {
+    /// Creates a new [`MockClock`] starting at zero that only moves when advanced explicitly.
+    pub const fn new() -> Self {
+        Self {
+            now: Cell::new(Duration::ZERO),
+            step: Cell::new(Duration::ZERO),
+        }
+    }
+
+    /// Creates a new [`MockClock`] starting at zero that moves forward by ``step`` every time one of its
+    /// timers is polled. This lets code that polls its timer in a loop, such as a receive on a channel with
+    /// no data, time out after a known number of polls.
+    pub const fn new_with_step(step: Duration) -> Self {
+        Self {
+            now: Cell::new(Duration::ZERO),
+            step: Cell::new(step),
+        }
+    }
+
+    /// Gets the time passed on this clock since it was created.
+    pub fn now(&self) -> Duration {
+        self.now.get()
+    }
+
+    /// Moves this clock forward by ``duration``.
+    pub fn advance(&self, duration: Duration) {
+        self.now.set(self.now.get().saturating_add(duration));
+    }
+
+    /// Sets how far this clock moves forward every time one of its timers is polled.
+    pub fn set_step(&self, step: Duration) {
+        self.step.set(step);
+    }
+
+    /// Creates a new [`MockTimer`] on this clock that is up after ``duration``.
+    pub fn timer(&self, duration: Duration) -> MockTimer<'_> {
+        MockTimer {
+            clock: self,
+            duration,
+            end: self.now().saturating_add(duration),
+        }
+    }
+}
+
+/// A timer driven by a [`MockClock`]. See the [`module`](self) documentation for more details.
+pub struct MockTimer<'a> {
+    clock: &'a MockClock,
+    duration: Duration,
+    end: Duration,
+}
+
+impl<'a> Timer for MockTimer<'a> {
+    /// Polls the timer, moving its clock forward by the clock's step afterwards.
+    fn poll(&mut self) -> bool {
+        let up = self.clock.now() >= self.end;
+
+        self.clock.advance(self.clock.step.get());
+
+        up
+    }
+
+    fn reset(&mut self) {
+        self.end = self.clock.now().saturating_add(self.duration);
+    }
+
+    fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    fn remaining(&self) -> Duration {
+        self.end.saturating_sub(self.clock.now())
+    }
 }
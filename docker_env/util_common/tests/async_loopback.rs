@@ -0,0 +1,182 @@
+//! Round trips through the async channels of a loopback pair, with the sender and receiver running as
+//! separate tasks on a minimal single-threaded executor.
+
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use std::{
+    cell::RefCell,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Wake, Waker},
+};
+
+use ucsc_ectf_util_common::{
+    communication::{
+        self,
+        fault_injection::SeededRng,
+        loopback::LoopbackPipe,
+        lower_layers::{
+            crypto::{XChacha20Poly1305RxChannel, XChacha20Poly1305TxChannel},
+            framing::{BogoFraming, CobsFraming, CrcFraming, FramingProtocol},
+        },
+        yield_now, AsyncRxChannel, AsyncTxChannel, CommunicationError,
+    },
+    timer::MockClock,
+};
+
+const PIPE_SIZE: usize = 4096;
+
+/// The lengths sent in every round trip, which include both sides of the COBS block boundaries.
+const LENGTHS: [usize; 10] = [1, 2, 100, 253, 254, 255, 256, 508, 509, 1000];
+
+/// A task of the executor.
+type Task<'a> = Pin<Box<dyn Future<Output = ()> + 'a>>;
+
+/// A waker that counts how many times it was woken.
+#[derive(Default)]
+struct CountingWaker(AtomicUsize);
+
+impl Wake for CountingWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Runs ``tasks`` to completion on the current thread, polling each unfinished task in turn. A task that
+/// is pending must have woken itself, as nothing else can wake it, so the executor would otherwise hang.
+/// Returns the number of times the tasks yielded.
+fn run(mut tasks: Vec<Task<'_>>) -> usize {
+    let waker_count = Arc::new(CountingWaker::default());
+    let waker = Waker::from(waker_count.clone());
+    let mut cx = Context::from_waker(&waker);
+    let mut yields = 0;
+
+    while !tasks.is_empty() {
+        tasks.retain_mut(|task| {
+            let woken = waker_count.0.load(Ordering::Relaxed);
+
+            match task.as_mut().poll(&mut cx) {
+                Poll::Ready(()) => false,
+                Poll::Pending => {
+                    assert!(
+                        waker_count.0.load(Ordering::Relaxed) > woken,
+                        "a pending task didn't wake itself"
+                    );
+                    yields += 1;
+
+                    true
+                }
+            }
+        });
+    }
+
+    yields
+}
+
+fn message(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i % 251) as u8 + 1).collect()
+}
+
+/// Receives one message from ``rx``, timing out after 1000 polls without receiving a byte.
+async fn recv<R: AsyncRxChannel>(rx: &mut R, dest: &mut [u8]) -> communication::Result<usize> {
+    let clock = MockClock::new_with_step(Duration::from_millis(1));
+
+    rx.recv_with_data_timeout(dest, &mut clock.timer(Duration::from_millis(1000)))
+        .await
+}
+
+/// Sends every message of [`LENGTHS`] through ``tx`` from one task while another task receives them from
+/// ``rx``, returning the messages received. The receiving task starts first, so it waits on an empty pipe
+/// before each message is sent.
+fn round_trip<T: AsyncTxChannel, R: AsyncRxChannel>(mut tx: T, mut rx: R) -> Vec<Vec<u8>> {
+    let received = RefCell::new(Vec::new());
+    let received_ref = &received;
+
+    let yields = run(vec![
+        Box::pin(async move {
+            let mut dest = [0; PIPE_SIZE];
+
+            for _ in LENGTHS {
+                let len = recv(&mut rx, &mut dest).await.unwrap();
+                received_ref.borrow_mut().push(dest[..len].to_vec());
+            }
+        }),
+        Box::pin(async move {
+            for len in LENGTHS {
+                // Let the receiver wait on the empty pipe first.
+                yield_now().await;
+                tx.send(&mut message(len)).await.unwrap();
+            }
+        }),
+    ]);
+
+    // The receiver waited for every message.
+    assert!(yields >= LENGTHS.len());
+
+    received.into_inner()
+}
+
+#[test]
+fn async_round_trip() {
+    fn check<F: FramingProtocol>() {
+        let pipe = LoopbackPipe::<PIPE_SIZE>::new();
+        let (tx, rx) = pipe.split::<F>();
+
+        let received = round_trip(tx, rx);
+
+        assert_eq!(received, LENGTHS.map(message));
+        assert!(pipe.is_empty());
+    }
+
+    check::<BogoFraming>();
+    check::<CobsFraming>();
+    check::<CrcFraming>();
+}
+
+#[test]
+fn async_xchacha20poly1305_round_trip() {
+    fn check<F: FramingProtocol>() {
+        let pipe = LoopbackPipe::<PIPE_SIZE>::new();
+        let (tx, rx) = pipe.split::<F>();
+        let key = [7; 32].into();
+        let tx = XChacha20Poly1305TxChannel::new_with_sequence_numbers(tx, SeededRng::new(1), &key);
+        let rx = XChacha20Poly1305RxChannel::new_with_replay_protection(rx, &key);
+
+        let received = round_trip(tx, rx);
+
+        assert_eq!(received, LENGTHS.map(message));
+        assert!(pipe.is_empty());
+    }
+
+    check::<BogoFraming>();
+    check::<CobsFraming>();
+    check::<CrcFraming>();
+}
+
+#[test]
+fn async_recv_times_out_on_an_empty_pipe() {
+    let pipe = LoopbackPipe::<PIPE_SIZE>::new();
+    let (_, mut rx) = pipe.split::<CobsFraming>();
+    let result = RefCell::new(None);
+
+    let yields = run(vec![Box::pin(async {
+        let mut dest = [0; PIPE_SIZE];
+
+        let res = recv(&mut rx, &mut dest).await;
+        *result.borrow_mut() = Some(res);
+    })]);
+
+    // The receiver yielded while waiting instead of blocking the executor.
+    assert!(yields > 0);
+    assert_eq!(result.into_inner(), Some(Err(CommunicationError::Timeout)));
+}
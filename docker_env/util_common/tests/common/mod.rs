@@ -0,0 +1,112 @@
+//! Helpers shared by the integration tests.
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use ucsc_ectf_util_common::{
+    communication::{
+        self,
+        lower_layers::framing::{Frame, FramedTxChannel},
+        CommunicationError, RxChannel,
+    },
+    timer::Timer,
+};
+
+/// A timer using the system clock.
+pub struct StdTimer {
+    start: Instant,
+    duration: Duration,
+}
+
+impl StdTimer {
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            start: Instant::now(),
+            duration,
+        }
+    }
+}
+
+impl Timer for StdTimer {
+    fn poll(&mut self) -> bool {
+        self.start.elapsed() >= self.duration
+    }
+
+    fn reset(&mut self) {
+        self.start = Instant::now();
+    }
+
+    fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    fn remaining(&self) -> Duration {
+        self.duration.saturating_sub(self.start.elapsed())
+    }
+}
+
+/// The messages in a wire that have been sent but not yet received.
+type Messages = Arc<Mutex<VecDeque<Vec<u8>>>>;
+
+/// Creates a thread-safe in-memory wire carrying whole messages in one direction, returning its sending
+/// and receiving ends. Unlike a loopback pipe, each end can be used on its own thread, which is needed to
+/// test protocols where both parties block waiting for each other.
+pub fn wire() -> (WireTx, WireRx) {
+    let messages = Messages::default();
+
+    (WireTx(messages.clone()), WireRx(messages))
+}
+
+/// The sending end of a [`wire`].
+pub struct WireTx(Messages);
+
+impl FramedTxChannel for WireTx {
+    fn frame<'a, const FRAME_CT: usize>(
+        &mut self,
+        frame: impl FnOnce() -> communication::Result<Frame<'a, FRAME_CT>>,
+    ) -> communication::Result<()> {
+        let message = frame()?.into_iter().flatten().copied().collect();
+        self.0.lock().unwrap().push_back(message);
+
+        Ok(())
+    }
+}
+
+/// The receiving end of a [`wire`].
+pub struct WireRx(Messages);
+
+impl RxChannel for WireRx {
+    fn recv_with_data_timeout<T: Timer>(
+        &mut self,
+        dest: &mut [u8],
+        timer: &mut T,
+    ) -> communication::Result<usize> {
+        self.recv_with_timeout(dest, timer)
+    }
+
+    fn recv_with_timeout<T: Timer>(
+        &mut self,
+        dest: &mut [u8],
+        timer: &mut T,
+    ) -> communication::Result<usize> {
+        loop {
+            if let Some(message) = self.0.lock().unwrap().pop_front() {
+                dest.get_mut(..message.len())
+                    .ok_or(CommunicationError::BufferTooSmall)?
+                    .copy_from_slice(&message);
+
+                return Ok(message.len());
+            }
+
+            if timer.poll() {
+                return Err(CommunicationError::Timeout);
+            }
+
+            thread::yield_now();
+        }
+    }
+}
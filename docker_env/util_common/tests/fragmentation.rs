@@ -0,0 +1,365 @@
+//! Reassembly of fragments sent in a chosen order, and transfers through a fragmenting and reassembling
+//! channel pair over a loopback pair with seeded faults injected into the fragments.
+
+use core::time::Duration;
+
+use ucsc_ectf_util_common::{
+    communication::{
+        self,
+        fault_injection::{FaultConfig, FaultyRxChannel, FaultyTxChannel},
+        fragmentation::{FragmentingTxChannel, ReassemblingRxChannel, FRAGMENT_HEADER_SIZE},
+        loopback::LoopbackPipe,
+        lower_layers::framing::CobsFraming,
+        CommunicationError, RxChannel, TxChannel,
+    },
+    timer::MockClock,
+};
+
+const PIPE_SIZE: usize = 32 * 1024;
+
+/// The size of every fragment, including the header.
+const FRAGMENT_SIZE: usize = 64;
+
+/// The size of the data in every fragment but the last.
+const FRAGMENT_DATA_SIZE: usize = FRAGMENT_SIZE - FRAGMENT_HEADER_SIZE;
+
+/// The number of messages sent in each transfer.
+const MESSAGE_CT: usize = 40;
+
+/// The size of the largest message sent, which takes several fragments.
+const MAX_MESSAGE_LEN: usize = 300;
+
+/// Creates the message with the given index. Every message has different contents, so a message mixed
+/// up with another one is caught.
+fn message(i: usize) -> Vec<u8> {
+    let len = 1 + i * 37 % MAX_MESSAGE_LEN;
+
+    (0..len).map(|j| (i * 31 + j * 7) as u8).collect()
+}
+
+/// Gets the number of fragments the message with the given index is sent as.
+fn fragment_ct(i: usize) -> usize {
+    message(i).len().div_ceil(FRAGMENT_DATA_SIZE)
+}
+
+/// Receives one message from ``rx``, timing out after 100 polls without receiving a byte.
+fn recv<R: RxChannel>(rx: &mut R, dest: &mut [u8]) -> communication::Result<usize> {
+    let clock = MockClock::new_with_step(Duration::from_millis(1));
+
+    rx.recv_with_data_timeout(dest, &mut clock.timer(Duration::from_millis(100)))
+}
+
+/// A [`TxChannel`] that keeps every fragment sent through it so they can be sent on in any order.
+#[derive(Default)]
+struct FragmentRecorder(Vec<Vec<u8>>);
+
+impl TxChannel for FragmentRecorder {
+    fn send(&mut self, src: &mut [u8]) -> communication::Result<()> {
+        self.0.push(src.to_vec());
+
+        Ok(())
+    }
+}
+
+/// Fragments each message in ``msgs`` with one sender, returning the fragments of each message.
+fn fragment(msgs: &[&[u8]]) -> Vec<Vec<Vec<u8>>> {
+    let mut tx = FragmentingTxChannel::<_, FRAGMENT_SIZE>::new(FragmentRecorder::default());
+
+    msgs.iter()
+        .map(|msg| {
+            tx.send(&mut msg.to_vec()).unwrap();
+
+            core::mem::take(&mut tx.channel_mut().0)
+        })
+        .collect()
+}
+
+/// Sends ``fragments`` through a loopback pair in the order given, then returns every message that is
+/// reassembled from them.
+fn reassemble(fragments: &[&[u8]]) -> Vec<Vec<u8>> {
+    let pipe = LoopbackPipe::<PIPE_SIZE>::new();
+    let (mut tx, rx) = pipe.split::<CobsFraming>();
+    let mut rx = ReassemblingRxChannel::<_, FRAGMENT_SIZE>::new(rx);
+    let mut dest = [0; MAX_MESSAGE_LEN];
+    let mut received = Vec::new();
+
+    for fragment in fragments {
+        tx.send(&mut fragment.to_vec()).unwrap();
+    }
+
+    loop {
+        match recv(&mut rx, &mut dest) {
+            Ok(len) => received.push(dest[..len].to_vec()),
+            Err(CommunicationError::Timeout) => return received,
+            Err(e) => panic!("unexpected error: {e:?}"),
+        }
+    }
+}
+
+/// Sends [`MESSAGE_CT`] messages as fragments with the faults in ``config`` injected on both ends, then
+/// receives every message that can be reassembled. Every message received must be one that was sent,
+/// whole. Returns the indices of the messages received, in the order they were received.
+fn transfer(config: FaultConfig, seed: u64) -> Vec<usize> {
+    let pipe = LoopbackPipe::<PIPE_SIZE>::new();
+    let (tx, rx) = pipe.split::<CobsFraming>();
+    let mut tx = FragmentingTxChannel::<_, FRAGMENT_SIZE>::new(
+        FaultyTxChannel::<_, FRAGMENT_SIZE>::new(tx, config, seed),
+    );
+    let mut rx = ReassemblingRxChannel::<_, FRAGMENT_SIZE>::new(
+        FaultyRxChannel::<_, FRAGMENT_SIZE>::new(rx, config, seed + 1),
+    );
+
+    for i in 0..MESSAGE_CT {
+        tx.send(&mut message(i)).unwrap();
+    }
+
+    let mut dest = [0; MAX_MESSAGE_LEN];
+    let mut received = Vec::new();
+
+    loop {
+        let len = match recv(&mut rx, &mut dest) {
+            Ok(len) => len,
+            Err(CommunicationError::Timeout) => return received,
+            Err(e) => panic!("unexpected error: {e:?}"),
+        };
+
+        let i = (0..MESSAGE_CT)
+            .find(|&i| message(i) == dest[..len])
+            .expect("received a message that wasn't sent");
+
+        received.push(i);
+    }
+}
+
+#[test]
+fn messages_larger_than_one_fragment_are_reassembled() {
+    let pipe = LoopbackPipe::<PIPE_SIZE>::new();
+    let (tx, rx) = pipe.split::<CobsFraming>();
+    let mut tx = FragmentingTxChannel::<_, FRAGMENT_SIZE>::new(tx);
+    let mut rx = ReassemblingRxChannel::<_, FRAGMENT_SIZE>::new(rx);
+    let mut dest = [0; 4 * FRAGMENT_SIZE];
+
+    for len in [
+        1,
+        FRAGMENT_DATA_SIZE - 1,
+        FRAGMENT_DATA_SIZE,
+        FRAGMENT_DATA_SIZE + 1,
+        3 * FRAGMENT_DATA_SIZE,
+    ] {
+        let msg = vec![0x5A; len];
+
+        tx.send(&mut msg.clone()).unwrap();
+
+        let len = recv(&mut rx, &mut dest).unwrap();
+        assert_eq!(&dest[..len], &msg[..]);
+    }
+}
+
+#[test]
+fn reordered_fragments_discard_their_message() {
+    let a = [0xAA; 3 * FRAGMENT_DATA_SIZE];
+    let b = [0xBB; 2 * FRAGMENT_DATA_SIZE];
+    let fragments = fragment(&[&a, &b]);
+    let (a_frags, b_frags) = (&fragments[0], &fragments[1]);
+
+    // Swapping the last two fragments of a message loses it, but not the message after it.
+    assert_eq!(
+        reassemble(&[
+            &a_frags[0],
+            &a_frags[2],
+            &a_frags[1],
+            &b_frags[0],
+            &b_frags[1]
+        ]),
+        [b.to_vec()]
+    );
+
+    // A first fragment that arrives after the fragments following it never starts the message.
+    assert_eq!(
+        reassemble(&[
+            &a_frags[1],
+            &a_frags[2],
+            &a_frags[0],
+            &b_frags[0],
+            &b_frags[1]
+        ]),
+        [b.to_vec()]
+    );
+}
+
+#[test]
+fn duplicated_fragments_only_discard_their_message_in_the_middle() {
+    let a = [0xAA; 3 * FRAGMENT_DATA_SIZE];
+    let b = [0xBB; FRAGMENT_DATA_SIZE];
+    let fragments = fragment(&[&a, &b]);
+    let (a_frags, b_frags) = (&fragments[0], &fragments[1]);
+
+    // A duplicated middle fragment doesn't continue the message, so the message is lost.
+    assert_eq!(
+        reassemble(&[
+            &a_frags[0],
+            &a_frags[1],
+            &a_frags[1],
+            &a_frags[2],
+            &b_frags[0]
+        ]),
+        [b.to_vec()]
+    );
+
+    // A duplicated first fragment starts the message over, and a duplicated last fragment arrives after
+    // the message is done, so neither loses it.
+    assert_eq!(
+        reassemble(&[
+            &a_frags[0],
+            &a_frags[0],
+            &a_frags[1],
+            &a_frags[2],
+            &a_frags[2],
+            &b_frags[0]
+        ]),
+        [a.to_vec(), b.to_vec()]
+    );
+}
+
+#[test]
+fn interleaved_messages_are_discarded() {
+    let a = [0xAA; 2 * FRAGMENT_DATA_SIZE];
+    let b = [0xBB; 2 * FRAGMENT_DATA_SIZE];
+    let c = [0xCC; 2 * FRAGMENT_DATA_SIZE];
+    let fragments = fragment(&[&a, &b, &c]);
+    let (a_frags, b_frags, c_frags) = (&fragments[0], &fragments[1], &fragments[2]);
+
+    // The first fragment of another message starts reassembly over, and the rest of the first message
+    // then doesn't continue it, so neither message is received.
+    assert_eq!(
+        reassemble(&[
+            &a_frags[0],
+            &b_frags[0],
+            &a_frags[1],
+            &b_frags[1],
+            &c_frags[0],
+            &c_frags[1]
+        ]),
+        [c.to_vec()]
+    );
+
+    // A message whose fragments all follow a stray first fragment is still received.
+    assert_eq!(
+        reassemble(&[&a_frags[0], &b_frags[0], &b_frags[1], &a_frags[1]]),
+        [b.to_vec()]
+    );
+}
+
+#[test]
+fn transfer_without_faults() {
+    assert_eq!(
+        transfer(FaultConfig::default(), 1),
+        (0..MESSAGE_CT).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn dropped_fragments_lose_only_their_own_messages() {
+    let mut lost = 0;
+
+    for seed in 0..8 {
+        let received = transfer(
+            FaultConfig {
+                frame_drop_rate: 500,
+                ..Default::default()
+            },
+            seed * 10,
+        );
+
+        // Messages are never repeated or reordered, only lost.
+        assert!(received.windows(2).all(|w| w[0] < w[1]), "{received:?}");
+        lost += MESSAGE_CT - received.len();
+    }
+
+    assert!(lost > 0);
+}
+
+#[test]
+fn duplicated_fragments_only_lose_messages_of_three_or_more_fragments() {
+    let mut lost = 0;
+
+    for seed in 0..8 {
+        let received = transfer(
+            FaultConfig {
+                frame_duplicate_rate: 500,
+                ..Default::default()
+            },
+            seed * 10,
+        );
+
+        // Messages stay in order, and only a message sent as a single fragment can be received more than
+        // once.
+        assert!(received.windows(2).all(|w| w[0] <= w[1]), "{received:?}");
+        assert!(received
+            .windows(2)
+            .all(|w| w[0] != w[1] || fragment_ct(w[0]) == 1));
+
+        // A duplicated first or last fragment doesn't lose the message, so only a message with a middle
+        // fragment can be lost.
+        for i in 0..MESSAGE_CT {
+            if !received.contains(&i) {
+                assert!(fragment_ct(i) >= 3, "lost message {i}");
+                lost += 1;
+            }
+        }
+    }
+
+    assert!(lost > 0);
+}
+
+#[test]
+fn delayed_fragments_never_reorder_multi_fragment_messages() {
+    let mut lost = 0;
+
+    for seed in 0..8 {
+        let received = transfer(
+            FaultConfig {
+                frame_delay_rate: 500,
+                ..Default::default()
+            },
+            seed * 10,
+        );
+
+        // Without duplicates no message is received twice. A delayed fragment only moves past the one
+        // after it, so only messages sent as a single fragment can end up out of order.
+        let mut sorted = received.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(sorted.len(), received.len(), "{received:?}");
+
+        let multi_fragment: Vec<_> = received.iter().filter(|&&i| fragment_ct(i) > 1).collect();
+        assert!(
+            multi_fragment.windows(2).all(|w| w[0] < w[1]),
+            "{received:?}"
+        );
+
+        lost += MESSAGE_CT - received.len();
+    }
+
+    assert!(lost > 0);
+}
+
+#[test]
+fn transfer_with_mixed_faults_loses_messages_but_never_mixes_them_up() {
+    for seed in 0..8 {
+        let mut received = transfer(
+            FaultConfig {
+                frame_drop_rate: 300,
+                frame_duplicate_rate: 300,
+                frame_delay_rate: 300,
+                ..Default::default()
+            },
+            seed * 10,
+        );
+
+        // Every message received was sent whole, which transfer checks, but some are lost.
+        received.sort();
+        received.dedup();
+        assert!(!received.is_empty() && received.len() < MESSAGE_CT);
+    }
+}
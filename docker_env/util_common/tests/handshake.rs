@@ -0,0 +1,185 @@
+//! Noise XX handshakes between an initiator and a responder, each running on its own thread.
+
+mod common;
+
+use std::{thread, time::Duration};
+
+use common::{wire, StdTimer, WireRx, WireTx};
+use k256::{NonZeroScalar, PublicKey, SecretKey};
+use ucsc_ectf_util_common::communication::{
+    self,
+    fault_injection::SeededRng,
+    handshake::{NoiseHandshake, NoiseSession},
+    lower_layers::crypto::SEQUENCED_METADATA_SIZE,
+    CommunicationError, RxChannel, TxChannel,
+};
+
+/// The time to wait for each handshake message.
+const TIMEOUT: Duration = Duration::from_millis(500);
+
+type Session = NoiseSession<WireRx, WireTx, SeededRng>;
+
+/// Creates a static key from a seed byte.
+fn static_key(seed: u8) -> SecretKey {
+    SecretKey::from(NonZeroScalar::try_from(&[seed; 32][..]).unwrap())
+}
+
+/// The parameters of one party in a handshake.
+struct Party<'a> {
+    static_key: &'a SecretKey,
+    prologue: &'a [u8],
+    accepts: Option<&'a PublicKey>,
+}
+
+impl<'a> Party<'a> {
+    fn new(static_key: &'a SecretKey) -> Self {
+        Self {
+            static_key,
+            prologue: b"ucsc-ectf test",
+            accepts: None,
+        }
+    }
+
+    /// Gets a callback accepting only the key this party expects, or any key if it doesn't expect one.
+    fn verify(&self) -> impl FnOnce(&PublicKey) -> bool + '_ {
+        |key| self.accepts.is_none_or(|accepts| key == accepts)
+    }
+}
+
+/// Runs a handshake between ``initiator`` and ``responder``, returning the result of each side.
+fn handshake(
+    initiator: Party,
+    responder: Party,
+) -> (
+    communication::Result<Session>,
+    communication::Result<Session>,
+) {
+    let (initiator_tx, responder_rx) = wire();
+    let (responder_tx, initiator_rx) = wire();
+
+    thread::scope(|s| {
+        let responder = s.spawn(|| {
+            NoiseHandshake::new(responder.static_key, SeededRng::new(2))
+                .with_prologue(responder.prologue)
+                .respond(
+                    responder_rx,
+                    responder_tx,
+                    &mut StdTimer::new(TIMEOUT),
+                    responder.verify(),
+                )
+        });
+
+        let initiator = NoiseHandshake::new(initiator.static_key, SeededRng::new(1))
+            .with_prologue(initiator.prologue)
+            .initiate(
+                initiator_rx,
+                initiator_tx,
+                &mut StdTimer::new(TIMEOUT),
+                initiator.verify(),
+            );
+
+        (initiator, responder.join().unwrap())
+    })
+}
+
+/// Sends ``msg`` from ``from`` to ``to`` through their session channels.
+fn send_through(from: &mut Session, to: &mut Session, msg: &[u8]) {
+    let mut dest = [0; 64 + SEQUENCED_METADATA_SIZE];
+
+    from.tx_channel.send(&mut msg.to_owned()).unwrap();
+
+    let len = to
+        .rx_channel
+        .recv_with_timeout(&mut dest, &mut StdTimer::new(TIMEOUT))
+        .unwrap();
+    assert_eq!(&dest[..len], msg);
+}
+
+#[test]
+fn handshake_establishes_a_session() {
+    let initiator_key = static_key(1);
+    let responder_key = static_key(2);
+    let initiator_public_key = initiator_key.public_key();
+    let responder_public_key = responder_key.public_key();
+
+    let (initiator, responder) = handshake(
+        Party {
+            accepts: Some(&responder_public_key),
+            ..Party::new(&initiator_key)
+        },
+        Party {
+            accepts: Some(&initiator_public_key),
+            ..Party::new(&responder_key)
+        },
+    );
+    let mut initiator = initiator.unwrap();
+    let mut responder = responder.unwrap();
+
+    assert_eq!(initiator.remote_static_key, responder_public_key);
+    assert_eq!(responder.remote_static_key, initiator_public_key);
+    assert_eq!(initiator.handshake_hash, responder.handshake_hash);
+
+    // Each direction has its own key, and both directions work.
+    send_through(&mut initiator, &mut responder, b"hello from the initiator");
+    send_through(&mut responder, &mut initiator, b"hello from the responder");
+    send_through(&mut initiator, &mut responder, b"and again");
+}
+
+#[test]
+fn mismatched_prologues_fail_authentication() {
+    let initiator_key = static_key(1);
+    let responder_key = static_key(2);
+
+    let (initiator, responder) = handshake(
+        Party::new(&initiator_key),
+        Party {
+            prologue: b"another protocol",
+            ..Party::new(&responder_key)
+        },
+    );
+
+    assert_eq!(
+        initiator.err(),
+        Some(CommunicationError::AuthenticationFailure)
+    );
+    assert_eq!(responder.err(), Some(CommunicationError::Timeout));
+}
+
+#[test]
+fn initiator_rejects_responder_key() {
+    let initiator_key = static_key(1);
+    let responder_key = static_key(2);
+    let other_public_key = static_key(3).public_key();
+
+    let (initiator, responder) = handshake(
+        Party {
+            accepts: Some(&other_public_key),
+            ..Party::new(&initiator_key)
+        },
+        Party::new(&responder_key),
+    );
+
+    assert_eq!(initiator.err(), Some(CommunicationError::HandshakeFailure));
+
+    // The initiator never sends its static key, so the responder times out.
+    assert_eq!(responder.err(), Some(CommunicationError::Timeout));
+}
+
+#[test]
+fn responder_rejects_initiator_key() {
+    let initiator_key = static_key(1);
+    let responder_key = static_key(2);
+    let other_public_key = static_key(3).public_key();
+
+    let (initiator, responder) = handshake(
+        Party::new(&initiator_key),
+        Party {
+            accepts: Some(&other_public_key),
+            ..Party::new(&responder_key)
+        },
+    );
+
+    // The initiator finishes before the responder checks its key.
+    assert!(initiator.is_ok());
+    assert_eq!(responder.err(), Some(CommunicationError::HandshakeFailure));
+}
@@ -0,0 +1,433 @@
+//! Round trips through a loopback pair for each framing protocol and the crypto layer on top of them.
+
+use core::{num::NonZeroU32, time::Duration};
+
+use ucsc_ectf_util_common::{
+    communication::{
+        self,
+        buffered::BufferedRxChannel,
+        fault_injection::SeededRng,
+        loopback::{LoopbackPipe, LoopbackRxChannel},
+        lower_layers::{
+            crypto::{
+                PaddingPolicy, XChacha20Poly1305RxChannel, XChacha20Poly1305TxChannel,
+                MAX_RATCHET_EPOCH_SKIP, METADATA_SIZE,
+            },
+            framing::{
+                crcframing::{CHECKSUM_SIZE, HEADER_SIZE, SYNC_BYTES},
+                BogoFraming, CobsFraming, CrcFraming, FramingProtocol,
+            },
+        },
+        CommunicationError, RxChannel, TxChannel,
+    },
+    timer::MockClock,
+};
+
+const PIPE_SIZE: usize = 4096;
+
+/// The lengths sent in every round trip, which include both sides of the COBS block boundaries.
+const LENGTHS: [usize; 10] = [1, 2, 100, 253, 254, 255, 256, 508, 509, 1000];
+
+/// The index of the first byte of the message in a CRC frame.
+const CRC_MESSAGE_START: usize = SYNC_BYTES.len() + HEADER_SIZE;
+
+/// Creates a message of ``len`` bytes covering every byte value, including NULL.
+fn message(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 7) as u8).collect()
+}
+
+/// Receives one message from ``rx``, timing out after 100 polls without receiving a byte.
+fn recv<R: RxChannel>(rx: &mut R, dest: &mut [u8]) -> communication::Result<usize> {
+    let clock = MockClock::new_with_step(Duration::from_millis(1));
+
+    rx.recv_with_data_timeout(dest, &mut clock.timer(Duration::from_millis(100)))
+}
+
+/// Sends ``msgs`` through a loopback pair using the framing protocol ``F`` and receives them in order.
+fn round_trip<F: FramingProtocol>(msgs: &[Vec<u8>]) {
+    let pipe = LoopbackPipe::<PIPE_SIZE>::new();
+    let (mut tx, mut rx) = pipe.split::<F>();
+    let mut dest = [0; PIPE_SIZE];
+
+    for msg in msgs {
+        tx.send(&mut msg.clone()).unwrap();
+
+        let len = recv(&mut rx, &mut dest).unwrap();
+        assert_eq!(&dest[..len], &msg[..]);
+        assert!(pipe.is_empty());
+    }
+}
+
+#[test]
+fn bogoframing_round_trip() {
+    round_trip::<BogoFraming>(&LENGTHS.map(message));
+}
+
+#[test]
+fn cobsframing_round_trip() {
+    round_trip::<CobsFraming>(&LENGTHS.map(message));
+}
+
+#[test]
+fn crcframing_round_trip() {
+    round_trip::<CrcFraming>(&LENGTHS.map(message));
+}
+
+#[test]
+fn back_to_back_frames_are_received_in_order() {
+    let pipe = LoopbackPipe::<PIPE_SIZE>::new();
+    let (mut tx, mut rx) = pipe.split::<CobsFraming>();
+    let mut dest = [0; 64];
+
+    for i in 0..5 {
+        tx.send(&mut [i; 10]).unwrap();
+    }
+
+    for i in 0..5 {
+        let len = recv(&mut rx, &mut dest).unwrap();
+        assert_eq!(&dest[..len], &[i; 10]);
+    }
+
+    assert_eq!(recv(&mut rx, &mut dest), Err(CommunicationError::Timeout));
+}
+
+#[test]
+fn cobsframing_block_boundary() {
+    // A block holds up to 254 bytes that aren't NULL, so messages around that length without any NULL
+    // bytes, or with a NULL byte right at the end of a block, exercise the full block code.
+    let mut msgs = Vec::new();
+
+    for len in [253, 254, 255, 508, 509] {
+        msgs.push(vec![0xAA; len]);
+
+        for null_at in [252, 253, 254] {
+            let mut msg = vec![0xAA; len];
+
+            if let Some(byte) = msg.get_mut(null_at) {
+                *byte = 0;
+            }
+
+            msgs.push(msg);
+        }
+    }
+
+    for msg in &msgs {
+        let pipe = LoopbackPipe::<PIPE_SIZE>::new();
+        let (mut tx, mut rx) = pipe.split::<CobsFraming>();
+
+        tx.send(&mut msg.clone()).unwrap();
+
+        // COBS adds at most one byte for every 254 bytes, plus the code byte and two NULL delimiters.
+        assert!(pipe.len() <= msg.len() + msg.len() / 254 + 3);
+
+        let mut dest = [0; PIPE_SIZE];
+        let len = recv(&mut rx, &mut dest).unwrap();
+        assert_eq!(&dest[..len], &msg[..]);
+    }
+}
+
+#[test]
+fn cobsframing_cut_short_frame_keeps_next_frame() {
+    let pipe = LoopbackPipe::<PIPE_SIZE>::new();
+    let (mut tx, mut rx) = pipe.split::<CobsFraming>();
+    let mut dest = [0; 64];
+
+    tx.send(&mut [0xAA; 10]).unwrap();
+    tx.send(&mut [0xBB; 10]).unwrap();
+
+    // Turn a byte in the middle of the first block into a NULL byte, cutting the first frame short.
+    assert!(pipe.corrupt_byte(5, 0xAA));
+
+    assert_eq!(
+        recv(&mut rx, &mut dest),
+        Err(CommunicationError::CorruptFrame)
+    );
+
+    let len = recv(&mut rx, &mut dest).unwrap();
+    assert_eq!(&dest[..len], &[0xBB; 10]);
+}
+
+#[test]
+fn crcframing_corrupt_message() {
+    let pipe = LoopbackPipe::<PIPE_SIZE>::new();
+    let (mut tx, mut rx) = pipe.split::<CrcFraming>();
+    let mut dest = [0; 64];
+
+    tx.send(&mut b"first message".to_owned()).unwrap();
+    tx.send(&mut b"second message".to_owned()).unwrap();
+    assert!(pipe.corrupt_byte(CRC_MESSAGE_START + 3, 0x01));
+
+    assert_eq!(
+        recv(&mut rx, &mut dest),
+        Err(CommunicationError::CorruptFrame)
+    );
+
+    let len = recv(&mut rx, &mut dest).unwrap();
+    assert_eq!(&dest[..len], b"second message");
+}
+
+#[test]
+fn crcframing_corrupt_checksum() {
+    let pipe = LoopbackPipe::<PIPE_SIZE>::new();
+    let (mut tx, mut rx) = pipe.split::<CrcFraming>();
+    let mut dest = [0; 64];
+
+    tx.send(&mut b"message".to_owned()).unwrap();
+    assert!(pipe.corrupt_byte(pipe.len() - CHECKSUM_SIZE, 0x80));
+
+    assert_eq!(
+        recv(&mut rx, &mut dest),
+        Err(CommunicationError::CorruptFrame)
+    );
+}
+
+#[test]
+fn crcframing_corrupt_length_is_rejected_at_the_header() {
+    let pipe = LoopbackPipe::<PIPE_SIZE>::new();
+    let (mut tx, mut rx) = pipe.split::<CrcFraming>();
+    let mut dest = [0; PIPE_SIZE];
+
+    tx.send(&mut b"first message".to_owned()).unwrap();
+    tx.send(&mut b"second message".to_owned()).unwrap();
+
+    // Make the first frame claim to be much longer than it is. Without a header checksum, the second
+    // frame would be read as part of the first.
+    assert!(pipe.corrupt_byte(SYNC_BYTES.len() + 2, 0x0F));
+
+    assert_eq!(
+        recv(&mut rx, &mut dest),
+        Err(CommunicationError::CorruptFrame)
+    );
+
+    let len = recv(&mut rx, &mut dest).unwrap();
+    assert_eq!(&dest[..len], b"second message");
+}
+
+#[test]
+fn buffer_too_small() {
+    fn check<F: FramingProtocol>() {
+        let pipe = LoopbackPipe::<PIPE_SIZE>::new();
+        let (mut tx, mut rx) = pipe.split::<F>();
+        let mut dest = [0; 8];
+
+        tx.send(&mut [1; 9]).unwrap();
+
+        assert_eq!(
+            recv(&mut rx, &mut dest),
+            Err(CommunicationError::BufferTooSmall)
+        );
+    }
+
+    check::<BogoFraming>();
+    check::<CobsFraming>();
+    check::<CrcFraming>();
+}
+
+#[test]
+fn send_larger_than_pipe_fails() {
+    let pipe = LoopbackPipe::<64>::new();
+    let (mut tx, _) = pipe.split::<CobsFraming>();
+
+    assert_eq!(tx.send(&mut [1; 100]), Err(CommunicationError::SendError));
+}
+
+#[test]
+fn xchacha20poly1305_over_loopback() {
+    fn check<F: FramingProtocol>() {
+        let pipe = LoopbackPipe::<PIPE_SIZE>::new();
+        let (tx, rx) = pipe.split::<F>();
+        let key = [7; 32].into();
+        let mut tx = XChacha20Poly1305TxChannel::new(tx, SeededRng::new(1), &key);
+        let mut rx = XChacha20Poly1305RxChannel::new(rx, &key);
+        let mut dest = [0; PIPE_SIZE];
+
+        for len in LENGTHS {
+            let msg = message(len);
+
+            tx.send(&mut msg.clone()).unwrap();
+
+            let len = recv(&mut rx, &mut dest).unwrap();
+            assert_eq!(&dest[..len], &msg[..]);
+        }
+
+        // Corrupted ciphertext is rejected by the crypto layer.
+        tx.send(&mut b"tampered".to_owned()).unwrap();
+        assert!(pipe.corrupt_byte(pipe.len() / 2, 0x01));
+        assert!(recv(&mut rx, &mut dest).is_err());
+    }
+
+    check::<BogoFraming>();
+    check::<CobsFraming>();
+    check::<CrcFraming>();
+}
+
+#[test]
+fn padding_is_capped_at_the_receive_buffer() {
+    const RECV_BUFFER_SIZE: usize = 300;
+    const MAX_PADDED_LEN: usize = RECV_BUFFER_SIZE - METADATA_SIZE;
+
+    let pipe = LoopbackPipe::<PIPE_SIZE>::new();
+    let (tx, rx) = pipe.split::<CobsFraming>();
+    let key = [7; 32].into();
+    let bucket = PaddingPolicy::Bucket(256.try_into().unwrap());
+    let mut tx = XChacha20Poly1305TxChannel::new(tx, SeededRng::new(1), &key)
+        .with_padding(bucket)
+        .with_max_padded_len(MAX_PADDED_LEN);
+    let mut rx = XChacha20Poly1305RxChannel::new(rx, &key).with_padding();
+    let mut dest = [0; RECV_BUFFER_SIZE];
+
+    // Messages past the first bucket are padded to the receive buffer instead of the next bucket.
+    for len in [1, 255, 256, MAX_PADDED_LEN - 1] {
+        let msg = message(len);
+
+        tx.send(&mut msg.clone()).unwrap();
+
+        let len = recv(&mut rx, &mut dest).unwrap();
+        assert_eq!(&dest[..len], &msg[..]);
+    }
+
+    // A message without space for the padding marker can't be sent.
+    assert_eq!(
+        tx.send(&mut message(MAX_PADDED_LEN)),
+        Err(CommunicationError::SendError)
+    );
+    assert!(pipe.is_empty());
+}
+
+#[test]
+fn buffered_channel_is_sized_from_the_largest_message() {
+    type Rx = XChacha20Poly1305RxChannel<LoopbackRxChannel<'static, PIPE_SIZE, CobsFraming>>;
+
+    const MAX_MESSAGE_SIZE: usize = 200;
+    const BUFFER_SIZE: usize = MAX_MESSAGE_SIZE + <Rx as RxChannel>::METADATA_SIZE;
+
+    let pipe = LoopbackPipe::<PIPE_SIZE>::new();
+    let (tx, rx) = pipe.split::<CobsFraming>();
+    let key = [7; 32].into();
+    let interval = NonZeroU32::new(2).unwrap();
+
+    // The metadata size covers the most metadata any configuration of the channel sends.
+    let mut tx = XChacha20Poly1305TxChannel::new_with_sequence_numbers(tx, SeededRng::new(1), &key)
+        .with_ratchet_interval(interval);
+    let mut rx = BufferedRxChannel::<_, BUFFER_SIZE>::new(
+        XChacha20Poly1305RxChannel::new_with_replay_protection(rx, &key).with_ratchet_epochs(),
+    );
+    let clock = MockClock::new_with_step(Duration::from_millis(1));
+
+    for len in [1, MAX_MESSAGE_SIZE] {
+        let msg = message(len);
+
+        tx.send(&mut msg.clone()).unwrap();
+
+        let received = rx
+            .recv_with_data_timeout(&mut clock.timer(Duration::from_millis(100)))
+            .unwrap();
+        assert_eq!(received, &msg[..]);
+    }
+}
+
+#[test]
+fn ratcheting_channels_catch_up_after_lost_messages() {
+    let pipe = LoopbackPipe::<PIPE_SIZE>::new();
+    let (tx, rx) = pipe.split::<CobsFraming>();
+    let key = [7; 32].into();
+    let interval = NonZeroU32::new(2).unwrap();
+    let mut tx = XChacha20Poly1305TxChannel::new_with_sequence_numbers(tx, SeededRng::new(1), &key)
+        .with_ratchet_interval(interval);
+    let mut rx =
+        XChacha20Poly1305RxChannel::new_with_replay_protection(rx, &key).with_ratchet_epochs();
+    let mut dest = [0; 256];
+
+    for i in 0..20u8 {
+        tx.send(&mut [i; 10]).unwrap();
+
+        // Lose a few messages in a row, sometimes across several epochs.
+        if matches!(i, 1 | 4..=8 | 13) {
+            pipe.clear();
+            continue;
+        }
+
+        let len = recv(&mut rx, &mut dest).unwrap();
+        assert_eq!(&dest[..len], &[i; 10]);
+    }
+}
+
+#[test]
+fn ratcheting_channels_reject_messages_from_erased_or_distant_epochs() {
+    let pipe = LoopbackPipe::<PIPE_SIZE>::new();
+    let (tx, rx) = pipe.split::<CobsFraming>();
+    let key = [7; 32].into();
+    let interval = NonZeroU32::new(1).unwrap();
+    let mut tx = XChacha20Poly1305TxChannel::new(tx, SeededRng::new(1), &key)
+        .with_ratchet_interval(interval);
+    let mut rx = XChacha20Poly1305RxChannel::new(rx, &key).with_ratchet_epochs();
+    let mut dest = [0; 256];
+
+    // Lose more epochs than the receiver catches up by.
+    for _ in 0..=MAX_RATCHET_EPOCH_SKIP {
+        tx.send(&mut [1; 10]).unwrap();
+        pipe.clear();
+    }
+
+    tx.send(&mut [2; 10]).unwrap();
+    assert_eq!(
+        recv(&mut rx, &mut dest),
+        Err(CommunicationError::AuthenticationFailure)
+    );
+
+    // A message from an epoch the receiver already left is rejected too.
+    let (tx, rx) = pipe.split::<CobsFraming>();
+    let mut tx = XChacha20Poly1305TxChannel::new(tx, SeededRng::new(2), &key)
+        .with_ratchet_interval(interval);
+    let mut rx = XChacha20Poly1305RxChannel::new(rx, &key).with_ratchet_epochs();
+
+    // The receiver only leaves an epoch once it receives a message from a later one.
+    for i in [3, 4] {
+        tx.send(&mut [i; 10]).unwrap();
+        let len = recv(&mut rx, &mut dest).unwrap();
+        assert_eq!(&dest[..len], &[i; 10]);
+    }
+
+    let mut stale_tx =
+        XChacha20Poly1305TxChannel::new(pipe.split::<CobsFraming>().0, SeededRng::new(3), &key)
+            .with_ratchet_interval(interval);
+    stale_tx.send(&mut [5; 10]).unwrap();
+    assert_eq!(
+        recv(&mut rx, &mut dest),
+        Err(CommunicationError::AuthenticationFailure)
+    );
+}
+
+#[test]
+fn replayed_messages_dont_ratchet_the_receiver_early() {
+    let pipe = LoopbackPipe::<PIPE_SIZE>::new();
+    let (tx, rx) = pipe.split::<CobsFraming>();
+    let key = [7; 32].into();
+    let interval = NonZeroU32::new(2).unwrap();
+    let mut tx = XChacha20Poly1305TxChannel::new(tx, SeededRng::new(1), &key)
+        .with_ratchet_interval(interval);
+    let mut rx = XChacha20Poly1305RxChannel::new(rx, &key).with_ratchet_epochs();
+    let mut dest = [0; 256];
+
+    tx.send(&mut [1; 10]).unwrap();
+    let len = recv(&mut rx, &mut dest).unwrap();
+    assert_eq!(&dest[..len], &[1; 10]);
+
+    // A channel with the same key and RNG seed sends exactly the same bytes, replaying the first message
+    // enough times to reach the ratchet interval.
+    for _ in 0..interval.get() {
+        let mut replay_tx =
+            XChacha20Poly1305TxChannel::new(pipe.split::<CobsFraming>().0, SeededRng::new(1), &key)
+                .with_ratchet_interval(interval);
+        replay_tx.send(&mut [1; 10]).unwrap();
+        let len = recv(&mut rx, &mut dest).unwrap();
+        assert_eq!(&dest[..len], &[1; 10]);
+    }
+
+    // The receiver is still in step with the sender, both in its epoch and after it ratchets.
+    for i in 2..6 {
+        tx.send(&mut [i; 10]).unwrap();
+        let len = recv(&mut rx, &mut dest).unwrap();
+        assert_eq!(&dest[..len], &[i; 10]);
+    }
+}
@@ -0,0 +1,220 @@
+//! Messages encoded and decoded with the layouts of older protocol versions, and the tagged form messages
+//! are signed in.
+
+use core::time::Duration;
+
+use ucsc_ectf_util_common::{
+    communication::{
+        loopback::LoopbackPipe, lower_layers::framing::CobsFraming, message_channel::MessageChannel,
+    },
+    messages::{
+        from_envelope_bytes, heapless::Vec, to_envelope_slice, EnableFeatureMessage, HostToolAck,
+        PackagedFeatureSigned, PackagedFeatureUnsigned, RevocationListUnsigned, SignatureTag,
+        SignedMessage, Uart0Message, Uart1Message, UnlockChallengeResponse, MIN_PROTOCOL_VERSION,
+        PROTOCOL_VERSION, UNSIGNED_FEATURES_VERSION,
+    },
+    timer::MockClock,
+};
+
+const SIGNATURE: &[u8] = &[0xAA, 0xBB, 0xCC];
+
+fn packaged_feature(not_after: Option<u64>) -> PackagedFeatureSigned<'static> {
+    PackagedFeatureSigned {
+        packaged_feature: PackagedFeatureUnsigned {
+            car_id: 7,
+            feature_number: 2,
+            not_before: None,
+            not_after,
+        },
+        signature: SIGNATURE,
+    }
+}
+
+fn unlock_challenge_response(not_after: Option<Option<u64>>) -> Uart1Message<'static> {
+    let mut features = Vec::new();
+
+    if let Some(not_after) = not_after {
+        features
+            .push(packaged_feature(not_after).packaged_feature)
+            .unwrap();
+    }
+
+    Uart1Message::UnlockChallengeResponse(UnlockChallengeResponse {
+        car_id: 7,
+        challenge_response: [0x11; 16],
+        features,
+    })
+}
+
+#[test]
+fn v1_enable_feature_request_has_no_validity_window() {
+    // Version 1, EnableFeatureRequest, car 7, feature 2, then the signature.
+    let bytes = [1, 0, 7, 2, 3, 0xAA, 0xBB, 0xCC];
+    let envelope = from_envelope_bytes::<Uart0Message>(&bytes).unwrap();
+
+    assert_eq!(envelope.version, 1);
+
+    let Uart0Message::EnableFeatureRequest(EnableFeatureMessage(feature)) = envelope.message else {
+        panic!("decoded the wrong message");
+    };
+
+    assert_eq!(feature.packaged_feature.car_id, 7);
+    assert_eq!(feature.packaged_feature.feature_number, 2);
+    assert!(!feature.packaged_feature.has_validity_window());
+    assert_eq!(feature.signature, SIGNATURE);
+}
+
+#[test]
+fn unlock_challenge_responses_without_features_round_trip_through_every_version() {
+    let mut buf = [0; 128];
+
+    for version in MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION {
+        let bytes = to_envelope_slice(&unlock_challenge_response(None), version, &mut buf).unwrap();
+        let envelope = from_envelope_bytes::<Uart1Message>(bytes).unwrap();
+
+        assert_eq!(envelope.version, version);
+
+        let Uart1Message::UnlockChallengeResponse(response) = envelope.message else {
+            panic!("decoded the wrong message");
+        };
+
+        assert_eq!(response.car_id, 7);
+        assert!(response.features.is_empty());
+    }
+}
+
+#[test]
+fn features_are_only_sent_to_peers_that_take_them_unsigned() {
+    let mut buf = [0; 128];
+
+    for version in MIN_PROTOCOL_VERSION..UNSIGNED_FEATURES_VERSION {
+        for not_after in [None, Some(100)] {
+            assert!(to_envelope_slice(
+                &unlock_challenge_response(Some(not_after)),
+                version,
+                &mut buf
+            )
+            .is_err());
+        }
+    }
+
+    let bytes = to_envelope_slice(
+        &unlock_challenge_response(Some(Some(100))),
+        PROTOCOL_VERSION,
+        &mut buf,
+    )
+    .unwrap();
+    let Uart1Message::UnlockChallengeResponse(response) =
+        from_envelope_bytes::<Uart1Message>(bytes).unwrap().message
+    else {
+        panic!("decoded the wrong message");
+    };
+
+    assert_eq!(response.features.len(), 1);
+    assert_eq!(response.features[0].not_after, Some(100));
+}
+
+#[test]
+fn signed_features_from_older_peers_are_decoded_without_their_signatures() {
+    // UnlockChallengeResponse, car 7, the challenge, then one feature for car 7, feature 2.
+    let mut v1_bytes = vec![1, 2, 7];
+    v1_bytes.extend_from_slice(&[0x11; 16]);
+    v1_bytes.extend_from_slice(&[1, 7, 2, 3, 0xAA, 0xBB, 0xCC]);
+
+    // Version 2 features also have a validity window, which ends at 100 here.
+    let mut v2_bytes = vec![2, 2, 7];
+    v2_bytes.extend_from_slice(&[0x11; 16]);
+    v2_bytes.extend_from_slice(&[1, 7, 2, 0, 1, 100, 3, 0xAA, 0xBB, 0xCC]);
+
+    for (bytes, not_after) in [(v1_bytes, None), (v2_bytes, Some(100))] {
+        let Uart1Message::UnlockChallengeResponse(response) =
+            from_envelope_bytes::<Uart1Message>(&bytes).unwrap().message
+        else {
+            panic!("decoded the wrong message");
+        };
+
+        assert_eq!(response.features.len(), 1);
+        assert_eq!(response.features[0].car_id, 7);
+        assert_eq!(response.features[0].feature_number, 2);
+        assert_eq!(response.features[0].not_after, not_after);
+    }
+}
+
+#[test]
+fn message_channel_records_the_version_of_each_message_received() {
+    let pipe = LoopbackPipe::<256>::new();
+    let (mut tx, mut rx) = pipe.split::<CobsFraming>();
+    let mut tx = MessageChannel::<_, Uart0Message, 128>::new(&mut tx);
+    let mut rx = MessageChannel::<_, Uart0Message, 128>::new(&mut rx);
+    let clock = MockClock::new_with_step(Duration::from_millis(1));
+
+    assert_eq!(rx.received_version(), None);
+
+    for version in [1, PROTOCOL_VERSION] {
+        tx.set_version(version);
+        tx.send(&Uart0Message::PairingPinResponse(HostToolAck(true)))
+            .unwrap();
+
+        assert!(matches!(
+            rx.recv_with_data_timeout(&mut clock.timer(Duration::from_millis(100))),
+            Ok(Uart0Message::PairingPinResponse(HostToolAck(true)))
+        ));
+        assert_eq!(rx.received_version(), Some(version));
+    }
+}
+
+#[test]
+fn v1_encoding_drops_the_empty_validity_window() {
+    let feature = packaged_feature(None).packaged_feature;
+    let mut buf = [0; PackagedFeatureUnsigned::MAX_SIZE];
+    let mut v1_buf = [0; PackagedFeatureUnsigned::MAX_SIZE];
+    let bytes = postcard::to_slice(&feature, &mut buf).unwrap();
+
+    assert_eq!(feature.to_v1_slice(&mut v1_buf).unwrap(), &[7, 2]);
+    assert_eq!(bytes, &[7, 2, 0, 0]);
+    assert!(packaged_feature(Some(100))
+        .packaged_feature
+        .to_v1_slice(&mut v1_buf)
+        .is_none());
+}
+
+#[test]
+fn signed_messages_start_with_their_tag() {
+    let feature = packaged_feature(None).packaged_feature;
+    let revocation_list = RevocationListUnsigned {
+        car_id: 7,
+        sequence: 2,
+        revoked: Vec::new(),
+    };
+    let mut buf = [0; 64];
+    let mut tagged_buf = [0; 64];
+
+    for (bytes, tagged_bytes, tag) in [
+        (
+            postcard::to_slice(&feature, &mut buf).unwrap().to_vec(),
+            postcard::to_slice(&feature.tagged(), &mut tagged_buf)
+                .unwrap()
+                .to_vec(),
+            SignatureTag::PackagedFeature,
+        ),
+        (
+            postcard::to_slice(&revocation_list, &mut buf)
+                .unwrap()
+                .to_vec(),
+            postcard::to_slice(&revocation_list.tagged(), &mut tagged_buf)
+                .unwrap()
+                .to_vec(),
+            SignatureTag::RevocationList,
+        ),
+    ] {
+        assert_eq!(tagged_bytes.len(), SignatureTag::SIZE + bytes.len());
+        assert_eq!(tagged_bytes[0], tag as u8);
+        assert_eq!(&tagged_bytes[SignatureTag::SIZE..], &bytes[..]);
+    }
+
+    // The same fields signed as a different type of message give different bytes.
+    assert_ne!(
+        postcard::to_slice(&feature.tagged(), &mut buf).unwrap()[0],
+        postcard::to_slice(&revocation_list.tagged(), &mut tagged_buf).unwrap()[0]
+    );
+}
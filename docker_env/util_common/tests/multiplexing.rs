@@ -0,0 +1,185 @@
+//! Streams of multiplexers on both ends of a loopback pair, including streams with their own keys.
+
+use core::time::Duration;
+
+use ucsc_ectf_util_common::{
+    communication::{
+        self,
+        fault_injection::SeededRng,
+        loopback::{LoopbackPipe, LoopbackRxChannel, LoopbackTxChannel},
+        lower_layers::{
+            crypto::{XChacha20Poly1305RxChannel, XChacha20Poly1305TxChannel},
+            framing::CobsFraming,
+        },
+        multiplexing::Multiplexer,
+        CommunicationError, RxChannel, TxChannel,
+    },
+    timer::MockClock,
+};
+
+const PIPE_SIZE: usize = 4096;
+
+/// The number of streams of each multiplexer.
+const STREAMS: usize = 3;
+
+/// The size of the buffers of each multiplexer.
+const BUF_SIZE: usize = 256;
+
+type TxMux<'a> = Multiplexer<LoopbackTxChannel<'a, PIPE_SIZE, CobsFraming>, STREAMS, BUF_SIZE>;
+type RxMux<'a> = Multiplexer<LoopbackRxChannel<'a, PIPE_SIZE, CobsFraming>, STREAMS, BUF_SIZE>;
+
+/// Creates a multiplexer on each end of ``pipe``.
+fn muxes(pipe: &LoopbackPipe<PIPE_SIZE>) -> (TxMux<'_>, RxMux<'_>) {
+    let (tx, rx) = pipe.split::<CobsFraming>();
+
+    (Multiplexer::new(tx), Multiplexer::new(rx))
+}
+
+/// Sends ``msg`` on the stream ``id`` of ``mux``.
+fn send(mux: &TxMux, id: u8, msg: &[u8]) {
+    mux.stream(id).unwrap().send(&mut msg.to_vec()).unwrap();
+}
+
+/// Receives one message from ``rx``, timing out after 100 polls without receiving a byte.
+fn recv<R: RxChannel>(rx: &mut R, dest: &mut [u8]) -> communication::Result<usize> {
+    let clock = MockClock::new_with_step(Duration::from_millis(1));
+
+    rx.recv_with_data_timeout(dest, &mut clock.timer(Duration::from_millis(100)))
+}
+
+/// Receives one message from the stream ``id`` of ``mux``.
+fn recv_stream(mux: &RxMux, id: u8) -> communication::Result<Vec<u8>> {
+    let mut dest = [0; BUF_SIZE];
+    let len = recv(&mut mux.stream(id).unwrap(), &mut dest)?;
+
+    Ok(dest[..len].to_vec())
+}
+
+#[test]
+fn interleaved_messages_go_to_their_own_streams() {
+    let pipe = LoopbackPipe::<PIPE_SIZE>::new();
+    let (tx, rx) = muxes(&pipe);
+
+    send(&tx, 0, b"first on 0");
+    send(&tx, 1, b"first on 1");
+    send(&tx, 2, b"first on 2");
+    send(&tx, 0, b"second on 0");
+
+    // Receiving on the last stream holds the messages received for the others on the way.
+    assert_eq!(recv_stream(&rx, 2).unwrap(), b"first on 2");
+    assert_eq!(recv_stream(&rx, 1).unwrap(), b"first on 1");
+    assert_eq!(recv_stream(&rx, 0).unwrap(), b"first on 0");
+    assert_eq!(recv_stream(&rx, 0).unwrap(), b"second on 0");
+    assert!(pipe.is_empty());
+
+    for id in 0..STREAMS as u8 {
+        assert_eq!(recv_stream(&rx, id), Err(CommunicationError::Timeout));
+    }
+}
+
+#[test]
+fn messages_for_a_stream_already_holding_one_are_dropped() {
+    let pipe = LoopbackPipe::<PIPE_SIZE>::new();
+    let (tx, rx) = muxes(&pipe);
+
+    send(&tx, 0, b"held");
+    send(&tx, 0, b"dropped");
+    send(&tx, 1, b"wanted");
+
+    assert_eq!(recv_stream(&rx, 1).unwrap(), b"wanted");
+    assert_eq!(recv_stream(&rx, 0).unwrap(), b"held");
+    assert_eq!(recv_stream(&rx, 0), Err(CommunicationError::Timeout));
+
+    // Once the held message is received, the stream holds the next message again.
+    send(&tx, 0, b"held again");
+    send(&tx, 1, b"wanted again");
+
+    assert_eq!(recv_stream(&rx, 1).unwrap(), b"wanted again");
+    assert_eq!(recv_stream(&rx, 0).unwrap(), b"held again");
+}
+
+#[test]
+fn messages_for_unknown_streams_are_dropped() {
+    let pipe = LoopbackPipe::<PIPE_SIZE>::new();
+    let (tx, rx) = muxes(&pipe);
+    let wide_tx = Multiplexer::<_, { STREAMS + 1 }, BUF_SIZE>::new(pipe.split::<CobsFraming>().0);
+
+    assert!(rx.stream(STREAMS as u8).is_none());
+
+    wide_tx
+        .stream(STREAMS as u8)
+        .unwrap()
+        .send(&mut b"unknown".to_vec())
+        .unwrap();
+    send(&tx, 0, b"known");
+
+    assert_eq!(recv_stream(&rx, 0).unwrap(), b"known");
+
+    for id in 0..STREAMS as u8 {
+        assert_eq!(recv_stream(&rx, id), Err(CommunicationError::Timeout));
+    }
+
+    // Messages without a header are dropped too.
+    pipe.split::<CobsFraming>().0.send(&mut []).unwrap();
+    send(&tx, 0, b"after empty");
+
+    assert_eq!(recv_stream(&rx, 0).unwrap(), b"after empty");
+}
+
+#[test]
+fn buffer_too_small_keeps_the_message() {
+    let pipe = LoopbackPipe::<PIPE_SIZE>::new();
+    let (tx, rx) = muxes(&pipe);
+    let msg = [0x5A; 32];
+
+    // A message received directly and a message held for another stream are both kept.
+    send(&tx, 0, &msg);
+    send(&tx, 1, &msg);
+
+    for id in 0..2 {
+        let mut small = [0; 31];
+
+        assert_eq!(
+            recv(&mut rx.stream(id).unwrap(), &mut small),
+            Err(CommunicationError::BufferTooSmall)
+        );
+    }
+
+    assert_eq!(recv_stream(&rx, 1).unwrap(), msg);
+    assert_eq!(recv_stream(&rx, 0).unwrap(), msg);
+    assert!(pipe.is_empty());
+}
+
+#[test]
+fn streams_can_have_their_own_keys() {
+    let pipe = LoopbackPipe::<PIPE_SIZE>::new();
+    let (tx, rx) = muxes(&pipe);
+    let keys = [[1; 32].into(), [2; 32].into()];
+
+    let mut txs = [0, 1].map(|id| {
+        XChacha20Poly1305TxChannel::new(
+            tx.stream(id).unwrap(),
+            SeededRng::new(id as u64),
+            &keys[id as usize],
+        )
+    });
+    let mut rxs = [0, 1]
+        .map(|id| XChacha20Poly1305RxChannel::new(rx.stream(id).unwrap(), &keys[id as usize]));
+    let mut dest = [0; BUF_SIZE];
+
+    txs[0].send(&mut b"secret 0".to_vec()).unwrap();
+    txs[1].send(&mut b"secret 1".to_vec()).unwrap();
+
+    let len = recv(&mut rxs[1], &mut dest).unwrap();
+    assert_eq!(&dest[..len], b"secret 1");
+    let len = recv(&mut rxs[0], &mut dest).unwrap();
+    assert_eq!(&dest[..len], b"secret 0");
+
+    // A message sent on a stream with a different key can't be decrypted.
+    let mut wrong_key =
+        XChacha20Poly1305TxChannel::new(tx.stream(1).unwrap(), SeededRng::new(2), &keys[0]);
+    wrong_key.send(&mut b"wrong key".to_vec()).unwrap();
+
+    assert!(recv(&mut rxs[1], &mut dest).is_err());
+    assert!(pipe.is_empty());
+}
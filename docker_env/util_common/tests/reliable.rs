@@ -0,0 +1,212 @@
+//! Transfers through a pair of reliable channels with seeded faults injected in both directions.
+//!
+//! Stop-and-wait ARQ needs both ends to run at once, so each end runs on its own thread and the ends are
+//! connected by a thread-safe [`wire`] instead of a loopback pipe.
+
+mod common;
+
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::Duration,
+};
+
+use common::{wire, StdTimer, WireRx, WireTx};
+use ucsc_ectf_util_common::{
+    communication::{
+        self,
+        fault_injection::{FaultConfig, FaultyRxChannel, FaultyTxChannel},
+        reliable::{ReliableChannel, HEADER_SIZE},
+        CommunicationError, RxChannel, TxChannel,
+    },
+    timer::Timer,
+};
+
+/// The size of the buffers used by every channel.
+const BUF_SIZE: usize = 64;
+
+/// The number of messages sent in each transfer.
+const MESSAGE_CT: u8 = 50;
+
+/// The time to wait for an acknowledgement before retransmitting.
+const RETRANSMIT_TIMEOUT: Duration = Duration::from_millis(5);
+
+/// The number of times a message is retransmitted before giving up, which is high enough that no
+/// message is given up on at the fault rates tested.
+const MAX_RETRANSMITS: u8 = 100;
+
+/// One end of a pair of wires, injecting faults into the messages sent and received.
+struct Endpoint {
+    tx: FaultyTxChannel<WireTx, BUF_SIZE>,
+    rx: FaultyRxChannel<WireRx, BUF_SIZE>,
+}
+
+impl TxChannel for Endpoint {
+    fn send(&mut self, src: &mut [u8]) -> communication::Result<()> {
+        self.tx.send(src)
+    }
+}
+
+impl RxChannel for Endpoint {
+    fn recv_with_data_timeout<T: Timer>(
+        &mut self,
+        dest: &mut [u8],
+        timer: &mut T,
+    ) -> communication::Result<usize> {
+        self.rx.recv_with_data_timeout(dest, timer)
+    }
+
+    fn recv_with_timeout<T: Timer>(
+        &mut self,
+        dest: &mut [u8],
+        timer: &mut T,
+    ) -> communication::Result<usize> {
+        self.rx.recv_with_timeout(dest, timer)
+    }
+}
+
+/// Creates a pair of connected endpoints, each injecting the faults in ``config`` with its own seed
+/// derived from ``seed``.
+fn endpoints(config: FaultConfig, seed: u64) -> (Endpoint, Endpoint) {
+    let (a_tx, b_rx) = wire();
+    let (b_tx, a_rx) = wire();
+
+    let a = Endpoint {
+        tx: FaultyTxChannel::new(a_tx, config, seed),
+        rx: FaultyRxChannel::new(a_rx, config, seed + 1),
+    };
+    let b = Endpoint {
+        tx: FaultyTxChannel::new(b_tx, config, seed + 2),
+        rx: FaultyRxChannel::new(b_rx, config, seed + 3),
+    };
+
+    (a, b)
+}
+
+/// Creates the message with the given index, whose length and contents depend on the index.
+fn message(i: u8) -> Vec<u8> {
+    vec![i; 1 + i as usize % (BUF_SIZE - HEADER_SIZE)]
+}
+
+/// Sends [`MESSAGE_CT`] messages from one reliable channel to another with the faults in ``config``
+/// injected in both directions, checking that every message is received once and in order.
+fn transfer(config: FaultConfig, seed: u64) {
+    let (mut a, mut b) = endpoints(config, seed);
+    let sent_all = AtomicBool::new(false);
+
+    thread::scope(|s| {
+        s.spawn(|| {
+            let mut sender = ReliableChannel::<_, _, BUF_SIZE>::new(
+                &mut a,
+                StdTimer::new(RETRANSMIT_TIMEOUT),
+                MAX_RETRANSMITS,
+            );
+
+            for i in 0..MESSAGE_CT {
+                sender.send(&mut message(i)).unwrap();
+            }
+
+            sent_all.store(true, Ordering::Release);
+        });
+
+        let mut receiver = ReliableChannel::<_, _, BUF_SIZE>::new(
+            &mut b,
+            StdTimer::new(RETRANSMIT_TIMEOUT),
+            MAX_RETRANSMITS,
+        );
+        let mut dest = [0; BUF_SIZE];
+
+        for i in 0..MESSAGE_CT {
+            let len = receiver
+                .recv_with_timeout(&mut dest, &mut StdTimer::new(Duration::from_secs(10)))
+                .unwrap();
+            assert_eq!(&dest[..len], &message(i)[..]);
+        }
+
+        // Keep acknowledging retransmits until the sender has seen the last acknowledgement. Nothing
+        // else should be delivered.
+        while !sent_all.load(Ordering::Acquire) {
+            assert_eq!(
+                receiver.recv_with_timeout(&mut dest, &mut StdTimer::new(RETRANSMIT_TIMEOUT)),
+                Err(CommunicationError::Timeout)
+            );
+        }
+    });
+}
+
+#[test]
+fn transfer_without_faults() {
+    transfer(FaultConfig::default(), 1);
+}
+
+#[test]
+fn transfer_with_dropped_frames() {
+    for seed in 0..4 {
+        transfer(
+            FaultConfig {
+                frame_drop_rate: 2_500,
+                ..Default::default()
+            },
+            seed * 10,
+        );
+    }
+}
+
+#[test]
+fn transfer_with_duplicated_frames() {
+    for seed in 0..4 {
+        transfer(
+            FaultConfig {
+                frame_duplicate_rate: 3_000,
+                ..Default::default()
+            },
+            seed * 10,
+        );
+    }
+}
+
+#[test]
+fn transfer_with_delayed_frames() {
+    for seed in 0..4 {
+        transfer(
+            FaultConfig {
+                frame_delay_rate: 3_000,
+                ..Default::default()
+            },
+            seed * 10,
+        );
+    }
+}
+
+#[test]
+fn transfer_with_mixed_faults() {
+    for seed in 0..4 {
+        transfer(
+            FaultConfig {
+                frame_drop_rate: 1_000,
+                frame_duplicate_rate: 1_000,
+                frame_delay_rate: 1_000,
+                ..Default::default()
+            },
+            seed * 10,
+        );
+    }
+}
+
+#[test]
+fn send_gives_up_when_every_frame_is_dropped() {
+    let (mut a, _b) = endpoints(
+        FaultConfig {
+            frame_drop_rate: 10_000,
+            ..Default::default()
+        },
+        1,
+    );
+    let mut sender =
+        ReliableChannel::<_, _, BUF_SIZE>::new(&mut a, StdTimer::new(RETRANSMIT_TIMEOUT), 3);
+
+    assert_eq!(
+        sender.send(&mut message(1)),
+        Err(CommunicationError::SendError)
+    );
+}
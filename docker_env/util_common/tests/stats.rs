@@ -0,0 +1,201 @@
+//! Counters kept at the framing and crypto layers of a stack over a loopback pair, including the bytes
+//! discarded by each framing protocol while looking for a frame.
+
+use core::time::Duration;
+
+use ucsc_ectf_util_common::{
+    communication::{
+        self,
+        fault_injection::SeededRng,
+        loopback::LoopbackPipe,
+        lower_layers::{
+            crypto::{XChacha20Poly1305RxChannel, XChacha20Poly1305TxChannel, METADATA_SIZE},
+            framing::{BogoFraming, CobsFraming, CrcFraming, FramingProtocol},
+        },
+        stats::{ChannelStats, StatsChannel},
+        CommunicationError, RxChannel, TxChannel,
+    },
+    timer::MockClock,
+};
+
+const PIPE_SIZE: usize = 4096;
+
+/// Receives one message from ``rx``, timing out after 100 polls without receiving a byte.
+fn recv<R: RxChannel>(rx: &mut R, dest: &mut [u8]) -> communication::Result<usize> {
+    let clock = MockClock::new_with_step(Duration::from_millis(1));
+
+    rx.recv_with_data_timeout(dest, &mut clock.timer(Duration::from_millis(100)))
+}
+
+#[test]
+fn framing_layer_counts_ciphertext_and_crypto_layer_counts_plaintext() {
+    let pipe = LoopbackPipe::<PIPE_SIZE>::new();
+    let (tx, rx) = pipe.split::<CobsFraming>();
+    let key = [7; 32].into();
+    let mut tx = XChacha20Poly1305TxChannel::new(StatsChannel::new(tx), SeededRng::new(1), &key);
+    let mut rx = StatsChannel::new(XChacha20Poly1305RxChannel::new(StatsChannel::new(rx), &key));
+    let mut dest = [0; PIPE_SIZE];
+
+    for _ in 0..3 {
+        tx.send(&mut [0x5A; 10]).unwrap();
+        assert_eq!(recv(&mut rx, &mut dest), Ok(10));
+    }
+
+    let ciphertext_len = 3 * (10 + METADATA_SIZE) as u32;
+
+    assert_eq!(
+        tx.channel().stats(),
+        Some(ChannelStats {
+            frames_sent: 3,
+            bytes_sent: ciphertext_len,
+            ..Default::default()
+        })
+    );
+    assert_eq!(
+        rx.channel().channel().stats(),
+        Some(ChannelStats {
+            frames_received: 3,
+            bytes_received: ciphertext_len,
+            ..Default::default()
+        })
+    );
+    assert_eq!(
+        rx.stats(),
+        Some(ChannelStats {
+            frames_received: 3,
+            bytes_received: 30,
+            ..Default::default()
+        })
+    );
+}
+
+#[test]
+fn auth_failures_are_only_counted_at_the_crypto_layer() {
+    let pipe = LoopbackPipe::<PIPE_SIZE>::new();
+    let (tx, rx) = pipe.split::<CobsFraming>();
+    let key = [7; 32].into();
+    let mut tx = XChacha20Poly1305TxChannel::new(tx, SeededRng::new(1), &key);
+    let mut rx = StatsChannel::new(XChacha20Poly1305RxChannel::new(StatsChannel::new(rx), &key));
+    let mut dest = [0; PIPE_SIZE];
+
+    tx.send(&mut [0x5A; 10]).unwrap();
+    assert!(pipe.corrupt_byte(pipe.len() / 2, 0x01));
+    assert_eq!(
+        recv(&mut rx, &mut dest),
+        Err(CommunicationError::AuthenticationFailure)
+    );
+
+    // The frame itself was intact, so the framing layer received it.
+    let framing = rx.channel().channel().stats().unwrap();
+    assert_eq!(framing.frames_received, 1);
+    assert_eq!(framing.auth_failures, 0);
+
+    let crypto = rx.stats().unwrap();
+    assert_eq!(crypto.frames_received, 0);
+    assert_eq!(crypto.auth_failures, 1);
+    assert_eq!(crypto.resyncs, 0);
+}
+
+#[test]
+fn clean_streams_discard_nothing() {
+    fn check<F: FramingProtocol>() {
+        let pipe = LoopbackPipe::<PIPE_SIZE>::new();
+        let (mut tx, rx) = pipe.split::<F>();
+        let mut rx = StatsChannel::new(rx);
+        let mut dest = [0; PIPE_SIZE];
+
+        for len in [1, 10, 300] {
+            tx.send(&mut vec![0; len]).unwrap();
+            tx.send(&mut vec![0xFF; len]).unwrap();
+        }
+
+        while recv(&mut rx, &mut dest).is_ok() {}
+
+        let stats = rx.stats().unwrap();
+        assert_eq!(stats.frames_received, 6);
+        assert_eq!(stats.bytes_discarded, 0);
+        assert_eq!(stats.resyncs, 0);
+    }
+
+    check::<BogoFraming>();
+    check::<CobsFraming>();
+    check::<CrcFraming>();
+}
+
+#[test]
+fn garbage_between_frames_is_counted_as_discarded_bytes_and_a_resync() {
+    fn check<F: FramingProtocol>(garbage: &[u8], discarded: u32) {
+        let pipe = LoopbackPipe::<PIPE_SIZE>::new();
+        let (mut tx, rx) = pipe.split::<F>();
+        let mut rx = StatsChannel::new(rx);
+        let mut dest = [0; PIPE_SIZE];
+
+        tx.send(&mut [1; 10]).unwrap();
+        pipe.inject_bytes(garbage).unwrap();
+        tx.send(&mut [2; 10]).unwrap();
+
+        assert_eq!(recv(&mut rx, &mut dest), Ok(10));
+        assert_eq!(&dest[..10], &[1; 10]);
+        assert_eq!(recv(&mut rx, &mut dest), Ok(10));
+        assert_eq!(&dest[..10], &[2; 10]);
+
+        let stats = rx.stats().unwrap();
+        assert_eq!(stats.frames_received, 2);
+        assert_eq!(stats.framing_errors, 0);
+        assert_eq!(stats.bytes_discarded, discarded);
+        assert_eq!(stats.resyncs, 1);
+    }
+
+    // COBS framing skips everything but the delimiter between frames.
+    check::<CobsFraming>(b"line noise", 10);
+
+    // BogoFraming skips everything but the delimiter between frames, but NULL characters aren't counted as
+    // they're also sent as padding.
+    check::<BogoFraming>(b"line\0noise\0", 9);
+
+    // CRC framing skips everything but the sync bytes, including a first sync byte that isn't followed by
+    // the second one.
+    check::<CrcFraming>(b"line noise", 10);
+}
+
+#[test]
+fn garbage_before_a_timeout_is_counted_as_a_resync_on_the_next_frame() {
+    let pipe = LoopbackPipe::<PIPE_SIZE>::new();
+    let (mut tx, rx) = pipe.split::<CobsFraming>();
+    let mut rx = StatsChannel::new(rx);
+    let mut dest = [0; PIPE_SIZE];
+
+    pipe.inject_bytes(b"noise").unwrap();
+    assert_eq!(recv(&mut rx, &mut dest), Err(CommunicationError::Timeout));
+
+    tx.send(&mut [1; 10]).unwrap();
+    assert_eq!(recv(&mut rx, &mut dest), Ok(10));
+
+    let stats = rx.stats().unwrap();
+    assert_eq!(stats.timeouts, 1);
+    assert_eq!(stats.bytes_discarded, 5);
+    assert_eq!(stats.resyncs, 1);
+}
+
+#[test]
+fn corrupt_frames_are_counted_as_framing_errors_and_a_resync() {
+    let pipe = LoopbackPipe::<PIPE_SIZE>::new();
+    let (mut tx, rx) = pipe.split::<CrcFraming>();
+    let mut rx = StatsChannel::new(rx);
+    let mut dest = [0; PIPE_SIZE];
+
+    tx.send(&mut [1; 10]).unwrap();
+    assert!(pipe.corrupt_byte(pipe.len() - 1, 0x01));
+    tx.send(&mut [2; 10]).unwrap();
+
+    assert_eq!(
+        recv(&mut rx, &mut dest),
+        Err(CommunicationError::CorruptFrame)
+    );
+    assert_eq!(recv(&mut rx, &mut dest), Ok(10));
+
+    let stats = rx.stats().unwrap();
+    assert_eq!(stats.framing_errors, 1);
+    assert_eq!(stats.frames_received, 1);
+    assert_eq!(stats.resyncs, 1);
+}
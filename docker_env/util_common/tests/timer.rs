@@ -0,0 +1,169 @@
+//! Timeouts driven by a stepping mock clock, both on timers directly and on receives through a loopback
+//! pair.
+
+use core::time::Duration;
+
+use ucsc_ectf_util_common::{
+    communication::{
+        loopback::LoopbackPipe, lower_layers::framing::CobsFraming, CommunicationError, RxChannel,
+        TxChannel,
+    },
+    timer::{MockClock, Timer},
+};
+
+const STEP: Duration = Duration::from_millis(1);
+
+/// Polls ``timer`` until it's up, returning the number of polls that weren't up.
+fn polls_until_up<T: Timer>(timer: &mut T) -> u32 {
+    let mut polls = 0;
+
+    while !timer.poll() {
+        polls += 1;
+        assert!(polls < 10_000, "timer never expired");
+    }
+
+    polls
+}
+
+#[test]
+fn stepping_timer_expires_after_its_duration() {
+    let clock = MockClock::new_with_step(STEP);
+    let mut timer = clock.timer(Duration::from_millis(10));
+
+    assert_eq!(timer.remaining(), Duration::from_millis(10));
+    assert_eq!(polls_until_up(&mut timer), 10);
+    assert_eq!(timer.remaining(), Duration::ZERO);
+    assert_eq!(timer.elapsed(), timer.duration());
+}
+
+#[test]
+fn reset_restarts_the_timer() {
+    let clock = MockClock::new_with_step(STEP);
+    let mut timer = clock.timer(Duration::from_millis(10));
+
+    for _ in 0..5 {
+        assert!(!timer.poll());
+    }
+
+    assert_eq!(timer.elapsed(), Duration::from_millis(5));
+
+    timer.reset();
+    assert_eq!(timer.remaining(), Duration::from_millis(10));
+    assert_eq!(polls_until_up(&mut timer), 10);
+}
+
+#[test]
+fn clock_without_a_step_only_moves_when_advanced() {
+    let clock = MockClock::new();
+    let mut timer = clock.timer(Duration::from_millis(10));
+
+    for _ in 0..100 {
+        assert!(!timer.poll());
+    }
+
+    clock.advance(Duration::from_millis(9));
+    assert!(!timer.poll());
+
+    clock.advance(Duration::from_millis(1));
+    assert!(timer.poll());
+}
+
+#[test]
+fn deadline_ignores_reset() {
+    let clock = MockClock::new_with_step(STEP);
+    let mut deadline = clock.timer(Duration::from_millis(10)).into_deadline();
+
+    for _ in 0..5 {
+        assert!(!deadline.poll());
+    }
+
+    deadline.reset();
+    assert_eq!(polls_until_up(&mut deadline), 5);
+
+    deadline.restart();
+    assert_eq!(polls_until_up(&mut deadline), 10);
+}
+
+#[test]
+fn per_operation_timer_never_runs_past_the_deadline() {
+    let clock = MockClock::new_with_step(STEP);
+    let mut deadline = clock.timer(Duration::from_millis(25)).into_deadline();
+    let mut timer = clock
+        .timer(Duration::from_millis(10))
+        .with_deadline(&mut deadline);
+
+    // Both timers share the clock, so every poll of the combined timer moves it forward twice.
+    let mut operations = 0;
+
+    loop {
+        timer.reset();
+
+        for _ in 0..3 {
+            if timer.poll() {
+                assert_eq!(operations, 4);
+                assert!(clock.now() >= Duration::from_millis(25));
+
+                return;
+            }
+        }
+
+        operations += 1;
+    }
+}
+
+#[test]
+fn earliest_is_up_with_the_shorter_timer() {
+    let clock = MockClock::new_with_step(STEP);
+    let mut short_deadline = clock.timer(Duration::from_millis(4)).into_deadline();
+    let mut timer = clock
+        .timer(Duration::from_millis(100))
+        .with_deadline(&mut short_deadline);
+
+    assert_eq!(timer.duration(), Duration::from_millis(4));
+    assert!(polls_until_up(&mut timer) <= 4);
+}
+
+#[test]
+fn receive_on_an_empty_channel_times_out() {
+    let pipe = LoopbackPipe::<64>::new();
+    let (_, mut rx) = pipe.split::<CobsFraming>();
+    let mut dest = [0; 16];
+
+    for timeout in [1, 10, 100] {
+        let clock = MockClock::new_with_step(STEP);
+        let timeout = Duration::from_millis(timeout);
+
+        assert_eq!(
+            rx.recv_with_timeout(&mut dest, &mut clock.timer(timeout)),
+            Err(CommunicationError::Timeout)
+        );
+        assert!(clock.now() >= timeout);
+
+        let clock = MockClock::new_with_step(STEP);
+
+        assert_eq!(
+            rx.recv_with_data_timeout(&mut dest, &mut clock.timer(timeout)),
+            Err(CommunicationError::Timeout)
+        );
+        assert!(clock.now() >= timeout);
+    }
+}
+
+#[test]
+fn receive_times_out_on_a_partial_frame() {
+    // A frame that doesn't fit in the pipe is cut short, leaving a partial frame that never finishes.
+    let pipe = LoopbackPipe::<8>::new();
+    let (mut tx, mut rx) = pipe.split::<CobsFraming>();
+    let mut dest = [0; 16];
+
+    assert_eq!(tx.send(&mut [0xAA; 12]), Err(CommunicationError::SendError));
+    assert!(!pipe.is_empty());
+
+    let clock = MockClock::new_with_step(STEP);
+
+    assert_eq!(
+        rx.recv_with_timeout(&mut dest, &mut clock.timer(Duration::from_millis(50))),
+        Err(CommunicationError::Timeout)
+    );
+    assert!(pipe.is_empty());
+}
@@ -1,7 +1,12 @@
 use super::{
-    lower_layers::crypto::{
-        KeyedChannel, RandomSource, XChacha20Poly1305RxChannel, XChacha20Poly1305TxChannel,
+    lower_layers::{
+        crypto::{
+            KeyedChannel, PaddingPolicy, RandomSource, XChacha20Poly1305RxChannel,
+            XChacha20Poly1305TxChannel,
+        },
+        framing::{BogoFraming, FramingProtocol},
     },
+    stats::{LayerStats, OptionalStats, StatsChannel},
     uart::{FramedUartRxChannel, FramedUartTxChannel},
     RxChannel, TxChannel,
 };
@@ -13,11 +18,13 @@ use tm4c123x_hal::{
 };
 use ucsc_ectf_util_common::timer::Timer;
 
-type EncryptedUartTxChannel<'a, UART, TX> =
-    XChacha20Poly1305TxChannel<FramedUartTxChannel<'a, UART, TX>, UartRandomSource>;
+type EncryptedUartTxChannel<'a, UART, TX, F> = XChacha20Poly1305TxChannel<
+    StatsChannel<FramedUartTxChannel<'a, UART, TX, F>>,
+    UartRandomSource,
+>;
 
-type EncryptedUartRxChannel<'a, UART, RX> =
-    XChacha20Poly1305RxChannel<FramedUartRxChannel<'a, UART, RX>>;
+type EncryptedUartRxChannel<'a, UART, RX, F> =
+    XChacha20Poly1305RxChannel<StatsChannel<FramedUartRxChannel<'a, UART, RX, F>>>;
 
 /// The [`RandomSource`] used for encrypted UART channels.
 pub struct UartRandomSource {
@@ -43,24 +50,46 @@ macro_rules! uart_impl {
         /// for how message confidentiality and integrity is guaranteed for transmissions
         /// in this struct.
         ///
-        /// To frame the UART data sent and received, BogoFraming is used.
+        /// To frame the UART data sent and received, the [`FramingProtocol`] given by the
+        /// ``F`` type parameter is used, which defaults to [`BogoFraming`].
         ///
         /// ## BogoFraming
         /// Each message sent/received will be hex encoded and decoded, delimited by a NULL (\0) character
         /// at the start and at the end. Messages must be at least 1 character long.
-        pub struct $ctr_ty<'a, TX, RX>
+        ///
+        /// ## COBS framing
+        /// Each message sent/received will be COBS encoded and decoded, delimited by a NULL (\0) character
+        /// at the start and at the end. See [`CobsFraming`](super::lower_layers::framing::CobsFraming)
+        /// for more details.
+        ///
+        /// ## Errors
+        /// Errors from the framing and encryption layers are passed through unchanged, so callers can tell
+        /// a [`Timeout`](super::CommunicationError::Timeout) apart from an
+        /// [`AuthenticationFailure`](super::CommunicationError::AuthenticationFailure) or a malformed frame.
+        ///
+        /// ## Statistics
+        /// A controller created with [`with_stats`](Self::with_stats) counts the messages sent and received
+        /// and the errors given on both the framing and the encryption layer, which can be read with
+        /// [`stats`](Self::stats) for diagnostics. The framing layer counts the encrypted bytes sent over the
+        /// UART, framing errors, and the bytes skipped while looking for a frame, while the encryption layer
+        /// counts the decrypted bytes and authentication failures. See [`LayerStats`] and
+        /// [`ChannelStats`](super::stats::ChannelStats) for the counters kept.
+        pub struct $ctr_ty<'a, TX, RX, F = BogoFraming>
         where
             TX: TxPin<$uart_typ>,
             RX: RxPin<$uart_typ>,
+            F: FramingProtocol,
         {
-            tx_channel: EncryptedUartTxChannel<'a, $uart_typ, TX>,
-            rx_channel: EncryptedUartRxChannel<'a, $uart_typ, RX>,
+            tx_channel: EncryptedUartTxChannel<'a, $uart_typ, TX, F>,
+            rx_channel: EncryptedUartRxChannel<'a, $uart_typ, RX, F>,
+            stats: OptionalStats,
         }
 
-        impl<'a, TX, RX> $ctr_ty<'a, TX, RX>
+        impl<'a, TX, RX, F> $ctr_ty<'a, TX, RX, F>
         where
             TX: TxPin<$uart_typ>,
             RX: RxPin<$uart_typ>,
+            F: FramingProtocol,
         {
             /// Creates a new UART controller using the provided split
             /// [Serial](tm4c123x_hal::serial::Serial) struct and encryption
@@ -73,18 +102,21 @@ macro_rules! uart_impl {
                 tx_key: &Key,
             ) -> Self {
                 let tx_channel = EncryptedUartTxChannel::new(
-                    FramedUartTxChannel::$tx_ctor(tx),
+                    StatsChannel::new_disabled(FramedUartTxChannel::$tx_ctor(tx)),
                     UartRandomSource {
                         _not_constructible: (),
                     },
                     tx_key,
                 );
-                let rx_channel =
-                    EncryptedUartRxChannel::new(FramedUartRxChannel::$rx_ctor(rx), rx_key);
+                let rx_channel = EncryptedUartRxChannel::new(
+                    StatsChannel::new_disabled(FramedUartRxChannel::$rx_ctor(rx)),
+                    rx_key,
+                );
 
                 Self {
                     tx_channel,
                     rx_channel,
+                    stats: OptionalStats::default(),
                 }
             }
 
@@ -99,10 +131,69 @@ macro_rules! uart_impl {
                 Self::$fn_name(tx, rx, &Default::default(), &Default::default())
             }
 
+            /// Makes this controller pad every message sent according to ``policy`` and remove the
+            /// padding from every message received. The controller on the other end must also have
+            /// padding enabled and receive messages of up to ``max_message_size`` bytes, which messages
+            /// are never padded past. See [`PaddingPolicy`] for more info.
+            pub fn with_padding(self, policy: PaddingPolicy, max_message_size: usize) -> Self {
+                Self {
+                    tx_channel: self
+                        .tx_channel
+                        .with_padding(policy)
+                        .with_max_padded_len(max_message_size),
+                    rx_channel: self.rx_channel.with_padding(),
+                    stats: self.stats,
+                }
+            }
+
+            /// Makes this controller count the messages sent and received and the errors given. See the
+            /// struct-level documentation for more info.
+            pub fn with_stats(mut self) -> Self {
+                self.tx_channel.channel_mut().enable_stats();
+                self.rx_channel.channel_mut().enable_stats();
+                self.stats.enable();
+
+                self
+            }
+
+            /// Gets the counters of each layer of this controller, or [`None`] if it wasn't created with
+            /// [`with_stats`](Self::with_stats). The framing layer counters combine the sending and
+            /// receiving ends of the UART.
+            pub fn stats(&self) -> Option<LayerStats> {
+                let framing = self
+                    .rx_channel
+                    .channel()
+                    .stats()?
+                    .combined(self.tx_channel.channel().stats()?);
+
+                Some(LayerStats {
+                    framing,
+                    crypto: self.stats.get()?,
+                })
+            }
+
+            /// Resets the counters of this controller to zero.
+            pub fn reset_stats(&mut self) {
+                self.tx_channel.channel_mut().reset_stats();
+                self.rx_channel.channel_mut().reset_stats();
+                self.stats.reset();
+            }
+
+            /// Checks whether the receive FIFO of the UART is empty. The FIFO only holds 16 bytes, so a
+            /// caller listening on several UARTs can use this to avoid blocking on another UART while a
+            /// message is arriving on this one.
+            pub fn rx_fifo_is_empty(&self) -> bool {
+                // SAFETY: This only reads the flag register, which has no side effects. The receive
+                // end of the UART is borrowed by this controller, so nothing else is reading from it.
+                let uart = unsafe { &*<$uart_typ>::ptr() };
+
+                uart.fr.read().rxfe().bit_is_set()
+            }
+
             /// Changes the encryption key used for the UART TX channel to the provided key.
             pub fn change_tx_key(
                 &mut self,
-                new_key: &<EncryptedUartTxChannel<$uart_typ, TX> as KeyedChannel>::KeyType,
+                new_key: &<EncryptedUartTxChannel<$uart_typ, TX, F> as KeyedChannel>::KeyType,
             ) {
                 self.tx_channel.change_key(new_key);
             }
@@ -110,23 +201,29 @@ macro_rules! uart_impl {
             /// Changes the decryption key used for the UART RX channel to the provided key.
             pub fn change_rx_key(
                 &mut self,
-                new_key: &<EncryptedUartRxChannel<$uart_typ, RX> as KeyedChannel>::KeyType,
+                new_key: &<EncryptedUartRxChannel<$uart_typ, RX, F> as KeyedChannel>::KeyType,
             ) {
                 self.rx_channel.change_key(new_key);
             }
         }
 
-        impl<'a, TX, RX> RxChannel for $ctr_ty<'a, TX, RX>
+        impl<'a, TX, RX, F> RxChannel for $ctr_ty<'a, TX, RX, F>
         where
             TX: TxPin<$uart_typ>,
             RX: RxPin<$uart_typ>,
+            F: FramingProtocol,
         {
+            const METADATA_SIZE: usize =
+                <EncryptedUartRxChannel<'a, $uart_typ, RX, F> as RxChannel>::METADATA_SIZE;
+
             fn recv_with_timeout<T: Timer>(
                 &mut self,
                 dest: &mut [u8],
                 timer: &mut T,
             ) -> super::Result<usize> {
-                self.rx_channel.recv_with_timeout(dest, timer)
+                let result = self.rx_channel.recv_with_timeout(dest, timer);
+
+                self.stats.record_recv(result)
             }
 
             fn recv_with_data_timeout<T: Timer>(
@@ -134,17 +231,23 @@ macro_rules! uart_impl {
                 dest: &mut [u8],
                 timer: &mut T,
             ) -> super::Result<usize> {
-                self.rx_channel.recv_with_data_timeout(dest, timer)
+                let result = self.rx_channel.recv_with_data_timeout(dest, timer);
+
+                self.stats.record_recv(result)
             }
         }
 
-        impl<'a, TX, RX> TxChannel for $ctr_ty<'a, TX, RX>
+        impl<'a, TX, RX, F> TxChannel for $ctr_ty<'a, TX, RX, F>
         where
             TX: TxPin<$uart_typ>,
             RX: RxPin<$uart_typ>,
+            F: FramingProtocol,
         {
             fn send(&mut self, src: &mut [u8]) -> super::Result<()> {
-                self.tx_channel.send(src)
+                let len = src.len();
+                let result = self.tx_channel.send(src);
+
+                self.stats.record_send(len, result)
             }
         }
     };
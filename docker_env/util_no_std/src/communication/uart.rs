@@ -1,4 +1,4 @@
-use core::ops::Deref;
+use core::{marker::PhantomData, ops::Deref};
 
 use cortex_m::prelude::_embedded_hal_serial_Read;
 use tm4c123x_hal::{
@@ -6,7 +6,10 @@ use tm4c123x_hal::{
     tm4c123x::{uart0, UART0, UART1},
 };
 use ucsc_ectf_util_common::{
-    communication::{lower_layers::framing::bogoframing, CommunicationError},
+    communication::{
+        lower_layers::framing::{BogoFraming, FramingProtocol},
+        CommunicationError,
+    },
     timer::Timer,
 };
 
@@ -25,81 +28,107 @@ pub const MIN_FRAMED_UART_MESSAGE: usize = UART_FIFO_LEN;
 /// in transmission. It is also insecure and should be wrapped around one of the channels in the
 /// [`crypto`](crate::communication::lower_layers::crypto) layer for confidentiality and/or integrity.
 /// A message sent by this channel must be at least [`MIN_FRAMED_UART_MESSAGE`] bytes long.
-/// See the module-level documentation for details on how framing works for this channel.
-pub struct FramedUartTxChannel<'a, UART, TX>
+/// The framing protocol used is chosen by the [`FramingProtocol`] type parameter, which defaults
+/// to [`BogoFraming`].
+pub struct FramedUartTxChannel<'a, UART, TX, F = BogoFraming>
 where
     UART: Deref<Target = uart0::RegisterBlock>,
     TX: TxPin<UART>,
+    F: FramingProtocol,
 {
     tx: &'a mut Tx<UART, TX, ()>,
+    _framing: PhantomData<F>,
 }
 
-impl<'a, TX> FramedUartTxChannel<'a, UART0, TX>
+impl<'a, TX, F> FramedUartTxChannel<'a, UART0, TX, F>
 where
     TX: TxPin<UART0>,
+    F: FramingProtocol,
 {
     /// Creates a new [`FramedUartTxChannel`] for UART0 tranmission given the [`Tx`] end
     /// of a split [`Serial`](tm4c123x_hal::serial::Serial).
     pub fn new_uart0_tx_channel(tx: &'a mut Tx<UART0, TX, ()>) -> Self {
-        Self { tx }
+        Self {
+            tx,
+            _framing: PhantomData,
+        }
     }
 }
 
-impl<'a, TX> FramedUartTxChannel<'a, UART1, TX>
+impl<'a, TX, F> FramedUartTxChannel<'a, UART1, TX, F>
 where
     TX: TxPin<UART1>,
+    F: FramingProtocol,
 {
     /// Creates a new [`FramedUartTxChannel`] for UART1 tranmission given the [`Tx`] end
     /// of a split [`Serial`](tm4c123x_hal::serial::Serial).
     pub fn new_uart1_tx_channel(tx: &'a mut Tx<UART1, TX, ()>) -> Self {
-        Self { tx }
+        Self {
+            tx,
+            _framing: PhantomData,
+        }
     }
 }
 
 /// An [`RxChannel`] for receiving UART data. This channel is unreliable and might not receive transmitted bytes.
 /// It can also receive data that was never sent, receive merged frames, or split a message. It is also insecure
 /// and should be wrapped around one of the channels in the [`crypto`](crate::communication::lower_layers::crypto)
-/// layer for confidentiality and/or integrity. See the module-level documentation for details on how framing works
-/// for this channel.
-pub struct FramedUartRxChannel<'a, UART, RX>
+/// layer for confidentiality and/or integrity. The framing protocol used is chosen by the [`FramingProtocol`]
+/// type parameter, which defaults to [`BogoFraming`]. Bytes discarded outside of frames are counted and given by
+/// [`RxChannel::take_discarded_bytes`].
+pub struct FramedUartRxChannel<'a, UART, RX, F = BogoFraming>
 where
     UART: Deref<Target = uart0::RegisterBlock>,
     RX: RxPin<UART>,
+    F: FramingProtocol,
 {
     rx: &'a mut Rx<UART, RX, ()>,
+    discarded: usize,
+    _framing: PhantomData<F>,
 }
 
-impl<'a, RX> FramedUartRxChannel<'a, UART0, RX>
+impl<'a, RX, F> FramedUartRxChannel<'a, UART0, RX, F>
 where
     RX: RxPin<UART0>,
+    F: FramingProtocol,
 {
     /// Creates a new [`FramedUartRxChannel`] for UART0 tranmission given the [`Rx`] end
     /// of a split [`Serial`](tm4c123x_hal::serial::Serial).
     pub fn new_uart0_rx_channel(rx: &'a mut Rx<UART0, RX, ()>) -> Self {
-        Self { rx }
+        Self {
+            rx,
+            discarded: 0,
+            _framing: PhantomData,
+        }
     }
 }
 
-impl<'a, RX> FramedUartRxChannel<'a, UART1, RX>
+impl<'a, RX, F> FramedUartRxChannel<'a, UART1, RX, F>
 where
     RX: RxPin<UART1>,
+    F: FramingProtocol,
 {
     /// Creates a new [`FramedUartRxChannel`] for UART1 tranmission given the [`Tx`] end
     /// of a split [`Serial`](tm4c123x_hal::serial::Serial).
     pub fn new_uart1_rx_channel(rx: &'a mut Rx<UART1, RX, ()>) -> Self {
-        Self { rx }
+        Self {
+            rx,
+            discarded: 0,
+            _framing: PhantomData,
+        }
     }
 }
 
-impl<'a, TX> FramedTxChannel for FramedUartTxChannel<'a, UART0, TX>
+impl<'a, TX, F> FramedTxChannel for FramedUartTxChannel<'a, UART0, TX, F>
 where
     TX: TxPin<UART0>,
+    F: FramingProtocol,
 {
     fn frame<'b, const FRAME_CT: usize>(
         &mut self,
         frame: impl FnOnce() -> communication::Result<Frame<'b, FRAME_CT>>,
     ) -> communication::Result<()> {
-        bogoframing::frame_bogoframe(
+        F::frame(
             self,
             frame()?,
             |ch, s| {
@@ -111,15 +140,16 @@ where
     }
 }
 
-impl<'a, TX> FramedTxChannel for FramedUartTxChannel<'a, UART1, TX>
+impl<'a, TX, F> FramedTxChannel for FramedUartTxChannel<'a, UART1, TX, F>
 where
     TX: TxPin<UART1>,
+    F: FramingProtocol,
 {
     fn frame<'b, const FRAME_CT: usize>(
         &mut self,
         frame: impl FnOnce() -> communication::Result<Frame<'b, FRAME_CT>>,
     ) -> communication::Result<()> {
-        bogoframing::frame_bogoframe(
+        F::frame(
             self,
             frame()?,
             |ch, s| {
@@ -131,21 +161,23 @@ where
     }
 }
 
-impl<'a, RX> RxChannel for FramedUartRxChannel<'a, UART0, RX>
+impl<'a, RX, F> RxChannel for FramedUartRxChannel<'a, UART0, RX, F>
 where
     RX: RxPin<UART0>,
+    F: FramingProtocol,
 {
     fn recv_with_data_timeout<T: Timer>(
         &mut self,
         dest: &mut [u8],
         timer: &mut T,
     ) -> communication::Result<usize> {
-        bogoframing::recv_frame_with_data_timeout(
-            self,
+        F::recv_frame_with_data_timeout(
+            self.rx,
             dest,
             timer,
-            |s| s.rx.read().map_err(|_| CommunicationError::RecvError),
+            |rx| rx.read().map_err(|_| CommunicationError::RecvError),
             MIN_FRAMED_UART_MESSAGE,
+            &mut self.discarded,
         )
     }
 
@@ -154,31 +186,38 @@ where
         dest: &mut [u8],
         timer: &mut T,
     ) -> ucsc_ectf_util_common::communication::Result<usize> {
-        bogoframing::recv_frame_with_timeout(
-            self,
+        F::recv_frame_with_timeout(
+            self.rx,
             dest,
             timer,
-            |s| s.rx.read().map_err(|_| CommunicationError::RecvError),
+            |rx| rx.read().map_err(|_| CommunicationError::RecvError),
             MIN_FRAMED_UART_MESSAGE,
+            &mut self.discarded,
         )
     }
+
+    fn take_discarded_bytes(&mut self) -> usize {
+        core::mem::take(&mut self.discarded)
+    }
 }
 
-impl<'a, RX> RxChannel for FramedUartRxChannel<'a, UART1, RX>
+impl<'a, RX, F> RxChannel for FramedUartRxChannel<'a, UART1, RX, F>
 where
     RX: RxPin<UART1>,
+    F: FramingProtocol,
 {
     fn recv_with_data_timeout<T: Timer>(
         &mut self,
         dest: &mut [u8],
         timer: &mut T,
     ) -> communication::Result<usize> {
-        bogoframing::recv_frame_with_data_timeout(
-            self,
+        F::recv_frame_with_data_timeout(
+            self.rx,
             dest,
             timer,
-            |s| s.rx.read().map_err(|_| CommunicationError::RecvError),
+            |rx| rx.read().map_err(|_| CommunicationError::RecvError),
             MIN_FRAMED_UART_MESSAGE,
+            &mut self.discarded,
         )
     }
 
@@ -187,12 +226,17 @@ where
         dest: &mut [u8],
         timer: &mut T,
     ) -> ucsc_ectf_util_common::communication::Result<usize> {
-        bogoframing::recv_frame_with_timeout(
-            self,
+        F::recv_frame_with_timeout(
+            self.rx,
             dest,
             timer,
-            |s| s.rx.read().map_err(|_| CommunicationError::RecvError),
+            |rx| rx.read().map_err(|_| CommunicationError::RecvError),
             MIN_FRAMED_UART_MESSAGE,
+            &mut self.discarded,
         )
     }
+
+    fn take_discarded_bytes(&mut self) -> usize {
+        core::mem::take(&mut self.discarded)
+    }
 }
@@ -0,0 +1,117 @@
+//! This module contains a clock for the trusted [`DeviceTime`] of a device, which is the number of
+//! seconds it has been running for over its lifetime.
+//!
+//! - Device clock
+//!     - A [`DeviceClock`] adds the time counted by the hibernation clock since it was created to
+//!       the device time stored in EEPROM. The device time doesn't move while the device is powered
+//!       off, so it can't be moved forwards or backwards by anything outside of the device.
+//!     - The device time is written back to EEPROM with [`DeviceClock::persist`] at most once every
+//!       [`DeviceClock::PERSIST_INTERVAL`] seconds. Up to that much time since the last write is lost
+//!       upon a reset, so the clock moves the stored time forward by
+//!       [`DeviceClock::PERSIST_INTERVAL`] and writes it back whenever it is created. The device time
+//!       can run ahead of the time the device has been running for, but never falls behind it, so
+//!       resetting the device can't stop it from moving forward.
+//! - Wear levelling
+//!     - Each write goes to the next of [`DEVICE_TIME_SLOTS`] EEPROM fields in turn, and the device
+//!       time is the largest time in any of them, as it never goes backwards. With a write every
+//!       minute, each field is written about once every 8 minutes, which lasts the 500,000 writes
+//!       an EEPROM word is rated for for over 7 years of running time.
+
+use crate::{
+    eeprom::{EepromController, EepromReadWriteField, DEVICE_TIME_SIZE, DEVICE_TIME_SLOTS},
+    hib::HibController,
+    messages::DeviceTime,
+};
+
+/// A clock for the trusted device time. See the [`module`](self) documentation for more details.
+pub struct DeviceClock {
+    /// The device time when this clock was created.
+    start: DeviceTime,
+    /// The uptime of the hibernation clock in seconds when this clock was created.
+    start_uptime: u64,
+    /// The device time last written to EEPROM.
+    persisted: DeviceTime,
+    /// The slot the device time was last written to.
+    slot: u32,
+}
+
+impl DeviceClock {
+    /// The minimum number of seconds between writes of the device time to EEPROM, which is also how
+    /// far the device time is moved forward upon a reset.
+    pub const PERSIST_INTERVAL: DeviceTime = 60;
+
+    /// Creates a new [`DeviceClock`] starting [`DeviceClock::PERSIST_INTERVAL`] seconds after the
+    /// device time stored in EEPROM, and writes that time back. This should be called right after the
+    /// [`Runtime`](crate::Runtime) is created.
+    pub fn new(eeprom_controller: &mut EepromController, hib_controller: &HibController) -> Self {
+        // Find the slot holding the latest device time.
+        let mut persisted = 0;
+        let mut slot = 0;
+
+        for i in 0..DEVICE_TIME_SLOTS as u32 {
+            let mut device_time_bytes = [0; DEVICE_TIME_SIZE];
+            eeprom_controller
+                .read_slice(EepromReadWriteField::DeviceTime(i), &mut device_time_bytes)
+                .expect("EEPROM read failed: device time.");
+            let device_time = DeviceTime::from_be_bytes(device_time_bytes);
+
+            if device_time > persisted {
+                persisted = device_time;
+                slot = i;
+            }
+        }
+
+        let start = persisted.saturating_add(Self::PERSIST_INTERVAL);
+        let mut device_clock = Self {
+            start,
+            start_uptime: hib_controller.uptime().as_secs(),
+            persisted,
+            slot,
+        };
+
+        // Write the start time right away so that resets before the next write still move the
+        // device time forward.
+        device_clock.write(eeprom_controller, start);
+
+        device_clock
+    }
+
+    /// Gets the current device time.
+    pub fn now(&self, hib_controller: &HibController) -> DeviceTime {
+        let elapsed = hib_controller
+            .uptime()
+            .as_secs()
+            .saturating_sub(self.start_uptime);
+
+        self.start.saturating_add(elapsed)
+    }
+
+    /// Writes the current device time to EEPROM if at least [`DeviceClock::PERSIST_INTERVAL`] seconds
+    /// have passed since it was last written.
+    pub fn persist(
+        &mut self,
+        eeprom_controller: &mut EepromController,
+        hib_controller: &HibController,
+    ) {
+        let now = self.now(hib_controller);
+
+        if now < self.persisted.saturating_add(Self::PERSIST_INTERVAL) {
+            return;
+        }
+
+        self.write(eeprom_controller, now);
+    }
+
+    /// Writes ``device_time`` to the slot after the one last written.
+    fn write(&mut self, eeprom_controller: &mut EepromController, device_time: DeviceTime) {
+        self.slot = (self.slot + 1) % DEVICE_TIME_SLOTS as u32;
+
+        eeprom_controller
+            .write_slice(
+                EepromReadWriteField::DeviceTime(self.slot),
+                &device_time.to_be_bytes(),
+            )
+            .expect("EEPROM write failed: device time.");
+        self.persisted = device_time;
+    }
+}
@@ -9,8 +9,10 @@ pub use ucsc_ectf_eeprom_layout::EepromReadField;
 pub use ucsc_ectf_eeprom_layout::EepromReadOnlyField;
 pub use ucsc_ectf_eeprom_layout::EepromReadWriteField;
 pub use ucsc_ectf_eeprom_layout::{
-    BYTE_FIELD_SIZE, CAR_ID_SIZE, MESSAGE_SIZE, PACKAGED_FEATURE_SIGNED_SIZE, PAIRING_PIN_SIZE,
-    PUBLIC_KEY_SIZE, SECRET_SIZE, SIGNATURE_SIZE,
+    FeatureSlot, BYTE_FIELD_SIZE, CAR_ID_SIZE, DEVICE_TIME_SIZE, DEVICE_TIME_SLOTS,
+    DISABLE_FEATURE_SEQUENCE_SIZE, EEPROM_LAYOUT_VERSION, FEATURE_ACTIVATION_SIZE, MESSAGE_SIZE,
+    PACKAGED_FEATURE_SIZE, PAIRING_PIN_SIZE, PUBLIC_KEY_SIZE, REVOCATION_LIST_SIGNED_SIZE,
+    SECRET_SIZE, SIGNATURE_SIZE,
 };
 
 /// The EEPROM controller. Holds a mutable reference to the EEPROM peripheral.
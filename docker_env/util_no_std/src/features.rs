@@ -1,29 +1,46 @@
-//! This module provides a function for verifying signed packaged features.
+//! This module provides functions for verifying packaged features, their signatures, their validity
+//! windows, and whether they have been revoked, along with installing signed revocation lists and
+//! verifying signed requests to disable features. Validity windows are counted from when the car
+//! first accepted each feature, which the car records in EEPROM.
 
 use crate::eeprom::EepromController;
+use core::mem;
 use k256::{
     ecdsa::{signature::Verifier, Signature, VerifyingKey},
     pkcs8::DecodePublicKey,
 };
 use ucsc_ectf_eeprom_layout::{
-    EepromReadOnlyField, EepromReadWriteField, CAR_ID_SIZE, PUBLIC_KEY_SIZE,
+    is_feature_number, EepromReadOnlyField, EepromReadWriteField, CAR_ID_SIZE,
+    DISABLE_FEATURE_SEQUENCE_SIZE, FEATURE_ACTIVATION_SIZE, PUBLIC_KEY_SIZE,
+    REVOCATION_LIST_SIGNED_SIZE,
+};
+use ucsc_ectf_util_common::messages::{
+    CarId, DeviceTime, DisableFeatureSigned, DisableFeatureUnsigned, FeatureDigest,
+    PackagedFeatureSigned, PackagedFeatureUnsigned, RevocationListSigned, RevocationListUnsigned,
+    SignatureTag, SignedMessage,
 };
-use ucsc_ectf_util_common::messages::PackagedFeatureSigned;
 
-/// Verifies the signature of a [`PackagedFeatureSigned`] and checks the car
-/// ID and feature number associated with it. This function should not be
-/// called on an unpaired key fob.
-pub fn verify_packaged_feature_signed<'a>(
-    eeprom_controller: &mut EepromController,
-    packaged_feature_signed: &'a PackagedFeatureSigned<'a>,
-) -> bool {
-    // Get the packaged feature and signature.
-    let packaged_feature = &packaged_feature_signed.packaged_feature;
-    let Ok(signature) = Signature::from_der(packaged_feature_signed.signature) else {
-        return false;
-    };
+/// The result of verifying a [`PackagedFeatureSigned`] or a [`PackagedFeatureUnsigned`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FeatureVerification {
+    /// The feature is authentic and valid.
+    Valid,
+    /// The feature is authentic, but the device time is outside of its validity window.
+    OutsideValidityWindow,
+    /// The feature is authentic, but it has been revoked by the installed revocation list.
+    Revoked,
+    /// The feature isn't authentic or isn't meant for this car.
+    Invalid,
+}
 
-    // Read the feature verifying key from EEPROM.
+// Check that a feature activation holds a feature digest and a device time.
+const _: () = assert!(
+    mem::size_of::<FeatureDigest>() + mem::size_of::<DeviceTime>() == FEATURE_ACTIVATION_SIZE,
+    "A feature activation must hold a feature digest and a device time."
+);
+
+/// Reads the feature verifying key from EEPROM.
+fn read_feature_verifying_key(eeprom_controller: &mut EepromController) -> VerifyingKey {
     let mut verifying_key_bytes = [0; PUBLIC_KEY_SIZE];
     eeprom_controller
         .read_slice(
@@ -31,35 +48,265 @@ pub fn verify_packaged_feature_signed<'a>(
             &mut verifying_key_bytes,
         )
         .expect("EEPROM read failed: feature verifying key.");
-    let verifying_key = VerifyingKey::from_public_key_der(
-        &verifying_key_bytes[1..verifying_key_bytes[0] as usize + 1],
-    )
-    .expect("Failed to deserialize feature verifying key.");
+
+    VerifyingKey::from_public_key_der(&verifying_key_bytes[1..verifying_key_bytes[0] as usize + 1])
+        .expect("Failed to deserialize feature verifying key.")
+}
+
+/// Reads the car ID from EEPROM.
+fn read_car_id(eeprom_controller: &mut EepromController) -> CarId {
+    let mut buf = [0; CAR_ID_SIZE];
+    eeprom_controller
+        .read_slice(EepromReadWriteField::CarId, &mut buf)
+        .expect("EEPROM read failed: car ID.");
+
+    CarId::from_be_bytes(buf)
+}
+
+/// Reads the sequence number of the last disable feature request accepted from EEPROM.
+fn read_disable_feature_sequence(eeprom_controller: &mut EepromController) -> u32 {
+    let mut buf = [0; DISABLE_FEATURE_SEQUENCE_SIZE];
+    eeprom_controller
+        .read_slice(EepromReadWriteField::DisableFeatureSequence, &mut buf)
+        .expect("EEPROM read failed: disable feature sequence.");
+
+    u32::from_be_bytes(buf)
+}
+
+/// Gets the device time when the car first accepted ``packaged_feature`` from EEPROM. If the car
+/// last accepted a different feature for its feature number, ``now`` is recorded as the time it first
+/// accepted ``packaged_feature`` and given instead. The feature number must be valid.
+fn activation_time(
+    eeprom_controller: &mut EepromController,
+    packaged_feature: &PackagedFeatureUnsigned,
+    now: DeviceTime,
+) -> DeviceTime {
+    let field = EepromReadWriteField::feature_activation(packaged_feature.feature_number)
+        .expect("Invalid feature number.");
+    let digest = packaged_feature.digest();
+    let mut buf = [0; FEATURE_ACTIVATION_SIZE];
+    eeprom_controller
+        .read_slice(field, &mut buf)
+        .expect("EEPROM read failed: feature activation.");
+
+    let (activated_digest, activated_at) = buf.split_at(mem::size_of::<FeatureDigest>());
+
+    if activated_digest == digest {
+        let mut activated_at_bytes = [0; mem::size_of::<DeviceTime>()];
+        activated_at_bytes.copy_from_slice(activated_at);
+
+        return DeviceTime::from_be_bytes(activated_at_bytes);
+    }
+
+    // Only write when the feature changes, so the field isn't worn by every unlock.
+    buf[..mem::size_of::<FeatureDigest>()].copy_from_slice(&digest);
+    buf[mem::size_of::<FeatureDigest>()..].copy_from_slice(&now.to_be_bytes());
+    eeprom_controller
+        .write_slice(field, &buf)
+        .expect("EEPROM write failed: feature activation.");
+
+    now
+}
+
+/// Verifies the DER-encoded ``signature`` of ``bytes`` with the feature verifying key. Apart from
+/// version 1 packaged features, ``bytes`` must be a message tagged with [`SignedMessage::tagged`].
+fn verify_feature_signature(
+    eeprom_controller: &mut EepromController,
+    bytes: &[u8],
+    signature: &[u8],
+) -> bool {
+    let Ok(signature) = Signature::from_der(signature) else {
+        return false;
+    };
+
+    read_feature_verifying_key(eeprom_controller)
+        .verify(bytes, &signature)
+        .is_ok()
+}
+
+/// Gets the revocation list installed in EEPROM, or [`None`] if no revocation list has been
+/// installed. The slice is used to store the [`RevocationListSigned`] in serialized form, which
+/// will be used by the returned [`RevocationListSigned`].
+pub fn get_installed_revocation_list<'a>(
+    eeprom_controller: &mut EepromController,
+    buf: &'a mut [u8; REVOCATION_LIST_SIGNED_SIZE],
+) -> Option<RevocationListSigned<'a>> {
+    eeprom_controller
+        .read_slice(EepromReadWriteField::RevocationList, buf)
+        .expect("EEPROM read failed: revocation list.");
+
+    // Revocation lists are verified before they are installed, so they aren't verified again.
+    postcard::from_bytes(buf).ok()
+}
+
+/// Verifies a [`RevocationListSigned`] and installs it in EEPROM in place of the one installed,
+/// returning whether it was installed. The list is only installed if its signature is valid, it is
+/// for this car, and its sequence number is greater than that of the list installed. This function
+/// should not be called on an unpaired key fob.
+pub fn install_revocation_list_signed<'a>(
+    eeprom_controller: &mut EepromController,
+    revocation_list_signed: &'a RevocationListSigned<'a>,
+) -> bool {
+    let revocation_list = &revocation_list_signed.revocation_list;
 
     // Verify the signature.
-    let mut packaged_feature_buf = [0; 16];
-    let packaged_feature_bytes = postcard::to_slice(&packaged_feature, &mut packaged_feature_buf)
-        .expect("Failed to serialize packaged feature.");
+    let mut revocation_list_buf = [0; SignatureTag::SIZE + RevocationListUnsigned::MAX_SIZE];
+    let revocation_list_bytes =
+        postcard::to_slice(&revocation_list.tagged(), &mut revocation_list_buf)
+            .expect("Failed to serialize revocation list.");
 
-    if verifying_key
-        .verify(packaged_feature_bytes, &signature)
-        .is_err()
-    {
+    if !verify_feature_signature(
+        eeprom_controller,
+        revocation_list_bytes,
+        revocation_list_signed.signature,
+    ) {
         return false;
     }
 
-    // Check that the car ID matches the car ID in the packaged feature.
-    let car_id = {
-        let mut buf = [0; CAR_ID_SIZE];
-        eeprom_controller
-            .read_slice(EepromReadWriteField::CarId, &mut buf)
-            .expect("EEPROM read failed: car ID.");
-        u32::from_be_bytes(buf)
+    // Check that the car ID matches the car ID in the revocation list.
+    if read_car_id(eeprom_controller) != revocation_list.car_id {
+        return false;
+    }
+
+    // Check that the revocation list is newer than the one installed.
+    let mut installed_buf = [0; REVOCATION_LIST_SIGNED_SIZE];
+
+    if let Some(installed) = get_installed_revocation_list(eeprom_controller, &mut installed_buf) {
+        if revocation_list.sequence <= installed.revocation_list.sequence {
+            return false;
+        }
+    }
+
+    // Write the signed revocation list to EEPROM.
+    let mut revocation_list_signed_buf = [0; REVOCATION_LIST_SIGNED_SIZE];
+    let Ok(_) = postcard::to_slice(revocation_list_signed, &mut revocation_list_signed_buf) else {
+        return false;
     };
+    eeprom_controller
+        .write_slice(
+            EepromReadWriteField::RevocationList,
+            &revocation_list_signed_buf,
+        )
+        .expect("EEPROM write failed: revocation list.");
+
+    true
+}
+
+/// Checks the car ID and feature number associated with a [`PackagedFeatureUnsigned`], along with
+/// whether it has been revoked, without verifying its signature. This is only meant for features
+/// whose signature has already been verified, such as the features a key fob stores or the
+/// features a car receives from its paired key fob. If ``device_time`` is given, the validity window
+/// of the feature is checked against the device time elapsed since the car first accepted it, which
+/// is recorded as ``device_time`` if the car hasn't accepted it before. Only the car should pass a
+/// [`DeviceTime`], and other devices should pass [`None`] to leave this check to the car. This
+/// function should not be called on an unpaired key fob.
+pub fn verify_packaged_feature(
+    eeprom_controller: &mut EepromController,
+    packaged_feature: &PackagedFeatureUnsigned,
+    device_time: Option<DeviceTime>,
+) -> FeatureVerification {
+    // Check that the car ID matches the car ID in the packaged feature.
+    if read_car_id(eeprom_controller) != packaged_feature.car_id
+        || !is_feature_number(packaged_feature.feature_number)
+    {
+        return FeatureVerification::Invalid;
+    }
+
+    // Check that the feature hasn't been revoked.
+    let mut revocation_list_buf = [0; REVOCATION_LIST_SIGNED_SIZE];
+
+    if let Some(revocation_list_signed) =
+        get_installed_revocation_list(eeprom_controller, &mut revocation_list_buf)
+    {
+        if revocation_list_signed
+            .revocation_list
+            .is_revoked(packaged_feature)
+        {
+            return FeatureVerification::Revoked;
+        }
+    }
+
+    // Check the validity window against the trusted device time elapsed since the feature was first
+    // accepted. Features without a window aren't recorded, as they are always valid.
+    match device_time {
+        Some(now) if packaged_feature.has_validity_window() => {
+            let elapsed =
+                now.saturating_sub(activation_time(eeprom_controller, packaged_feature, now));
+
+            if packaged_feature.is_valid_at(elapsed) {
+                FeatureVerification::Valid
+            } else {
+                FeatureVerification::OutsideValidityWindow
+            }
+        }
+        _ => FeatureVerification::Valid,
+    }
+}
+
+/// Verifies the signature of a [`PackagedFeatureSigned`], then checks it like
+/// [`verify_packaged_feature`]. This function should not be called on an unpaired key fob.
+pub fn verify_packaged_feature_signed<'a>(
+    eeprom_controller: &mut EepromController,
+    packaged_feature_signed: &'a PackagedFeatureSigned<'a>,
+    device_time: Option<DeviceTime>,
+) -> FeatureVerification {
+    // Get the packaged feature.
+    let packaged_feature = &packaged_feature_signed.packaged_feature;
+
+    // Verify the signature. Features without a validity window may have been packaged for protocol
+    // version 1, which signed their version 1 encoding without a tag instead.
+    let mut packaged_feature_buf = [0; SignatureTag::SIZE + PackagedFeatureUnsigned::MAX_SIZE];
+    let packaged_feature_bytes =
+        postcard::to_slice(&packaged_feature.tagged(), &mut packaged_feature_buf)
+            .expect("Failed to serialize packaged feature.");
+    let authentic = verify_feature_signature(
+        eeprom_controller,
+        packaged_feature_bytes,
+        packaged_feature_signed.signature,
+    ) || packaged_feature
+        .to_v1_slice(&mut packaged_feature_buf)
+        .is_some_and(|v1_bytes| {
+            verify_feature_signature(
+                eeprom_controller,
+                v1_bytes,
+                packaged_feature_signed.signature,
+            )
+        });
+
+    if !authentic {
+        return FeatureVerification::Invalid;
+    }
+
+    verify_packaged_feature(eeprom_controller, packaged_feature, device_time)
+}
+
+/// Verifies the signature of a [`DisableFeatureSigned`] and checks the car ID, feature number, and
+/// sequence number associated with it. The sequence number must be greater than that of the last
+/// request accepted, and storing it once the request is accepted and whether it matches the feature
+/// installed are left to the caller. This function should not be called on an unpaired key fob.
+pub fn verify_disable_feature_signed<'a>(
+    eeprom_controller: &mut EepromController,
+    disable_feature_signed: &'a DisableFeatureSigned<'a>,
+) -> bool {
+    let disable_feature = &disable_feature_signed.disable_feature;
+
+    // Verify the signature.
+    let mut disable_feature_buf = [0; SignatureTag::SIZE + DisableFeatureUnsigned::MAX_SIZE];
+    let disable_feature_bytes =
+        postcard::to_slice(&disable_feature.tagged(), &mut disable_feature_buf)
+            .expect("Failed to serialize disable feature request.");
 
-    if car_id != packaged_feature.car_id {
+    if !verify_feature_signature(
+        eeprom_controller,
+        disable_feature_bytes,
+        disable_feature_signed.signature,
+    ) {
         return false;
     }
 
-    matches!(packaged_feature.feature_number, 1..=3)
+    // Check that the car ID matches the car ID in the request, and that the request is newer than
+    // the last one accepted.
+    read_car_id(eeprom_controller) == disable_feature.car_id
+        && is_feature_number(disable_feature.feature_number)
+        && disable_feature.sequence > read_disable_feature_sequence(eeprom_controller)
 }
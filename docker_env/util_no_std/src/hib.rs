@@ -50,4 +50,10 @@ impl HibController {
     pub fn create_timer(&self, duration: Duration) -> HibTimer {
         HibTimer::new(&self.hib, duration)
     }
+
+    /// Gets the time passed since the hibernation clock was started, which is when the
+    /// [`Runtime`](crate::Runtime) was created.
+    pub fn uptime(&self) -> Duration {
+        HibTimer::uptime(&self.hib)
+    }
 }
@@ -5,9 +5,11 @@
 
 pub mod button;
 pub mod communication;
+pub mod device_time;
 pub mod eeprom;
 pub mod features;
 pub mod hib;
+pub mod revocation;
 pub mod timer;
 
 pub(crate) mod random;
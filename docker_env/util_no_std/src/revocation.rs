@@ -0,0 +1,33 @@
+//! This module contains the handler for revocation list requests sent by the install revocation
+//! list host tool, which the car and paired key fobs process the same way.
+
+use crate::{
+    communication::{message_channel::MessageChannel, CommunicationError},
+    features::install_revocation_list_signed,
+    messages::{HostToolAck, ProtocolVersion, Uart0Message},
+    Runtime, RECV_BUFFER_SIZE,
+};
+
+/// Processes a revocation list request, responding with the protocol version ``version`` it was
+/// sent with. Other messages are ignored. This function should not be called on an unpaired key
+/// fob.
+pub fn process_msg(rt: &mut Runtime, msg: &Uart0Message, version: ProtocolVersion) {
+    // Check the message type.
+    let revocation_list_signed = match msg {
+        Uart0Message::RevocationListRequest(msg) => &msg.0,
+        _ => return,
+    };
+
+    // Verify and install the signed revocation list.
+    let status = install_revocation_list_signed(&mut rt.eeprom_controller, revocation_list_signed);
+
+    let mut uart0 =
+        MessageChannel::<_, Uart0Message, RECV_BUFFER_SIZE>::new(&mut rt.uart0_controller);
+    uart0.set_version(version);
+
+    if let Err(CommunicationError::InternalError) =
+        uart0.send(&Uart0Message::RevocationListResponse(HostToolAck(status)))
+    {
+        panic!("Failed to send revocation list response (internal error).");
+    }
+}
@@ -3,12 +3,17 @@
 
 use crate::{
     button::Sw1ButtonController,
-    communication::{Uart0Controller, Uart1Controller},
+    communication::{
+        lower_layers::{crypto::PaddingPolicy, framing::CobsFraming},
+        RxChannel, Uart0Controller, Uart1Controller,
+    },
     eeprom::EepromController,
     hib::HibController,
+    messages::{UnlockChallengeResponse, UnlockMessage, ENVELOPE_VERSION_MAX_SIZE},
     random,
 };
 use chacha20poly1305::Key;
+use core::num::NonZeroUsize;
 use heapless::pool::{
     self,
     singleton::arc::{self, ArcInner, Pool},
@@ -41,6 +46,46 @@ const HIB_POOL_MEMORY_SIZE: usize = 32;
 /// Bits-per-second for UART communications.
 const BPS: u32 = 115200;
 
+/// The largest message the car and fob send and receive, not including any metadata of the UART channels.
+/// This fits the unlock message sent to the unlock host tool with every feature enabled.
+pub const MAX_MESSAGE_SIZE: usize = 1280;
+
+// Check that the unlock message with every feature enabled fits in its envelope.
+const _: () = assert!(
+    ENVELOPE_VERSION_MAX_SIZE + 1 + UnlockMessage::MAX_SIZE <= MAX_MESSAGE_SIZE,
+    "MAX_MESSAGE_SIZE is too small for an unlock message."
+);
+
+/// The size of the buffers the car and fob receive messages into, which fit a message of
+/// [`MAX_MESSAGE_SIZE`] bytes along with the metadata of either UART channel.
+pub const RECV_BUFFER_SIZE: usize = MAX_MESSAGE_SIZE + {
+    let uart0_metadata_size =
+        <Uart0Controller<'static, Uart0TxPin, Uart0RxPin> as RxChannel>::METADATA_SIZE;
+    let uart1_metadata_size =
+        <Uart1Controller<'static, Uart1TxPin, Uart1RxPin, CobsFraming> as RxChannel>::METADATA_SIZE;
+
+    if uart0_metadata_size > uart1_metadata_size {
+        uart0_metadata_size
+    } else {
+        uart1_metadata_size
+    }
+};
+
+/// The size every message sent on UART1 is padded to, which is larger than any message sent between
+/// boards. This hides which message was sent, such as how many features are in an unlock.
+const UART1_PADDING_BUCKET_SIZE: NonZeroUsize = match NonZeroUsize::new(576) {
+    Some(size) => size,
+    None => unreachable!(),
+};
+
+// Check that the unlock challenge response with every feature enabled fits in one bucket along with
+// its envelope and the start of its padding.
+const _: () = assert!(
+    ENVELOPE_VERSION_MAX_SIZE + 1 + UnlockChallengeResponse::MAX_SIZE
+        < UART1_PADDING_BUCKET_SIZE.get(),
+    "UART1_PADDING_BUCKET_SIZE is too small for an unlock challenge response."
+);
+
 /// The TX pin for UART 0.
 pub type Uart0TxPin = PA1<AlternateFunction<AF1, PullUp>>;
 
@@ -137,7 +182,9 @@ pub struct Runtime<'a> {
     pub uart0_controller: Uart0Controller<'a, Uart0TxPin, Uart0RxPin>,
 
     /// The controller for UART1. See the documentation for [`Uart1Controller`] for more details.
-    pub uart1_controller: Uart1Controller<'a, Uart1TxPin, Uart1RxPin>,
+    /// UART1 uses COBS framing to reduce the size of messages sent between boards and pads every message
+    /// to the same size to hide which message was sent.
+    pub uart1_controller: Uart1Controller<'a, Uart1TxPin, Uart1RxPin, CobsFraming>,
 }
 
 impl<'a> Runtime<'a> {
@@ -170,6 +217,10 @@ impl<'a> Runtime<'a> {
             &mut peripherals.uart1_rx,
             uart1_rx_key,
             uart1_tx_key,
+        )
+        .with_padding(
+            PaddingPolicy::Bucket(UART1_PADDING_BUCKET_SIZE),
+            MAX_MESSAGE_SIZE,
         );
 
         Runtime {
@@ -39,6 +39,16 @@ impl<'a> HibTimer<'a> {
         }
     }
 
+    /// Gets the time passed since the hibernation clock was started.
+    pub(crate) fn uptime(hib: &Arc<HibPool>) -> Duration {
+        let (sec, subsec) = Self::get_time_hib(hib);
+
+        Duration::from_secs(sec as u64)
+            + Duration::from_micros(
+                subsec as u64 * Self::MICROSECONDS_PER_SECOND / Self::SUBSECONDS_PER_SECOND,
+            )
+    }
+
     /// Gets the current time from the hibernation clock.
     fn get_time(&self) -> (u32, u16) {
         Self::get_time_hib(self.hib)
@@ -89,4 +99,14 @@ impl<'a> Timer for HibTimer<'a> {
     fn duration(&self) -> Duration {
         self.duration
     }
+
+    fn remaining(&self) -> Duration {
+        let remaining_subseconds = self
+            .end_subseconds
+            .saturating_sub(Self::time_to_subseconds(self.get_time()));
+
+        Duration::from_micros(
+            remaining_subseconds * Self::MICROSECONDS_PER_SECOND / Self::SUBSECONDS_PER_SECOND,
+        )
+    }
 }
@@ -14,7 +14,8 @@
 //!       module. See that module for more detail on the traits and structs provided for this layer.
 //! - The application layer
 //!     - The application layer is the layer responsible for incorporating the lower two layers together.
-//!       This crate provides an implementation of this layer through the [`VerifiedFramedTcpSocket`] struct.
+//!       This crate provides an implementation of this layer through the [`VerifiedFramedTcpSocket`] struct,
+//!       and through the [`AsyncVerifiedFramedTcpSocket`] struct for use with async code.
 
 pub(crate) mod framed_tcp;
 mod verified_framed_tcp;
@@ -1,5 +1,6 @@
 use std::{
-    io::{Read, Write},
+    io::{ErrorKind, Read, Write},
+    marker::PhantomData,
     net::{TcpStream, ToSocketAddrs},
     time::{Duration, Instant},
 };
@@ -7,8 +8,8 @@ use std::{
 use ucsc_ectf_util_common::{
     communication::{
         self,
-        lower_layers::framing::{bogoframing, Frame, FramedTxChannel},
-        CommunicationError, RxChannel,
+        lower_layers::framing::{AsyncFramedTxChannel, Frame, FramedTxChannel, FramingProtocol},
+        yield_now, AsyncRxChannel, CommunicationError, RxChannel,
     },
     timer::Timer,
 };
@@ -32,9 +33,9 @@ fn flush_rx_stream(stream: &mut TcpStream) -> Result<(), CommunicationError> {
     Ok(())
 }
 
-pub(crate) fn connect(
+pub(crate) fn connect<F: FramingProtocol>(
     addr: impl ToSocketAddrs,
-) -> Result<(FramedTcpTxChannel, FramedTcpRxChannel), CommunicationError> {
+) -> Result<(FramedTcpTxChannel<F>, FramedTcpRxChannel<F>), CommunicationError> {
     let stream_tx = TcpStream::connect(addr).map_err(|_| CommunicationError::InternalError)?;
     let mut stream_rx = stream_tx
         .try_clone()
@@ -46,7 +47,28 @@ pub(crate) fn connect(
 
     flush_rx_stream(&mut stream_rx)?;
 
-    Ok((FramedTcpTxChannel(stream_tx), FramedTcpRxChannel(stream_rx)))
+    Ok((
+        FramedTcpTxChannel(stream_tx, PhantomData),
+        FramedTcpRxChannel(stream_rx, 0, PhantomData),
+    ))
+}
+
+/// Connects like [`connect`], but puts the socket in non-blocking mode and gives channels that implement
+/// the async channel traits instead.
+pub(crate) fn connect_async<F: FramingProtocol>(
+    addr: impl ToSocketAddrs,
+) -> Result<(AsyncFramedTcpTxChannel<F>, AsyncFramedTcpRxChannel<F>), CommunicationError> {
+    let (FramedTcpTxChannel(stream_tx, _), FramedTcpRxChannel(stream_rx, ..)) = connect::<F>(addr)?;
+
+    // Both channels share the same socket, so this applies to both of them.
+    stream_tx
+        .set_nonblocking(true)
+        .map_err(|_| CommunicationError::InternalError)?;
+
+    Ok((
+        AsyncFramedTcpTxChannel(stream_tx, PhantomData),
+        AsyncFramedTcpRxChannel(stream_rx, 0, PhantomData),
+    ))
 }
 
 fn read_byte(stream: &mut TcpStream) -> Result<u8, CommunicationError> {
@@ -55,13 +77,16 @@ fn read_byte(stream: &mut TcpStream) -> Result<u8, CommunicationError> {
     // Our socket should never close, so if it does (returns 0), it's an error
     match stream.read(&mut data) {
         Ok(1..) => Ok(data[0]),
+        Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+            Err(CommunicationError::Timeout)
+        }
         _ => Err(CommunicationError::RecvError),
     }
 }
 
-pub struct FramedTcpRxChannel(TcpStream);
+pub struct FramedTcpRxChannel<F: FramingProtocol>(TcpStream, usize, PhantomData<F>);
 
-impl RxChannel for FramedTcpRxChannel {
+impl<F: FramingProtocol> RxChannel for FramedTcpRxChannel<F> {
     fn recv_with_data_timeout<T: Timer>(
         &mut self,
         dest: &mut [u8],
@@ -74,12 +99,13 @@ impl RxChannel for FramedTcpRxChannel {
             .set_read_timeout(Some(timeout_duration))
             .map_err(|_| CommunicationError::InternalError)?;
 
-        bogoframing::recv_frame_with_timeout(
-            self,
+        F::recv_frame_with_timeout(
+            &mut self.0,
             dest,
             timer,
-            |ch| read_byte(&mut ch.0),
+            read_byte,
             MIN_FRAMED_UART_MESSAGE,
+            &mut self.1,
         )
     }
 
@@ -93,35 +119,41 @@ impl RxChannel for FramedTcpRxChannel {
         let start_instant = Instant::now();
         let timeout_duration = timer.duration();
 
-        bogoframing::recv_frame_with_timeout(
-            self,
+        F::recv_frame_with_timeout(
+            &mut self.0,
             dest,
             timer,
-            |ch| {
+            |stream| {
                 let read_timeout = timeout_duration.saturating_sub(Instant::now() - start_instant);
                 if read_timeout == Duration::ZERO {
-                    return Err(CommunicationError::RecvError);
+                    return Err(CommunicationError::Timeout);
                 }
 
                 // We keep track of this to not kill our CPUs on host tools :)
-                ch.0.set_read_timeout(Some(read_timeout))
+                stream
+                    .set_read_timeout(Some(read_timeout))
                     .map_err(|_| CommunicationError::InternalError)?;
 
-                read_byte(&mut ch.0)
+                read_byte(stream)
             },
             MIN_FRAMED_UART_MESSAGE,
+            &mut self.1,
         )
     }
+
+    fn take_discarded_bytes(&mut self) -> usize {
+        std::mem::take(&mut self.1)
+    }
 }
 
-pub struct FramedTcpTxChannel(TcpStream);
+pub struct FramedTcpTxChannel<F: FramingProtocol>(TcpStream, PhantomData<F>);
 
-impl FramedTxChannel for FramedTcpTxChannel {
+impl<F: FramingProtocol> FramedTxChannel for FramedTcpTxChannel<F> {
     fn frame<'a, const FRAME_CT: usize>(
         &mut self,
         frame: impl FnOnce() -> Result<Frame<'a, FRAME_CT>, CommunicationError>,
     ) -> communication::Result<()> {
-        bogoframing::frame_bogoframe(
+        F::frame(
             self,
             frame()?,
             |ch, s| ch.0.write_all(s).map_err(|_| CommunicationError::SendError),
@@ -129,3 +161,84 @@ impl FramedTxChannel for FramedTcpTxChannel {
         )
     }
 }
+
+pub struct AsyncFramedTcpRxChannel<F: FramingProtocol>(TcpStream, usize, PhantomData<F>);
+
+impl<F: FramingProtocol> AsyncRxChannel for AsyncFramedTcpRxChannel<F> {
+    async fn recv_with_data_timeout<T: Timer>(
+        &mut self,
+        dest: &mut [u8],
+        timer: &mut T,
+    ) -> communication::Result<usize> {
+        F::async_recv_frame_with_data_timeout(
+            &mut self.0,
+            dest,
+            timer,
+            read_byte,
+            MIN_FRAMED_UART_MESSAGE,
+            &mut self.1,
+        )
+        .await
+    }
+
+    async fn recv_with_timeout<T: Timer>(
+        &mut self,
+        dest: &mut [u8],
+        timer: &mut T,
+    ) -> communication::Result<usize> {
+        F::async_recv_frame_with_timeout(
+            &mut self.0,
+            dest,
+            timer,
+            read_byte,
+            MIN_FRAMED_UART_MESSAGE,
+            &mut self.1,
+        )
+        .await
+    }
+
+    fn take_discarded_bytes(&mut self) -> usize {
+        std::mem::take(&mut self.1)
+    }
+}
+
+/// Writes all of ``buf`` to a non-blocking ``stream``, yielding to the executor while the socket's
+/// send buffer is full.
+async fn write_all_async(stream: &mut TcpStream, mut buf: &[u8]) -> communication::Result<()> {
+    while !buf.is_empty() {
+        match stream.write(buf) {
+            Ok(0) => return Err(CommunicationError::SendError),
+            Ok(written) => buf = &buf[written..],
+            Err(e) if e.kind() == ErrorKind::WouldBlock => yield_now().await,
+            Err(e) if e.kind() == ErrorKind::Interrupted => (),
+            Err(_) => return Err(CommunicationError::SendError),
+        }
+    }
+
+    Ok(())
+}
+
+pub struct AsyncFramedTcpTxChannel<F: FramingProtocol>(TcpStream, PhantomData<F>);
+
+impl<F: FramingProtocol> AsyncFramedTxChannel for AsyncFramedTcpTxChannel<F> {
+    async fn frame<'a, const FRAME_CT: usize>(
+        &mut self,
+        frame: impl FnOnce() -> Result<Frame<'a, FRAME_CT>, CommunicationError>,
+    ) -> communication::Result<()> {
+        // The frame is encoded up front so a full send buffer can be waited on between writes.
+        let mut encoded = Vec::new();
+
+        F::frame(
+            &mut encoded,
+            frame()?,
+            |buf, s| {
+                buf.extend_from_slice(s);
+
+                Ok(())
+            },
+            MIN_FRAMED_UART_MESSAGE,
+        )?;
+
+        write_all_async(&mut self.0, &encoded).await
+    }
+}
@@ -4,19 +4,30 @@ use rand::RngCore;
 use ucsc_ectf_util_common::{
     communication::{
         self,
-        lower_layers::crypto::{
-            RandomSource, XChacha20Poly1305RxChannel, XChacha20Poly1305TxChannel,
+        lower_layers::{
+            crypto::{RandomSource, XChacha20Poly1305RxChannel, XChacha20Poly1305TxChannel},
+            framing::{BogoFraming, FramingProtocol},
         },
-        RxChannel, TxChannel,
+        stats::{LayerStats, OptionalStats, StatsChannel},
+        AsyncRxChannel, AsyncTxChannel, RxChannel, TxChannel,
     },
     timer::Timer,
 };
 
-use super::framed_tcp::{self, FramedTcpRxChannel, FramedTcpTxChannel};
+use super::framed_tcp::{
+    self, AsyncFramedTcpRxChannel, AsyncFramedTcpTxChannel, FramedTcpRxChannel, FramedTcpTxChannel,
+};
+
+type VerifiedFramedTcpTxChannel<F> =
+    XChacha20Poly1305TxChannel<StatsChannel<FramedTcpTxChannel<F>>, StdRandomSource>;
 
-type VerifiedFramedTcpTxChannel = XChacha20Poly1305TxChannel<FramedTcpTxChannel, StdRandomSource>;
+type VerifiedFramedTcpRxChannel<F> =
+    XChacha20Poly1305RxChannel<StatsChannel<FramedTcpRxChannel<F>>>;
 
-type VerifiedFramedTcpRxChannel = XChacha20Poly1305RxChannel<FramedTcpRxChannel>;
+type AsyncVerifiedFramedTcpTxChannel<F> =
+    XChacha20Poly1305TxChannel<AsyncFramedTcpTxChannel<F>, StdRandomSource>;
+
+type AsyncVerifiedFramedTcpRxChannel<F> = XChacha20Poly1305RxChannel<AsyncFramedTcpRxChannel<F>>;
 
 /// This [`RandomSource`] uses OS-provided entropy to generate random numbers.
 pub struct StdRandomSource {
@@ -30,41 +41,99 @@ impl RandomSource for StdRandomSource {
 }
 
 /// This struct contains an [`RxChannel`] and [`TxChannel`] for a TCP socket that frames messages
-/// using BogoFraming. See the [`framing`](super::lower_layers::framing) module for more information
-/// on this type of framing.
-pub struct VerifiedFramedTcpSocket {
-    tx_channel: VerifiedFramedTcpTxChannel,
-    rx_channel: VerifiedFramedTcpRxChannel,
+/// using the [`FramingProtocol`] given by the ``F`` type parameter, which defaults to BogoFraming.
+/// See the [`framing`](super::lower_layers::framing) module for more information on the types of
+/// framing available.
+///
+/// A socket configured with [`with_stats`](Self::with_stats) counts the messages sent and received and
+/// the errors given on both the framing and the encryption layer, which can be read with
+/// [`stats`](Self::stats) for diagnostics. The framing layer counts the encrypted bytes sent over the
+/// socket, framing errors, and the bytes skipped while looking for a frame, while the encryption layer
+/// counts the decrypted bytes and authentication failures. See [`LayerStats`] and
+/// [`ChannelStats`](communication::stats::ChannelStats) for the counters kept.
+pub struct VerifiedFramedTcpSocket<F: FramingProtocol = BogoFraming> {
+    tx_channel: VerifiedFramedTcpTxChannel<F>,
+    rx_channel: VerifiedFramedTcpRxChannel<F>,
+    stats: OptionalStats,
 }
 
 impl VerifiedFramedTcpSocket {
     /// This connects to the provided address over TCP and creates a [`VerifiedFramedTcpSocket`]
-    /// from the connection.
+    /// from the connection using BogoFraming.
     pub fn keyless_connect(addr: impl ToSocketAddrs) -> communication::Result<Self> {
+        Self::keyless_connect_with_framing(addr)
+    }
+}
+
+impl<F: FramingProtocol> VerifiedFramedTcpSocket<F> {
+    /// This connects to the provided address over TCP and creates a [`VerifiedFramedTcpSocket`]
+    /// from the connection using the framing protocol given by the ``F`` type parameter.
+    pub fn keyless_connect_with_framing(addr: impl ToSocketAddrs) -> communication::Result<Self> {
         let (framed_tx_channel, framed_rx_channel) = framed_tcp::connect(addr)?;
         let tx_channel = XChacha20Poly1305TxChannel::new(
-            framed_tx_channel,
+            StatsChannel::new_disabled(framed_tx_channel),
             StdRandomSource {
                 _not_constructible: (),
             },
             &Default::default(),
         );
-        let rx_channel = XChacha20Poly1305RxChannel::new(framed_rx_channel, &Default::default());
+        let rx_channel = XChacha20Poly1305RxChannel::new(
+            StatsChannel::new_disabled(framed_rx_channel),
+            &Default::default(),
+        );
 
         Ok(VerifiedFramedTcpSocket {
             tx_channel,
             rx_channel,
+            stats: OptionalStats::default(),
         })
     }
+
+    /// Makes this socket count the messages sent and received and the errors given. See the
+    /// struct-level documentation for more info.
+    pub fn with_stats(mut self) -> Self {
+        self.tx_channel.channel_mut().enable_stats();
+        self.rx_channel.channel_mut().enable_stats();
+        self.stats.enable();
+
+        self
+    }
+
+    /// Gets the counters of each layer of this socket, or [`None`] if it wasn't configured with
+    /// [`with_stats`](Self::with_stats). The framing layer counters combine the sending and receiving
+    /// ends of the socket.
+    pub fn stats(&self) -> Option<LayerStats> {
+        let framing = self
+            .rx_channel
+            .channel()
+            .stats()?
+            .combined(self.tx_channel.channel().stats()?);
+
+        Some(LayerStats {
+            framing,
+            crypto: self.stats.get()?,
+        })
+    }
+
+    /// Resets the counters of this socket to zero.
+    pub fn reset_stats(&mut self) {
+        self.tx_channel.channel_mut().reset_stats();
+        self.rx_channel.channel_mut().reset_stats();
+        self.stats.reset();
+    }
 }
 
-impl RxChannel for VerifiedFramedTcpSocket {
+impl<F: FramingProtocol> RxChannel for VerifiedFramedTcpSocket<F> {
+    const METADATA_SIZE: usize = <VerifiedFramedTcpRxChannel<F> as RxChannel>::METADATA_SIZE;
+
     fn recv_with_data_timeout<T: Timer>(
         &mut self,
         dest: &mut [u8],
         timer: &mut T,
     ) -> communication::Result<usize> {
-        self.rx_channel.recv_with_data_timeout(dest, timer)
+        let result = self.rx_channel.recv_with_data_timeout(dest, timer);
+
+        self.stats.record_recv(result)
     }
 
     fn recv_with_timeout<T: Timer>(
@@ -72,12 +141,81 @@ impl RxChannel for VerifiedFramedTcpSocket {
         dest: &mut [u8],
         timer: &mut T,
     ) -> communication::Result<usize> {
-        self.rx_channel.recv_with_timeout(dest, timer)
+        let result = self.rx_channel.recv_with_timeout(dest, timer);
+
+        self.stats.record_recv(result)
     }
 }
 
-impl TxChannel for VerifiedFramedTcpSocket {
+impl<F: FramingProtocol> TxChannel for VerifiedFramedTcpSocket<F> {
     fn send(&mut self, src: &mut [u8]) -> communication::Result<()> {
-        self.tx_channel.send(src)
+        let len = src.len();
+        let result = self.tx_channel.send(src);
+
+        self.stats.record_send(len, result)
+    }
+}
+
+/// This struct is the async counterpart of [`VerifiedFramedTcpSocket`], containing an [`AsyncRxChannel`]
+/// and [`AsyncTxChannel`] for a non-blocking TCP socket. Waiting for data yields to the executor instead of
+/// blocking the thread, so one thread can service multiple sockets at once.
+pub struct AsyncVerifiedFramedTcpSocket<F: FramingProtocol = BogoFraming> {
+    tx_channel: AsyncVerifiedFramedTcpTxChannel<F>,
+    rx_channel: AsyncVerifiedFramedTcpRxChannel<F>,
+}
+
+impl AsyncVerifiedFramedTcpSocket {
+    /// This connects to the provided address over TCP and creates an [`AsyncVerifiedFramedTcpSocket`]
+    /// from the connection using BogoFraming.
+    pub fn keyless_connect(addr: impl ToSocketAddrs) -> communication::Result<Self> {
+        Self::keyless_connect_with_framing(addr)
+    }
+}
+
+impl<F: FramingProtocol> AsyncVerifiedFramedTcpSocket<F> {
+    /// This connects to the provided address over TCP and creates an [`AsyncVerifiedFramedTcpSocket`]
+    /// from the connection using the framing protocol given by the ``F`` type parameter.
+    pub fn keyless_connect_with_framing(addr: impl ToSocketAddrs) -> communication::Result<Self> {
+        let (framed_tx_channel, framed_rx_channel) = framed_tcp::connect_async(addr)?;
+        let tx_channel = XChacha20Poly1305TxChannel::new(
+            framed_tx_channel,
+            StdRandomSource {
+                _not_constructible: (),
+            },
+            &Default::default(),
+        );
+        let rx_channel = XChacha20Poly1305RxChannel::new(framed_rx_channel, &Default::default());
+
+        Ok(AsyncVerifiedFramedTcpSocket {
+            tx_channel,
+            rx_channel,
+        })
+    }
+}
+
+impl<F: FramingProtocol> AsyncRxChannel for AsyncVerifiedFramedTcpSocket<F> {
+    const METADATA_SIZE: usize =
+        <AsyncVerifiedFramedTcpRxChannel<F> as AsyncRxChannel>::METADATA_SIZE;
+
+    async fn recv_with_data_timeout<T: Timer>(
+        &mut self,
+        dest: &mut [u8],
+        timer: &mut T,
+    ) -> communication::Result<usize> {
+        self.rx_channel.recv_with_data_timeout(dest, timer).await
+    }
+
+    async fn recv_with_timeout<T: Timer>(
+        &mut self,
+        dest: &mut [u8],
+        timer: &mut T,
+    ) -> communication::Result<usize> {
+        self.rx_channel.recv_with_timeout(dest, timer).await
+    }
+}
+
+impl<F: FramingProtocol> AsyncTxChannel for AsyncVerifiedFramedTcpSocket<F> {
+    async fn send(&mut self, src: &mut [u8]) -> communication::Result<()> {
+        self.tx_channel.send(src).await
     }
 }
@@ -35,4 +35,8 @@ impl Timer for StdTimer {
     fn duration(&self) -> Duration {
         self.duration
     }
+
+    fn remaining(&self) -> Duration {
+        self.end.saturating_duration_since(Instant::now())
+    }
 }
@@ -270,6 +270,13 @@ fn main() {
                 EepromReadWriteField::PairingByte,
                 &[1u8; BYTE_FIELD_SIZE],
             );
+
+            // No disable feature request has been accepted when the fob is built.
+            eeprom_field_from_buf(
+                &mut eeprom_file,
+                EepromReadWriteField::DisableFeatureSequence,
+                &0u32.to_be_bytes(),
+            );
         } else {
             // Is unpaired key fob.
             eeprom_field_from_path(
@@ -0,0 +1,84 @@
+use crate::{features, RECV_BUFFER_SIZE};
+use ucsc_ectf_util_no_std::{
+    communication::{message_channel::MessageChannel, CommunicationError},
+    eeprom::{EepromController, EepromReadWriteField, PACKAGED_FEATURE_SIZE},
+    features::verify_disable_feature_signed,
+    messages::{DisableFeatureSigned, HostToolAck, ProtocolVersion, Uart0Message},
+    Runtime,
+};
+
+/// Clears the feature named by a [`DisableFeatureSigned`] if it is installed, returning whether it
+/// was cleared. The digest of the feature cleared is kept in its field so that it can't be
+/// installed again.
+fn disable_feature(
+    eeprom_controller: &mut EepromController,
+    disable_feature_signed: &DisableFeatureSigned,
+) -> bool {
+    // Verify the signed disable feature request.
+    if !verify_disable_feature_signed(eeprom_controller, disable_feature_signed) {
+        return false;
+    }
+
+    let disable_feature = &disable_feature_signed.disable_feature;
+
+    let Some(feature_eeprom_field) =
+        EepromReadWriteField::feature_packaged(disable_feature.feature_number)
+    else {
+        return false;
+    };
+
+    // Check that the feature installed is the one named by the request. Features are verified
+    // before they are installed, so they aren't verified again. This allows revoked features to be
+    // removed too.
+    let mut packaged_feature_buf = [0; PACKAGED_FEATURE_SIZE];
+    eeprom_controller
+        .read_slice(feature_eeprom_field, &mut packaged_feature_buf)
+        .expect("EEPROM read failed: packaged feature.");
+
+    match features::installed_feature(&packaged_feature_buf) {
+        Some(installed) if disable_feature.matches(&installed) => (),
+        _ => return false,
+    }
+
+    // Store the sequence number once the request is accepted so that it can't be accepted again.
+    // Requests that don't match the feature installed leave it unchanged, so a request sent before
+    // its feature is installed can still be used afterwards.
+    eeprom_controller
+        .write_slice(
+            EepromReadWriteField::DisableFeatureSequence,
+            &disable_feature.sequence.to_be_bytes(),
+        )
+        .expect("EEPROM write failed: disable feature sequence.");
+
+    // Replace the packaged feature with its digest.
+    eeprom_controller
+        .write_slice(
+            feature_eeprom_field,
+            &features::disabled_feature_field(&disable_feature.feature_digest),
+        )
+        .expect("EEPROM write failed: packaged feature.");
+
+    true
+}
+
+/// Processes a disable feature request, responding with the protocol version ``version`` it was
+/// sent with.
+pub(crate) fn paired_process_msg(rt: &mut Runtime, msg: &Uart0Message, version: ProtocolVersion) {
+    // Check the message type.
+    let disable_feature_signed = match msg {
+        Uart0Message::DisableFeatureRequest(msg) => &msg.0,
+        _ => return,
+    };
+
+    let status = disable_feature(&mut rt.eeprom_controller, disable_feature_signed);
+
+    let mut uart0 =
+        MessageChannel::<_, Uart0Message, RECV_BUFFER_SIZE>::new(&mut rt.uart0_controller);
+    uart0.set_version(version);
+
+    if let Err(CommunicationError::InternalError) =
+        uart0.send(&Uart0Message::DisableFeatureResponse(HostToolAck(status)))
+    {
+        panic!("Failed to send disable feature response (internal error).");
+    }
+}
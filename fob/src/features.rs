@@ -1,82 +1,160 @@
-use crate::MAX_MESSAGE_SIZE;
+use crate::RECV_BUFFER_SIZE;
+use core::mem;
 use ucsc_ectf_util_no_std::{
-    communication::{CommunicationError, TxChannel},
-    eeprom::{EepromController, EepromReadWriteField, PACKAGED_FEATURE_SIGNED_SIZE},
-    features::verify_packaged_feature_signed,
-    messages::{FeatureNumber, HostToolAck, PackagedFeatureSigned, Uart0Message},
+    communication::{message_channel::MessageChannel, CommunicationError},
+    eeprom::{EepromController, EepromReadWriteField, PACKAGED_FEATURE_SIZE},
+    features::{verify_packaged_feature, verify_packaged_feature_signed, FeatureVerification},
+    messages::{
+        FeatureDigest, FeatureNumber, HostToolAck, PackagedFeatureUnsigned, ProtocolVersion,
+        Uart0Message,
+    },
     Runtime,
 };
 
-/// Gets the feature with the given feature number if it is installed. The slice is used to store
-/// the [`PackagedFeatureSigned`] in serialized form, which will be used by the returned
-/// [`PackagedFeatureSigned`]. This function should not be called on an unpaired key fob.
-pub(crate) fn get_installed_feature<'a>(
+/// The first byte of a feature field holding an installed feature, which is followed by the
+/// [`PackagedFeatureUnsigned`] in serialized form. Its signature is verified before it is installed,
+/// so it isn't stored.
+const INSTALLED_FEATURE_TAG: u8 = 1;
+
+/// The first byte of a feature field holding the [`FeatureDigest`] of the last feature disabled in
+/// it, which is followed by the digest. That feature can't be installed again. Fields holding
+/// neither tag are empty.
+const DISABLED_FEATURE_TAG: u8 = 2;
+
+// Check that an installed feature and a disabled feature both fit in a feature field.
+const _: () = assert!(
+    1 + PackagedFeatureUnsigned::MAX_SIZE <= PACKAGED_FEATURE_SIZE
+        && 1 + mem::size_of::<FeatureDigest>() <= PACKAGED_FEATURE_SIZE,
+    "PACKAGED_FEATURE_SIZE is too small for a feature."
+);
+
+/// Gets the feature installed in a feature field read into ``buf`` without verifying it, or
+/// [`None`] if the field doesn't hold an installed feature.
+pub(crate) fn installed_feature(buf: &[u8]) -> Option<PackagedFeatureUnsigned> {
+    match buf.split_first() {
+        Some((&INSTALLED_FEATURE_TAG, bytes)) => postcard::from_bytes(bytes).ok(),
+        _ => None,
+    }
+}
+
+/// Gets the digest of the feature disabled in a feature field read into ``buf``, or [`None`] if
+/// the field doesn't hold a disabled feature.
+fn disabled_feature(buf: &[u8]) -> Option<FeatureDigest> {
+    match buf.split_first() {
+        Some((&DISABLED_FEATURE_TAG, bytes)) => bytes
+            .get(..mem::size_of::<FeatureDigest>())?
+            .try_into()
+            .ok(),
+        _ => None,
+    }
+}
+
+/// Gets the contents of a feature field holding the installed feature ``packaged_feature``.
+fn installed_feature_field(
+    packaged_feature: &PackagedFeatureUnsigned,
+) -> [u8; PACKAGED_FEATURE_SIZE] {
+    let mut buf = [0; PACKAGED_FEATURE_SIZE];
+    buf[0] = INSTALLED_FEATURE_TAG;
+    postcard::to_slice(packaged_feature, &mut buf[1..])
+        .expect("Failed to serialize packaged feature.");
+
+    buf
+}
+
+/// Gets the contents of a feature field holding the disabled feature with the digest
+/// ``feature_digest``.
+pub(crate) fn disabled_feature_field(
+    feature_digest: &FeatureDigest,
+) -> [u8; PACKAGED_FEATURE_SIZE] {
+    let mut buf = [0; PACKAGED_FEATURE_SIZE];
+    buf[0] = DISABLED_FEATURE_TAG;
+    buf[1..1 + feature_digest.len()].copy_from_slice(feature_digest);
+
+    buf
+}
+
+/// Gets the feature with the given feature number if it is installed and hasn't been revoked since.
+/// This function should not be called on an unpaired key fob.
+pub(crate) fn get_installed_feature(
     eeprom_controller: &mut EepromController,
     feature: FeatureNumber,
-    buf: &'a mut [u8; PACKAGED_FEATURE_SIGNED_SIZE],
-) -> Option<PackagedFeatureSigned<'a>> {
-    let feature_eeprom_field = match feature {
-        1 => EepromReadWriteField::FeatureOneSignedPackaged,
-        2 => EepromReadWriteField::FeatureTwoSignedPackaged,
-        3 => EepromReadWriteField::FeatureThreeSignedPackaged,
-        _ => return None,
-    };
+) -> Option<PackagedFeatureUnsigned> {
+    let feature_eeprom_field = EepromReadWriteField::feature_packaged(feature)?;
 
-    // Read the appropriate signed packaged feature field from EEPROM.
+    // Read the appropriate packaged feature field from EEPROM.
+    let mut buf = [0; PACKAGED_FEATURE_SIZE];
     eeprom_controller
-        .read_slice(feature_eeprom_field, buf)
-        .expect("EEPROM read failed: signed packaged feature.");
+        .read_slice(feature_eeprom_field, &mut buf)
+        .expect("EEPROM read failed: packaged feature.");
 
-    let packaged_feature_signed = postcard::from_bytes::<PackagedFeatureSigned>(buf).ok()?;
+    let packaged_feature = installed_feature(&buf)?;
 
-    // Verify the signed packaged feature.
-    verify_packaged_feature_signed(eeprom_controller, &packaged_feature_signed)
-        .then_some(packaged_feature_signed)
+    // Check the packaged feature again, as its signature was verified before it was installed but a
+    // revocation list may have been installed since. The key fob has no trusted device time, so the
+    // validity window is checked by the car.
+    (verify_packaged_feature(eeprom_controller, &packaged_feature, None)
+        == FeatureVerification::Valid)
+        .then_some(packaged_feature)
 }
 
-fn send_ack(rt: &mut Runtime, status: bool) {
-    let mut buf = [0; MAX_MESSAGE_SIZE];
-    let res = postcard::to_slice(
-        &Uart0Message::EnableFeatureResponse(HostToolAck(status)),
-        &mut buf,
-    )
-    .expect("Failed to serialize enable feature response.");
+fn send_ack(rt: &mut Runtime, status: bool, version: ProtocolVersion) {
+    let mut uart0 =
+        MessageChannel::<_, Uart0Message, RECV_BUFFER_SIZE>::new(&mut rt.uart0_controller);
+    uart0.set_version(version);
 
-    if let Err(CommunicationError::InternalError) = rt.uart0_controller.send(res) {
+    if let Err(CommunicationError::InternalError) =
+        uart0.send(&Uart0Message::EnableFeatureResponse(HostToolAck(status)))
+    {
         panic!("Failed to send enable feature response (internal error).");
     }
 }
 
-pub(crate) fn paired_process_msg(rt: &mut Runtime, msg: &Uart0Message) {
+/// Processes an enable feature request, responding with the protocol version ``version`` it was sent
+/// with.
+pub(crate) fn paired_process_msg(rt: &mut Runtime, msg: &Uart0Message, version: ProtocolVersion) {
     // Check the message type.
     let packaged_feature_signed = match msg {
         Uart0Message::EnableFeatureRequest(msg) => &msg.0,
         _ => return,
     };
 
-    // Verify the signed packaged feature.
-    if !verify_packaged_feature_signed(&mut rt.eeprom_controller, packaged_feature_signed) {
-        send_ack(rt, false);
+    // Verify the signed packaged feature. Features outside of their validity window are still
+    // installed, as the key fob has no trusted device time.
+    if verify_packaged_feature_signed(&mut rt.eeprom_controller, packaged_feature_signed, None)
+        != FeatureVerification::Valid
+    {
+        send_ack(rt, false, version);
         return;
     };
 
-    // Write the signed packaged feature to the appropriate EEPROM field.
-    let feature_eeprom_field = match packaged_feature_signed.packaged_feature.feature_number {
-        1 => EepromReadWriteField::FeatureOneSignedPackaged,
-        2 => EepromReadWriteField::FeatureTwoSignedPackaged,
-        3 => EepromReadWriteField::FeatureThreeSignedPackaged,
-        _ => {
-            send_ack(rt, false);
-            return;
-        }
+    // Write the packaged feature to the appropriate EEPROM field.
+    let packaged_feature = &packaged_feature_signed.packaged_feature;
+
+    let Some(feature_eeprom_field) =
+        EepromReadWriteField::feature_packaged(packaged_feature.feature_number)
+    else {
+        send_ack(rt, false, version);
+        return;
     };
 
-    let mut packaged_feature_signed_buf = [0; PACKAGED_FEATURE_SIGNED_SIZE];
-    postcard::to_slice(&packaged_feature_signed, &mut packaged_feature_signed_buf)
-        .expect("Failed to serialize signed packaged feature.");
+    // Refuse the feature if it was the last one disabled in its field, so that a disabled feature
+    // can't simply be enabled again.
+    let mut packaged_feature_buf = [0; PACKAGED_FEATURE_SIZE];
+    rt.eeprom_controller
+        .read_slice(feature_eeprom_field, &mut packaged_feature_buf)
+        .expect("EEPROM read failed: packaged feature.");
+
+    if disabled_feature(&packaged_feature_buf) == Some(packaged_feature.digest()) {
+        send_ack(rt, false, version);
+        return;
+    }
+
     rt.eeprom_controller
-        .write_slice(feature_eeprom_field, &packaged_feature_signed_buf)
-        .expect("EEPROM write failed: signed packaged feature.");
+        .write_slice(
+            feature_eeprom_field,
+            &installed_feature_field(packaged_feature),
+        )
+        .expect("EEPROM write failed: packaged feature.");
 
-    send_ack(rt, true);
+    send_ack(rt, true, version);
 }
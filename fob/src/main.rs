@@ -11,18 +11,23 @@ use cortex_m::interrupt;
 use cortex_m_rt::entry;
 use tm4c123x_hal::{CorePeripherals, Peripherals};
 use ucsc_ectf_util_no_std::{
-    communication::RxChannel,
+    communication::{message_channel::MessageChannel, CommunicationError, RxChannel, TxChannel},
     eeprom::{EepromReadWriteField, BYTE_FIELD_SIZE},
-    messages::Uart0Message,
+    messages::{from_envelope_bytes, Capabilities, ProtocolVersion, Uart0Message, Uart1Message},
+    revocation,
+    timer::Timer,
     Runtime, RuntimePeripherals,
 };
 
+mod disable;
 mod features;
 mod pairing;
 mod unlock;
 
-/// The maximum size of a message that can be received/sent.
-pub const MAX_MESSAGE_SIZE: usize = 1024;
+/// The size of the buffers messages are received into, which fit a message of
+/// [`MAX_MESSAGE_SIZE`](ucsc_ectf_util_no_std::MAX_MESSAGE_SIZE) bytes along with the metadata of the UART
+/// channels.
+pub const RECV_BUFFER_SIZE: usize = ucsc_ectf_util_no_std::RECV_BUFFER_SIZE;
 
 const UNPAIRED: u8 = 0;
 const MS_TO_WAIT_FOR_MSG: u64 = 5;
@@ -39,6 +44,37 @@ jump_to_reset:
 "#
 );
 
+/// Sends a hello in response to a hello received on UART0.
+fn respond_to_hello(rt: &mut Runtime) {
+    if let Err(CommunicationError::InternalError) =
+        MessageChannel::<_, Uart0Message, RECV_BUFFER_SIZE>::new(&mut rt.uart0_controller)
+            .send_hello()
+    {
+        panic!("Failed to send hello (internal error).");
+    }
+}
+
+/// Starts a session on UART1 by exchanging a hello with the peer through ``uart1``, which then sends
+/// messages with the negotiated version. Returns the negotiated version, or [`None`] if no session could
+/// be started, including if the peer doesn't support [`Capabilities::REQUIRED_UART1`].
+fn start_uart1_session<C: TxChannel + RxChannel, T: Timer>(
+    uart1: &mut MessageChannel<C, Uart1Message, RECV_BUFFER_SIZE>,
+    timer: &mut T,
+) -> Option<ProtocolVersion> {
+    let parameters = match uart1.exchange_hello_with_timeout(timer) {
+        Ok(parameters) => parameters,
+        Err(CommunicationError::InternalError) => {
+            panic!("Failed to exchange hello (internal error).")
+        }
+        Err(_) => return None,
+    };
+
+    parameters
+        .capabilities
+        .contains(Capabilities::REQUIRED_UART1)
+        .then_some(parameters.version)
+}
+
 #[entry]
 fn main() -> ! {
     // Enable interrupts because the bootloader disables them and leaves them disabled.
@@ -73,22 +109,30 @@ fn main() -> ! {
 
     // Listen for pairing requests from host, features, and button presses.
     loop {
-        let mut receive_buffer = [0; MAX_MESSAGE_SIZE];
+        let mut receive_buffer = [0; RECV_BUFFER_SIZE];
 
         // Process message if one is received on UART0.
-        if let Ok(size_read) = rt.uart0_controller.recv_with_data_timeout(
+        if let Ok(received) = rt.uart0_controller.recv_view_with_data_timeout(
             &mut receive_buffer,
             &mut rt
                 .hib_controller
                 .create_timer(Duration::from_millis(MS_TO_WAIT_FOR_MSG)),
         ) {
-            let msg = match postcard::from_bytes::<Uart0Message>(&receive_buffer[..size_read]) {
-                Ok(msg) => msg,
+            let envelope = match from_envelope_bytes::<Uart0Message>(received) {
+                Ok(envelope) => envelope,
                 Err(_) => continue,
             };
 
-            pairing::paired_process_msg(&mut rt, &msg);
-            features::paired_process_msg(&mut rt, &msg);
+            // Respond to a host tool starting a session.
+            if let Uart0Message::Hello(_) = envelope.message {
+                respond_to_hello(&mut rt);
+                continue;
+            }
+
+            pairing::paired_process_msg(&mut rt, &envelope.message);
+            features::paired_process_msg(&mut rt, &envelope.message, envelope.version);
+            revocation::process_msg(&mut rt, &envelope.message, envelope.version);
+            disable::paired_process_msg(&mut rt, &envelope.message, envelope.version);
         }
 
         // Process SW1 button press.
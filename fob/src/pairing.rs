@@ -1,10 +1,10 @@
-use crate::MAX_MESSAGE_SIZE;
+use crate::RECV_BUFFER_SIZE;
 use core::time::Duration;
 use ucsc_ectf_util_no_std::{
-    communication::{CommunicationError, TxChannel},
+    communication::{message_channel::MessageChannel, CommunicationError},
     eeprom::{EepromController, EepromReadWriteField, BYTE_FIELD_SIZE, PAIRING_PIN_SIZE},
     hib::HibController,
-    messages::{HostToolAck, Uart0Message},
+    messages::{HostToolAck, ProtocolVersion, Uart0Message, MIN_PROTOCOL_VERSION},
     timer::Timer,
     Runtime,
 };
@@ -13,15 +13,16 @@ use zeroize::Zeroize;
 mod diffie_hellman;
 mod pairing_sequence;
 
+/// Sends an acknowledgement to the host tool. The host tool never starts a session with the unpaired key
+/// fob, so the oldest version is used so that any host tool can decode it.
 fn send_ack(rt: &mut Runtime) {
-    let mut buf = [0; MAX_MESSAGE_SIZE];
-    let res = postcard::to_slice(
-        &Uart0Message::PairingPinResponse(HostToolAck(true)),
-        &mut buf,
-    )
-    .expect("Failed to serialize pairing response.");
-
-    if let Err(CommunicationError::InternalError) = rt.uart0_controller.send(res) {
+    let mut uart0 =
+        MessageChannel::<_, Uart0Message, RECV_BUFFER_SIZE>::new(&mut rt.uart0_controller);
+    uart0.set_version(MIN_PROTOCOL_VERSION);
+
+    if let Err(CommunicationError::InternalError) =
+        uart0.send(&Uart0Message::PairingPinResponse(HostToolAck(true)))
+    {
         panic!("Failed to send pairing response (internal error).");
     }
 }
@@ -99,18 +100,20 @@ fn set_pairing_longer_cooldown_byte(rt: &mut Runtime, to_set: bool) {
         .expect("EEPROM write failed: pairing longer cooldown byte.");
 }
 
+/// Checks a pairing PIN attempt and performs the Diffie-Hellman key exchange if it's correct. Returns the
+/// protocol version negotiated with the unpaired key fob.
 fn check_pin_and_diffie_hellman(
     rt: &mut Runtime,
     pairing_pin_attempt: u32,
     pairing_longer_cooldown_byte: u8,
-) -> bool {
+) -> Option<ProtocolVersion> {
     // Check PIN attempt.
     if !check_pin_attempt(rt, pairing_pin_attempt) {
         if pairing_longer_cooldown_byte == 0 {
             set_pairing_longer_cooldown_byte(rt, true);
         }
 
-        return false;
+        return None;
     }
 
     // PIN is correct. Reset longer cooldown timer.
@@ -135,11 +138,11 @@ pub(crate) fn paired_process_msg(rt: &mut Runtime, msg: &Uart0Message) {
         spin_pin_cooldown_timer(&mut rt.eeprom_controller, &rt.hib_controller);
 
     // Process PIN and Diffie-Hellman key exchange.
-    let success =
+    let version =
         check_pin_and_diffie_hellman(rt, pairing_pin_attempt, pairing_longer_cooldown_byte);
 
-    // Pair if Diffie-Hellman was successful.
-    if success {
-        pairing_sequence::run_paired(rt);
+    // Pair with the negotiated version if Diffie-Hellman was successful.
+    if let Some(version) = version {
+        pairing_sequence::run_paired(rt, version);
     }
 }
@@ -1,4 +1,4 @@
-use crate::MAX_MESSAGE_SIZE;
+use crate::{start_uart1_session, RECV_BUFFER_SIZE};
 use core::time::Duration;
 use k256::{
     ecdh,
@@ -9,46 +9,53 @@ use k256::{
     PublicKey, SecretKey,
 };
 use ucsc_ectf_util_no_std::{
-    communication::{CommunicationError, RxChannel, TxChannel, Uart1Controller},
+    communication::{
+        lower_layers::framing::CobsFraming, message_channel::MessageChannel, CommunicationError,
+        Uart1Controller,
+    },
     eeprom::{
         EepromController, EepromReadOnlyField, EepromReadWriteField, PUBLIC_KEY_SIZE, SECRET_SIZE,
         SIGNATURE_SIZE,
     },
-    messages::{DiffieHellmanMessage, Key, Uart1Message, VerifiedPublicKey},
-    timer::{HibTimer, Timer},
+    messages::{DiffieHellmanMessage, Key, ProtocolVersion, Uart1Message, VerifiedPublicKey},
+    timer::HibTimer,
     Runtime, Uart1RxPin, Uart1TxPin,
 };
 use zeroize::Zeroize;
 
 /// Waits for up to the expiration of `timeout_timer` to receive and verify an ephemeral public key.
+/// Returns the key along with the protocol version of the message it was received in.
 fn recv_verified_ephemeral_public_key(
-    uart1_controller: &mut Uart1Controller<Uart1TxPin, Uart1RxPin>,
+    uart1_controller: &mut Uart1Controller<Uart1TxPin, Uart1RxPin, CobsFraming>,
     eeprom_controller: &mut EepromController,
     timeout_timer: &mut HibTimer,
     paired: bool,
-) -> Option<PublicKey> {
+) -> Option<(PublicKey, ProtocolVersion)> {
+    let mut uart1 = MessageChannel::<_, Uart1Message, RECV_BUFFER_SIZE>::new(uart1_controller);
+
     // Loop to ignore any invalid messages.
     loop {
-        // Make sure timer hasn't expired.
-        if timeout_timer.poll() {
-            return None;
-        }
+        // Receive Diffie-Hellman message on UART1, or a hello from a paired key fob starting a session.
+        let msg = match uart1.recv_until_with_data_timeout(timeout_timer, |msg| {
+            matches!(msg, Uart1Message::DiffieHellman(_) | Uart1Message::Hello(_))
+        }) {
+            Ok(Uart1Message::DiffieHellman(msg)) => Some(msg),
+            Ok(_) => None,
+            Err(CommunicationError::InternalError) => {
+                panic!("Failed to receive Diffie-Hellman message (internal error).")
+            }
+            Err(CommunicationError::Timeout) => return None,
+            // Keep waiting after messages that couldn't be received.
+            Err(_) => continue,
+        };
+
+        // Respond to a paired key fob starting a session.
+        let Some(msg) = msg else {
+            if let Err(CommunicationError::InternalError) = uart1.send_hello() {
+                panic!("Failed to send hello (internal error).");
+            }
 
-        // Receive Diffie-Hellman message on UART1.
-        let mut receive_buffer = [0; MAX_MESSAGE_SIZE];
-
-        let size_read =
-            match uart1_controller.recv_with_data_timeout(&mut receive_buffer, timeout_timer) {
-                Ok(size_read) => size_read,
-                Err(CommunicationError::InternalError) => {
-                    panic!("Failed to receive Diffie-Hellman message (internal error).")
-                }
-                Err(_) => continue,
-            };
-
-        let msg = match postcard::from_bytes::<Uart1Message>(&receive_buffer[..size_read]) {
-            Ok(Uart1Message::DiffieHellman(msg)) => msg,
-            _ => continue,
+            continue;
         };
 
         // Determine which pairing verifying key field to use. Use the verifying key for the other side.
@@ -85,16 +92,17 @@ fn recv_verified_ephemeral_public_key(
             .ephemeral_public_key
             .verify_and_get_key(&key_signing_public_key.into())
         {
-            return Some(ephemeral_public_key);
+            return Some((ephemeral_public_key, uart1.received_version()?));
         } else {
             continue;
         }
     }
 }
 
-/// Sends a Diffie-Hellman message.
+/// Sends a Diffie-Hellman message with the protocol version ``version``.
 fn send_diffie_hellman_msg(
     rt: &mut Runtime,
+    version: ProtocolVersion,
     pairing_public_key: &PublicKey,
     pairing_public_key_signature: &Signature,
     ephemeral_public_key: &PublicKey,
@@ -118,15 +126,18 @@ fn send_diffie_hellman_msg(
     });
 
     // Send message.
-    let mut buff = [0; MAX_MESSAGE_SIZE];
+    let mut uart1 =
+        MessageChannel::<_, Uart1Message, RECV_BUFFER_SIZE>::new(&mut rt.uart1_controller);
+    uart1.set_version(version);
 
-    match rt.uart1_controller.send(
-        postcard::to_slice(&msg, &mut buff).expect("Failed to serialize Diffie-Hellman message."),
-    ) {
+    match uart1.send(&msg) {
         Ok(_) => true,
         Err(CommunicationError::InternalError) => {
             panic!("Failed to send Diffie-Hellman message (internal error).")
         }
+        Err(CommunicationError::EncodingError) => {
+            panic!("Failed to serialize Diffie-Hellman message.")
+        }
         Err(_) => false,
     }
 }
@@ -232,10 +243,14 @@ fn get_pairing_public_key_signature(rt: &mut Runtime, paired: bool) -> Signature
         .expect("Failed to deserialize pairing public key signature.")
 }
 
-/// Prepares and sends a Diffie-Hellman message. Inlined to prevent copying of the ephemeral private
-/// key.
+/// Prepares and sends a Diffie-Hellman message with the protocol version ``version``. Inlined to prevent
+/// copying of the ephemeral private key.
 #[inline(always)]
-fn prepare_and_send_diffie_hellman_message(rt: &mut Runtime, paired: bool) -> Option<SecretKey> {
+fn prepare_and_send_diffie_hellman_message(
+    rt: &mut Runtime,
+    paired: bool,
+    version: ProtocolVersion,
+) -> Option<SecretKey> {
     // Get fields necessary for Diffie-Hellman message.
     let ephemeral_private_key = generate_ephemeral_key(rt);
     let ephemeral_public_key = ephemeral_private_key.public_key();
@@ -246,6 +261,7 @@ fn prepare_and_send_diffie_hellman_message(rt: &mut Runtime, paired: bool) -> Op
     // Send Diffie-Hellman message to unpaired key fob.
     if send_diffie_hellman_msg(
         rt,
+        version,
         &pairing_public_key,
         &pairing_public_key_signature,
         &ephemeral_public_key,
@@ -265,7 +281,7 @@ pub(crate) fn run_unpaired(rt: &mut Runtime) -> bool {
     rt.uart1_controller.change_tx_key(&default_key);
 
     // Receive ephemeral public key from paired key fob.
-    let Some(paired_ephemeral_public_key) = recv_verified_ephemeral_public_key(
+    let Some((paired_ephemeral_public_key, version)) = recv_verified_ephemeral_public_key(
         &mut rt.uart1_controller,
         &mut rt.eeprom_controller,
         &mut rt.hib_controller.create_timer(Duration::from_secs(1000)),
@@ -274,8 +290,9 @@ pub(crate) fn run_unpaired(rt: &mut Runtime) -> bool {
         return false;
     };
 
-    // Generate ephemeral private key and send Diffie-Hellman message.
-    let Some(ephemeral_private_key) = prepare_and_send_diffie_hellman_message(rt, false)
+    // Generate ephemeral private key and send Diffie-Hellman message with the version of the paired
+    // key fob's message.
+    let Some(ephemeral_private_key) = prepare_and_send_diffie_hellman_message(rt, false, version)
     else {
         return false;
     };
@@ -286,31 +303,33 @@ pub(crate) fn run_unpaired(rt: &mut Runtime) -> bool {
     true
 }
 
-/// Performs the Diffie-Hellman key exchange as a paired key fob and sets the UART1 channel key.
-pub(crate) fn run_paired(rt: &mut Runtime) -> bool {
+/// Performs the Diffie-Hellman key exchange as a paired key fob and sets the UART1 channel key. Returns
+/// the protocol version negotiated with the unpaired key fob.
+pub(crate) fn run_paired(rt: &mut Runtime) -> Option<ProtocolVersion> {
     // Set keys to default. Necessary in case of failure after key exchange.
     let default_key: Key = Default::default();
     rt.uart1_controller.change_rx_key(&default_key);
     rt.uart1_controller.change_tx_key(&default_key);
 
+    // Start the session, rejecting unpaired key fobs without a protocol version in common.
+    let version = start_uart1_session(
+        &mut MessageChannel::<_, Uart1Message, RECV_BUFFER_SIZE>::new(&mut rt.uart1_controller),
+        &mut rt.hib_controller.create_timer(Duration::from_secs(1)),
+    )?;
+
     // Generate ephemeral private key and send Diffie-Hellman message.
-    let Some(ephemeral_private_key) = prepare_and_send_diffie_hellman_message(rt, true)
-    else {
-        return false;
-    };
+    let ephemeral_private_key = prepare_and_send_diffie_hellman_message(rt, true, version)?;
 
     // Receive ephemeral public key from unpaired key fob.
-    let Some(unpaired_ephemeral_public_key) = recv_verified_ephemeral_public_key(
+    let (unpaired_ephemeral_public_key, _) = recv_verified_ephemeral_public_key(
         &mut rt.uart1_controller,
         &mut rt.eeprom_controller,
         &mut rt.hib_controller.create_timer(Duration::from_secs(1)),
         true,
-    ) else {
-        return false;
-    };
+    )?;
 
     // Set UART1 channel key.
     diffie_hellman_set_key(rt, &unpaired_ephemeral_public_key, &ephemeral_private_key);
 
-    true
+    Some(version)
 }
@@ -1,21 +1,25 @@
-use crate::MAX_MESSAGE_SIZE;
+use crate::RECV_BUFFER_SIZE;
 use core::{mem, time::Duration};
 use ucsc_ectf_util_no_std::{
-    communication::{CommunicationError, RxChannel, TxChannel},
+    communication::{message_channel::MessageChannel, CommunicationError},
     eeprom::{
         EepromReadWriteField, BYTE_FIELD_SIZE, CAR_ID_SIZE, PAIRING_PIN_SIZE, SECRET_SIZE,
         SIGNATURE_SIZE,
     },
     messages::{
-        Nonce, PairingChallenge, PairingChallengeResponse, PairingPin, PairingRequest, Uart1Message,
+        Nonce, PairingChallenge, PairingChallengeResponse, PairingPin, PairingRequest,
+        ProtocolVersion, Uart1Message,
     },
-    timer::Timer,
     Runtime,
 };
 use zeroize::Zeroize;
 
-/// Generates a sends a challenge message.
-fn generate_and_send_challenge(rt: &mut Runtime, request_nonce: Nonce) -> Option<Nonce> {
+/// Generates a sends a challenge message with the protocol version ``version``.
+fn generate_and_send_challenge(
+    rt: &mut Runtime,
+    request_nonce: Nonce,
+    version: ProtocolVersion,
+) -> Option<Nonce> {
     let mut challenge = [0; mem::size_of::<Nonce>()];
     rt.fill_rand_slice(&mut challenge);
 
@@ -25,16 +29,16 @@ fn generate_and_send_challenge(rt: &mut Runtime, request_nonce: Nonce) -> Option
         challenge,
     });
 
-    let mut buff = [0; MAX_MESSAGE_SIZE];
+    let mut uart1 =
+        MessageChannel::<_, Uart1Message, RECV_BUFFER_SIZE>::new(&mut rt.uart1_controller);
+    uart1.set_version(version);
 
-    match rt.uart1_controller.send(
-        postcard::to_slice(&challenge_msg, &mut buff)
-            .expect("Failed to serialize pairing challenge."),
-    ) {
+    match uart1.send(&challenge_msg) {
         Ok(_) => (),
         Err(CommunicationError::InternalError) => {
             panic!("Failed to send pairing challenge (internal error).")
         }
+        Err(CommunicationError::EncodingError) => panic!("Failed to serialize pairing challenge."),
         Err(_) => return None,
     }
 
@@ -45,43 +49,26 @@ fn generate_and_send_challenge(rt: &mut Runtime, request_nonce: Nonce) -> Option
 /// [`PairingChallengeResponse`].
 #[inline(always)]
 fn recv_challenge_response(rt: &mut Runtime) -> Option<PairingChallengeResponse> {
-    let mut response_bytes = [0; MAX_MESSAGE_SIZE];
+    // The receive buffer holding the response is zeroized when the channel is dropped.
+    let mut uart1 =
+        MessageChannel::<_, Uart1Message, RECV_BUFFER_SIZE>::new(&mut rt.uart1_controller);
     let mut timeout_timer = rt.hib_controller.create_timer(Duration::from_secs(1));
 
-    let challenge_response_msg = loop {
-        // Zeroize from previous iteration.
-        response_bytes.zeroize();
-
-        // Make sure timer hasn't expired on this iteration first.
-        if timeout_timer.poll() {
-            return None;
-        }
-
-        let size_read = match rt
-            .uart1_controller
-            .recv_with_timeout(&mut response_bytes, &mut timeout_timer)
-        {
-            Ok(size_read) => size_read,
-            Err(CommunicationError::InternalError) => {
-                panic!("Failed to receive unlock challenge response (internal error).")
+    loop {
+        match uart1.recv_until_with_timeout(&mut timeout_timer, |msg| {
+            matches!(msg, Uart1Message::PairingChallengeResponse(_))
+        }) {
+            Ok(Uart1Message::PairingChallengeResponse(challenge_response)) => {
+                return Some(challenge_response)
             }
-            Err(_) => {
-                continue;
+            Err(CommunicationError::InternalError) => {
+                panic!("Failed to receive pairing challenge response (internal error).")
             }
-        };
-
-        if let Ok(Uart1Message::PairingChallengeResponse(challenge_response)) =
-            postcard::from_bytes::<Uart1Message>(&response_bytes[..size_read])
-        {
-            break challenge_response;
-        } else {
-            continue;
+            Err(CommunicationError::Timeout) => return None,
+            // Keep waiting after messages that couldn't be received.
+            _ => continue,
         }
-    };
-
-    response_bytes.zeroize();
-
-    Some(challenge_response_msg)
+    }
 }
 
 /// Processes a pairing request, sends a pairing challenge, and receives a pairing challenge
@@ -90,28 +77,23 @@ fn recv_challenge_response(rt: &mut Runtime) -> Option<PairingChallengeResponse>
 #[inline(always)]
 fn unpaired_recv_verified_pairing_info(rt: &mut Runtime) -> Option<PairingChallengeResponse> {
     // Receive pairing request.
-    let mut receive_buffer = [0; MAX_MESSAGE_SIZE];
+    let mut uart1 =
+        MessageChannel::<_, Uart1Message, RECV_BUFFER_SIZE>::new(&mut rt.uart1_controller);
 
-    let size_read = match rt.uart1_controller.recv_with_data_timeout(
-        &mut receive_buffer,
-        &mut rt.hib_controller.create_timer(Duration::from_secs(5)),
-    ) {
-        Ok(size_read) => size_read,
+    let request_nonce = match uart1
+        .recv_with_data_timeout(&mut rt.hib_controller.create_timer(Duration::from_secs(5)))
+    {
+        Ok(Uart1Message::PairingRequest(PairingRequest(request_nonce))) => request_nonce,
         Err(CommunicationError::InternalError) => {
             panic!("Failed to receive pairing request message (internal error).")
         }
-        Err(_) => return None,
-    };
-
-    let pairing_request = match postcard::from_bytes::<Uart1Message>(&receive_buffer[..size_read]) {
-        Ok(Uart1Message::PairingRequest(pairing_request)) => pairing_request,
         _ => return None,
     };
+    let version = uart1.received_version()?;
+    drop(uart1);
 
-    let request_nonce = pairing_request.0;
-
-    // Generate challenge.
-    let challenge = generate_and_send_challenge(rt, request_nonce)?;
+    // Generate challenge, responding with the version of the request.
+    let challenge = generate_and_send_challenge(rt, request_nonce, version)?;
 
     // Wait for challenge response.
     let challenge_response_msg = recv_challenge_response(rt)?;
@@ -243,56 +225,45 @@ fn generate_challenge_response_msg(
     }
 }
 
-// Pairs an unpaired key fob from a paired key fob. Requires a secure UART1 channel.
-pub(crate) fn run_paired(rt: &mut Runtime) {
+// Pairs an unpaired key fob from a paired key fob with the negotiated protocol version ``version``.
+// Requires a secure UART1 channel.
+pub(crate) fn run_paired(rt: &mut Runtime, version: ProtocolVersion) {
     // Generate request nonce.
     let mut request_nonce = [0; mem::size_of::<Nonce>()];
     rt.fill_rand_slice(&mut request_nonce);
 
     // Send pairing request.
     let pairing_request = Uart1Message::PairingRequest(PairingRequest(request_nonce));
-    let mut buff = [0; MAX_MESSAGE_SIZE];
+    let mut uart1 =
+        MessageChannel::<_, Uart1Message, RECV_BUFFER_SIZE>::new(&mut rt.uart1_controller);
+    uart1.set_version(version);
 
-    match rt.uart1_controller.send(
-        postcard::to_slice(&pairing_request, &mut buff)
-            .expect("Failed to serialize pairing request."),
-    ) {
+    match uart1.send(&pairing_request) {
         Ok(_) => (),
         Err(CommunicationError::InternalError) => {
             panic!("Failed to send pairing request (internal error).")
         }
+        Err(CommunicationError::EncodingError) => panic!("Failed to serialize pairing request."),
         Err(_) => return,
     }
 
     // Receive pairing challenge.
-    let mut challenge_bytes = [0; MAX_MESSAGE_SIZE];
     let mut timeout_timer = rt.hib_controller.create_timer(Duration::from_secs(1));
 
     let challenge_msg = loop {
-        // Make sure timer hasn't expired on this iteration first.
-        if timeout_timer.poll() {
-            return;
-        }
-
-        let size_read = match rt
-            .uart1_controller
-            .recv_with_data_timeout(&mut challenge_bytes, &mut timeout_timer)
-        {
-            Ok(size_read) => size_read,
+        match uart1.recv_until_with_data_timeout(&mut timeout_timer, |msg| {
+            matches!(msg, Uart1Message::PairingChallenge(_))
+        }) {
+            Ok(Uart1Message::PairingChallenge(challenge)) => break challenge,
             Err(CommunicationError::InternalError) => {
                 panic!("Failed to receive pairing challenge (internal error).")
             }
-            Err(_) => continue,
-        };
-
-        if let Ok(Uart1Message::PairingChallenge(challenge)) =
-            postcard::from_bytes::<Uart1Message>(&challenge_bytes[..size_read])
-        {
-            break challenge;
-        } else {
-            continue;
+            Err(CommunicationError::Timeout) => return,
+            // Keep waiting after messages that couldn't be received.
+            _ => continue,
         }
     };
+    drop(uart1);
 
     // Verify nonce.
     if challenge_msg.request_nonce != request_nonce {
@@ -305,12 +276,17 @@ pub(crate) fn run_paired(rt: &mut Runtime) {
     let challenge_response_msg = Uart1Message::PairingChallengeResponse(challenge_response);
 
     // Send challenge response.
-    let mut buff = [0; MAX_MESSAGE_SIZE];
+    let mut uart1 =
+        MessageChannel::<_, Uart1Message, RECV_BUFFER_SIZE>::new(&mut rt.uart1_controller);
+    uart1.set_version(version);
 
-    if let Err(CommunicationError::InternalError) = rt.uart1_controller.send(
-        postcard::to_slice(&challenge_response_msg, &mut buff)
-            .expect("Failed to serialize pairing challenge response message."),
-    ) {
-        panic!("Failed to send pairing challenge response message (internal error).")
-    };
+    match uart1.send(&challenge_response_msg) {
+        Err(CommunicationError::InternalError) => {
+            panic!("Failed to send pairing challenge response message (internal error).")
+        }
+        Err(CommunicationError::EncodingError) => {
+            panic!("Failed to serialize pairing challenge response message.")
+        }
+        _ => (),
+    }
 }
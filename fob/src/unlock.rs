@@ -1,11 +1,11 @@
-use crate::{features, MAX_MESSAGE_SIZE};
+use crate::{features, start_uart1_session, RECV_BUFFER_SIZE};
 use core::time::Duration;
 use ucsc_ectf_util_no_std::{
-    communication::{CommunicationError, RxChannel, TxChannel},
-    eeprom::{EepromReadWriteField, CAR_ID_SIZE, PACKAGED_FEATURE_SIGNED_SIZE, SECRET_SIZE},
+    communication::{message_channel::MessageChannel, CommunicationError},
+    eeprom::{EepromReadWriteField, CAR_ID_SIZE, SECRET_SIZE},
     messages::{
         heapless::Vec, FeatureNumber, Uart1Message, UnlockChallengeResponse, UnlockRequest,
-        NUM_FEATURES,
+        NUM_FEATURES, UNSIGNED_FEATURES_VERSION,
     },
     timer::Timer,
     Runtime,
@@ -49,45 +49,36 @@ pub(crate) fn process_button_press(rt: &mut Runtime) {
 
     // Send unlock request to car.
     let unlock_request = Uart1Message::UnlockRequest(UnlockRequest(car_id));
-    let mut unlock_request_buff = [0; MAX_MESSAGE_SIZE];
+    let mut uart1 =
+        MessageChannel::<_, Uart1Message, RECV_BUFFER_SIZE>::new(&mut rt.uart1_controller);
 
-    match rt.uart1_controller.send(
-        postcard::to_slice(&unlock_request, &mut unlock_request_buff)
-            .expect("Failed to serialize unlock request."),
-    ) {
+    // Start the session, negotiating the protocol version with the car.
+    let mut hello_timer = rt.hib_controller.create_timer(Duration::from_secs(1));
+
+    let Some(version) = start_uart1_session(&mut uart1, &mut hello_timer) else {
+        return;
+    };
+
+    match uart1.send(&unlock_request) {
         Ok(_) => (),
         Err(CommunicationError::InternalError) => {
             panic!("Failed to send unlock request (internal error).")
         }
+        Err(CommunicationError::EncodingError) => panic!("Failed to serialize unlock request."),
         Err(_) => return,
     }
 
     // Wait for challenge.
-    let mut challenge_bytes = [0; MAX_MESSAGE_SIZE];
     let mut timeout_timer = rt.hib_controller.create_timer(Duration::from_secs(1));
 
-    let challenge = loop {
-        // Make sure timer hasn't expired on this iteration first.
-        if timeout_timer.poll() {
-            return;
-        }
-
-        let size_read = match rt
-            .uart1_controller
-            .recv_with_timeout(&mut challenge_bytes, &mut timeout_timer)
-        {
-            Ok(size_read) => size_read,
-            Err(CommunicationError::InternalError) => {
-                panic!("Failed to receive unlock challenge (internal error).")
-            }
-            Err(_) => return,
-        };
-
-        if let Ok(Uart1Message::UnlockChallenge(msg)) =
-            postcard::from_bytes::<Uart1Message>(&challenge_bytes[..size_read])
-        {
-            break msg;
+    let challenge = match uart1.recv_until_with_timeout(&mut timeout_timer, |msg| {
+        matches!(msg, Uart1Message::UnlockChallenge(_))
+    }) {
+        Ok(Uart1Message::UnlockChallenge(msg)) => msg,
+        Err(CommunicationError::InternalError) => {
+            panic!("Failed to receive unlock challenge (internal error).")
         }
+        _ => return,
     };
 
     // Verify car ID.
@@ -95,19 +86,19 @@ pub(crate) fn process_button_press(rt: &mut Runtime) {
         return;
     }
 
-    // Grab features.
-    let mut features_bytes = [[0; PACKAGED_FEATURE_SIGNED_SIZE]; NUM_FEATURES];
+    // Grab features. Cars older than the unsigned features version verify the signature of each
+    // feature, which isn't stored, so they are sent no features.
     let mut features = Vec::new();
 
-    for (i, feature) in features_bytes.iter_mut().enumerate() {
-        if let Some(packaged_feature_signed) = features::get_installed_feature(
-            &mut rt.eeprom_controller,
-            (i + 1) as FeatureNumber,
-            feature,
-        ) {
-            features
-                .push(packaged_feature_signed)
-                .expect("Failed to push feature to UnlockChallengeResponse feature vec.");
+    if version >= UNSIGNED_FEATURES_VERSION {
+        for feature in 1..=NUM_FEATURES as FeatureNumber {
+            if let Some(packaged_feature) =
+                features::get_installed_feature(&mut rt.eeprom_controller, feature)
+            {
+                features
+                    .push(packaged_feature)
+                    .expect("Failed to push feature to UnlockChallengeResponse feature vec.");
+            }
         }
     }
 
@@ -117,13 +108,15 @@ pub(crate) fn process_button_press(rt: &mut Runtime) {
         challenge_response: challenge.challenge,
         features,
     });
-    let mut challenge_response_msg_buff = [0; MAX_MESSAGE_SIZE];
 
-    if let Err(CommunicationError::InternalError) = rt.uart1_controller.send(
-        postcard::to_slice(&challenge_response_msg, &mut challenge_response_msg_buff)
-            .expect("Failed to serialize unlock challenge response."),
-    ) {
-        panic!("Failed to send unlock challenge response (internal error).")
+    match uart1.send(&challenge_response_msg) {
+        Err(CommunicationError::InternalError) => {
+            panic!("Failed to send unlock challenge response (internal error).")
+        }
+        Err(CommunicationError::EncodingError) => {
+            panic!("Failed to serialize unlock challenge response.")
+        }
+        _ => (),
     }
 
     // Spin while unlock timer has not expired. This is because the button controller does not
@@ -0,0 +1,108 @@
+use std::{error::Error, fs::File, io::Read, path::PathBuf, time::Duration};
+
+use clap::Parser;
+use k256::ecdsa::{signature::Signer, Signature, SigningKey};
+use postcard::to_allocvec;
+use ucsc_ectf_util_std::{
+    communication::{
+        self, message_channel::MessageChannel, CommunicationError, VerifiedFramedTcpSocket,
+    },
+    messages::{
+        DisableFeatureMessage, DisableFeatureSigned, DisableFeatureUnsigned, HostToolAck,
+        PackagedFeatureSigned, SignedMessage, Uart0Message,
+    },
+    timer::StdTimer,
+};
+
+const MESSAGE_BUFF_LEN: usize = 1024;
+
+#[derive(Parser)]
+struct Args {
+    /// Bridge for the fob
+    #[arg(long)]
+    fob_bridge: u16,
+
+    /// Name of the package file of the feature to disable
+    #[arg(long)]
+    package_name: String,
+
+    /// Sequence number of the request, which must be greater than that of the last request the fob
+    /// accepted.
+    #[arg(long)]
+    sequence: u32,
+}
+
+fn get_package(
+    name: String,
+    package_vec: &mut Vec<u8>,
+) -> Result<PackagedFeatureSigned, Box<dyn Error>> {
+    let mut path = PathBuf::from("/package_dir");
+    path.push(name);
+
+    let mut package_file = File::open(path)?;
+    package_file.read_to_end(package_vec)?;
+
+    Ok(postcard::from_bytes(package_vec)?)
+}
+
+fn send_disable_request(request: DisableFeatureSigned, port: u16) -> communication::Result<()> {
+    let mut socket = VerifiedFramedTcpSocket::keyless_connect(("ectf-net", port))?;
+    let mut channel = MessageChannel::<_, Uart0Message, MESSAGE_BUFF_LEN>::new(&mut socket);
+    let mut timeout_timer = StdTimer::new(Duration::from_millis(4950));
+
+    channel.exchange_hello_with_timeout(&mut timeout_timer)?;
+
+    let disable_req = Uart0Message::DisableFeatureRequest(DisableFeatureMessage(request));
+
+    channel.send(&disable_req)?;
+
+    let resp = channel.recv_until_with_data_timeout(&mut timeout_timer, |msg| {
+        matches!(msg, Uart0Message::DisableFeatureResponse(_))
+    })?;
+
+    match resp {
+        Uart0Message::DisableFeatureResponse(HostToolAck(true)) => Ok(()),
+        _ => Err(CommunicationError::RecvError),
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+    let mut package_vec = Vec::new();
+    let package = match get_package(args.package_name, &mut package_vec) {
+        Ok(package) => package,
+        Err(_) => {
+            println!("Couldn't find specified package or package malformed.");
+
+            return;
+        }
+    };
+
+    // Open feature signing key.
+    let mut signing_key_file = File::open("/secrets/FEATURE_SIGNING_KEY").unwrap();
+    let mut signing_key_bytes: Vec<u8> = Vec::new();
+    signing_key_file
+        .read_to_end(&mut signing_key_bytes)
+        .unwrap();
+    let signing_key = SigningKey::from_bytes(&signing_key_bytes).unwrap();
+
+    let packaged_feature = &package.packaged_feature;
+    let disable_feature = DisableFeatureUnsigned {
+        car_id: packaged_feature.car_id,
+        feature_number: packaged_feature.feature_number,
+        feature_digest: packaged_feature.digest(),
+        sequence: args.sequence,
+    };
+
+    let signature: Signature = signing_key.sign(&to_allocvec(&disable_feature.tagged()).unwrap());
+
+    let disable_feature_signed = DisableFeatureSigned {
+        disable_feature,
+        signature: &signature.to_der().to_bytes(),
+    };
+
+    match send_disable_request(disable_feature_signed, args.fob_bridge) {
+        Ok(()) => println!("Disabled."),
+        Err(_) => println!("Failed to disable feature."),
+    }
+}
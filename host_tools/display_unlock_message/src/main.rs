@@ -3,11 +3,16 @@ use std::{str, time::Duration};
 use clap::Parser;
 use ucsc_ectf_util_std::{
     communication::{self, CommunicationError, RxChannel, VerifiedFramedTcpSocket},
-    messages::{Uart0Message, UnlockMessage},
+    messages::{from_envelope_bytes, Uart0Message, UnlockMessage, ENVELOPE_VERSION_MAX_SIZE},
     timer::StdTimer,
 };
 
-const UNLOCK_BUFF_LEN: usize = 1024;
+/// The size of the buffer the unlock message is received into, which fits an unlock message with
+/// every feature enabled.
+const UNLOCK_BUFF_LEN: usize = ENVELOPE_VERSION_MAX_SIZE
+    + 1
+    + UnlockMessage::MAX_SIZE
+    + <VerifiedFramedTcpSocket as RxChannel>::METADATA_SIZE;
 
 #[derive(Parser)]
 struct Args {
@@ -21,8 +26,7 @@ fn get_unlock_message(car_bridge: u16, buff: &mut [u8]) -> communication::Result
     let mut timeout_timer = StdTimer::new(Duration::from_secs(5));
     let msg_len = socket.recv_with_data_timeout(buff, &mut timeout_timer)?;
     let msg_bytes = &buff[..msg_len];
-    let msg: Uart0Message =
-        postcard::from_bytes(msg_bytes).map_err(|_| CommunicationError::RecvError)?;
+    let msg = from_envelope_bytes::<Uart0Message>(msg_bytes)?.message;
 
     match msg {
         Uart0Message::HostUnlock(msg) => Ok(msg),
@@ -2,12 +2,14 @@ use std::{error::Error, fs::File, io::Read, path::PathBuf, time::Duration};
 
 use clap::Parser;
 use ucsc_ectf_util_std::{
-    communication::{self, CommunicationError, RxChannel, TxChannel, VerifiedFramedTcpSocket},
+    communication::{
+        self, message_channel::MessageChannel, CommunicationError, VerifiedFramedTcpSocket,
+    },
     messages::{EnableFeatureMessage, HostToolAck, PackagedFeatureSigned, Uart0Message},
     timer::StdTimer,
 };
 
-const RECV_BUFF_LEN: usize = 128;
+const MESSAGE_BUFF_LEN: usize = 1024;
 
 #[derive(Parser)]
 struct Args {
@@ -37,17 +39,18 @@ fn get_package(
 
 fn send_package(package: PackagedFeatureSigned, port: u16) -> communication::Result<()> {
     let mut socket = VerifiedFramedTcpSocket::keyless_connect(("ectf-net", port))?;
+    let mut channel = MessageChannel::<_, Uart0Message, MESSAGE_BUFF_LEN>::new(&mut socket);
+    let mut timeout_timer = StdTimer::new(Duration::from_millis(4950));
+
+    channel.exchange_hello_with_timeout(&mut timeout_timer)?;
+
     let enable_req = Uart0Message::EnableFeatureRequest(EnableFeatureMessage(package));
-    let mut enable_req_bytes =
-        postcard::to_allocvec(&enable_req).map_err(|_| CommunicationError::InternalError)?;
 
-    socket.send(&mut enable_req_bytes)?;
+    channel.send(&enable_req)?;
 
-    let mut buff = [0; RECV_BUFF_LEN];
-    let mut timeout_timer = StdTimer::new(Duration::from_millis(4950));
-    let resp_len = socket.recv_with_data_timeout(&mut buff, &mut timeout_timer)?;
-    let resp = postcard::from_bytes::<Uart0Message>(&buff[..resp_len])
-        .map_err(|_| CommunicationError::RecvError)?;
+    let resp = channel.recv_until_with_data_timeout(&mut timeout_timer, |msg| {
+        matches!(msg, Uart0Message::EnableFeatureResponse(_))
+    })?;
 
     match resp {
         Uart0Message::EnableFeatureResponse(HostToolAck(true)) => Ok(()),
@@ -0,0 +1,75 @@
+use std::{error::Error, fs::File, io::Read, path::PathBuf, time::Duration};
+
+use clap::Parser;
+use ucsc_ectf_util_std::{
+    communication::{
+        self, message_channel::MessageChannel, CommunicationError, VerifiedFramedTcpSocket,
+    },
+    messages::{HostToolAck, RevocationListMessage, RevocationListSigned, Uart0Message},
+    timer::StdTimer,
+};
+
+const MESSAGE_BUFF_LEN: usize = 1024;
+
+#[derive(Parser)]
+struct Args {
+    /// Bridge for the car or the paired fob
+    #[arg(long)]
+    bridge: u16,
+
+    /// Name of the revocation list file
+    #[arg(long)]
+    list_name: String,
+}
+
+fn get_revocation_list(
+    name: String,
+    list_vec: &mut Vec<u8>,
+) -> Result<RevocationListSigned, Box<dyn Error>> {
+    let mut path = PathBuf::from("/package_dir");
+    path.push(name);
+
+    let mut list_file = File::open(path)?;
+    list_file.read_to_end(list_vec)?;
+
+    Ok(postcard::from_bytes(list_vec)?)
+}
+
+fn send_revocation_list(list: RevocationListSigned, port: u16) -> communication::Result<()> {
+    let mut socket = VerifiedFramedTcpSocket::keyless_connect(("ectf-net", port))?;
+    let mut channel = MessageChannel::<_, Uart0Message, MESSAGE_BUFF_LEN>::new(&mut socket);
+    let mut timeout_timer = StdTimer::new(Duration::from_millis(4950));
+
+    channel.exchange_hello_with_timeout(&mut timeout_timer)?;
+
+    let revocation_req = Uart0Message::RevocationListRequest(RevocationListMessage(list));
+
+    channel.send(&revocation_req)?;
+
+    let resp = channel.recv_until_with_data_timeout(&mut timeout_timer, |msg| {
+        matches!(msg, Uart0Message::RevocationListResponse(_))
+    })?;
+
+    match resp {
+        Uart0Message::RevocationListResponse(HostToolAck(true)) => Ok(()),
+        _ => Err(CommunicationError::RecvError),
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+    let mut list_vec = Vec::new();
+    let list = match get_revocation_list(args.list_name, &mut list_vec) {
+        Ok(list) => list,
+        Err(_) => {
+            println!("Couldn't find specified revocation list or revocation list malformed.");
+
+            return;
+        }
+    };
+
+    match send_revocation_list(list, args.bridge) {
+        Ok(()) => println!("Installed."),
+        Err(_) => println!("Failed to install revocation list."),
+    }
+}
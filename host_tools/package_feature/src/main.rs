@@ -7,7 +7,7 @@ use std::{
 use clap::Parser;
 use k256::ecdsa::{signature::Signer, Signature, SigningKey};
 use postcard::to_allocvec;
-use ucsc_ectf_util_std::messages::{PackagedFeatureSigned, PackagedFeatureUnsigned};
+use ucsc_ectf_util_std::messages::{PackagedFeatureSigned, PackagedFeatureUnsigned, SignedMessage};
 
 #[derive(Parser)]
 struct Args {
@@ -22,6 +22,18 @@ struct Args {
     /// Feature number to create a package for.
     #[arg(long)]
     feature_number: u32,
+
+    /// Seconds the car must run for after the feature is activated before the feature is valid.
+    /// The feature is activated by the first unlock with a key fob it is enabled on, and only time
+    /// the car spends running is counted.
+    #[arg(long)]
+    not_before: Option<u64>,
+
+    /// Seconds the car can run for after the feature is activated before the feature is no longer
+    /// valid, such as the length of a trial. The feature is activated by the first unlock with a key
+    /// fob it is enabled on, and only time the car spends running is counted.
+    #[arg(long)]
+    not_after: Option<u64>,
 }
 
 fn main() {
@@ -43,9 +55,11 @@ fn main() {
     let packaged_feature = PackagedFeatureUnsigned {
         car_id: args.car_id,
         feature_number: args.feature_number,
+        not_before: args.not_before,
+        not_after: args.not_after,
     };
 
-    let signature: Signature = signing_key.sign(&to_allocvec(&packaged_feature).unwrap());
+    let signature: Signature = signing_key.sign(&to_allocvec(&packaged_feature.tagged()).unwrap());
 
     let packaged_feature_signed = PackagedFeatureSigned {
         packaged_feature,
@@ -0,0 +1,102 @@
+use std::{
+    fs::File,
+    io::{Read, Write},
+    path::PathBuf,
+};
+
+use clap::Parser;
+use k256::ecdsa::{signature::Signer, Signature, SigningKey};
+use postcard::to_allocvec;
+use ucsc_ectf_util_std::messages::{
+    heapless::Vec as HeaplessVec, PackagedFeatureSigned, RevocationListSigned,
+    RevocationListUnsigned, SignedMessage, MAX_REVOKED_FEATURES,
+};
+
+#[derive(Parser)]
+struct Args {
+    /// Name of the revocation list file.
+    #[arg(long)]
+    list_name: String,
+
+    /// ID of the car to create a revocation list for.
+    #[arg(long)]
+    car_id: u32,
+
+    /// Sequence number of the revocation list, which must be greater than that of the list
+    /// installed.
+    #[arg(long)]
+    sequence: u32,
+
+    /// Names of the package files to revoke. Every package still revoked must be given, as the
+    /// revocation list replaces the one installed. At most 8 packages can be given, so at most 8
+    /// packages can be revoked for a car at a time. Packages whose validity window has ended can be
+    /// left out to make room.
+    #[arg(long)]
+    package_name: Vec<String>,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    if args.package_name.len() > MAX_REVOKED_FEATURES {
+        println!(
+            "Too many packages to revoke: {} were given, but a revocation list holds at most {MAX_REVOKED_FEATURES}.",
+            args.package_name.len()
+        );
+
+        return;
+    }
+
+    // Get the digest of every package to revoke.
+    let mut revoked = HeaplessVec::new();
+
+    for package_name in args.package_name {
+        let mut package_path = PathBuf::from("/package_dir");
+        package_path.push(package_name);
+        let mut package_bytes = Vec::new();
+        File::open(package_path)
+            .unwrap()
+            .read_to_end(&mut package_bytes)
+            .unwrap();
+        let package: PackagedFeatureSigned = postcard::from_bytes(&package_bytes).unwrap();
+
+        if package.packaged_feature.car_id != args.car_id {
+            println!("Package is not for the given car.");
+
+            return;
+        }
+
+        // The number of packages was checked above.
+        revoked.push(package.packaged_feature.digest()).unwrap();
+    }
+
+    // Open the revocation list file for writing.
+    let mut list_path = PathBuf::from("/package_dir");
+    list_path.push(args.list_name);
+    let mut list_file = File::create(list_path).unwrap();
+
+    // Open feature signing key.
+    let mut signing_key_file = File::open("/secrets/FEATURE_SIGNING_KEY").unwrap();
+    let mut signing_key_bytes: Vec<u8> = Vec::new();
+    signing_key_file
+        .read_to_end(&mut signing_key_bytes)
+        .unwrap();
+    let signing_key = SigningKey::from_bytes(&signing_key_bytes).unwrap();
+
+    let revocation_list = RevocationListUnsigned {
+        car_id: args.car_id,
+        sequence: args.sequence,
+        revoked,
+    };
+
+    let signature: Signature = signing_key.sign(&to_allocvec(&revocation_list.tagged()).unwrap());
+
+    let revocation_list_signed = RevocationListSigned {
+        revocation_list,
+        signature: &signature.to_der().to_bytes(),
+    };
+
+    list_file
+        .write_all(&to_allocvec(&revocation_list_signed).unwrap())
+        .unwrap();
+}
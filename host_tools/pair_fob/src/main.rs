@@ -2,12 +2,14 @@ use std::time::Duration;
 
 use clap::Parser;
 use ucsc_ectf_util_std::{
-    communication::{self, CommunicationError, RxChannel, TxChannel, VerifiedFramedTcpSocket},
+    communication::{
+        self, message_channel::MessageChannel, CommunicationError, VerifiedFramedTcpSocket,
+    },
     messages::{HostToolAck, PairingPin, Uart0Message},
     timer::StdTimer,
 };
 
-const RECV_BUFF_LEN: usize = 128;
+const MESSAGE_BUFF_LEN: usize = 1024;
 const FAILED_PAIRING: &str = "Failed to pair fob.";
 
 #[derive(Parser)]
@@ -29,17 +31,19 @@ fn pair(pin: PairingPin, unpaired_port: u16, paired_port: u16) -> communication:
     let mut unpaired_socket =
         VerifiedFramedTcpSocket::keyless_connect(("ectf-net", unpaired_port))?;
     let mut paired_socket = VerifiedFramedTcpSocket::keyless_connect(("ectf-net", paired_port))?;
+    let mut paired_channel =
+        MessageChannel::<_, Uart0Message, MESSAGE_BUFF_LEN>::new(&mut paired_socket);
+
+    paired_channel.exchange_hello_with_timeout(&mut StdTimer::new(Duration::from_secs(1)))?;
+
     let pin_msg = Uart0Message::PairingPin(pin);
-    let mut pin_msg_bytes =
-        postcard::to_allocvec(&pin_msg).map_err(|_| CommunicationError::InternalError)?;
 
-    paired_socket.send(&mut pin_msg_bytes)?;
+    paired_channel.send(&pin_msg)?;
 
-    let mut buff = [0; RECV_BUFF_LEN];
+    let mut unpaired_channel =
+        MessageChannel::<_, Uart0Message, MESSAGE_BUFF_LEN>::new(&mut unpaired_socket);
     let mut timeout_timer = StdTimer::new(Duration::from_secs(5));
-    let resp_len = unpaired_socket.recv_with_data_timeout(&mut buff, &mut timeout_timer)?;
-    let resp = postcard::from_bytes::<Uart0Message>(&buff[..resp_len])
-        .map_err(|_| CommunicationError::RecvError)?;
+    let resp = unpaired_channel.recv_with_data_timeout(&mut timeout_timer)?;
 
     match resp {
         Uart0Message::PairingPinResponse(HostToolAck(true)) => Ok(()),
@@ -1,52 +1,130 @@
 #![cfg(debug_assertions)]
 
 use core::iter;
-use ucsc_ectf_util_no_std::eeprom::{
-    EepromController, EepromReadField, EepromReadOnlyField, EepromReadWriteField, PUBLIC_KEY_SIZE,
+use ucsc_ectf_util_no_std::{
+    eeprom::{
+        EepromController, EepromReadField, EepromReadOnlyField, EepromReadWriteField,
+        DEVICE_TIME_SLOTS, REVOCATION_LIST_SIGNED_SIZE,
+    },
+    messages::NUM_FEATURES,
 };
 
-const READ_ONLY_FIELDS: [EepromReadOnlyField; 10] = [
-    EepromReadOnlyField::PairedFobPairingSigningKey,
-    EepromReadOnlyField::PairedFobPairingPublicKeySignature,
-    EepromReadOnlyField::PairingManufacturerPairedFobVerifyingKey,
-    EepromReadOnlyField::PairingManufacturerUnpairedFobVerifyingKey,
-    EepromReadOnlyField::FeatureVerifyingKey,
-    EepromReadOnlyField::SecretSeed,
-    EepromReadOnlyField::FeatureThreeMessage,
-    EepromReadOnlyField::FeatureTwoMessage,
-    EepromReadOnlyField::FeatureOneMessage,
-    EepromReadOnlyField::UnlockMessage,
-];
+const READ_ONLY_FIELDS: [EepromReadOnlyField; 7 + NUM_FEATURES] = {
+    let mut fields = [EepromReadOnlyField::UnlockMessage; 7 + NUM_FEATURES];
+    fields[0] = EepromReadOnlyField::FeatureVerifyingKey;
+    fields[1] = EepromReadOnlyField::SecretSeed;
+    fields[2] = EepromReadOnlyField::PairedFobPairingSigningKey;
+    fields[3] = EepromReadOnlyField::PairedFobPairingPublicKeySignature;
+    fields[4] = EepromReadOnlyField::PairingManufacturerPairedFobVerifyingKey;
+    fields[5] = EepromReadOnlyField::PairingManufacturerUnpairedFobVerifyingKey;
+
+    // Feature messages are stored in reverse order of feature number, followed by the unlock
+    // message.
+    let mut i = 0;
+
+    while i < NUM_FEATURES {
+        fields[6 + i] = match EepromReadOnlyField::feature_message((NUM_FEATURES - i) as u32) {
+            Some(field) => field,
+            None => panic!("Invalid feature number."),
+        };
+        i += 1;
+    }
+
+    fields
+};
 
-const READ_WRITE_FIELDS: [EepromReadWriteField; 11] = [
-    EepromReadWriteField::UnpairedFobPairingSigningKey,
-    EepromReadWriteField::UnpairedFobPairingPublicKeySignature,
+/// The read-write fields used by both the car and the key fob, which are stored before the fields
+/// of either.
+const COMMON_READ_WRITE_FIELDS: [EepromReadWriteField; 4] = [
     EepromReadWriteField::KeyFobEncryptionKey,
     EepromReadWriteField::CarEncryptionKey,
     EepromReadWriteField::CarId,
-    EepromReadWriteField::PairingByte,
-    EepromReadWriteField::PairingPin,
-    EepromReadWriteField::PairingLongerCooldownByte,
-    EepromReadWriteField::FeatureOneSignedPackaged,
-    EepromReadWriteField::FeatureTwoSignedPackaged,
-    EepromReadWriteField::FeatureThreeSignedPackaged,
+    EepromReadWriteField::RevocationList,
 ];
 
+/// The read-write fields used by the car. These share addresses with the fields of the key fob.
+const CAR_READ_WRITE_FIELDS: [EepromReadWriteField; 4 + DEVICE_TIME_SLOTS + NUM_FEATURES] = {
+    let mut fields = [EepromReadWriteField::RevocationList; 4 + DEVICE_TIME_SLOTS + NUM_FEATURES];
+    let mut i = 0;
+
+    while i < COMMON_READ_WRITE_FIELDS.len() {
+        fields[i] = COMMON_READ_WRITE_FIELDS[i];
+        i += 1;
+    }
+
+    // The device time slots are stored in order.
+    let mut slot = 0;
+
+    while slot < DEVICE_TIME_SLOTS {
+        fields[4 + slot] = EepromReadWriteField::DeviceTime(slot as u32);
+        slot += 1;
+    }
+
+    // Feature activations are stored in order of feature number.
+    let mut i = 0;
+
+    while i < NUM_FEATURES {
+        fields[4 + DEVICE_TIME_SLOTS + i] =
+            match EepromReadWriteField::feature_activation((i + 1) as u32) {
+                Some(field) => field,
+                None => panic!("Invalid feature number."),
+            };
+        i += 1;
+    }
+
+    fields
+};
+
+/// The read-write fields used by the key fob. These share addresses with the fields of the car.
+const FOB_READ_WRITE_FIELDS: [EepromReadWriteField; 10 + NUM_FEATURES] = {
+    let mut fields = [EepromReadWriteField::RevocationList; 10 + NUM_FEATURES];
+    let mut i = 0;
+
+    while i < COMMON_READ_WRITE_FIELDS.len() {
+        fields[i] = COMMON_READ_WRITE_FIELDS[i];
+        i += 1;
+    }
+
+    fields[4] = EepromReadWriteField::UnpairedFobPairingSigningKey;
+    fields[5] = EepromReadWriteField::UnpairedFobPairingPublicKeySignature;
+    fields[6] = EepromReadWriteField::PairingByte;
+    fields[7] = EepromReadWriteField::PairingPin;
+    fields[8] = EepromReadWriteField::PairingLongerCooldownByte;
+    fields[9] = EepromReadWriteField::DisableFeatureSequence;
+
+    // Packaged features are stored in order of feature number.
+    let mut i = 0;
+
+    while i < NUM_FEATURES {
+        fields[10 + i] = match EepromReadWriteField::feature_packaged((i + 1) as u32) {
+            Some(field) => field,
+            None => panic!("Invalid feature number."),
+        };
+        i += 1;
+    }
+
+    fields
+};
+
 const DEFAULT_EEPROM_DATA: u8 = 0xFF; // All 1s.
 
+const MAX_FIELD_SIZE: usize = REVOCATION_LIST_SIGNED_SIZE; // The size of the largest field.
+
 pub fn run(eeprom: &mut EepromController) {
     // Erase EEPROM before running tests.
     eeprom.erase_mem();
 
     // Run tests.
     read_default(eeprom);
-    basic_write_read_test(eeprom);
-    write_read_bleed_test(eeprom);
+    basic_write_read_test(eeprom, &CAR_READ_WRITE_FIELDS);
+    basic_write_read_test(eeprom, &FOB_READ_WRITE_FIELDS);
+    write_read_bleed_test(eeprom, &CAR_READ_WRITE_FIELDS);
+    write_read_bleed_test(eeprom, &FOB_READ_WRITE_FIELDS);
 }
 
 /// Tests reads of default EEPROM values (0xFF for all bytes).
 fn read_default(eeprom: &mut EepromController) {
-    let mut data = [0; PUBLIC_KEY_SIZE];
+    let mut data = [0; MAX_FIELD_SIZE];
 
     for field in READ_ONLY_FIELDS.into_iter() {
         eeprom.read_slice(field, &mut data).unwrap();
@@ -58,7 +136,10 @@ fn read_default(eeprom: &mut EepromController) {
         data.fill(0);
     }
 
-    for field in READ_WRITE_FIELDS.into_iter() {
+    for field in CAR_READ_WRITE_FIELDS
+        .into_iter()
+        .chain(FOB_READ_WRITE_FIELDS.into_iter())
+    {
         eeprom.read_slice(field, &mut data).unwrap();
 
         assert!(&data[..field.get_field_bounds().size]
@@ -69,17 +150,20 @@ fn read_default(eeprom: &mut EepromController) {
     }
 }
 
-/// Tests writing and reading of EEPROM for read-write fields.
-fn basic_write_read_test(eeprom: &mut EepromController) {
+/// Tests writing and reading of EEPROM for the read-write fields of one device.
+fn basic_write_read_test(
+    eeprom: &mut EepromController,
+    read_write_fields: &[EepromReadWriteField],
+) {
     const TEST_DATA_1: u8 = 0x55; // Alternate 0 and 1.
     const TEST_DATA_2: u8 = 0xAA; // Alternate 1 and 0.
-    let mut data = [0; PUBLIC_KEY_SIZE];
-    let mut read_data = [0; PUBLIC_KEY_SIZE];
+    let mut data = [0; MAX_FIELD_SIZE];
+    let mut read_data = [0; MAX_FIELD_SIZE];
     let mut test_data_iter = iter::once(TEST_DATA_1)
         .chain(iter::once(TEST_DATA_2))
         .cycle();
 
-    for field in READ_WRITE_FIELDS.into_iter() {
+    for &field in read_write_fields {
         data.fill(test_data_iter.next().unwrap());
         eeprom
             .write_slice(field, &data[..field.get_field_bounds().size])
@@ -93,14 +177,18 @@ fn basic_write_read_test(eeprom: &mut EepromController) {
     }
 }
 
-/// Tests that writing to one field does not affect another field.
-fn write_read_bleed_test(eeprom: &mut EepromController) {
+/// Tests that writing to one field does not affect another field of the same device. Fields of the
+/// car and the key fob share addresses, so they are tested separately.
+fn write_read_bleed_test(
+    eeprom: &mut EepromController,
+    read_write_fields: &[EepromReadWriteField],
+) {
     const TEST_DATA_1: u8 = 0x55; // Alternate 0 and 1.
     const TEST_DATA_2: u8 = 0xAA; // Alternate 1 and 0.
-    let mut data = [0; PUBLIC_KEY_SIZE];
+    let mut data = [0; MAX_FIELD_SIZE];
 
     // Set all writable fields to the default values before starting test.
-    for field in READ_WRITE_FIELDS.into_iter() {
+    for &field in read_write_fields {
         data.fill(DEFAULT_EEPROM_DATA);
         eeprom
             .write_slice(field, &data[..field.get_field_bounds().size])
@@ -108,12 +196,12 @@ fn write_read_bleed_test(eeprom: &mut EepromController) {
     }
 
     // Test that writing to one field does not affect another field.
-    let mut read_data = [0; PUBLIC_KEY_SIZE];
+    let mut read_data = [0; MAX_FIELD_SIZE];
     let mut test_data_iter = iter::once(TEST_DATA_1)
         .chain(iter::once(TEST_DATA_2))
         .cycle();
 
-    for fields in READ_WRITE_FIELDS.windows(2) {
+    for fields in read_write_fields.windows(2) {
         data.fill(test_data_iter.next().unwrap());
         eeprom
             .write_slice(fields[0], &data[..fields[0].get_field_bounds().size])
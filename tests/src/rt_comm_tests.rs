@@ -1,12 +1,13 @@
 #![cfg(debug_assertions)]
 
 use ucsc_ectf_util_no_std::{
-    communication::{self, TxChannel},
+    communication::{self, lower_layers::framing::CobsFraming, TxChannel},
     Uart0RxPin, Uart0TxPin, Uart1RxPin, Uart1TxPin,
 };
 
 type Uart0Controller<'a> = communication::Uart0Controller<'a, Uart0TxPin, Uart0RxPin>;
-type Uart1Controller<'a> = communication::Uart1Controller<'a, Uart1TxPin, Uart1RxPin>;
+type Uart1Controller<'a> =
+    communication::Uart1Controller<'a, Uart1TxPin, Uart1RxPin, CobsFraming>;
 
 pub fn run(uart0: &mut Uart0Controller, _uart1: &mut Uart1Controller) {
     basic_uart0_send_test(uart0);